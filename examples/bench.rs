@@ -0,0 +1,34 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+//! Runs the benchmark suites.  Compare the signatures before and after a change to see whether
+//! it changed what the search does; compare the node rates to see whether it changed its speed.
+//! Build in release mode for meaningful times.
+
+extern crate zero_sum;
+
+use zero_sum::analysis::bench;
+
+fn main() {
+    println!("Tak:");
+    println!("{}", bench::tak());
+    println!();
+    println!("Tic-tac-toe:");
+    println!("{}", bench::tic_tac_toe());
+}
@@ -0,0 +1,54 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+extern crate zero_sum;
+
+use std::env;
+
+use zero_sum::analysis::search::PvSearch;
+use zero_sum::cli::Game;
+use zero_sum::impls::tak::*;
+
+/// Plays tak against the engine.
+///
+/// Usage: tak [board size] [seconds per ply] [engine color]
+///
+/// The board size defaults to 5, the engine thinks for about 10 seconds per ply, and plays
+/// black unless the engine color is "white".
+fn main() {
+    let mut args = env::args().skip(1);
+    let board_size = args.next().and_then(|arg| arg.parse::<usize>().ok()).unwrap_or(5);
+    let goal = args.next().and_then(|arg| arg.parse::<u16>().ok()).unwrap_or(10);
+    let engine_player = match args.next() {
+        Some(ref color) if color == "white" => 0,
+        _ => 1,
+    };
+
+    if board_size < 3 || board_size > 8 {
+        println!("The board size must be from 3 to 8");
+        return;
+    }
+
+    let search = PvSearch::with_goal(evaluator::StaticEvaluator::new(), goal, 12.0);
+    let mut game = Game::new(State::new(board_size), search, Some(engine_player));
+
+    if let Err(error) = game.play() {
+        println!("Error: {}", error);
+    }
+}
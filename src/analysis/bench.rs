@@ -0,0 +1,278 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+//! A fixed benchmark for catching changes in the behavior and speed of the search.
+//!
+//! `run` searches each position of a suite to a fixed depth with a seeded `PvSearch`, and
+//! reports the nodes searched and the time taken for each.  The report's signature is a hash of
+//! every position's node count, evaluation, and principal variation, so a change that should
+//! only make the search faster ought to leave it alone, while a change to what the search
+//! actually does will almost certainly alter it.
+//!
+//! Suites of tak and tic-tac-toe positions are provided when those games are enabled.
+
+use std::fmt;
+use std::hash::Hasher;
+use std::time::{Duration, Instant};
+
+use fnv::FnvHasher;
+
+use analysis::{Evaluator, Extrapolatable};
+use analysis::search::{PvSearch, PvSearchAnalysis, Search};
+use state::State;
+
+/// The seed given to every benchmark search.
+pub const BENCH_SEED: u64 = 0;
+
+/// The result of searching one position.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub struct PositionResult {
+    pub name: String,
+    /// The number of nodes visited or evaluated.
+    pub nodes: u64,
+    pub time: Duration,
+    pub evaluation: String,
+    pub principal_variation: Vec<String>,
+}
+
+/// The results of a benchmark run.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub struct BenchReport {
+    pub depth: u8,
+    pub positions: Vec<PositionResult>,
+}
+
+impl BenchReport {
+    /// Returns the number of nodes searched over all positions.
+    pub fn total_nodes(&self) -> u64 {
+        self.positions.iter().map(|position| position.nodes).sum()
+    }
+
+    /// Returns the time taken over all positions.
+    pub fn total_time(&self) -> Duration {
+        self.positions.iter().fold(Duration::new(0, 0), |total, position| total + position.time)
+    }
+
+    /// Returns the number of nodes searched per second over all positions.
+    pub fn nodes_per_second(&self) -> f64 {
+        let seconds = duration_seconds(self.total_time());
+        if seconds > 0.0 {
+            self.total_nodes() as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns a number that identifies what the searches did, independent of how long they took.
+    pub fn signature(&self) -> u64 {
+        let mut hasher = FnvHasher::default();
+        for position in &self.positions {
+            hasher.write_u64(position.nodes);
+            hasher.write(position.evaluation.as_bytes());
+            for ply in &position.principal_variation {
+                hasher.write_u8(0);
+                hasher.write(ply.as_bytes());
+            }
+            hasher.write_u8(1);
+        }
+        hasher.finish()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name_width = self.positions.iter().map(|position| position.name.len()).max().unwrap_or(0);
+
+        for position in &self.positions {
+            writeln!(
+                f, "{:<width$}  {:>10} nodes  {:>8.3}s  {:>8}  {}",
+                position.name, position.nodes, duration_seconds(position.time),
+                position.evaluation, position.principal_variation.join(" "),
+                width = name_width,
+            )?;
+        }
+        writeln!(
+            f, "Depth {}: {} nodes in {:.3}s ({:.0} nodes/s)",
+            self.depth, self.total_nodes(), duration_seconds(self.total_time()), self.nodes_per_second(),
+        )?;
+        write!(f, "Signature: {}", self.signature())
+    }
+}
+
+/// Searches each of `positions` to `depth` with a fresh, seeded `PvSearch` using an evaluator
+/// from `evaluator`.
+pub fn run<S, E, F>(positions: &[(&str, S)], depth: u8, evaluator: F) -> BenchReport where
+    S: 'static + State + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S>,
+    F: Fn() -> E {
+    let mut results = Vec::with_capacity(positions.len());
+
+    for &(name, ref state) in positions {
+        let mut search = PvSearch::with_depth(evaluator(), depth);
+        search.set_seed(BENCH_SEED);
+
+        let start = Instant::now();
+        let analysis = search.search(state, None);
+        let time = start.elapsed();
+
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<S, E>>().unwrap();
+        results.push(PositionResult {
+            name: String::from(name),
+            nodes: analysis.statistics.nodes(),
+            time: time,
+            evaluation: format!("{}", analysis.evaluation),
+            principal_variation: analysis.principal_variation.iter().map(|ply| format!("{}", ply)).collect(),
+        });
+    }
+
+    BenchReport {
+        depth: depth,
+        positions: results,
+    }
+}
+
+/// The depth the tak suite is searched to.
+#[cfg(feature = "with_tak")]
+pub const TAK_DEPTH: u8 = 5;
+
+/// The TPS of each position in the tak suite, by name.
+#[cfg(feature = "with_tak")]
+pub const TAK_POSITIONS: [(&'static str, &'static str); 7] = [
+    ("opening", "[TPS \"x5/x5/x5/x5/x5 1 1\"]"),
+    ("early", "[TPS \"x5/x5/x,2,1,x2/x,1,2,x2/x5 1 3\"]"),
+    ("contested center", "[TPS \"2,1,x3/x,2,1,x2/x,1,2,1,x/x2,2,x2/x5 1 6\"]"),
+    ("road win in one", "[TPS \"x5/x5/x5/2,2,2,x2/1,1,1,1,x 1 5\"]"),
+    ("block road", "[TPS \"x5/x5/x5/2,2,2,x2/1,1,1,1,x 2 4\"]"),
+    ("break road", "[TPS \"x5/x5/1,x,1,x2/2,2,2,2,x/1,x,1,x2 1 5\"]"),
+    ("tall stacks", "[TPS \"21,22221C,1,12212S,x/2121,2S,2,1S,2/x2,2,2,x/1,2111112C,2,x,21/x,1,21,x2 1 32\"]"),
+];
+
+/// Returns the tak suite.
+#[cfg(feature = "with_tak")]
+pub fn tak_positions() -> Vec<(&'static str, ::impls::tak::State)> {
+    TAK_POSITIONS.iter().map(|&(name, tps)| {
+        (name, ::impls::tak::State::from_tps(tps).expect("Invalid TPS in the tak suite!"))
+    }).collect()
+}
+
+/// Runs the tak suite with the `StaticEvaluator`.
+#[cfg(feature = "with_tak")]
+pub fn tak() -> BenchReport {
+    run(&tak_positions(), TAK_DEPTH, ::impls::tak::evaluator::StaticEvaluator::new)
+}
+
+/// The depth the tic-tac-toe suite is searched to, which is enough to solve every position.
+#[cfg(feature = "with_tic_tac_toe")]
+pub const TIC_TAC_TOE_DEPTH: u8 = 9;
+
+/// The coordinates of the marks made to reach each position in the tic-tac-toe suite, by name.
+/// X moves first.
+#[cfg(feature = "with_tic_tac_toe")]
+pub const TIC_TAC_TOE_POSITIONS: [(&'static str, &'static [(usize, usize)]); 5] = [
+    ("empty", &[]),
+    ("corner", &[(0, 0)]),
+    ("center", &[(1, 1)]),
+    ("opposite corners", &[(0, 0), (1, 1), (2, 2)]),
+    ("must block", &[(0, 0), (1, 1), (1, 0)]),
+];
+
+/// Returns the tic-tac-toe suite.
+#[cfg(feature = "with_tic_tac_toe")]
+pub fn tic_tac_toe_positions() -> Vec<(&'static str, ::impls::tic_tac_toe::Board)> {
+    use impls::tic_tac_toe::{Board, Ply};
+
+    TIC_TAC_TOE_POSITIONS.iter().map(|&(name, marks)| {
+        let mut board = Board::new();
+        for &coordinates in marks {
+            let ply = Ply {
+                mark: board.next_mark(),
+                coordinates: coordinates,
+            };
+            board.execute_ply(Some(&ply)).expect("Invalid mark in the tic-tac-toe suite!");
+        }
+        (name, board)
+    }).collect()
+}
+
+/// Runs the tic-tac-toe suite.
+#[cfg(feature = "with_tic_tac_toe")]
+pub fn tic_tac_toe() -> BenchReport {
+    run(&tic_tac_toe_positions(), TIC_TAC_TOE_DEPTH, || ::impls::tic_tac_toe::Evaluator)
+}
+
+fn duration_seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+#[cfg(all(test, any(feature = "with_tak", feature = "with_tic_tac_toe")))]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "with_tic_tac_toe")]
+    #[test]
+    fn test_tic_tac_toe_is_reproducible() {
+        let a = tic_tac_toe();
+        let b = tic_tac_toe();
+
+        assert_eq!(a.positions.len(), TIC_TAC_TOE_POSITIONS.len());
+        assert!(a.total_nodes() > 0);
+        assert_eq!(
+            a.positions.iter().map(|position| position.nodes).collect::<Vec<_>>(),
+            b.positions.iter().map(|position| position.nodes).collect::<Vec<_>>(),
+        );
+        assert_eq!(a.signature(), b.signature());
+    }
+
+    #[cfg(all(feature = "with_serde", feature = "with_tic_tac_toe"))]
+    #[test]
+    fn test_serialize() {
+        use serde_json;
+
+        use analysis::search::{PvSearch, PvSearchAnalysis, Search};
+        use impls::tic_tac_toe::{Board, Evaluator};
+
+        let report = tic_tac_toe();
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(serde_json::from_str::<BenchReport>(&json).unwrap().signature(), report.signature());
+
+        let (_, board) = tic_tac_toe_positions().remove(4);
+        let mut search = PvSearch::with_depth(Evaluator, TIC_TAC_TOE_DEPTH);
+        search.set_explain(true);
+        let analysis = search.search(&board, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, Evaluator>>().unwrap();
+
+        let json = serde_json::to_string(&analysis).unwrap();
+        let decoded: PvSearchAnalysis<Board, Evaluator> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.state, analysis.state);
+        assert_eq!(decoded.evaluation, analysis.evaluation);
+        assert_eq!(decoded.principal_variation, analysis.principal_variation);
+        assert_eq!(
+            decoded.explanation.unwrap().root_plies.len(),
+            analysis.explanation.as_ref().unwrap().root_plies.len(),
+        );
+    }
+
+    #[cfg(feature = "with_tak")]
+    #[test]
+    fn test_tak_positions() {
+        assert_eq!(tak_positions().len(), TAK_POSITIONS.len());
+    }
+}
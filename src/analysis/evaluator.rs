@@ -17,8 +17,10 @@
 // Copyright 2016-2017 Chris Foster
 //
 
-use std::fmt::Display;
+use std::fmt::{self, Display};
 use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::string::ToString;
+use std::vec::Vec;
 
 use state::State;
 
@@ -85,6 +87,42 @@ pub trait Evaluation:
     fn is_lose(&self) -> bool { (-*self).is_win() }
     /// Returns `true` if this evaluation is either a win or a loss.
     fn is_end(&self) -> bool { self.is_win() || self.is_lose() }
+    /// Returns `true` if no state with a ply count of `n` is ever evaluated above
+    /// `win().shift(-n)` or below `lose().shift(n)`, as when wins are discounted by the ply on
+    /// which they occur.  Searches can then stop exploring lines that can't end soon enough to
+    /// improve on a win already found.  Defaults to `false`.
+    fn encodes_win_distance() -> bool { false }
+    /// The size of the smallest difference between evaluations that is meaningful to the
+    /// evaluator, comparable to a centipawn in chess.  Search heuristics that depend on
+    /// margins, like aspiration windows, express them as multiples of this value so that
+    /// they behave similarly across evaluators of different scales.  The default is the
+    /// smallest representable step.
+    fn granularity() -> Self { Self::null().shift(1) }
+    /// Returns `units` multiples of `granularity()`, or `max()` if that's more than can be
+    /// represented.
+    fn granularity_multiple(units: u32) -> Self {
+        let max = Self::max();
+        let mut result = Self::null();
+        let mut power = Self::granularity();
+        let mut units = units;
+        while units > 0 {
+            if units & 1 == 1 {
+                if power > max - result {
+                    return max;
+                }
+                result = result + power;
+            }
+            units >>= 1;
+            if units > 0 {
+                // A remaining unit would add at least the doubled power
+                if power > max - power {
+                    return max;
+                }
+                power = power + power;
+            }
+        }
+        result
+    }
 }
 
 /// Evaluates a State.
@@ -95,6 +133,31 @@ pub trait Evaluator {
     /// Returns the evaluation of `state`.
     fn evaluate(&self, state: &Self::State) -> Self::Evaluation;
 
+    /// The bonus that the evaluator awards to the side to move, simply for having the move.
+    ///
+    /// By convention, `evaluate` already includes this bonus; it is exposed separately so that
+    /// searches can account for it when comparing evaluations across a change of turn that
+    /// isn't a real move, as with null-move pruning.  Defaults to zero.
+    fn tempo(&self) -> Self::Evaluation {
+        Self::Evaluation::null()
+    }
+
+    /// Returns the model that maps this evaluator's evaluations to win probabilities, if it
+    /// has one.  Evaluations from different games, or different evaluators, can only be
+    /// compared as probabilities.  Defaults to `None`.
+    fn score_model(&self) -> Option<ScoreModel<Self::Evaluation>> {
+        None
+    }
+
+    /// Returns the evaluations of each of `states`, in order.
+    ///
+    /// The default implementation calls `evaluate` on each state.  Evaluators that can
+    /// amortize work across many states, like those backed by a neural network, should
+    /// override this.
+    fn evaluate_batch(&self, states: &[Self::State]) -> Vec<Self::Evaluation> {
+        states.iter().map(|state| self.evaluate(state)).collect()
+    }
+
     /// Returns the evaluation of `state` after executing `plies`.
     ///
     /// # Panics
@@ -112,6 +175,108 @@ pub trait Evaluator {
     }
 }
 
+/// An evaluator that keeps accumulators of its own, updated as plies are executed and reverted,
+/// rather than evaluating each state from scratch.
+///
+/// A search that supports incremental evaluation calls `reset` with the root state, and then
+/// notifies the evaluator of every ply it executes or reverts on the way to the states it
+/// evaluates.  Calls to `evaluate` are then only made with the state that the accumulators
+/// describe.
+pub trait IncrementalEvaluator: Evaluator {
+    /// Initializes the accumulators from `state`.
+    fn reset(&mut self, state: &Self::State);
+
+    /// Updates the accumulators after `ply` has been executed, resulting in `state`.  A
+    /// `ply` of `None` is a null move.
+    fn apply_ply(&mut self, state: &Self::State, ply: Option<&<Self::State as State>::Ply>);
+
+    /// Updates the accumulators after `ply` has been reverted, resulting in `state`.  A
+    /// `ply` of `None` is a null move.
+    fn revert_ply(&mut self, state: &Self::State, ply: Option<&<Self::State as State>::Ply>);
+}
+
+/// An evaluator that can break its evaluation of a state into the terms that make it up,
+/// for debugging changes to its weights, or for explaining a position to a player.
+pub trait ExplainableEvaluator: Evaluator {
+    /// Returns the terms of the evaluation of `state`.
+    fn explain(&self, state: &Self::State) -> EvaluationBreakdown<Self::Evaluation>;
+}
+
+/// The evaluation of a state, broken into terms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvaluationBreakdown<E> where
+    E: Evaluation {
+    /// The name of each term, and its value for each player, indexed by player number as in
+    /// `Resolution::get_winner`.  Empty if the state is resolved.
+    pub terms: Vec<(&'static str, [E; 2])>,
+    /// The evaluation of the state, as returned by `Evaluator::evaluate`.
+    pub evaluation: E,
+}
+
+impl<E> EvaluationBreakdown<E> where
+    E: Evaluation {
+    /// Returns the value of the term named `name` for each player.
+    pub fn get(&self, name: &str) -> Option<[E; 2]> {
+        self.terms.iter().find(|&&(term, _)| term == name).map(|&(_, values)| values)
+    }
+
+    /// Returns the sum of every term for each player.
+    pub fn totals(&self) -> [E; 2] {
+        self.terms.iter().fold([E::null(), E::null()], |totals, &(_, values)| {
+            [totals[0] + values[0], totals[1] + values[1]]
+        })
+    }
+}
+
+impl<E> fmt::Display for EvaluationBreakdown<E> where
+    E: Evaluation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.terms.iter().map(|&(name, _)| name.len()).max().unwrap_or(0);
+
+        writeln!(f, "{:width$}  {:>10}  {:>10}", "", "player 0", "player 1", width = width)?;
+        for &(name, values) in &self.terms {
+            writeln!(
+                f, "{:width$}  {:>10}  {:>10}",
+                name, values[0].to_string(), values[1].to_string(), width = width,
+            )?;
+        }
+        write!(f, "evaluation {}", self.evaluation)
+    }
+}
+
+/// A logistic mapping from evaluations to the probability that the player they're for wins.
+#[derive(Clone, Copy)]
+pub struct ScoreModel<E> where
+    E: Evaluation {
+    /// The slope of the logistic curve, per unit of `value`.
+    pub scale: f64,
+    /// Returns an evaluation as a plain number.
+    pub value: fn(E) -> f64,
+}
+
+impl<E> ScoreModel<E> where
+    E: Evaluation {
+    pub fn new(scale: f64, value: fn(E) -> f64) -> ScoreModel<E> {
+        ScoreModel {
+            scale: scale,
+            value: value,
+        }
+    }
+
+    /// Returns the probability, from 0 to 1, of a win for the player `evaluation` is for.
+    /// Wins and losses map to exactly 1 and 0.  Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn win_probability(&self, evaluation: E) -> f64 {
+        if evaluation.is_win() {
+            1.0
+        } else if evaluation.is_lose() {
+            0.0
+        } else {
+            1.0 / (1.0 + (-self.scale * (self.value)(evaluation)).exp())
+        }
+    }
+}
+
 /// Implement arithmetic operators (`Add`, `Sub`, `Mul`, `Neg`, `Div`) and `Display` for a tuple
 /// struct in terms of the enclosed type.
 ///
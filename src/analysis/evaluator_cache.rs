@@ -0,0 +1,209 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::cell::{Cell, RefCell};
+use std::hash::{Hash, Hasher};
+
+use fnv::FnvHasher;
+
+use analysis::{Evaluator, ScoreModel};
+use state::State;
+
+/// Wraps an evaluator, memoizing its evaluations in a fixed-size table.
+///
+/// Each state hashes to a single slot, and a new evaluation always replaces whatever occupied
+/// its slot.  Entries store the full state, so hash collisions never return a wrong
+/// evaluation.  This is worthwhile for expensive evaluators, since a search visits the same
+/// positions many times.
+pub struct EvaluatorCache<E> where
+    E: Evaluator {
+    evaluator: E,
+    entries: RefCell<Vec<Option<(E::State, E::Evaluation)>>>,
+    mask: usize,
+    hits: Cell<u64>,
+    misses: Cell<u64>,
+}
+
+impl<E> EvaluatorCache<E> where
+    E: Evaluator {
+    /// Creates a cache of `capacity` entries around `evaluator`.  `capacity` is rounded up
+    /// to the next power of two.
+    pub fn new(evaluator: E, capacity: usize) -> EvaluatorCache<E> {
+        let capacity = capacity.max(1).next_power_of_two();
+        EvaluatorCache {
+            evaluator: evaluator,
+            entries: RefCell::new(vec![None; capacity]),
+            mask: capacity - 1,
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        }
+    }
+
+    /// Returns the wrapped evaluator.
+    pub fn get_evaluator(&self) -> &E {
+        &self.evaluator
+    }
+
+    /// Empties the cache and resets the statistics.
+    pub fn clear(&mut self) {
+        for entry in self.entries.borrow_mut().iter_mut() {
+            *entry = None;
+        }
+        self.hits.set(0);
+        self.misses.set(0);
+    }
+
+    /// Returns the number of evaluations answered from the cache.
+    pub fn get_hits(&self) -> u64 {
+        self.hits.get()
+    }
+
+    /// Returns the number of evaluations passed through to the wrapped evaluator.
+    pub fn get_misses(&self) -> u64 {
+        self.misses.get()
+    }
+
+    /// Returns the fraction of evaluations answered from the cache.
+    pub fn get_hit_rate(&self) -> f64 {
+        let total = self.hits.get() + self.misses.get();
+        if total == 0 {
+            0.0
+        } else {
+            self.hits.get() as f64 / total as f64
+        }
+    }
+
+    fn index(&self, state: &E::State) -> usize {
+        let mut hasher = FnvHasher::default();
+        state.hash(&mut hasher);
+        hasher.finish() as usize & self.mask
+    }
+}
+
+impl<E> Evaluator for EvaluatorCache<E> where
+    E: Evaluator {
+    type State = E::State;
+    type Evaluation = E::Evaluation;
+
+    fn evaluate(&self, state: &E::State) -> E::Evaluation {
+        let index = self.index(state);
+
+        if let Some((ref cached, evaluation)) = self.entries.borrow()[index] {
+            if cached == state {
+                self.hits.set(self.hits.get() + 1);
+                return evaluation;
+            }
+        }
+
+        self.misses.set(self.misses.get() + 1);
+        let evaluation = self.evaluator.evaluate(state);
+        self.entries.borrow_mut()[index] = Some((state.clone(), evaluation));
+        evaluation
+    }
+
+    fn tempo(&self) -> E::Evaluation {
+        self.evaluator.tempo()
+    }
+
+    fn score_model(&self) -> Option<ScoreModel<E::Evaluation>> {
+        self.evaluator.score_model()
+    }
+
+    fn evaluate_batch(&self, states: &[E::State]) -> Vec<E::Evaluation> {
+        let mut evaluations = Vec::with_capacity(states.len());
+        let mut missing = Vec::new();
+        let mut missing_indices = Vec::new();
+
+        {
+            let entries = self.entries.borrow();
+            for (i, state) in states.iter().enumerate() {
+                let index = self.index(state);
+                match entries[index] {
+                    Some((ref cached, evaluation)) if cached == state => {
+                        self.hits.set(self.hits.get() + 1);
+                        evaluations.push(Some(evaluation));
+                    },
+                    _ => {
+                        missing.push(state.clone());
+                        missing_indices.push(i);
+                        evaluations.push(None);
+                    },
+                }
+            }
+        }
+
+        if !missing.is_empty() {
+            self.misses.set(self.misses.get() + missing.len() as u64);
+            let results = self.evaluator.evaluate_batch(&missing);
+            let mut entries = self.entries.borrow_mut();
+            for ((state, evaluation), &i) in missing.into_iter().zip(results).zip(missing_indices.iter()) {
+                evaluations[i] = Some(evaluation);
+                let index = self.index(&state);
+                entries[index] = Some((state, evaluation));
+            }
+        }
+
+        evaluations.into_iter().map(|evaluation| evaluation.unwrap()).collect()
+    }
+
+    fn evaluate_plies(&self, state: &E::State, plies: &[<E::State as State>::Ply]) -> E::Evaluation {
+        let mut state = state.clone();
+        if let Err(error) = state.execute_plies(plies) {
+            panic!("Error calculating evaluation: {}", error);
+        }
+        if plies.len() % 2 == 0 {
+            self.evaluate(&state)
+        } else {
+            -self.evaluate(&state)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "with_tak"))]
+mod test {
+    use analysis::Evaluator;
+    use impls::tak::{Color, Ply, State};
+    use impls::tak::evaluator::StaticEvaluator;
+    use state::State as StateTrait;
+    use super::*;
+
+    #[test]
+    fn test_evaluator_cache() {
+        let cache = EvaluatorCache::new(StaticEvaluator::new(), 1000);
+        let evaluator = StaticEvaluator::new();
+
+        let mut state = State::new(5);
+        state.execute_plies(&[
+            Ply::from_ptn("a1", Color::Black).unwrap(),
+            Ply::from_ptn("e5", Color::White).unwrap(),
+        ]).unwrap();
+
+        assert!(cache.evaluate(&state) == evaluator.evaluate(&state));
+        assert!(cache.evaluate(&state) == evaluator.evaluate(&state));
+        assert!(cache.get_hits() == 1 && cache.get_misses() == 1);
+        assert!(cache.get_hit_rate() == 0.5);
+
+        let mut next = state.clone();
+        next.execute_ply(Some(&Ply::from_ptn("c3", Color::White).unwrap())).unwrap();
+        let states = [state.clone(), next.clone(), next.clone()];
+        let evaluations = cache.evaluate_batch(&states);
+        assert!(evaluations == evaluator.evaluate_batch(&states));
+        assert!(cache.get_hits() == 2 && cache.get_misses() == 3);
+    }
+}
@@ -0,0 +1,313 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+use fnv::FnvHasher;
+
+use analysis::Evaluation;
+use persist::{Decoder, Encode, Persistable, PersistError, Reader, Writer};
+use state::State;
+
+const EXPERIENCE_MAGIC: &'static [u8; 4] = b"ZSEX";
+const EXPERIENCE_VERSION: u32 = 1;
+
+/// A corrected evaluation of a state, learned from a game.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExperienceEntry<E> where
+    E: Evaluation {
+    /// The evaluation the state turned out to deserve, from the perspective of the player to move.
+    pub value: E,
+    /// The depth of the search that misjudged the state.
+    pub depth: u8,
+    /// The number of games in which the state was misjudged.
+    pub games: u32,
+}
+
+/// Evaluations of states that a search misjudged in earlier games, corrected by how those
+/// games went, similar to the experience files of chess engines.
+///
+/// After a game, `learn` compares the evaluation of each of the engine's root searches
+/// against its evaluation of the next root it searched, or against the result of the game
+/// for the last one.  States whose evaluation fell by more than a margin are stored with the
+/// later value, so that searches consulting the experience with `PvSearch::set_experience`
+/// steer away from lines that went badly before.  `play_match_with_experience` learns from
+/// each game of a match.
+///
+/// States are keyed by their hashes, so states that hash equally, like those that differ only
+/// in their ply counts, share an entry.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let mut experience = Experience::load(File::open("engine.exp")?)?;
+/// experience.learn(&roots, winner, state.get_ply_count(), depth, Evaluation::win().shift(-500));
+/// experience.save(File::create("engine.exp")?)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct Experience<S, E> where
+    S: State,
+    E: Evaluation {
+    entries: HashMap<u64, ExperienceEntry<E>>,
+    state: PhantomData<S>,
+}
+
+impl<S, E> Experience<S, E> where
+    S: State,
+    E: Evaluation {
+    /// Creates an empty experience.
+    pub fn new() -> Experience<S, E> {
+        Experience {
+            entries: HashMap::new(),
+            state: PhantomData,
+        }
+    }
+
+    /// Returns the corrected evaluation of `state`, if it has one.
+    pub fn get(&self, state: &S) -> Option<&ExperienceEntry<E>> {
+        self.entries.get(&hash(state))
+    }
+
+    /// Stores `value` as the corrected evaluation of `state`, as judged by a search to `depth`.
+    /// A state already in the experience takes the new value and counts another game.
+    pub fn record(&mut self, state: &S, value: E, depth: u8) {
+        let entry = self.entries.entry(hash(state)).or_insert(ExperienceEntry {
+            value: value,
+            depth: depth,
+            games: 0,
+        });
+        entry.value = value;
+        entry.depth = depth;
+        entry.games += 1;
+    }
+
+    /// Learns from a finished game.  `roots` holds each state the engine searched as the root,
+    /// with its evaluation, from the perspective of the player to move, by a search to `depth`.
+    /// `winner` is the winner of the game, or `None` for a draw, which ended after `ply_count`
+    /// plies.  Each root whose evaluation was higher than the next root's of the same player,
+    /// or than the result of the game, by more than `margin` is recorded with the lower value.
+    ///
+    /// Returns the number of states recorded.
+    pub fn learn(&mut self, roots: &[(S, E)], winner: Option<u8>, ply_count: usize, depth: u8, margin: E) -> usize {
+        let mut recorded = 0;
+
+        for (index, &(ref state, value)) in roots.iter().enumerate() {
+            let player = state.get_ply_count() % 2;
+
+            let later = roots[index + 1..].iter().find(|&&(ref later, _)| later.get_ply_count() % 2 == player);
+            let corrected = match later {
+                Some(&(_, later_value)) => later_value,
+                None => result_evaluation(player, winner, ply_count),
+            };
+
+            if value - corrected > margin {
+                self.record(state, corrected, depth);
+                recorded += 1;
+            }
+        }
+
+        recorded
+    }
+
+    /// Returns the number of states in the experience.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the experience holds no states.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forgets every state.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<S, E> Experience<S, E> where
+    S: State,
+    E: Evaluation + Persistable {
+    /// Writes the experience to `writer`.
+    pub fn save<W>(&self, writer: W) -> Result<(), PersistError> where
+        W: Write {
+        let mut writer = Writer::new(writer, EXPERIENCE_MAGIC, EXPERIENCE_VERSION)?;
+
+        let mut bytes = Vec::new();
+        for (&key, entry) in &self.entries {
+            bytes.write_u64(key);
+            bytes.write_u8(entry.depth);
+            bytes.write_u32(entry.games);
+            entry.value.encode(&mut bytes);
+        }
+        writer.write_section(&bytes)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Reads an experience written by `save`.
+    pub fn load<R>(reader: R) -> Result<Experience<S, E>, PersistError> where
+        R: Read {
+        let mut reader = Reader::new(reader, EXPERIENCE_MAGIC, EXPERIENCE_VERSION)?;
+        let bytes = match reader.read_section()? {
+            Some(bytes) => bytes,
+            None => return Err(PersistError::Malformed(String::from("Missing experience section"))),
+        };
+
+        let mut decoder = Decoder::new(&bytes);
+        let mut entries = HashMap::new();
+        while !decoder.is_empty() {
+            let key = decoder.read_u64()?;
+            let depth = decoder.read_u8()?;
+            let games = decoder.read_u32()?;
+            let value = E::decode(&mut decoder)?;
+            entries.insert(key, ExperienceEntry {
+                value: value,
+                depth: depth,
+                games: games,
+            });
+        }
+
+        Ok(Experience {
+            entries: entries,
+            state: PhantomData,
+        })
+    }
+}
+
+/// Returns the evaluation of a game's result for `player`, shifted by the ply on which the
+/// game ended if `E` encodes win distance.
+fn result_evaluation<E>(player: usize, winner: Option<u8>, ply_count: usize) -> E where
+    E: Evaluation {
+    let ply_count = if E::encodes_win_distance() {
+        ply_count as i32
+    } else {
+        0
+    };
+
+    match winner {
+        Some(winner) if winner as usize == player => E::win().shift(-ply_count),
+        Some(_) => E::lose().shift(ply_count),
+        None => E::null(),
+    }
+}
+
+fn hash<S>(state: &S) -> u64 where
+    S: State {
+    let mut hasher = FnvHasher::default();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(all(test, feature = "with_tic_tac_toe"))]
+mod test {
+    use std::sync::Arc;
+
+    use analysis::{Evaluation as EvaluationTrait, Evaluator as EvaluatorTrait, Extrapolatable};
+    use analysis::search::{PvSearch, PvSearchAnalysis, Search};
+    use impls::tic_tac_toe::{Board, Evaluator};
+    use state::State;
+    use super::*;
+
+    type Value = <Evaluator as EvaluatorTrait>::Evaluation;
+
+    fn value(steps: i32) -> Value {
+        Value::null().shift(steps)
+    }
+
+    fn after(board: &Board, index: usize) -> Board {
+        let mut next = board.clone();
+        next.execute_ply(Some(&board.extrapolate()[index])).unwrap();
+        next
+    }
+
+    #[test]
+    fn test_learn() {
+        let first = Board::new();
+        let second = after(&after(&first, 0), 0);
+        let third = after(&after(&second, 0), 0);
+        let roots = [(first.clone(), value(3)), (second.clone(), value(-5)), (third.clone(), value(-5))];
+
+        // O wins on the seventh ply, but X only saw it coming after the first
+        let mut experience = Experience::<Board, Value>::new();
+        assert_eq!(experience.learn(&roots, Some(1), 7, 4, value(2)), 1);
+        assert_eq!(experience.get(&first), Some(&ExperienceEntry {
+            value: value(-5),
+            depth: 4,
+            games: 1,
+        }));
+        assert!(experience.get(&second).is_none());
+
+        // The last root is compared against the result
+        assert_eq!(experience.learn(&roots, Some(1), 7, 4, value(1)), 2);
+        assert_eq!(experience.get(&third).unwrap().value, Value::lose().shift(7));
+        assert_eq!(experience.get(&first).unwrap().games, 2);
+        assert_eq!(experience.len(), 2);
+    }
+
+    #[test]
+    fn test_search() {
+        let board = Board::new();
+        let mut search = PvSearch::with_depth(Evaluator, 2);
+
+        let first_ply = |search: &mut PvSearch<Board, Evaluator>| {
+            let analysis = search.search(&board, None);
+            let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, Evaluator>>().unwrap();
+            analysis.principal_variation[0].clone()
+        };
+
+        // Once the chosen ply is known to lose, the search avoids it
+        let chosen = first_ply(&mut search);
+        let mut next = board.clone();
+        next.execute_ply(Some(&chosen)).unwrap();
+
+        let mut experience = Experience::new();
+        experience.record(&next, Value::win(), 9);
+        search.set_experience(Some(Arc::new(experience)));
+        search.clear();
+        assert!(first_ply(&mut search) != chosen);
+    }
+
+    #[cfg(feature = "with_tak")]
+    #[test]
+    fn test_save_load() {
+        use analysis::Evaluator as EvaluatorTrait;
+        use impls::tak::State;
+        use impls::tak::evaluator::StaticEvaluator;
+
+        type Evaluation = <StaticEvaluator as EvaluatorTrait>::Evaluation;
+
+        let mut experience = Experience::<State, Evaluation>::new();
+        let mut state = State::new(5);
+        for _ in 0..3 {
+            experience.record(&state, Evaluation::lose().shift(40), 6);
+            state.execute_ply(Some(&state.extrapolate()[0])).unwrap();
+        }
+
+        let mut bytes = Vec::new();
+        experience.save(&mut bytes).unwrap();
+        let loaded = Experience::<State, Evaluation>::load(&bytes[..]).unwrap();
+        assert_eq!(loaded.entries, experience.entries);
+
+        bytes.truncate(bytes.len() - 1);
+        assert!(Experience::<State, Evaluation>::load(&bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+//! Writes training data in formats that external machine learning tools can read.
+//!
+//! A `Dataset` is a table with one row per position, holding the position's features, its
+//! label, and optionally the value of a search from it.  It can be written as CSV, or as a
+//! NumPy `.npy` file holding a single two-dimensional `float32` array with the same columns,
+//! which loads directly with `numpy.load`.
+
+use std::io::{self, Write};
+
+use analysis::{Extrapolatable, FeatureExtractor};
+use analysis::search::{PvSearch, PvSearchAnalysis, Search};
+use analysis::tuning::{LabeledPosition, Tunable};
+use state::State;
+
+/// A table of features, labels, and search values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dataset {
+    dimension: usize,
+    features: Vec<f32>,
+    labels: Vec<f32>,
+    search_values: Option<Vec<f32>>,
+}
+
+impl Dataset {
+    /// Creates an empty dataset whose rows have `dimension` features.
+    pub fn new(dimension: usize) -> Dataset {
+        Dataset {
+            dimension: dimension,
+            features: Vec::new(),
+            labels: Vec::new(),
+            search_values: None,
+        }
+    }
+
+    /// Creates a dataset from the features and labels of `positions`.
+    pub fn from_positions<S, X>(extractor: &X, positions: &[LabeledPosition<S>]) -> Dataset where
+        S: State,
+        X: FeatureExtractor<S> {
+        let mut dataset = Dataset::new(extractor.dimension());
+        for position in positions {
+            dataset.push(&extractor.feature_vector(&position.state), position.label as f32, None);
+        }
+        dataset
+    }
+
+    /// Creates a dataset from the features and labels of `positions`, along with the value of a
+    /// search of each to `depth` with `evaluator`, from the perspective of the player to move.
+    pub fn from_searched_positions<S, X, E>(extractor: &X, evaluator: &E, depth: u8, positions: &[LabeledPosition<S>]) -> Dataset where
+        S: 'static + State + Extrapolatable<<S as State>::Ply>,
+        X: FeatureExtractor<S>,
+        E: 'static + Tunable<State = S> + Clone {
+        let mut dataset = Dataset::new(extractor.dimension());
+        let mut search = PvSearch::with_depth(evaluator.clone(), depth);
+
+        for position in positions {
+            let analysis = search.search(&position.state, None);
+            let evaluation = analysis.as_any().downcast_ref::<PvSearchAnalysis<S, E>>().unwrap().evaluation;
+
+            dataset.push(
+                &extractor.feature_vector(&position.state),
+                position.label as f32,
+                Some(evaluator.evaluation_value(evaluation) as f32),
+            );
+        }
+        dataset
+    }
+
+    /// Adds a row.  Either every row or none of them must have a search value.
+    pub fn push(&mut self, features: &[f32], label: f32, search_value: Option<f32>) {
+        assert!(features.len() == self.dimension, "Incorrect number of features!");
+
+        match (&mut self.search_values, search_value) {
+            (&mut Some(ref mut values), Some(value)) => values.push(value),
+            (&mut None, None) => (),
+            (search_values, Some(value)) if self.labels.is_empty() => *search_values = Some(vec![value]),
+            _ => panic!("Search values must be given for every row or for none!"),
+        }
+
+        self.features.extend_from_slice(features);
+        self.labels.push(label);
+    }
+
+    /// Returns the number of rows.
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Returns the names of the columns: `f0` through `fN` for the features, then `label`,
+    /// then `search` if the rows have search values.
+    pub fn column_names(&self) -> Vec<String> {
+        let mut names = (0..self.dimension).map(|i| format!("f{}", i)).collect::<Vec<_>>();
+        names.push(String::from("label"));
+        if self.search_values.is_some() {
+            names.push(String::from("search"));
+        }
+        names
+    }
+
+    /// Writes the dataset as CSV, with a header row of column names.
+    pub fn write_csv<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
+        writeln!(writer, "{}", self.column_names().join(","))?;
+
+        for row in 0..self.len() {
+            for value in self.row(row) {
+                write!(writer, "{},", value)?;
+            }
+            write!(writer, "{}", self.labels[row])?;
+            if let Some(ref search_values) = self.search_values {
+                write!(writer, ",{}", search_values[row])?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the dataset as a version 1.0 `.npy` file holding a `float32` array with one row
+    /// per position and the columns given by `column_names`.
+    pub fn write_npy<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
+        let columns = self.column_names().len();
+
+        let mut header = format!(
+            "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+            self.len(), columns,
+        );
+
+        // The magic string, version, and header length take ten bytes, and the data must start
+        // on a 64-byte boundary.  The header is padded with spaces and ends with a newline.
+        let padding = 63 - (10 + header.len()) % 64;
+        for _ in 0..padding {
+            header.push(' ');
+        }
+        header.push('\n');
+
+        writer.write_all(b"\x93NUMPY\x01\x00")?;
+        writer.write_all(&[header.len() as u8, (header.len() >> 8) as u8])?;
+        writer.write_all(header.as_bytes())?;
+
+        let mut bytes = Vec::with_capacity(self.len() * columns * 4);
+        for row in 0..self.len() {
+            let search_value = self.search_values.as_ref().map(|values| values[row]);
+            for &value in self.row(row).iter().chain(Some(self.labels[row]).iter()).chain(search_value.iter()) {
+                let bits = value.to_bits();
+                bytes.extend_from_slice(&[bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8]);
+            }
+        }
+        writer.write_all(&bytes)
+    }
+
+    fn row(&self, row: usize) -> &[f32] {
+        &self.features[row * self.dimension..(row + 1) * self.dimension]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dataset() -> Dataset {
+        let mut dataset = Dataset::new(2);
+        dataset.push(&[0.0, 1.0], 1.0, Some(0.25));
+        dataset.push(&[0.5, -2.0], 0.5, Some(-1.5));
+        dataset
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut csv = Vec::new();
+        dataset().write_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "f0,f1,label,search\n0,1,1,0.25\n0.5,-2,0.5,-1.5\n");
+    }
+
+    #[test]
+    fn test_npy() {
+        let mut npy = Vec::new();
+        dataset().write_npy(&mut npy).unwrap();
+
+        assert_eq!(&npy[0..8], b"\x93NUMPY\x01\x00");
+        let header_length = npy[8] as usize | (npy[9] as usize) << 8;
+        assert_eq!((10 + header_length) % 64, 0);
+
+        let header = String::from_utf8(npy[10..10 + header_length].to_vec()).unwrap();
+        assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (2, 4), }"));
+        assert!(header.ends_with('\n'));
+
+        let data = &npy[10 + header_length..];
+        assert_eq!(data.len(), 2 * 4 * 4);
+        assert_eq!(&data[28..32], &[0, 0, 0xC0, 0xBF]);
+    }
+}
@@ -17,6 +17,8 @@
 // Copyright 2016-2017 Chris Foster
 //
 
+use std::vec::Vec;
+
 use ply::Ply;
 
 /// Provides the possible plies from a given state.
@@ -25,4 +27,31 @@ pub trait Extrapolatable<P> where
     /// Returns a list of plies that should be considered from the current state.  The search
     /// system does not assume that all plies returned are correct.
     fn extrapolate(&self) -> Vec<P>;
+
+    /// Clears `plies` and fills it with the plies that `extrapolate` would return, so that a
+    /// caller can reuse one buffer across many states.  The default implementation calls
+    /// `extrapolate`; implementations that generate many plies should override it to avoid
+    /// the allocation.
+    fn extrapolate_into(&self, plies: &mut Vec<P>) {
+        plies.clear();
+        plies.append(&mut self.extrapolate());
+    }
+}
+
+/// Provides the possible plies from a given state in stages, ordered roughly from the most to
+/// the least promising, so that a search can often find a cutoff before generating every ply.
+pub trait StagedExtrapolatable<P>: Extrapolatable<P> where
+    P: Ply {
+    /// Clears `plies` and fills it with the plies of stage `stage`, counting from 0.  Returns
+    /// `false` if there is no such stage.  Together, the stages should produce the same plies
+    /// as `extrapolate`, each exactly once.
+    fn extrapolate_stage(&self, stage: usize, plies: &mut Vec<P>) -> bool;
+}
+
+/// Recognizes forcing plies, whose consequences a search should look further into than usual.
+pub trait ThreatDetector<P>: Extrapolatable<P> where
+    P: Ply {
+    /// Returns `true` if `ply`, which was just made to reach this state, is forcing, as when it
+    /// threatens to win on its player's next turn.
+    fn is_forcing(&self, ply: &P) -> bool;
 }
@@ -0,0 +1,40 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use state::State;
+
+/// Describes a state as a fixed-length list of numeric features.
+///
+/// This is the interface between a game implementation and anything that learns from its states,
+/// such as a trained evaluator, a training pipeline, or an external machine learning framework.
+pub trait FeatureExtractor<S> where
+    S: State {
+    /// Returns the number of features written by `features`.
+    fn dimension(&self) -> usize;
+
+    /// Writes the features of `state` into `out`, which must be `dimension()` long.
+    fn features(&self, state: &S, out: &mut [f32]);
+
+    /// Returns the features of `state` in a new vector.
+    fn feature_vector(&self, state: &S) -> Vec<f32> {
+        let mut features = vec![0.0; self.dimension()];
+        self.features(state, &mut features);
+        features
+    }
+}
@@ -0,0 +1,368 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+//! Randomized testing of `State` implementations.
+//!
+//! A `Fuzzer` plays random lines of legal plies from a starting state and checks the
+//! invariants that searches rely on after every ply: reverting a ply restores the previous
+//! state, executing it again restores the next one, equal states hash equally and resolve
+//! alike, ply counts advance by one, and no ply follows a resolution.  When a line breaks an invariant, the
+//! fuzzer shrinks it to as few plies as still break one, so the failure is easy to follow:
+//!
+//! ```rust,ignore
+//! #[test]
+//! fn test_fuzz() {
+//!     if let Err(failure) = Fuzzer::new(Board::new(), 40).run(1000) {
+//!         panic!("{}", failure);
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use fnv::FnvHasher;
+use rand::Rng;
+
+use analysis::Extrapolatable;
+use resolution::Resolution;
+use state::State;
+use util::JKiss32Rng;
+
+/// Plays random lines of plies from a state, checking the invariants of its implementation.
+pub struct Fuzzer<S> where
+    S: State + Extrapolatable<<S as State>::Ply> {
+    start: S,
+    length: usize,
+    seed: u64,
+}
+
+/// A line of plies that breaks an invariant.
+#[derive(Clone, Debug)]
+pub struct FuzzFailure<P> {
+    /// The seed of the line before it was minimized.
+    pub seed: u64,
+    /// The minimized line, from the fuzzer's starting state.
+    pub plies: Vec<P>,
+    /// A description of the broken invariant.
+    pub message: String,
+}
+
+impl<S> Fuzzer<S> where
+    S: State + Extrapolatable<<S as State>::Ply> {
+    /// Creates a fuzzer that plays lines of up to `length` plies from `start`.
+    pub fn new(start: S, length: usize) -> Fuzzer<S> {
+        Fuzzer {
+            start: start,
+            length: length,
+            seed: 0,
+        }
+    }
+
+    /// Sets the seed of the first line.  Each later line uses the next seed.  Defaults to 0.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Plays `lines` random lines, returning the first failure, minimized.
+    pub fn run(&self, lines: usize) -> Result<(), FuzzFailure<<S as State>::Ply>> {
+        for seed in self.seed..self.seed + lines as u64 {
+            let plies = self.random_line(seed);
+            if let Err(message) = self.check(&plies) {
+                let (plies, message) = self.minimize(plies, message);
+                return Err(FuzzFailure {
+                    seed: seed,
+                    plies: plies,
+                    message: message,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a random line of legal plies from the starting state, ending early if the game
+    /// is resolved or there are no legal plies.
+    pub fn random_line(&self, seed: u64) -> Vec<<S as State>::Ply> {
+        let mut rng = JKiss32Rng::with_seed(seed);
+        let mut state = self.start.clone();
+        let mut plies = Vec::new();
+
+        while plies.len() < self.length && state.check_resolution().is_none() {
+            let mut candidates = state.extrapolate();
+            rng.shuffle(&mut candidates);
+            match candidates.into_iter().find(|ply| state.execute_ply(Some(ply)).is_ok()) {
+                Some(ply) => plies.push(ply),
+                None => break,
+            }
+        }
+
+        plies
+    }
+
+    /// Plays `plies` from the starting state, checking the invariants along the way.  Returns
+    /// a description of the first broken invariant.  Plies that can't be executed are reported
+    /// as failures too.
+    pub fn check(&self, plies: &[<S as State>::Ply]) -> Result<(), String> {
+        let mut state = self.start.clone();
+        check_hash(&state)?;
+
+        for (index, ply) in plies.iter().enumerate() {
+            if state.check_resolution().is_some() {
+                return Err(format!("Ply {} ({}) follows a resolution", index, ply));
+            }
+
+            let before = state.clone();
+            if let Err(error) = state.execute_ply(Some(ply)) {
+                return Err(format!("Ply {} ({}) could not be executed: {}", index, ply, error));
+            }
+            check_hash(&state)?;
+            if state.get_ply_count() != before.get_ply_count() + 1 {
+                return Err(format!("Ply {} ({}) advanced the ply count from {} to {}", index, ply, before.get_ply_count(), state.get_ply_count()));
+            }
+
+            let after = state.clone();
+            if let Err(error) = state.revert_ply(Some(ply)) {
+                return Err(format!("Ply {} ({}) could not be reverted: {}", index, ply, error));
+            }
+            check_same(&state, &before, &format!("Reverting ply {} ({})", index, ply))?;
+
+            if let Err(error) = state.execute_ply(Some(ply)) {
+                return Err(format!("Ply {} ({}) could not be executed again after reverting it: {}", index, ply, error));
+            }
+            check_same(&state, &after, &format!("Executing ply {} ({}) again", index, ply))?;
+        }
+
+        Ok(())
+    }
+
+    /// Shrinks `plies`, which break an invariant as described by `message`, to as few plies
+    /// as still break one.  Returns the shrunken line and the description of its failure.
+    pub fn minimize(&self, mut plies: Vec<<S as State>::Ply>, mut message: String) -> (Vec<<S as State>::Ply>, String) {
+        // The shortest failing prefix first, since lines fail at the first broken invariant
+        for length in 0..plies.len() {
+            if let Err(prefix_message) = self.check(&plies[..length]) {
+                plies.truncate(length);
+                message = prefix_message;
+                break;
+            }
+        }
+
+        // Then drop whatever single plies can be dropped, as long as the rest are still legal
+        let mut shrunk = true;
+        while shrunk {
+            shrunk = false;
+            for index in (0..plies.len()).rev() {
+                let mut candidate = plies.clone();
+                candidate.remove(index);
+                if !self.is_legal(&candidate) {
+                    continue;
+                }
+                if let Err(candidate_message) = self.check(&candidate) {
+                    plies = candidate;
+                    message = candidate_message;
+                    shrunk = true;
+                }
+            }
+        }
+
+        (plies, message)
+    }
+
+    /// Returns true if each of `plies` can be executed in turn from the starting state,
+    /// before the game is resolved.
+    fn is_legal(&self, plies: &[<S as State>::Ply]) -> bool {
+        let mut state = self.start.clone();
+        plies.iter().all(|ply| state.check_resolution().is_none() && state.execute_ply(Some(ply)).is_ok())
+    }
+}
+
+impl<P> fmt::Display for FuzzFailure<P> where
+    P: fmt::Display {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (seed {}, plies:", self.message, self.seed)?;
+        for ply in &self.plies {
+            write!(f, " {}", ply)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Checks that `state` hashes the same as a copy of itself.
+fn check_hash<S>(state: &S) -> Result<(), String> where
+    S: State {
+    if hash(state) != hash(&state.clone()) {
+        return Err(format!("A copy of the state hashes differently\n{}", state));
+    }
+    Ok(())
+}
+
+/// Checks that `state` equals `expected`, and hashes and resolves the same.
+fn check_same<S>(state: &S, expected: &S, action: &str) -> Result<(), String> where
+    S: State {
+    if state != expected {
+        return Err(format!("{} produced\n{}\ninstead of\n{}", action, state, expected));
+    }
+    if hash(state) != hash(expected) {
+        return Err(format!("{} produced a state with a different hash\n{}", action, state));
+    }
+    let same_resolution = match (state.check_resolution(), expected.check_resolution()) {
+        (Some(ref a), Some(ref b)) => same_outcome(a, b),
+        (None, None) => true,
+        _ => false,
+    };
+    if !same_resolution {
+        return Err(format!("{} produced a state with a different resolution\n{}", action, state));
+    }
+    Ok(())
+}
+
+fn same_outcome<R>(a: &R, b: &R) -> bool where
+    R: Resolution {
+    a.get_winner() == b.get_winner() && a.is_draw() == b.is_draw()
+}
+
+fn hash<S>(state: &S) -> u64 where
+    S: Hash {
+    let mut hasher = FnvHasher::default();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt;
+
+    use analysis::Extrapolatable;
+    use ply::Ply;
+    use resolution::Resolution;
+    use state::{PlyError, State};
+    use super::*;
+
+    /// Players add one to three to a running total, and whoever reaches 20 wins.  With the
+    /// `buggy` flag, reverting a 3 from a total over 6 only subtracts 2.
+    #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+    struct Count {
+        total: u8,
+        ply_count: u8,
+        buggy: bool,
+    }
+
+    #[derive(Clone, Debug, Hash, PartialEq)]
+    struct Add(u8);
+
+    struct Win(u8);
+
+    impl Ply for Add { }
+
+    impl Resolution for Win {
+        fn get_winner(&self) -> Option<u8> { Some(self.0) }
+        fn is_draw(&self) -> bool { false }
+    }
+
+    impl State for Count {
+        type Ply = Add;
+        type Resolution = Win;
+
+        fn get_ply_count(&self) -> usize {
+            self.ply_count as usize
+        }
+
+        fn execute_ply(&mut self, ply: Option<&Add>) -> Result<(), PlyError> {
+            if let Some(&Add(amount)) = ply {
+                self.total += amount;
+            }
+            self.ply_count += 1;
+            Ok(())
+        }
+
+        fn revert_ply(&mut self, ply: Option<&Add>) -> Result<(), PlyError> {
+            if let Some(&Add(amount)) = ply {
+                self.total -= if self.buggy && amount == 3 && self.total > 6 { 2 } else { amount };
+            }
+            self.ply_count -= 1;
+            Ok(())
+        }
+
+        fn check_resolution(&self) -> Option<Win> {
+            if self.total >= 20 {
+                Some(Win((self.ply_count + 1) % 2))
+            } else {
+                None
+            }
+        }
+    }
+
+    impl Extrapolatable<Add> for Count {
+        fn extrapolate(&self) -> Vec<Add> {
+            vec![Add(1), Add(2), Add(3)]
+        }
+    }
+
+    impl fmt::Display for Add {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "+{}", self.0) }
+    }
+
+    impl fmt::Display for Win {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "Player {} wins", self.0 + 1) }
+    }
+
+    impl fmt::Display for Count {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "Total: {}", self.total) }
+    }
+
+    fn count(buggy: bool) -> Count {
+        Count {
+            total: 0,
+            ply_count: 0,
+            buggy: buggy,
+        }
+    }
+
+    #[test]
+    fn test_minimize() {
+        assert!(Fuzzer::new(count(false), 20).run(100).is_ok());
+
+        let failure = Fuzzer::new(count(true), 20).run(100).unwrap_err();
+        assert_eq!(failure.plies.len(), 3);
+        assert_eq!(failure.plies[2], Add(3));
+        assert!(failure.plies.iter().map(|ply| ply.0).sum::<u8>() > 6);
+        assert!(failure.message.starts_with("Reverting ply 2 (+3)"));
+    }
+
+    #[cfg(feature = "with_tak")]
+    #[test]
+    fn test_tak() {
+        use impls::tak::State;
+
+        for &size in &[3, 5] {
+            if let Err(failure) = Fuzzer::new(State::new(size), 80).run(50) {
+                panic!("{}", failure);
+            }
+        }
+    }
+
+    #[cfg(feature = "with_tic_tac_toe")]
+    #[test]
+    fn test_tic_tac_toe() {
+        use impls::tic_tac_toe::Board;
+
+        if let Err(failure) = Fuzzer::new(Board::new(), 9).run(100) {
+            panic!("{}", failure);
+        }
+    }
+}
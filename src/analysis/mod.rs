@@ -19,11 +19,49 @@
 
 //! Contains the traits and tools to provide analysis of zero-sum games.
 
+//!
+//! Only the evaluation traits and `Extrapolatable` are available without the `std` feature.
+
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "with_fuzz")]
+pub mod fuzz;
+#[cfg(feature = "std")]
 pub mod search;
+#[cfg(feature = "std")]
+pub mod test_utils;
+#[cfg(feature = "std")]
+pub mod tuning;
 
-pub use self::evaluator::{Evaluation, Evaluator};
-pub use self::extrapolatable::Extrapolatable;
+pub use self::evaluator::{Evaluation, EvaluationBreakdown, Evaluator, ExplainableEvaluator, IncrementalEvaluator, ScoreModel};
+#[cfg(feature = "std")]
+pub use self::evaluator_cache::EvaluatorCache;
+#[cfg(feature = "std")]
+pub use self::experience::{Experience, ExperienceEntry};
+pub use self::extrapolatable::{Extrapolatable, StagedExtrapolatable, ThreatDetector};
+#[cfg(feature = "std")]
+pub use self::features::FeatureExtractor;
+#[cfg(feature = "std")]
+pub use self::repetition::RepetitionTracker;
+#[cfg(feature = "std")]
+pub use self::session::{EngineMove, EngineSession, TimeControl};
+#[cfg(feature = "std")]
+pub use self::tablebase::{Tablebase, TablebaseValue};
 
 #[macro_use]
 mod evaluator;
+#[cfg(feature = "std")]
+mod evaluator_cache;
+#[cfg(feature = "std")]
+mod experience;
 mod extrapolatable;
+#[cfg(feature = "std")]
+mod features;
+#[cfg(feature = "std")]
+mod repetition;
+#[cfg(feature = "std")]
+mod session;
+#[cfg(feature = "std")]
+mod tablebase;
@@ -0,0 +1,183 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+use std::collections::HashMap;
+use std::hash::Hasher;
+
+use fnv::FnvHasher;
+
+use position_key::PositionKey;
+use state::State;
+
+/// Counts how many times each state has occurred in a game, for adjudicating draws by
+/// repetition, as with chess's threefold repetition rule.
+///
+/// States are keyed by their hashes, so states that hash equally, like those that differ only
+/// in their ply counts, count as repetitions of each other.  Games in which the history of a
+/// position matters should account for it in their `Hash` implementation.  States that
+/// implement `PositionKey` can be tracked by their keys instead, with `push_position` and
+/// `get_position_count`; a tracker should be used with one or the other, not both.
+///
+/// Each state is pushed as it's reached and popped as it's reverted, so a tracker can follow
+/// a game as it's played, or the path from the root of a search.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let mut repetitions = RepetitionTracker::new(3);
+/// repetitions.push(&state);
+/// while !repetitions.is_draw() {
+///     state.execute_ply(Some(&next_ply(&state))).unwrap();
+///     repetitions.push(&state);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RepetitionTracker {
+    limit: usize,
+    counts: HashMap<u64, usize>,
+    history: Vec<u64>,
+}
+
+impl RepetitionTracker {
+    /// Creates a tracker that calls a draw once any state has occurred `limit` times.
+    /// A `limit` of zero never calls a draw.
+    pub fn new(limit: usize) -> RepetitionTracker {
+        RepetitionTracker {
+            limit: limit,
+            counts: HashMap::new(),
+            history: Vec::new(),
+        }
+    }
+
+    /// Records an occurrence of `state`, returning the number of times it has now occurred.
+    pub fn push<S>(&mut self, state: &S) -> usize where
+        S: State {
+        self.push_key(hash(state))
+    }
+
+    /// Records an occurrence of `state` under its position key, returning the number of times
+    /// it has now occurred.
+    pub fn push_position<S>(&mut self, state: &S) -> usize where
+        S: PositionKey {
+        self.push_key(state.position_key())
+    }
+
+    fn push_key(&mut self, key: u64) -> usize {
+        self.history.push(key);
+
+        let count = self.counts.entry(key).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    /// Forgets the most recent occurrence, as when its ply is reverted.
+    pub fn pop(&mut self) {
+        if let Some(key) = self.history.pop() {
+            let remove = match self.counts.get_mut(&key) {
+                Some(count) => {
+                    *count -= 1;
+                    *count == 0
+                },
+                None => false,
+            };
+            if remove {
+                self.counts.remove(&key);
+            }
+        }
+    }
+
+    /// Returns the number of times `state` has occurred.
+    pub fn get_count<S>(&self, state: &S) -> usize where
+        S: State {
+        *self.counts.get(&hash(state)).unwrap_or(&0)
+    }
+
+    /// Returns the number of times `state` has occurred, as recorded by `push_position`.
+    pub fn get_position_count<S>(&self, state: &S) -> usize where
+        S: PositionKey {
+        *self.counts.get(&state.position_key()).unwrap_or(&0)
+    }
+
+    /// Returns true if the most recent state has occurred often enough to draw the game.
+    pub fn is_draw(&self) -> bool {
+        match self.history.last() {
+            Some(key) if self.limit > 0 => self.counts[key] >= self.limit,
+            _ => false,
+        }
+    }
+
+    /// Returns the number of states recorded.
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Returns true if no states are recorded.
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    /// Forgets every state.
+    pub fn clear(&mut self) {
+        self.counts.clear();
+        self.history.clear();
+    }
+}
+
+fn hash<S>(state: &S) -> u64 where
+    S: State {
+    let mut hasher = FnvHasher::default();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(all(test, feature = "with_tak"))]
+mod test {
+    use impls::tak::State;
+    use state::State as StateTrait;
+    use super::RepetitionTracker;
+
+    #[test]
+    fn test_repetition() {
+        // Tak's hash ignores the ply count, so a pair of null moves repeats the position
+        let mut state = State::new(5);
+        let mut repetitions = RepetitionTracker::new(3);
+
+        assert_eq!(repetitions.push(&state), 1);
+        for i in 0..4 {
+            state.execute_ply(None).unwrap();
+            assert_eq!(repetitions.push(&state), (i + 1) / 2 + 1);
+            assert_eq!(repetitions.is_draw(), i == 3);
+        }
+        assert_eq!(repetitions.len(), 5);
+
+        repetitions.pop();
+        state.revert_ply(None).unwrap();
+        assert_eq!(repetitions.get_count(&state), 2);
+        assert!(!repetitions.is_draw());
+
+        repetitions.clear();
+        assert!(repetitions.is_empty());
+        assert_eq!(repetitions.get_count(&state), 0);
+
+        let mut unlimited = RepetitionTracker::new(0);
+        for _ in 0..4 {
+            unlimited.push(&state);
+        }
+        assert!(!unlimited.is_draw());
+    }
+}
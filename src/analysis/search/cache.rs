@@ -0,0 +1,476 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+
+use fnv::FnvHasher;
+
+use analysis::Extrapolatable;
+use analysis::search::{Analysis, AnalysisReport, Search};
+use persist::{Decoder, Encode, Persistable, PersistError, Reader, Writer};
+use ply::Ply;
+use position_key::PositionKey;
+use state::State;
+
+const CACHE_MAGIC: &'static [u8; 4] = b"ZSAC";
+const CACHE_VERSION: u32 = 2;
+
+/// The number of entries written to each section of a saved cache.
+const SECTION_ENTRIES: usize = 1024;
+
+/// A bounded map from position keys to the reports of completed analyses, along with their
+/// principal variations, for interfaces that revisit the same positions, such as when stepping
+/// back and forth through a game.  Once full, storing a new position evicts the least recently
+/// used one.
+///
+/// The cache knows nothing of the search that produced its reports, so it should be
+/// cleared when the search's settings change.
+pub struct AnalysisCache<P> where
+    P: Ply {
+    capacity: usize,
+    entries: HashMap<u64, (CachedAnalysis<P>, u64)>,
+    clock: u64,
+}
+
+impl<P> AnalysisCache<P> where
+    P: Ply {
+    /// Creates a cache holding at most `capacity` analyses.
+    pub fn new(capacity: usize) -> AnalysisCache<P> {
+        AnalysisCache {
+            capacity: capacity,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the analysis stored for `key`, marking it as the most recently used.
+    pub fn get(&mut self, key: u64) -> Option<&CachedAnalysis<P>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.entries.get_mut(&key).map(|entry| {
+            entry.1 = clock;
+            &entry.0
+        })
+    }
+
+    /// Stores `analysis` for `key`, replacing any analysis already there, and evicting the
+    /// least recently used analysis if the cache is full.
+    pub fn insert(&mut self, key: u64, analysis: CachedAnalysis<P>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let oldest = self.entries.iter().min_by_key(|&(_, entry)| entry.1).map(|(&key, _)| key);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.clock += 1;
+        self.entries.insert(key, (analysis, self.clock));
+    }
+
+    /// Removes and returns the analysis stored for `key`.
+    pub fn remove(&mut self, key: u64) -> Option<CachedAnalysis<P>> {
+        self.entries.remove(&key).map(|entry| entry.0)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<P> AnalysisCache<P> where
+    P: Ply + Persistable {
+    /// Writes every analysis to `writer`, from least to most recently used.
+    pub fn save<W>(&self, writer: W) -> Result<(), PersistError> where W: Write {
+        let mut writer = Writer::new(writer, CACHE_MAGIC, CACHE_VERSION)?;
+
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|&(_, entry)| entry.1);
+
+        for section in entries.chunks(SECTION_ENTRIES) {
+            let mut bytes = Vec::new();
+            bytes.write_u32(section.len() as u32);
+            for &(&key, entry) in section {
+                bytes.write_u64(key);
+                bytes.write_u64(entry.0.state_hash);
+                entry.0.report.encode(&mut bytes);
+                bytes.write_u32(entry.0.principal_variation.len() as u32);
+                for ply in &entry.0.principal_variation {
+                    ply.encode(&mut bytes);
+                }
+            }
+            writer.write_section(&bytes)?;
+        }
+
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Stores every analysis written by `save`, as the most recently used and in the order
+    /// they were used.  Returns the number of analyses loaded.
+    ///
+    /// If the file is damaged, the analyses before the damage are still loaded before the
+    /// error is returned.
+    pub fn load<R>(&mut self, reader: R) -> Result<usize, PersistError> where R: Read {
+        let mut reader = Reader::new(reader, CACHE_MAGIC, CACHE_VERSION)?;
+        let mut loaded = 0;
+
+        while let Some(bytes) = reader.read_section()? {
+            let mut decoder = Decoder::new(&bytes);
+            let count = decoder.read_u32()?;
+
+            for _ in 0..count {
+                let key = decoder.read_u64()?;
+                let state_hash = decoder.read_u64()?;
+                let report = AnalysisReport::decode(&mut decoder)?;
+                let length = decoder.read_u32()?;
+                let mut principal_variation = Vec::new();
+                for _ in 0..length {
+                    principal_variation.push(P::decode(&mut decoder)?);
+                }
+
+                self.insert(key, CachedAnalysis {
+                    state_hash: state_hash,
+                    report: report,
+                    principal_variation: principal_variation,
+                });
+                loaded += 1;
+            }
+
+            if !decoder.is_empty() {
+                return Err(PersistError::Malformed(String::from("Unexpected data after cache entries")));
+            }
+        }
+
+        Ok(loaded)
+    }
+
+    /// Saves the cache to the file at `path`, so that a later session can pick it up with
+    /// `load_file`.
+    pub fn save_file<Q>(&self, path: Q) -> Result<(), PersistError> where Q: AsRef<Path> {
+        let file = File::create(path)?;
+        self.save(BufWriter::new(file))
+    }
+
+    /// Loads a cache saved by `save_file`, as with `load`.
+    pub fn load_file<Q>(&mut self, path: Q) -> Result<usize, PersistError> where Q: AsRef<Path> {
+        let file = File::open(path)?;
+        self.load(BufReader::new(file))
+    }
+}
+
+/// An analysis answered from an `AnalysisCache`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedAnalysis<P> where
+    P: Ply {
+    /// The hash of the analyzed state, independent of its position key, so that a
+    /// `CachedSearch` can tell apart states whose keys collide.
+    pub state_hash: u64,
+    /// The report of the analysis when it was cached.
+    pub report: AnalysisReport,
+    /// The principal variation of the analysis, as its search gave it.
+    pub principal_variation: Vec<P>,
+}
+
+impl<P> fmt::Display for CachedAnalysis<P> where
+    P: Ply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.report)
+    }
+}
+
+impl<P> Analysis for CachedAnalysis<P> where
+    P: 'static + Ply {
+    fn as_any(&self) -> &Any {
+        self
+    }
+
+    fn report(&self) -> AnalysisReport {
+        self.report.clone()
+    }
+}
+
+/// A search that answers from an `AnalysisCache` when it can, and otherwise runs the search
+/// it wraps, caching the report and principal variation of each analysis that completes.
+/// Analyses from the cache are `CachedAnalysis`es.  A cached analysis is only used if the
+/// state's hash matches as well as its position key.
+pub struct CachedSearch<S> where
+    S: State + Extrapolatable<<S as State>::Ply> {
+    search: Box<Search<S>>,
+    cache: AnalysisCache<<S as State>::Ply>,
+}
+
+impl<S> CachedSearch<S> where
+    S: State + Extrapolatable<<S as State>::Ply> {
+    /// Wraps `search` with a cache of at most `capacity` analyses.
+    pub fn new(search: Box<Search<S>>, capacity: usize) -> CachedSearch<S> {
+        CachedSearch {
+            search: search,
+            cache: AnalysisCache::new(capacity),
+        }
+    }
+
+    pub fn get_cache(&self) -> &AnalysisCache<<S as State>::Ply> {
+        &self.cache
+    }
+
+    /// Returns the cache, e.g. to save it, or to load one saved earlier.
+    pub fn get_cache_mut(&mut self) -> &mut AnalysisCache<<S as State>::Ply> {
+        &mut self.cache
+    }
+}
+
+impl<S> Search<S> for CachedSearch<S> where
+    S: State + Extrapolatable<<S as State>::Ply> + PositionKey,
+    <S as State>::Ply: 'static {
+    fn search(&mut self, state: &S, interrupt: Option<Receiver<()>>) -> Box<Analysis> {
+        let key = state.position_key();
+        let state_hash = {
+            let mut hasher = FnvHasher::default();
+            state.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        match self.cache.get(key) {
+            Some(analysis) if analysis.state_hash == state_hash => return Box::new(analysis.clone()),
+            _ => (),
+        }
+
+        let analysis = self.search.search(state, interrupt);
+        if analysis.is_complete() {
+            self.cache.insert(key, CachedAnalysis {
+                state_hash: state_hash,
+                report: analysis.report(),
+                principal_variation: self.search.get_principal_variation(&*analysis),
+            });
+        }
+        analysis
+    }
+
+    fn get_principal_variation(&self, analysis: &Analysis) -> Vec<<S as State>::Ply> {
+        match analysis.as_any().downcast_ref::<CachedAnalysis<<S as State>::Ply>>() {
+            Some(analysis) => analysis.principal_variation.clone(),
+            None => self.search.get_principal_variation(analysis),
+        }
+    }
+
+    /// Clears the cache along with the wrapped search.
+    fn clear(&mut self) {
+        self.search.clear();
+        self.cache.clear();
+    }
+}
+
+impl Persistable for AnalysisReport {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        encode_optional_string(&self.best_move, bytes);
+        encode_string(&self.score, bytes);
+        bytes.write_u32(self.pv.len() as u32);
+        for ply in &self.pv {
+            encode_string(ply, bytes);
+        }
+        bytes.write_u64(self.depth as u64);
+        bytes.write_u64(self.nodes);
+        bytes.write_u32(self.time.to_bits());
+        encode_optional_string(&self.resolution, bytes);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<AnalysisReport, PersistError> {
+        let best_move = decode_optional_string(decoder)?;
+        let score = decode_string(decoder)?;
+        let pv_length = decoder.read_u32()?;
+        let mut pv = Vec::new();
+        for _ in 0..pv_length {
+            pv.push(decode_string(decoder)?);
+        }
+
+        Ok(AnalysisReport {
+            best_move: best_move,
+            score: score,
+            pv: pv,
+            depth: decoder.read_u64()? as usize,
+            nodes: decoder.read_u64()?,
+            time: f32::from_bits(decoder.read_u32()?),
+            resolution: decode_optional_string(decoder)?,
+        })
+    }
+}
+
+fn encode_string(string: &str, bytes: &mut Vec<u8>) {
+    bytes.write_u32(string.len() as u32);
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+fn decode_string(decoder: &mut Decoder) -> Result<String, PersistError> {
+    let length = decoder.read_u32()? as usize;
+    String::from_utf8(decoder.read_bytes(length)?.to_vec()).map_err(|_| PersistError::Malformed(String::from("Invalid UTF-8 in string")))
+}
+
+fn encode_optional_string(string: &Option<String>, bytes: &mut Vec<u8>) {
+    match *string {
+        Some(ref string) => {
+            bytes.write_u8(1);
+            encode_string(string, bytes);
+        },
+        None => bytes.write_u8(0),
+    }
+}
+
+fn decode_optional_string(decoder: &mut Decoder) -> Result<Option<String>, PersistError> {
+    match decoder.read_u8()? {
+        0 => Ok(None),
+        1 => decode_string(decoder).map(Some),
+        _ => Err(PersistError::Malformed(String::from("Invalid optional string"))),
+    }
+}
+
+#[cfg(all(test, feature = "with_tak"))]
+mod test {
+    use std::sync::mpsc;
+
+    use analysis::search::{AnalysisReport, PvSearch, Search};
+    use impls::tak::{evaluator, Color, Ply, State};
+    use position_key::PositionKey;
+    use super::*;
+
+    fn analysis(best_move: &str) -> CachedAnalysis<Ply> {
+        CachedAnalysis {
+            state_hash: 42,
+            report: AnalysisReport {
+                best_move: Some(String::from(best_move)),
+                score: String::from("12"),
+                pv: vec![String::from(best_move), String::from("b2")],
+                depth: 3,
+                nodes: 456,
+                time: 0.25,
+                resolution: None,
+            },
+            principal_variation: vec![
+                Ply::from_ptn(best_move, Color::White).unwrap(),
+                Ply::from_ptn("b2", Color::Black).unwrap(),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut cache = AnalysisCache::new(2);
+        cache.insert(1, analysis("a1"));
+        cache.insert(2, analysis("a2"));
+
+        // Using the first makes the second the one to go
+        assert!(cache.get(1).is_some());
+        cache.insert(3, analysis("a3"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(2).is_none());
+        assert_eq!(cache.get(1), Some(&analysis("a1")));
+
+        // Replacing an analysis doesn't evict anything
+        cache.insert(1, analysis("b1"));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(1), Some(&analysis("b1")));
+        assert!(cache.get(3).is_some());
+
+        let mut empty = AnalysisCache::new(0);
+        empty.insert(1, analysis("a1"));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut cache = AnalysisCache::new(3);
+        let mut other = analysis("a1");
+        other.report.best_move = None;
+        other.report.resolution = Some(String::from("White wins"));
+        other.principal_variation.clear();
+        cache.insert(1, other.clone());
+        cache.insert(2, analysis("a2"));
+        cache.insert(3, analysis("a3"));
+        cache.get(1);
+
+        let mut bytes = Vec::new();
+        cache.save(&mut bytes).unwrap();
+
+        // Loading into a smaller cache keeps the most recently used
+        let mut loaded = AnalysisCache::new(2);
+        assert_eq!(loaded.load(&bytes[..]).unwrap(), 3);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.get(1), Some(&other));
+        assert_eq!(loaded.get(3), Some(&analysis("a3")));
+
+        let length = bytes.len();
+        bytes[length - 9] ^= 0xFF;
+        assert!(AnalysisCache::<Ply>::new(3).load(&bytes[..]).unwrap_err().is_corruption());
+    }
+
+    #[test]
+    fn test_cached_search() {
+        let state = State::new(5);
+        let mut search = CachedSearch::new(Box::new(PvSearch::with_depth(evaluator::StaticEvaluator::new(), 2)), 4);
+
+        let analysis = search.search(&state, None);
+        let principal_variation = search.get_principal_variation(&*analysis);
+        assert!(analysis.as_any().downcast_ref::<CachedAnalysis<Ply>>().is_none());
+        assert!(!principal_variation.is_empty());
+        assert_eq!(search.get_cache().len(), 1);
+
+        // A revisited position is answered from the cache, plies and all
+        let cached = search.search(&state, None);
+        assert!(cached.as_any().downcast_ref::<CachedAnalysis<Ply>>().is_some());
+        assert_eq!(cached.report(), analysis.report());
+        assert_eq!(search.get_principal_variation(&*cached), principal_variation);
+        assert_eq!(search.get_cache_mut().get(state.position_key()).unwrap().principal_variation, principal_variation);
+
+        // Interrupted analyses aren't cached
+        search.clear();
+        let (sender, receiver) = mpsc::channel();
+        sender.send(()).unwrap();
+        search.search(&state, Some(receiver));
+        assert!(search.get_cache().is_empty());
+
+        // Another state's analysis under the same key isn't mistaken for this one's
+        search.get_cache_mut().insert(state.position_key(), self::analysis("a1"));
+        let searched = search.search(&state, None);
+        assert!(searched.as_any().downcast_ref::<CachedAnalysis<Ply>>().is_none());
+        let principal_variation = search.get_principal_variation(&*searched);
+        assert_eq!(search.get_cache_mut().get(state.position_key()).unwrap().principal_variation, principal_variation);
+    }
+}
@@ -0,0 +1,111 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use analysis::Evaluation;
+use state::State;
+
+/// Receives events from inside a search, for diagnostics or to feed a logging framework.
+///
+/// Every method does nothing by default, so an implementor only needs to provide the events
+/// it's interested in.  Events are reported synchronously from the search's inner loop, so
+/// implementations should be quick.
+pub trait SearchLogger<S, E> where
+    S: State,
+    E: Evaluation {
+    /// A node was entered with `depth` plies left to search.
+    fn node_entered(&mut self, _state: &S, _depth: u8) { }
+
+    /// `state` was found in the transposition table.  `usable` is true if the entry was
+    /// good enough to answer the node without searching it.
+    fn tt_hit(&mut self, _state: &S, _usable: bool) { }
+
+    /// The search of `state` was cut off by `ply`, the `index`th ply searched.  A `ply` of
+    /// `None` is a cutoff by the null move search.
+    fn cutoff(&mut self, _state: &S, _ply: Option<&S::Ply>, _index: usize) { }
+
+    /// `ply` beat the best so far under a null window, and `state`, the result of `ply`, is
+    /// about to be searched again with a full window.
+    fn re_search(&mut self, _state: &S, _ply: &S::Ply) { }
+
+    /// The root's aspiration window failed in the iteration of depth `depth`, and the root is
+    /// about to be searched again between `alpha` and `beta`.
+    fn aspiration_re_search(&mut self, _depth: u8, _alpha: E, _beta: E) { }
+
+    /// The iteration of depth `depth` finished in `time` seconds, evaluating the root as
+    /// `evaluation` with `principal_variation`.
+    fn iteration_finished(&mut self, _depth: u8, _evaluation: E, _principal_variation: &[S::Ply], _time: f32) { }
+}
+
+#[cfg(all(test, feature = "with_tic_tac_toe"))]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use analysis::Evaluator as EvaluatorTrait;
+    use analysis::search::{PvSearch, PvSearchAnalysis, Search, SearchLogger};
+    use impls::tic_tac_toe::{Board, Evaluator, Ply};
+
+    type Evaluation = <Evaluator as EvaluatorTrait>::Evaluation;
+
+    #[derive(Default)]
+    struct Counts {
+        nodes: u64,
+        tt_saves: u32,
+        cutoffs: u32,
+        iterations: Vec<u8>,
+    }
+
+    struct Counter(Arc<Mutex<Counts>>);
+
+    impl SearchLogger<Board, Evaluation> for Counter {
+        fn node_entered(&mut self, _: &Board, _: u8) {
+            self.0.lock().unwrap().nodes += 1;
+        }
+
+        fn tt_hit(&mut self, _: &Board, usable: bool) {
+            if usable {
+                self.0.lock().unwrap().tt_saves += 1;
+            }
+        }
+
+        fn cutoff(&mut self, _: &Board, _: Option<&Ply>, _: usize) {
+            self.0.lock().unwrap().cutoffs += 1;
+        }
+
+        fn iteration_finished(&mut self, depth: u8, _: Evaluation, _: &[Ply], _: f32) {
+            self.0.lock().unwrap().iterations.push(depth);
+        }
+    }
+
+    #[test]
+    fn test_logger() {
+        let counts = Arc::new(Mutex::new(Counts::default()));
+        let mut search = PvSearch::with_depth(Evaluator, 5);
+        search.set_logger(Some(Box::new(Counter(counts.clone()))));
+
+        let analysis = search.search(&Board::new(), None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, Evaluator>>().unwrap();
+        let totals = analysis.statistics.calculate_totals();
+
+        let counts = counts.lock().unwrap();
+        assert_eq!(counts.nodes, totals.visited as u64);
+        assert_eq!(counts.tt_saves, totals.tt_saves);
+        assert_eq!(counts.cutoffs, totals.cutoffs.iter().sum::<u32>() + totals.null_cutoffs);
+        assert_eq!(counts.iterations, vec![1, 2, 3, 4, 5]);
+    }
+}
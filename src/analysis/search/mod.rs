@@ -28,8 +28,8 @@ use state::State;
 
 /// The results of the search.
 ///
-/// The search returns a boxed `Analysis`, which can either be printed as-is, or downcast into
-/// a concrete analysis type from a particular search.
+/// The search returns a boxed `Analysis`, which can either be printed as-is, summarized as an
+/// `AnalysisReport`, or downcast into a concrete analysis type from a particular search.
 ///
 /// # Example
 ///
@@ -45,9 +45,10 @@ use state::State;
 /// # impl std::fmt::Display for Ply { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
 /// # struct Resolution(i8);
 /// # impl zero_sum::Resolution for Resolution { fn get_winner(&self) -> Option<u8> { None } fn is_draw(&self) -> bool { false } }
+/// # impl std::fmt::Display for Resolution { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
 /// # #[derive(Clone, Eq, Hash, PartialEq)] struct State(i8);
 /// # impl State { fn new() -> State { State(0) } }
-/// # impl zero_sum::State for State { type Ply = Ply; type Resolution = Resolution; fn get_ply_count(&self) -> usize { 0 } fn execute_ply(&mut self, _: Option<&Ply>) -> Result<(), String> { Ok(()) } fn revert_ply(&mut self, _: Option<&Ply>) -> Result<(), String> { Ok(()) } fn check_resolution(&self) -> Option<Resolution> { None } }
+/// # impl zero_sum::State for State { type Ply = Ply; type Resolution = Resolution; fn get_ply_count(&self) -> usize { 0 } fn execute_ply(&mut self, _: Option<&Ply>) -> Result<(), zero_sum::PlyError> { Ok(()) } fn revert_ply(&mut self, _: Option<&Ply>) -> Result<(), zero_sum::PlyError> { Ok(()) } fn check_resolution(&self) -> Option<Resolution> { None } }
 /// # struct Evaluator;
 /// # impl zero_sum::analysis::Evaluator for Evaluator { type State = State; type Evaluation = Eval; fn evaluate(&self, _: &State) -> Eval { Eval(0) } }
 /// # impl zero_sum::analysis::Extrapolatable<Ply> for State { fn extrapolate(&self) -> Vec<Ply> { Vec::new() } }
@@ -62,6 +63,7 @@ use state::State;
 /// let analysis = search.search(&state, Some(interrupt_receiver));
 ///
 /// println!("{}", analysis);
+/// println!("{:?}", analysis.report().best_move);
 ///
 /// let pvsearch_analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<State, Evaluator>>().unwrap();
 /// println!("{}", pvsearch_analysis.evaluation);
@@ -69,6 +71,27 @@ use state::State;
 /// ```
 pub trait Analysis: Display {
     fn as_any(&self) -> &Any;
+
+    /// Returns the essentials of the analysis as plain values.
+    fn report(&self) -> AnalysisReport;
+
+    /// Returns false if the search was cut short, so that its results are less than it was
+    /// asked for.  This is optional to implement, returning a default of `true`.
+    fn is_complete(&self) -> bool {
+        true
+    }
+}
+
+/// Derives an independent seed for the `index`th of several searches from `master_seed`.
+///
+/// Searches running in parallel on the same position should be seeded differently so that
+/// they explore different parts of the tree; deriving their seeds from a single master seed
+/// keeps the whole group reproducible.
+pub fn derive_seed(master_seed: u64, index: usize) -> u64 {
+    let mut z = master_seed.wrapping_add((index as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
 }
 
 /// Provides search capabilities.
@@ -76,8 +99,25 @@ pub trait Search<S> where
     S: State + Extrapolatable<<S as State>::Ply> {
     /// Generates an analysis of `state`.  `interrupt` is optionally provided to interrupt long searches.
     fn search(&mut self, state: &S, interrupt: Option<Receiver<()>>) -> Box<Analysis>;
+
+    /// Returns the principal variation of an analysis generated by this search, beginning with
+    /// the ply it recommends.  This is optional to implement, returning a default of an empty
+    /// variation.
+    fn get_principal_variation(&self, _analysis: &Analysis) -> Vec<<S as State>::Ply> {
+        Vec::new()
+    }
+
+    /// Forgets anything carried over from previous searches, such as a transposition table,
+    /// so that the next search starts fresh.  This is optional to implement.
+    fn clear(&mut self) { }
 }
 
-pub use self::pvsearch::{PvSearch, PvSearchAnalysis};
+pub use self::cache::{AnalysisCache, CachedAnalysis, CachedSearch};
+pub use self::logger::SearchLogger;
+pub use self::report::AnalysisReport;
+pub use self::pvsearch::{Bound, ButterflyIndex, Explanation, History, HistoryOrdering, MoveOrdering, OptionError, PvSearch, PvSearchAnalysis, PvSearchOptions, RootPlyReport, RootScore, Statistics, StatisticsLevel, StoreHook, TranspositionTableEntry, CUTOFF_BUCKETS, EXPLAINED_PLIES};
 
+mod cache;
+mod logger;
 mod pvsearch;
+mod report;
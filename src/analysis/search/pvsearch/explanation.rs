@@ -0,0 +1,181 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::fmt;
+
+use analysis::Evaluation;
+use ply::Ply;
+
+use super::statistics::StatisticsLevel;
+use super::transposition_table::Bound;
+
+/// The number of root plies described individually by an `Explanation`; the rest are
+/// summarized.
+pub const EXPLAINED_PLIES: usize = 5;
+
+/// What the final iteration of a search found out about a single root ply.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub struct RootPlyReport<P, E> where
+    P: Ply,
+    E: Evaluation {
+    pub ply: P,
+    /// The value of the ply.  Unless `bound` is `Exact`, this is only a bound on its true
+    /// value: the most it could be worth for `Upper`, or the least for `Lower`.
+    pub value: E,
+    pub bound: Bound,
+    /// Whether the ply beat the best so far under a null window and had to be searched again
+    /// with a full one.
+    pub re_searched: bool,
+    /// The statistics of the ply's subtree.
+    pub statistics: StatisticsLevel,
+}
+
+/// The value of a single root ply in the final iteration of a search, collected when
+/// `PvSearch::set_root_scores` is enabled.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub struct RootScore<P, E> where
+    P: Ply,
+    E: Evaluation {
+    pub ply: P,
+    /// The value of the ply, bounded as in `RootPlyReport`.
+    pub value: E,
+    pub bound: Bound,
+}
+
+impl<P, E> fmt::Display for RootScore<P, E> where
+    P: Ply,
+    E: Evaluation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.ply, describe_value(self.value, &self.bound))
+    }
+}
+
+/// A description of how a search chose its move, collected when `PvSearch::set_explain`
+/// is enabled.  Its `Display` implementation is a short narrative meant to be read by a
+/// person.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub struct Explanation<P, E> where
+    P: Ply,
+    E: Evaluation {
+    /// The depth of the iteration that the reports come from.
+    pub depth: u8,
+    /// The root plies in the order they were searched.
+    pub root_plies: Vec<RootPlyReport<P, E>>,
+    /// The index in `root_plies` of the ply that begins the principal variation, if it was
+    /// searched in this iteration.
+    pub chosen: Option<usize>,
+    /// The number of root plies that weren't searched because of root widening.
+    pub skipped: usize,
+}
+
+impl<P, E> Explanation<P, E> where
+    P: Ply,
+    E: Evaluation {
+    /// Returns the reports ordered from best to worst.  The chosen ply is first, followed by
+    /// the others by value, with lower bounds ahead of exact values and exact values ahead
+    /// of upper bounds that equal them.
+    pub fn ranked(&self) -> Vec<&RootPlyReport<P, E>> {
+        let rank = |report: &RootPlyReport<P, E>| match report.bound {
+            Bound::Lower => 0,
+            Bound::Exact => 1,
+            Bound::Upper => 2,
+        };
+
+        let mut ranked = self.root_plies.iter().enumerate()
+            .filter(|&(i, _)| Some(i) != self.chosen)
+            .map(|(_, report)| report)
+            .collect::<Vec<_>>();
+
+        ranked.sort_by(|a, b| b.value.partial_cmp(&a.value).unwrap().then(rank(a).cmp(&rank(b))));
+
+        if let Some(chosen) = self.chosen {
+            ranked.insert(0, &self.root_plies[chosen]);
+        }
+        ranked
+    }
+}
+
+fn describe_value<E>(value: E, bound: &Bound) -> String where E: Evaluation {
+    match *bound {
+        Bound::Exact => format!("{}", value),
+        Bound::Upper => format!("at most {}", value),
+        Bound::Lower => format!("at least {}", value),
+    }
+}
+
+fn describe_pruning(statistics: &StatisticsLevel) -> String {
+    let cutoffs = statistics.cutoffs.iter().sum::<u32>();
+    format!(
+        "{} nodes, {} transposition saves, {} beta cutoffs ({} on the first ply), {} null move cutoffs",
+        statistics.nodes(), statistics.tt_saves, cutoffs, statistics.cutoffs[0], statistics.null_cutoffs,
+    )
+}
+
+impl<P, E> fmt::Display for Explanation<P, E> where
+    P: Ply,
+    E: Evaluation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ranked = self.ranked();
+
+        let best = match self.chosen {
+            Some(chosen) => &self.root_plies[chosen],
+            None => return write!(f, "At depth {}, the chosen ply came from the transposition table.", self.depth),
+        };
+
+        write!(f, "At depth {}, searched {} root plies", self.depth, self.root_plies.len())?;
+        if self.skipped > 0 {
+            write!(f, " ({} more skipped by root widening)", self.skipped)?;
+        }
+        writeln!(f, ".")?;
+
+        writeln!(f, "Chose {}, worth {}: {}.", best.ply, describe_value(best.value, &best.bound), describe_pruning(&best.statistics))?;
+
+        for report in ranked.iter().skip(1).take(EXPLAINED_PLIES - 1) {
+            writeln!(f, "  {}, worth {}{}: {}.",
+                report.ply,
+                describe_value(report.value, &report.bound),
+                if report.re_searched { ", re-searched" } else { "" },
+                describe_pruning(&report.statistics),
+            )?;
+        }
+
+        if ranked.len() > EXPLAINED_PLIES {
+            let rest = &ranked[EXPLAINED_PLIES..];
+            let nodes = rest.iter().map(|report| report.statistics.nodes()).sum::<u64>();
+            writeln!(f, "  {} more, worth no more than {}, took {} nodes.", rest.len(), rest[0].value, nodes)?;
+        }
+
+        match ranked.get(1) {
+            Some(runner_up) => write!(f, "{} was preferred over {}, which was worth {}{}.",
+                best.ply,
+                runner_up.ply,
+                describe_value(runner_up.value, &runner_up.bound),
+                if runner_up.bound == Bound::Upper {
+                    " and so couldn't beat it"
+                } else {
+                    ""
+                },
+            ),
+            None => write!(f, "{} was the only ply searched.", best.ply),
+        }
+    }
+}
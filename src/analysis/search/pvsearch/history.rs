@@ -18,12 +18,29 @@
 //
 
 use std::cell::RefCell;
+use std::cmp;
 use std::collections::BTreeMap;
-use std::collections::btree_map::Entry;
 use std::hash::Hasher;
 
 use ply::Ply;
 
+/// A ply that can be reduced to an index in a small table, usually by where it moves from and
+/// to.  The search's history heuristic can then credit a cutoff to every ply that shares the
+/// index, rather than only to identical plies, which lets the history generalize sooner.
+pub trait ButterflyIndex: Ply {
+    /// Returns the ply's index.  Plies that share an index share history.
+    fn butterfly_index(&self) -> usize;
+}
+
+/// Computes the key of a ply in the history table.
+pub type KeyFn<P> = fn(&P) -> u64;
+
+/// Rewards larger than this are capped, so that a deep cutoff can't overflow the table.
+const MAX_REWARD_SHIFT: u8 = 24;
+
+/// When a score passes this, every score is halved, keeping the scores' order.
+const AGING_LIMIT: u32 = 1 << 30;
+
 struct SingleHasher(u64);
 
 impl Hasher for SingleHasher {
@@ -38,45 +55,121 @@ impl Hasher for SingleHasher {
     }
 }
 
-pub struct History {
+/// The history heuristic scores and countermoves gathered by a `PvSearch`, for use by a
+/// [`MoveOrdering`](trait.MoveOrdering.html).
+pub struct History<P> where
+    P: Ply {
     hasher: RefCell<SingleHasher>,
-    map: BTreeMap<u64, u32>,
+    tables: [BTreeMap<u64, u32>; 2],
+    per_color: bool,
+    key: Option<KeyFn<P>>,
+    countermoves: Option<BTreeMap<u64, P>>,
 }
 
-impl History {
-    pub fn new() -> History {
+impl<P> History<P> where
+    P: Ply {
+    pub fn new() -> History<P> {
         History {
             hasher: RefCell::new(SingleHasher(0)),
-            map: BTreeMap::new(),
+            tables: [BTreeMap::new(), BTreeMap::new()],
+            per_color: false,
+            key: None,
+            countermoves: None,
         }
     }
 
-    pub fn get<P>(&self, ply: &P) -> Option<&u32> where P: Ply {
-        let hash = self.hash(ply);
-        self.map.get(&hash)
+    /// Sets whether each side to move keeps its own scores.  Clears the history.
+    pub fn set_per_color(&mut self, per_color: bool) {
+        self.per_color = per_color;
+        self.clear();
+    }
+
+    /// Sets the function that keys plies in the table, or `None` to key them by their hashes.
+    /// Clears the history.
+    pub fn set_key(&mut self, key: Option<KeyFn<P>>) {
+        self.key = key;
+        self.clear();
     }
 
-    pub fn entry<P>(&mut self, ply: &P) -> Entry<u64, u32> where P: Ply {
-        let hash = self.hash(ply);
-        self.map.entry(hash)
+    /// Sets whether the plies that refute each ply are remembered.  Clears the history.
+    pub fn set_countermoves(&mut self, countermoves: bool) {
+        self.countermoves = if countermoves {
+            Some(BTreeMap::new())
+        } else {
+            None
+        };
+        self.clear();
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.map.is_empty()
+    /// Returns the ply that last refuted `previous_ply`, if countermoves are remembered.
+    pub fn get_countermove(&self, previous_ply: &P) -> Option<&P> {
+        match self.countermoves {
+            Some(ref countermoves) => countermoves.get(&self.key(previous_ply)),
+            None => None,
+        }
+    }
+
+    /// Remembers `ply` as the refutation of `previous_ply`, if countermoves are remembered.
+    pub fn set_countermove(&mut self, previous_ply: &P, ply: &P) {
+        let key = self.key(previous_ply);
+        if let Some(ref mut countermoves) = self.countermoves {
+            countermoves.insert(key, ply.clone());
+        }
+    }
+
+    /// Returns the score of `ply` for the player to move at `ply_count`.
+    pub fn get(&self, ply_count: usize, ply: &P) -> u32 {
+        let key = self.key(ply);
+        *self.tables[self.table(ply_count)].get(&key).unwrap_or(&0)
+    }
+
+    /// Credits `ply` with a cutoff at `depth` for the player to move at `ply_count`.
+    pub fn reward(&mut self, ply_count: usize, ply: &P, depth: u8) {
+        let key = self.key(ply);
+        let table = self.table(ply_count);
+
+        let score = {
+            let entry = self.tables[table].entry(key).or_insert(0);
+            *entry = entry.saturating_add(1 << cmp::min(depth, MAX_REWARD_SHIFT));
+            *entry
+        };
+
+        if score > AGING_LIMIT {
+            self.age();
+        }
+    }
+
+    /// Halves every score, so that recent cutoffs outweigh older ones.
+    pub fn age(&mut self) {
+        for table in self.tables.iter_mut() {
+            for score in table.values_mut() {
+                *score /= 2;
+            }
+            let forget = table.iter().filter(|&(_, &score)| score == 0).map(|(&key, _)| key).collect::<Vec<_>>();
+            for key in forget {
+                table.remove(&key);
+            }
+        }
     }
 
     pub fn clear(&mut self) {
-        self.map.clear();
+        for table in self.tables.iter_mut() {
+            table.clear();
+        }
+        if let Some(ref mut countermoves) = self.countermoves {
+            countermoves.clear();
+        }
     }
 
-    pub fn sort_plies<P>(&self, plies: &mut [P]) where P: Ply {
-        if self.is_empty() {
+    /// Sorts `plies` into ascending order of score for the player to move at `ply_count`.
+    pub fn sort_plies(&self, ply_count: usize, plies: &mut [P]) {
+        if self.tables[self.table(ply_count)].is_empty() {
             return;
         }
 
         let mut histories = Vec::with_capacity(plies.len());
         for (index, ply) in plies.iter().enumerate() {
-            histories.push((*self.get(ply).unwrap_or(&0), index));
+            histories.push((self.get(ply_count, ply), index));
         }
 
         histories.sort_by(|a, b| {
@@ -91,7 +184,19 @@ impl History {
         apply_permutation(plies, &final_indices);
     }
 
-    fn hash<P>(&self, ply: &P) -> u64 where P: Ply {
+    fn table(&self, ply_count: usize) -> usize {
+        if self.per_color {
+            ply_count % 2
+        } else {
+            0
+        }
+    }
+
+    fn key(&self, ply: &P) -> u64 {
+        if let Some(key) = self.key {
+            return key(ply);
+        }
+
         let mut hasher = self.hasher.borrow_mut();
         ply.hash(&mut *hasher);
         hasher.finish()
@@ -116,3 +221,100 @@ fn apply_permutation<T>(v: &mut [T], p: &[usize]) {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::fmt;
+    use std::hash::Hash;
+
+    use ply;
+    use super::*;
+
+    /// A ply that drops a disc into a column.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Ply {
+        column: usize,
+    }
+
+    impl ply::Ply for Ply { }
+
+    // Written as a single u64, as the history's hasher expects
+    impl Hash for Ply {
+        fn hash<H>(&self, state: &mut H) where H: Hasher {
+            state.write_u64(self.column as u64);
+        }
+    }
+
+    impl fmt::Display for Ply {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.column) }
+    }
+
+    fn ply(column: usize) -> Ply {
+        Ply {
+            column: column,
+        }
+    }
+
+    #[test]
+    fn test_per_color() {
+        let mut history = History::new();
+        history.reward(0, &ply(3), 2);
+        assert_eq!(history.get(1, &ply(3)), 4);
+
+        history.set_per_color(true);
+        history.reward(0, &ply(3), 2);
+        assert_eq!(history.get(0, &ply(3)), 4);
+        assert_eq!(history.get(1, &ply(3)), 0);
+        assert_eq!(history.get(2, &ply(3)), 4);
+    }
+
+    #[test]
+    fn test_aging() {
+        let mut history = History::new();
+        history.reward(0, &ply(1), 0);
+        history.reward(0, &ply(2), 3);
+        history.age();
+        assert_eq!(history.get(0, &ply(1)), 0);
+        assert_eq!(history.get(0, &ply(2)), 4);
+
+        // Scores are halved before they can overflow
+        for _ in 0..100 {
+            history.reward(0, &ply(2), 255);
+        }
+        assert!(history.get(0, &ply(2)) <= AGING_LIMIT);
+    }
+
+    #[test]
+    fn test_countermoves() {
+        let mut history = History::new();
+        history.set_countermove(&ply(1), &ply(2));
+        assert_eq!(history.get_countermove(&ply(1)), None);
+
+        history.set_countermoves(true);
+        history.set_countermove(&ply(1), &ply(2));
+        history.set_countermove(&ply(3), &ply(4));
+        history.set_countermove(&ply(3), &ply(5));
+        assert_eq!(history.get_countermove(&ply(1)), Some(&ply(2)));
+        assert_eq!(history.get_countermove(&ply(3)), Some(&ply(5)));
+
+        history.clear();
+        assert_eq!(history.get_countermove(&ply(1)), None);
+    }
+
+    #[test]
+    fn test_key() {
+        fn parity(ply: &Ply) -> u64 {
+            ply.column as u64 % 2
+        }
+
+        let mut history = History::new();
+        history.set_key(Some(parity));
+        history.reward(0, &ply(1), 1);
+        assert_eq!(history.get(0, &ply(5)), 2);
+        assert_eq!(history.get(0, &ply(4)), 0);
+
+        let mut plies = vec![ply(1), ply(2), ply(3), ply(4)];
+        history.sort_plies(0, &mut plies);
+        assert_eq!(plies.iter().map(|ply| ply.column % 2).collect::<Vec<_>>(), vec![0, 0, 1, 1]);
+    }
+}
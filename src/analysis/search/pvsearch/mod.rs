@@ -20,21 +20,40 @@
 //! Principal Variation Search
 
 use std::any::Any;
+use std::cmp;
 use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::mem;
+use std::path::Path;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::Receiver;
-use std::time::Instant;
+use std::time::Duration;
 use std::u8;
 
-use analysis::{Evaluation, Evaluator, Extrapolatable};
-use analysis::search::{Analysis, Search};
-use state::State;
+use rand::Rng;
 
-use self::history::History;
-use self::ply_generator::PlyGenerator;
-use self::transposition_table::{Bound, TranspositionTable, TranspositionTableEntry};
+use analysis::{Evaluation, Evaluator, Experience, Extrapolatable, IncrementalEvaluator, StagedExtrapolatable, Tablebase, ThreatDetector};
+use analysis::search::{Analysis, AnalysisReport, Search, SearchLogger};
+use persist::{Decoder, Persistable, PersistError, Reader, Writer};
+use position_key::PositionKey;
+use resolution::Resolution;
+use state::{PlyError, State};
+use symmetric::Symmetric;
+use util::{Instant, JKiss32Rng};
+
+use self::ply_generator::{PlyGenerator, StageFn};
+use self::transposition_table::{Symmetry, TranspositionTable};
 
 /// The results of the PV search.
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "with_serde", serde(bound(
+    serialize = "S: ::serde::Serialize, <S as State>::Ply: ::serde::Serialize, \
+                 <S as State>::Resolution: ::serde::Serialize, <E as Evaluator>::Evaluation: ::serde::Serialize",
+    deserialize = "S: ::serde::Deserialize<'de>, <S as State>::Ply: ::serde::Deserialize<'de>, \
+                   <S as State>::Resolution: ::serde::Deserialize<'de>, <E as Evaluator>::Evaluation: ::serde::Deserialize<'de>",
+)))]
 pub struct PvSearchAnalysis<S, E> where
     S: State + Extrapolatable<<S as State>::Ply>,
     E: Evaluator<State = S> {
@@ -42,10 +61,31 @@ pub struct PvSearchAnalysis<S, E> where
     pub state: S,
     /// The evaluation of the state after applying the principal variation.
     pub evaluation: <E as Evaluator>::Evaluation,
+    /// The probability that the player to move wins, according to the evaluator's score
+    /// model, if it has one.
+    #[cfg_attr(feature = "with_serde", serde(default))]
+    pub win_probability: Option<f64>,
     /// The principal variation of the state.
     pub principal_variation: Vec<<S as State>::Ply>,
+    /// The resolution of the state after applying the principal variation, if the game ends there.
+    pub resolution: Option<<S as State>::Resolution>,
     /// Statistics from the search.
     pub statistics: Statistics,
+    /// How the search chose its move, if `set_explain` was enabled.
+    pub explanation: Option<Explanation<<S as State>::Ply, <E as Evaluator>::Evaluation>>,
+    /// The value of every root ply searched in the final iteration, in the order they were
+    /// searched, if `set_root_scores` was enabled.
+    #[cfg_attr(feature = "with_serde", serde(default))]
+    pub root_scores: Option<Vec<RootScore<<S as State>::Ply, <E as Evaluator>::Evaluation>>>,
+    /// Whether the search was interrupted, by its interrupt channel or its node limit, before
+    /// it finished.
+    #[cfg_attr(feature = "with_serde", serde(default))]
+    pub interrupted: bool,
+    /// The depth of the last iteration the search completed, from which the evaluation and
+    /// principal variation come.  If the search was interrupted before completing any, this
+    /// is 0, and they're the best the first iteration found before it stopped.
+    #[cfg_attr(feature = "with_serde", serde(default))]
+    pub completed_depth: u8,
 }
 
 /// A PVS implementation of `Search` with a few common optimizations.
@@ -64,9 +104,10 @@ pub struct PvSearchAnalysis<S, E> where
 /// # impl std::fmt::Display for Ply { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
 /// # struct Resolution(i8);
 /// # impl zero_sum::Resolution for Resolution { fn get_winner(&self) -> Option<u8> { None } fn is_draw(&self) -> bool { false } }
+/// # impl std::fmt::Display for Resolution { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
 /// # #[derive(Clone, Eq, Hash, PartialEq)] struct State(i8);
 /// # impl State { fn new() -> State { State(0) } }
-/// # impl zero_sum::State for State { type Ply = Ply; type Resolution = Resolution; fn get_ply_count(&self) -> usize { 0 } fn execute_ply(&mut self, _: Option<&Ply>) -> Result<(), String> { Ok(()) } fn revert_ply(&mut self, _: Option<&Ply>) -> Result<(), String> { Ok(()) } fn check_resolution(&self) -> Option<Resolution> { None } }
+/// # impl zero_sum::State for State { type Ply = Ply; type Resolution = Resolution; fn get_ply_count(&self) -> usize { 0 } fn execute_ply(&mut self, _: Option<&Ply>) -> Result<(), zero_sum::PlyError> { Ok(()) } fn revert_ply(&mut self, _: Option<&Ply>) -> Result<(), zero_sum::PlyError> { Ok(()) } fn check_resolution(&self) -> Option<Resolution> { None } }
 /// # struct Evaluator;
 /// # impl zero_sum::analysis::Evaluator for Evaluator { type State = State; type Evaluation = Eval; fn evaluate(&self, _: &State) -> Eval { Eval(0) } }
 /// # impl zero_sum::analysis::Extrapolatable<Ply> for State { fn extrapolate(&self) -> Vec<Ply> { Vec::new() } }
@@ -86,12 +127,95 @@ pub struct PvSearch<S, E> where
     depth: u8,
     goal: u16,
     branching_factor: f32,
+    node_limit: u64,
+    nodes: u64,
     evaluator: E,
-    history: Arc<Mutex<History>>,
+    history: Arc<Mutex<History<<S as State>::Ply>>>,
+    history_aging: bool,
+    move_ordering: Box<MoveOrdering<S>>,
     transposition_table: TranspositionTable<S, <E as Evaluator>::Evaluation>,
+    forced_move_extensions: u8,
+    aspiration_window: u32,
+    futility_margin: u32,
+    reverse_futility_margin: u32,
+    singular_margin: u32,
+    null_move: bool,
+    threats: Option<ThreatFn<S, <S as State>::Ply>>,
+    rng: JKiss32Rng,
+    seeded: bool,
+    incremental: Option<Incremental<S, E>>,
+    ply_buffers: Vec<Vec<<S as State>::Ply>>,
+    pv_lines: Vec<Vec<<S as State>::Ply>>,
+    pv_saved: Vec<Vec<<S as State>::Ply>>,
+    staged: Option<StageFn<S, <S as State>::Ply>>,
+    root_widening: usize,
+    root_limit: usize,
+    root_searched: usize,
+    seldepth: usize,
+    explain: bool,
+    root_reports: Vec<RootPlyReport<<S as State>::Ply, <E as Evaluator>::Evaluation>>,
+    record_root_scores: bool,
+    root_scores: Vec<RootScore<<S as State>::Ply, <E as Evaluator>::Evaluation>>,
+    logger: Option<Box<SearchLogger<S, <E as Evaluator>::Evaluation> + Send>>,
+    tablebase: Option<Arc<Tablebase<S>>>,
+    experience: Option<Arc<Experience<S, <E as Evaluator>::Evaluation>>>,
+    reuse_precalculated: bool,
+    verifying: bool,
     interrupted: bool,
 }
 
+/// The notification functions of an `IncrementalEvaluator`, stored so that the search
+/// doesn't require every evaluator to be incremental.
+struct Incremental<S, E> where
+    S: State {
+    reset: fn(&mut E, &S),
+    apply_ply: fn(&mut E, &S, Option<&<S as State>::Ply>),
+    revert_ply: fn(&mut E, &S, Option<&<S as State>::Ply>),
+}
+
+impl<S, E> Incremental<S, E> where
+    S: State,
+    E: IncrementalEvaluator<State = S> {
+    fn new() -> Incremental<S, E> {
+        fn reset<S, E>(evaluator: &mut E, state: &S) where S: State, E: IncrementalEvaluator<State = S> {
+            evaluator.reset(state);
+        }
+
+        fn apply_ply<S, E>(evaluator: &mut E, state: &S, ply: Option<&<S as State>::Ply>) where S: State, E: IncrementalEvaluator<State = S> {
+            evaluator.apply_ply(state, ply);
+        }
+
+        fn revert_ply<S, E>(evaluator: &mut E, state: &S, ply: Option<&<S as State>::Ply>) where S: State, E: IncrementalEvaluator<State = S> {
+            evaluator.revert_ply(state, ply);
+        }
+
+        Incremental {
+            reset: reset::<S, E>,
+            apply_ply: apply_ply::<S, E>,
+            revert_ply: revert_ply::<S, E>,
+        }
+    }
+}
+
+/// Aspiration windows wider than this many units of granularity give way to a full search.
+const ASPIRATION_LIMIT: u32 = 10_000;
+
+/// Futility pruning applies to nodes with at most this much depth remaining.
+const FUTILITY_DEPTH: u8 = 2;
+
+/// Reverse futility pruning applies to nodes with at most this much depth remaining.
+const REVERSE_FUTILITY_DEPTH: u8 = 3;
+
+/// Singular extensions are only considered for nodes with at least this much depth remaining.
+const SINGULAR_DEPTH: u8 = 4;
+
+const CHECKPOINT_MAGIC: &'static [u8; 4] = b"ZSCP";
+/// Checkpoints embed the transposition table's entries, so this changes along with its version.
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// Recognizes forcing plies, as with `ThreatDetector::is_forcing`.
+type ThreatFn<S, P> = fn(&S, &P) -> bool;
+
 impl<S, E> PvSearch<S, E> where
     S: State + Extrapolatable<<S as State>::Ply>,
     E: Evaluator<State = S> {
@@ -102,9 +226,40 @@ impl<S, E> PvSearch<S, E> where
             depth: 0,
             goal: 0,
             branching_factor: 0.0,
+            node_limit: 0,
+            nodes: 0,
             evaluator: evaluator,
             history: Arc::new(Mutex::new(History::new())),
+            history_aging: false,
+            move_ordering: Box::new(HistoryOrdering),
             transposition_table: TranspositionTable::new(),
+            forced_move_extensions: 4,
+            aspiration_window: 0,
+            futility_margin: 0,
+            reverse_futility_margin: 0,
+            singular_margin: 0,
+            null_move: true,
+            threats: None,
+            rng: JKiss32Rng::new(),
+            seeded: false,
+            incremental: None,
+            ply_buffers: Vec::new(),
+            pv_lines: Vec::new(),
+            pv_saved: Vec::new(),
+            staged: None,
+            root_widening: 0,
+            root_limit: 0,
+            root_searched: 0,
+            seldepth: 0,
+            explain: false,
+            root_reports: Vec::new(),
+            record_root_scores: false,
+            root_scores: Vec::new(),
+            logger: None,
+            tablebase: None,
+            experience: None,
+            reuse_precalculated: true,
+            verifying: false,
             interrupted: false,
         }
     }
@@ -121,40 +276,396 @@ impl<S, E> PvSearch<S, E> where
     /// the required time to search at the next depth.
     pub fn with_goal(evaluator: E, goal: u16, branching_factor: f32) -> PvSearch<S, E> {
         let mut search = PvSearch::new(evaluator);
-        search.goal = goal;
-        search.branching_factor = if branching_factor <= 0.0 || branching_factor.is_nan() || branching_factor.is_infinite() {
+        search.set_goal(goal, branching_factor);
+        search
+    }
+
+    /// Creates a `PvSearch` that will stop once it has entered `nodes` nodes, as counted by
+    /// `Statistics::node_count`.  Unlike a time goal, this stops the search at the same place
+    /// on every run, so matches played with it are reproducible.
+    pub fn with_node_limit(evaluator: E, nodes: u64) -> PvSearch<S, E> {
+        let mut search = PvSearch::new(evaluator);
+        search.node_limit = nodes;
+        search
+    }
+
+    /// Creates a `PvSearch` with every setting taken from `options`.
+    pub fn with_options(evaluator: E, options: &PvSearchOptions) -> PvSearch<S, E> {
+        let mut search = PvSearch::new(evaluator);
+        search.set_options(options);
+        search
+    }
+
+    /// Applies every setting in `options`, as with their individual setters.
+    pub fn set_options(&mut self, options: &PvSearchOptions) {
+        self.set_depth(options.depth);
+        self.set_goal(options.goal, options.branching_factor);
+        self.set_node_limit(options.node_limit);
+        self.set_forced_move_extensions(options.forced_move_extensions);
+        self.set_aspiration_window(options.aspiration_window);
+        self.set_futility_margin(options.futility_margin);
+        self.set_reverse_futility_margin(options.reverse_futility_margin);
+        self.set_singular_margin(options.singular_margin);
+        self.set_null_move(options.null_move);
+        self.set_root_widening(options.root_widening);
+        self.set_history_per_color(options.history_per_color);
+        self.set_history_aging(options.history_aging);
+        self.set_countermoves(options.countermoves);
+        self.set_explain(options.explain);
+        self.set_root_scores(options.root_scores);
+        self.set_reuse_precalculated(options.reuse_precalculated);
+        if let Some(seed) = options.seed {
+            self.set_seed(seed);
+        }
+    }
+
+    /// Sets one option by name, parsing `value` as its type, for front ends that configure the
+    /// search from text, as with a protocol's option command.  The names are those of the
+    /// fields of `PvSearchOptions`, except that `goal` takes only the goal, leaving the
+    /// branching factor to `branching_factor`.  Booleans are `true` or `false`.
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), OptionError> {
+        fn parse<T>(name: &str, value: &str) -> Result<T, OptionError> where T: FromStr {
+            T::from_str(value.trim()).map_err(|_| OptionError::InvalidValue {
+                name: String::from(name),
+                value: String::from(value),
+            })
+        }
+
+        match name {
+            "depth" => self.set_depth(parse(name, value)?),
+            "goal" => {
+                let goal = parse(name, value)?;
+                let branching_factor = self.branching_factor;
+                self.set_goal(goal, branching_factor);
+            },
+            "branching_factor" => {
+                let goal = self.goal;
+                self.set_goal(goal, parse(name, value)?);
+            },
+            "node_limit" => self.set_node_limit(parse(name, value)?),
+            "forced_move_extensions" => self.set_forced_move_extensions(parse(name, value)?),
+            "aspiration_window" => self.set_aspiration_window(parse(name, value)?),
+            "futility_margin" => self.set_futility_margin(parse(name, value)?),
+            "reverse_futility_margin" => self.set_reverse_futility_margin(parse(name, value)?),
+            "singular_margin" => self.set_singular_margin(parse(name, value)?),
+            "null_move" => self.set_null_move(parse(name, value)?),
+            "root_widening" => self.set_root_widening(parse(name, value)?),
+            "history_per_color" => self.set_history_per_color(parse(name, value)?),
+            "history_aging" => self.set_history_aging(parse(name, value)?),
+            "countermoves" => self.set_countermoves(parse(name, value)?),
+            "explain" => self.set_explain(parse(name, value)?),
+            "root_scores" => self.set_root_scores(parse(name, value)?),
+            "reuse_precalculated" => self.set_reuse_precalculated(parse(name, value)?),
+            "seed" => self.set_seed(parse(name, value)?),
+            _ => return Err(OptionError::Unknown(String::from(name))),
+        }
+        Ok(())
+    }
+
+    /// Sets the maximum depth of the search.  Defaults to 0, which searches until the
+    /// search is otherwise stopped.
+    pub fn set_depth(&mut self, depth: u8) {
+        self.depth = depth;
+    }
+
+    /// Sets the time goal of the search in seconds, as with `with_goal`.  A goal of 0
+    /// disables it.
+    pub fn set_goal(&mut self, goal: u16, branching_factor: f32) {
+        self.goal = goal;
+        self.branching_factor = if branching_factor <= 0.0 || branching_factor.is_nan() || branching_factor.is_infinite() {
             1.0
         } else {
             branching_factor
         };
-        search
     }
 
+    /// Sets the number of nodes after which the search stops, alongside any depth or time
+    /// goal.  The limit is compared with a count kept at every node, so it's only passed by
+    /// the nodes already underway when it's reached.  Defaults to 0, which disables the limit.
+    pub fn set_node_limit(&mut self, nodes: u64) {
+        self.node_limit = nodes;
+    }
+
+    /// Sets the maximum number of plies that can be searched along a single line without
+    /// consuming depth.  Forced moves, which are plies from a state that has only one ply to
+    /// choose from, are always extended while this allows; threat and singular extensions draw
+    /// on the same allowance.  Defaults to 4; pass 0 to disable.
+    pub fn set_forced_move_extensions(&mut self, extensions: u8) {
+        self.forced_move_extensions = extensions;
+    }
+
+    /// Sets the half-width of the aspiration window, in units of the evaluation's
+    /// granularity.  Each iteration of the search after the first searches only this
+    /// distance around the previous evaluation, widening the window and searching again if
+    /// the result falls outside of it.  Defaults to 0, which disables aspiration windows.
+    pub fn set_aspiration_window(&mut self, units: u32) {
+        self.aspiration_window = units;
+    }
+
+    /// Sets the futility margin per ply of remaining depth, in units of the evaluation's
+    /// granularity.  Near the leaves, if the static evaluation of a state plus the margin can't
+    /// reach alpha, only its first ply is searched fully; the rest are skipped unless they end
+    /// the game.  Suitable margins depend on the scale of the evaluator.  Defaults to 0, which
+    /// disables futility pruning.
+    pub fn set_futility_margin(&mut self, units: u32) {
+        self.futility_margin = units;
+    }
+
+    /// Sets the reverse futility margin per ply of remaining depth, in units of the evaluation's
+    /// granularity.  Near the leaves, if the static evaluation of a state minus the margin and
+    /// the evaluator's tempo still reaches beta, the state is assumed to fail high without being
+    /// searched.  Suitable margins depend on the scale of the evaluator.  Defaults to 0, which
+    /// disables reverse futility pruning.
+    pub fn set_reverse_futility_margin(&mut self, units: u32) {
+        self.reverse_futility_margin = units;
+    }
+
+    /// Sets the singular extension margin per ply of remaining depth, in units of the
+    /// evaluation's granularity.  When the transposition table suggests a ply for a state, and
+    /// a shallower search shows that every other ply falls short of its value by the margin,
+    /// the suggested ply is searched one ply deeper.  Defaults to 0, which disables singular
+    /// extensions.
+    pub fn set_singular_margin(&mut self, units: u32) {
+        self.singular_margin = units;
+    }
+
+    /// Sets whether the search tries a null move before a state's plies, cutting the state off
+    /// if passing still leaves the opponent unable to reach beta.  Defaults to true; games
+    /// in which passing is ever as good as a move should disable it, if their states don't
+    /// already forbid it with `State::null_move_allowed`.
+    pub fn set_null_move(&mut self, null_move: bool) {
+        self.null_move = null_move;
+    }
+
+    /// Sets whether the history heuristic keeps separate scores for each side to move, rather
+    /// than crediting a cutoff by either player to the same ply of the other.  Defaults to
+    /// false.
+    pub fn set_history_per_color(&mut self, per_color: bool) {
+        self.history.lock().unwrap().set_per_color(per_color);
+    }
+
+    /// Sets whether the history heuristic's scores are halved after each iteration of
+    /// iterative deepening, so that cutoffs found by the latest, deepest iteration weigh the
+    /// most in its ordering.  Defaults to false.
+    pub fn set_history_aging(&mut self, aging: bool) {
+        self.history_aging = aging;
+    }
+
+    /// Sets whether the search remembers which ply refuted each ply, and tries that ply early
+    /// when the ply it refuted is made again elsewhere in the tree.  Defaults to false.
+    pub fn set_countermoves(&mut self, countermoves: bool) {
+        self.history.lock().unwrap().set_countermoves(countermoves);
+    }
+
+    /// Sets the strategy used to order each state's plies after the principal ply.  The
+    /// history and countermove settings above feed the strategy, but only take effect if
+    /// the strategy consults them.  Defaults to `HistoryOrdering`.
+    pub fn set_move_ordering(&mut self, move_ordering: Box<MoveOrdering<S>>) {
+        self.move_ordering = move_ordering;
+    }
+
+    /// Seeds the random number generator used to break ties in move ordering, making the
+    /// search reproducible.  Once seeded, aspiration windows are also widened by a random
+    /// amount of up to half their width on either side, so that searches given different
+    /// seeds explore differently.  Use [`derive_seed`](fn.derive_seed.html) to give each of
+    /// several parallel searches its own seed.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = JKiss32Rng::with_seed(seed);
+        self.seeded = true;
+    }
+
+    /// Enables progressive root widening, for games with very large branching factors.  The
+    /// first iteration of iterative deepening searches only the first `width` root plies in
+    /// move order, and each iteration after that doubles the number.  The iteration expected
+    /// to be the last, either because it reaches the target depth or because the time goal
+    /// predicts no further iterations, always searches every root ply.  Searches with neither
+    /// a depth nor a goal have no such guarantee.  Defaults to 0, which disables widening.
+    pub fn set_root_widening(&mut self, width: usize) {
+        self.root_widening = width;
+    }
+
+    /// Sets whether the search reports how it chose its move, in the `explanation` of its
+    /// analysis.  This keeps a few statistics for each root ply, and costs little.
+    pub fn set_explain(&mut self, explain: bool) {
+        self.explain = explain;
+    }
+
+    /// Sets whether the search records the value of every root ply it searches in its final
+    /// iteration, in the `root_scores` of its analysis.  Each value is bounded as the search
+    /// found it, so only the best ply's is usually exact.  Plies that were pruned or skipped by
+    /// root widening are absent.  This costs nothing more than the list itself.
+    pub fn set_root_scores(&mut self, root_scores: bool) {
+        self.record_root_scores = root_scores;
+    }
+
+    /// Sets a logger to receive events from inside the search.
+    pub fn set_logger(&mut self, logger: Option<Box<SearchLogger<S, <E as Evaluator>::Evaluation> + Send>>) {
+        self.logger = logger;
+    }
+
+    /// Sets a tablebase for the search to consult below the root.  States found in it are
+    /// scored by their solved values instead of being searched.  Defaults to `None`.
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase<S>>>) {
+        self.tablebase = tablebase;
+    }
+
+    /// Sets the experience of earlier games for the search to consult below the root.  States
+    /// the experience has corrected, at least as deeply as they're about to be searched, are
+    /// scored by their corrected evaluations instead.  Defaults to `None`.
+    pub fn set_experience(&mut self, experience: Option<Arc<Experience<S, <E as Evaluator>::Evaluation>>>) {
+        self.experience = experience;
+    }
+
+    /// Sets whether a search may begin from an exact transposition table entry for its root,
+    /// as left by an earlier search, a loaded table, or another machine, rather than from the
+    /// first depth.  Before the entry's depth is skipped, its principal variation is checked
+    /// against the plies the state provides, and the root is searched again to that depth
+    /// without consulting its own entry.  Below the root, this verification search is mostly
+    /// answered by the table.  An entry that fails either check is discarded, and the search
+    /// starts over from the first depth, as recorded in `Statistics::precalculation_rejected`.
+    /// Defaults to `true`.
+    pub fn set_reuse_precalculated(&mut self, reuse: bool) {
+        self.reuse_precalculated = reuse;
+    }
+
+    /// Sets a function to be called with every entry the search stores in its transposition
+    /// table, for example to replicate deep entries to searches on other machines.  The state
+    /// and principal variation are as searched, regardless of `set_symmetric`.
+    pub fn set_store_hook(&mut self, store_hook: Option<StoreHook<S, <E as Evaluator>::Evaluation>>) {
+        self.transposition_table.set_store_hook(store_hook);
+    }
+
+    /// Merges a transposition table entry received from another search, preferring the
+    /// deeper of it and any existing entry for `state`.  Entries without a principal
+    /// variation are ignored.  Returns true if the entry was stored.
+    pub fn ingest(&mut self, state: S, entry: TranspositionTableEntry<<S as State>::Ply, <E as Evaluator>::Evaluation>) -> bool {
+        self.transposition_table.ingest(state, entry)
+    }
+
+    /// Executes `ply` on `state`, notifying an incremental evaluator.
+    fn execute_ply(&mut self, state: &mut S, ply: Option<&<S as State>::Ply>) -> Result<(), PlyError> {
+        state.execute_ply(ply)?;
+        if let Some(ref incremental) = self.incremental {
+            (incremental.apply_ply)(&mut self.evaluator, state, ply);
+        }
+        Ok(())
+    }
+
+    /// Reverts `ply` from `state`, notifying an incremental evaluator.
+    fn revert_ply(&mut self, state: &mut S, ply: Option<&<S as State>::Ply>) -> Result<(), PlyError> {
+        state.revert_ply(ply)?;
+        if let Some(ref incremental) = self.incremental {
+            (incremental.revert_ply)(&mut self.evaluator, state, ply);
+        }
+        Ok(())
+    }
+
+    /// Searches `state`, which is `height` plies from the root and was reached by
+    /// `previous_ply`, if any.
+    ///
+    /// Principal variations are kept in `pv_lines`, one line per height, so they don't need to be
+    /// allocated at every node.  On entry, the line at `height` holds the expected principal
+    /// variation, which is used to order plies; on return, it holds the principal variation found.
     fn minimax(
         &mut self,
         state: &mut S,
-        principal_variation: &mut Vec<<S as State>::Ply>,
+        previous_ply: Option<&<S as State>::Ply>,
+        height: usize,
         depth: u8,
         max_depth: u8,
         mut alpha: <E as Evaluator>::Evaluation,
-        beta: <E as Evaluator>::Evaluation,
+        mut beta: <E as Evaluator>::Evaluation,
         stats: &mut [StatisticsLevel],
         interrupt: Option<&Receiver<()>>,
         null_move_allowed: bool,
+        extensions: u8,
     ) -> <E as Evaluator>::Evaluation {
         let search_iteration = (max_depth - depth) as usize;
 
+        self.nodes += 1;
+        if self.node_limit != 0 && self.nodes >= self.node_limit {
+            self.interrupted = true;
+        }
+
+        while self.pv_lines.len() < height + 2 {
+            self.pv_lines.push(Vec::new());
+            self.pv_saved.push(Vec::new());
+        }
+
+        if height > self.seldepth {
+            self.seldepth = height;
+        }
+
+        // No line from a dead draw can end any other way
+        if height > 0 && state.is_dead_draw() {
+            self.pv_lines[height].clear();
+            return <E as Evaluator>::Evaluation::null();
+        }
+
+        let solved = match self.tablebase {
+            Some(ref tablebase) if height > 0 => tablebase.get(state),
+            _ => None,
+        };
+        if let Some(value) = solved {
+            self.pv_lines[height].clear();
+            return value.evaluation(state.get_ply_count());
+        }
+
+        let corrected = match self.experience {
+            Some(ref experience) if height > 0 => match experience.get(state) {
+                Some(entry) if entry.depth >= depth => Some(entry.value),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(value) = corrected {
+            self.pv_lines[height].clear();
+            return value;
+        }
+
         if depth == 0 || state.check_resolution().is_some() {
             if search_iteration > 0 {
                 stats[search_iteration - 1].evaluated += 1;
             }
-            principal_variation.clear();
+            self.pv_lines[height].clear();
             return self.evaluator.evaluate(state);
         }
 
         stats[search_iteration].visited += 1;
 
-        if let Some(entry) = self.transposition_table.get(state) {
+        if let Some(ref mut logger) = self.logger {
+            logger.node_entered(state, depth);
+        }
+
+        // Every line from here ends on the next ply at the soonest, so nothing can be found that
+        // is better than winning or worse than losing on it.  The root is left alone so that it
+        // always chooses a ply.
+        if height > 0 && <E as Evaluator>::Evaluation::encodes_win_distance() {
+            let next_ply = state.get_ply_count() as i32 + 1;
+            let best = <E as Evaluator>::Evaluation::win().shift(-next_ply);
+            let worst = <E as Evaluator>::Evaluation::lose().shift(next_ply);
+
+            if alpha >= best || beta <= worst {
+                stats[search_iteration].win_distance_cutoffs += 1;
+                self.pv_lines[height].clear();
+                return if alpha >= best { alpha } else { beta };
+            }
+
+            if beta > best {
+                beta = best;
+            }
+        }
+
+        let mut singular_candidate = None;
+
+        // A verification search mustn't simply return the root entry it's verifying
+        let entry = if height == 0 && self.verifying {
+            None
+        } else {
+            self.transposition_table.get(state)
+        };
+
+        if let Some(entry) = entry {
             stats[search_iteration].tt_hits += 1;
 
             let mut usable = false;
@@ -170,113 +681,290 @@ impl<S, E> PvSearch<S, E> where
                 usable = true;
             }
 
+            let entry_principal_variation = if usable {
+                self.transposition_table.get_principal_variation(state, entry)
+            } else {
+                Vec::new()
+            };
+
             if usable {
-                if state.execute_ply(Some(&entry.principal_variation[0])).is_ok() {
-                    if let Err(error) = state.revert_ply(Some(&entry.principal_variation[0])) {
+                if state.execute_ply(Some(&entry_principal_variation[0])).is_ok() {
+                    if let Err(error) = state.revert_ply(Some(&entry_principal_variation[0])) {
                         panic!("Error reverting state: {}", error);
                     }
-                    stats[search_iteration].tt_saves += 1;
+                } else {
+                    usable = false;
+                }
+            }
 
-                    principal_variation.clear();
-                    principal_variation.append(&mut entry.principal_variation.clone());
+            if let Some(ref mut logger) = self.logger {
+                logger.tt_hit(state, usable);
+            }
+
+            if usable {
+                stats[search_iteration].tt_saves += 1;
+
+                self.pv_lines[height] = entry_principal_variation;
+
+                let value = entry.value;
+                self.transposition_table.refresh(state);
+                return value;
+            }
+
+            // A ply that proved good enough for a nearly as deep search is a candidate for
+            // a singular extension
+            if self.singular_margin > 0 && search_iteration > 0 && depth >= SINGULAR_DEPTH &&
+               entry.depth + 3 >= depth && entry.bound != Bound::Upper && !entry.value.is_end() {
+                if let Some(ply) = self.transposition_table.get_principal_variation(state, entry).into_iter().next() {
+                    singular_candidate = Some((ply, entry.value));
+                }
+            }
+        }
+
+        // Near the leaves, a state whose static evaluation is far enough from the window is
+        // unlikely to be brought back into it by the few plies left
+        let static_evaluation = if search_iteration > 0 &&
+            ((self.reverse_futility_margin > 0 && depth <= REVERSE_FUTILITY_DEPTH) ||
+            (self.futility_margin > 0 && depth <= FUTILITY_DEPTH)) {
+            Some(self.evaluator.evaluate(state))
+        } else {
+            None
+        };
 
-                    return entry.value;
+        if let Some(static_evaluation) = static_evaluation {
+            if self.reverse_futility_margin > 0 && depth <= REVERSE_FUTILITY_DEPTH && !beta.is_end() {
+                // The static evaluation credits the state with the tempo of moving, which a
+                // search of its plies wouldn't
+                let margin = <E as Evaluator>::Evaluation::granularity_multiple(self.reverse_futility_margin.saturating_mul(depth as u32));
+                if lower_bound(lower_bound(static_evaluation, margin), self.evaluator.tempo()) >= beta {
+                    stats[search_iteration].reverse_futility_cutoffs += 1;
+                    return beta;
                 }
             }
         }
 
-        if null_move_allowed &&
+        let futile = match static_evaluation {
+            Some(static_evaluation) if self.futility_margin > 0 && depth <= FUTILITY_DEPTH && !alpha.is_end() => {
+                let margin = <E as Evaluator>::Evaluation::granularity_multiple(self.futility_margin.saturating_mul(depth as u32));
+                upper_bound(static_evaluation, margin) <= alpha
+            },
+            _ => false,
+        };
+
+        // A real move is assumed to be worth at least the tempo that passing forfeits
+        if self.null_move && null_move_allowed &&
             search_iteration > 0 && depth >= 3 &&
             state.null_move_allowed() {
-            if state.execute_ply(None).is_ok() {
-                let mut scratch = Vec::new();
+            if self.execute_ply(state, None).is_ok() {
+                let null_beta = beta - self.evaluator.tempo();
+                self.pv_lines[height + 1].clear();
                 let eval = -self.minimax(
-                    state, &mut scratch, depth - 3, max_depth,
-                    -beta, (-beta).shift(1),
+                    state, None, height + 1, depth - 3, max_depth,
+                    -null_beta, (-null_beta).shift(1),
                     stats,
                     interrupt,
                     false,
+                    extensions,
                 );
 
-                if let Err(error) = state.revert_ply(None) {
+                if let Err(error) = self.revert_ply(state, None) {
                     panic!("Error reverting state: {}", error);
                 }
 
-                if eval >= beta {
+                if eval >= null_beta {
+                    stats[search_iteration].null_cutoffs += 1;
+                    if let Some(ref mut logger) = self.logger {
+                        logger.cutoff(state, None, 0);
+                    }
                     return beta;
                 }
             }
         }
 
-        let ply_generator = PlyGenerator::new(
+        let mut ply_generator = PlyGenerator::new(
             state,
-            principal_variation.first().cloned(),
+            self.pv_lines[height].first().cloned(),
             self.history.clone(),
+            &mut self.rng,
+            self.ply_buffers.pop().unwrap_or_default(),
+            self.staged,
         );
 
-        let mut next_principal_variation = if !principal_variation.is_empty() {
-            principal_variation[1..].to_vec()
+        // Plies are only generated for states that weren't cut off above.  Forced moves are
+        // searched without consuming depth.
+        let forced = extensions > 0 && ply_generator.is_forced(state, &mut self.rng);
+        let (next_depth, next_extensions) = if forced {
+            (depth, extensions - 1)
         } else {
-            Vec::new()
+            (depth - 1, extensions)
+        };
+
+        let singular_ply = match singular_candidate {
+            Some((ply, value)) if !forced && extensions > 0 => {
+                if self.is_singular(state, height, &ply, value, depth, max_depth, stats, interrupt) {
+                    Some(ply)
+                } else {
+                    None
+                }
+            },
+            _ => None,
         };
 
+        // Each child starts from the principal variation of the previous one, or the remainder
+        // of the expected line for the first
+        {
+            let (lines, next_lines) = self.pv_lines.split_at_mut(height + 1);
+            next_lines[0].clear();
+            if !lines[height].is_empty() {
+                next_lines[0].extend_from_slice(&lines[height][1..]);
+            }
+        }
+
         let mut first_iteration = true;
         let mut raised_alpha = false;
 
-        for ply in ply_generator {
-            if state.execute_ply(Some(&ply)).is_err() {
+        let mut index = 0;
+        let mut executed = 0;
+
+        while let Some(ply) = ply_generator.next(state, previous_ply, &mut self.rng, &*self.move_ordering) {
+            if height == 0 {
+                if self.root_limit > 0 && index >= self.root_limit {
+                    break;
+                }
+                self.root_searched += 1;
+            }
+
+            index += 1;
+
+            if self.execute_ply(state, Some(&ply)).is_err() {
+                continue;
+            }
+            executed += 1;
+
+            if futile && !first_iteration && state.check_resolution().is_none() {
+                if let Err(error) = self.revert_ply(state, Some(&ply)) {
+                    panic!("Error reverting state: {}\n{}\n{:?}", error, state, ply);
+                }
+                stats[search_iteration].futility_pruned += 1;
                 continue;
             }
 
+            // Plies that are singular or that the state recognizes as forcing are searched deeper
+            let (ply_depth, ply_extensions) = if !forced && next_extensions > 0 {
+                if singular_ply.as_ref() == Some(&ply) {
+                    stats[search_iteration].singular_extensions += 1;
+                    (depth, next_extensions - 1)
+                } else if self.threats.map_or(false, |is_forcing| is_forcing(state, &ply)) {
+                    stats[search_iteration].threat_extensions += 1;
+                    (depth, next_extensions - 1)
+                } else {
+                    (next_depth, next_extensions)
+                }
+            } else {
+                (next_depth, next_extensions)
+            };
+
+            let explain = self.explain && height == 0;
+            let (explain_alpha, explain_statistics) = if explain {
+                (alpha, total_statistics(stats))
+            } else {
+                (alpha, StatisticsLevel::new())
+            };
+            let mut re_searched = false;
+
             let next_eval = if first_iteration {
                 -self.minimax(
-                    state, &mut next_principal_variation, depth - 1, max_depth,
+                    state, Some(&ply), height + 1, ply_depth, max_depth,
                     -beta, -alpha,
                     stats,
                     interrupt,
                     true,
+                    ply_extensions,
                 )
             } else {
-                let mut npv = next_principal_variation.clone();
+                self.pv_saved[height].clone_from(&self.pv_lines[height + 1]);
                 let next_eval = -self.minimax(
-                    state, &mut npv, depth - 1, max_depth,
+                    state, Some(&ply), height + 1, ply_depth, max_depth,
                     (-alpha).shift(-1), -alpha,
                     stats,
                     interrupt,
                     true,
+                    ply_extensions,
                 );
 
                 if next_eval > alpha && next_eval < beta {
+                    re_searched = true;
+                    if let Some(ref mut logger) = self.logger {
+                        logger.re_search(state, &ply);
+                    }
+                    self.pv_lines[height + 1].clone_from(&self.pv_saved[height]);
                     -self.minimax(
-                        state, &mut next_principal_variation, depth - 1, max_depth,
+                        state, Some(&ply), height + 1, ply_depth, max_depth,
                         -beta, -alpha,
                         stats,
                         interrupt,
                         true,
+                        ply_extensions,
                     )
                 } else {
-                    next_principal_variation = npv;
                     next_eval
                 }
             };
 
-            if let Err(error) = state.revert_ply(Some(&ply)) {
+            if let Err(error) = self.revert_ply(state, Some(&ply)) {
                 panic!("Error reverting state: {}\n{}\n{:?}", error, state, ply);
             }
 
+            if height == 0 && (explain || self.record_root_scores) {
+                let bound = if next_eval <= explain_alpha {
+                    Bound::Upper
+                } else if next_eval >= beta {
+                    Bound::Lower
+                } else {
+                    Bound::Exact
+                };
+
+                if self.record_root_scores {
+                    self.root_scores.push(RootScore {
+                        ply: ply.clone(),
+                        value: next_eval,
+                        bound: bound,
+                    });
+                }
+
+                if explain {
+                    self.root_reports.push(RootPlyReport {
+                        ply: ply.clone(),
+                        value: next_eval,
+                        bound: bound,
+                        re_searched: re_searched,
+                        statistics: statistics_since(&total_statistics(stats), &explain_statistics),
+                    });
+                }
+            }
+
             if next_eval > alpha {
                 alpha = next_eval;
                 raised_alpha = true;
 
-                principal_variation.clear();
-                principal_variation.push(ply.clone());
-                principal_variation.append(&mut next_principal_variation.clone());
+                {
+                    let (lines, next_lines) = self.pv_lines.split_at_mut(height + 1);
+                    lines[height].clear();
+                    lines[height].push(ply.clone());
+                    lines[height].extend_from_slice(&next_lines[0]);
+                }
 
                 if alpha >= beta {
+                    stats[search_iteration].record_cutoff(index - 1);
+                    if let Some(ref mut logger) = self.logger {
+                        logger.cutoff(state, Some(&ply), index - 1);
+                    }
                     {
                         let mut history = self.history.lock().unwrap();
-                        let entry = history.entry(&ply).or_insert(0);
-                        *entry += 1 << depth;
+                        history.reward(state.get_ply_count(), &ply, depth);
+                        if let Some(previous_ply) = previous_ply {
+                            history.set_countermove(previous_ply, &ply);
+                        }
                     }
                     break;
                 }
@@ -289,7 +977,34 @@ impl<S, E> PvSearch<S, E> where
             }
         }
 
-        if let Some(ply) = principal_variation.first() {
+        self.ply_buffers.push(ply_generator.into_plies());
+
+        // The player to move has no legal plies, so either the game ends here or they pass
+        if executed == 0 {
+            self.pv_lines[height].clear();
+            return match state.no_plies_resolution() {
+                Some(resolution) => resolution_evaluation::<S, E>(state, &resolution),
+                None => if self.execute_ply(state, None).is_ok() {
+                    let eval = -self.minimax(
+                        state, None, height + 1, depth - 1, max_depth,
+                        -beta, -alpha,
+                        stats,
+                        interrupt,
+                        false,
+                        extensions,
+                    );
+
+                    if let Err(error) = self.revert_ply(state, None) {
+                        panic!("Error reverting state: {}", error);
+                    }
+                    eval
+                } else {
+                    self.evaluator.evaluate(state)
+                },
+            };
+        }
+
+        if let Some(ply) = self.pv_lines[height].first() {
             if state.execute_ply(Some(ply)).is_ok() {
                 if let Err(error) = state.revert_ply(Some(ply)) {
                     panic!("Error reverting state: {}", error);
@@ -305,8 +1020,8 @@ impl<S, E> PvSearch<S, E> where
                         } else {
                             Bound::Exact
                         },
-                        principal_variation: principal_variation.clone(),
-                        lifetime: 2,
+                        principal_variation: self.pv_lines[height].clone(),
+                        generation: 0,
                     }
                 );
                 stats[search_iteration].tt_stores += 1;
@@ -316,6 +1031,104 @@ impl<S, E> PvSearch<S, E> where
         alpha
     }
 
+    /// Returns true if a search of half the depth shows that every ply from `state` other than
+    /// `ply` falls short of `value` by at least the singular margin.
+    fn is_singular(
+        &mut self,
+        state: &mut S,
+        height: usize,
+        ply: &<S as State>::Ply,
+        value: <E as Evaluator>::Evaluation,
+        depth: u8,
+        max_depth: u8,
+        stats: &mut [StatisticsLevel],
+        interrupt: Option<&Receiver<()>>,
+    ) -> bool {
+        let margin = <E as Evaluator>::Evaluation::granularity_multiple(self.singular_margin.saturating_mul(depth as u32));
+        let singular_alpha = lower_bound(value.shift(-1), margin);
+
+        let mut plies = self.ply_buffers.pop().unwrap_or_default();
+        state.extrapolate_into(&mut plies);
+
+        let mut singular = true;
+        for other in &plies {
+            if other == ply || self.execute_ply(state, Some(other)).is_err() {
+                continue;
+            }
+
+            self.pv_lines[height + 1].clear();
+            let eval = -self.minimax(
+                state, Some(other), height + 1, (depth - 1) / 2, max_depth,
+                (-singular_alpha).shift(-1), -singular_alpha,
+                stats,
+                interrupt,
+                true,
+                0,
+            );
+
+            if let Err(error) = self.revert_ply(state, Some(other)) {
+                panic!("Error reverting state: {}", error);
+            }
+
+            if eval > singular_alpha || self.is_interrupted(&interrupt) {
+                singular = false;
+                break;
+            }
+        }
+
+        self.ply_buffers.push(plies);
+        singular
+    }
+
+    /// Returns true if the exact root entry with `value` and `principal_variation`, searched
+    /// to `depth`, can be trusted in place of searching that deep again.  An interrupted
+    /// verification gives no reason to distrust the entry.
+    fn verify_precalculated(
+        &mut self,
+        state: &mut S,
+        principal_variation: &[<S as State>::Ply],
+        value: <E as Evaluator>::Evaluation,
+        depth: u8,
+        interrupt: Option<&Receiver<()>>,
+    ) -> bool {
+        if principal_variation.is_empty() {
+            return false;
+        }
+
+        let mut line = state.clone();
+        for ply in principal_variation {
+            if !line.extrapolate().contains(ply) || line.execute_ply(Some(ply)).is_err() {
+                return false;
+            }
+        }
+
+        self.root_limit = 0;
+        self.root_searched = 0;
+        self.seldepth = 0;
+        if self.pv_lines.is_empty() {
+            self.pv_lines.push(Vec::new());
+            self.pv_saved.push(Vec::new());
+        }
+        self.pv_lines[0].clear();
+        self.pv_lines[0].extend_from_slice(principal_variation);
+
+        self.verifying = true;
+        let verified = self.minimax(
+            state,
+            None,
+            0,
+            depth, depth,
+            <E as Evaluator>::Evaluation::min(), <E as Evaluator>::Evaluation::max(),
+            &mut vec![StatisticsLevel::new(); depth as usize],
+            interrupt,
+            true,
+            self.forced_move_extensions,
+        );
+        self.verifying = false;
+
+        verified == value || self.is_interrupted(&interrupt)
+    }
+
     fn is_interrupted(&mut self, interrupt: &Option<&Receiver<()>>) -> bool {
         if self.interrupted {
             return true;
@@ -330,84 +1143,389 @@ impl<S, E> PvSearch<S, E> where
     }
 }
 
-impl<S, E> Search<S> for PvSearch<S, E> where
-    S: 'static + State + Extrapolatable<<S as State>::Ply>,
-    E: 'static + Evaluator<State = S> {
-    fn search(&mut self, state: &S, interrupt: Option<Receiver<()>>) -> Box<Analysis> {
-        let mut state = state.clone();
-        let mut eval = <E as Evaluator>::Evaluation::null();
-        let mut principal_variation = Vec::new();
-        let mut statistics = Vec::new();
+impl<S, E> PvSearch<S, E> where
+    S: State + Extrapolatable<<S as State>::Ply> + Persistable,
+    <S as State>::Ply: Persistable,
+    E: Evaluator<State = S>,
+    <E as Evaluator>::Evaluation: Persistable {
+    /// Saves the transposition table to the file at `path`, so that a later session can
+    /// resume from it with `load_table`.
+    pub fn save_table<P>(&self, path: P) -> Result<(), PersistError> where P: AsRef<Path> {
+        let file = File::create(path)?;
+        self.transposition_table.save(BufWriter::new(file))
+    }
 
-        let max_depth = if self.depth == 0 {
-            u8::MAX - 1
-        } else {
-            self.depth
-        };
+    /// Loads a transposition table saved by `save_table`, keeping the deeper of each loaded
+    /// entry and any existing one.  Returns the number of entries loaded.
+    ///
+    /// If the file is damaged, the entries before the damage are still loaded before the
+    /// error is returned.
+    pub fn load_table<P>(&mut self, path: P) -> Result<usize, PersistError> where P: AsRef<Path> {
+        let file = File::open(path)?;
+        self.transposition_table.load(BufReader::new(file))
+    }
 
-        let start_move = Instant::now();
+    /// Saves a checkpoint of a search of `state` to the file at `path`, so that the search
+    /// can be picked up later with `resume`.  The checkpoint holds only `state` and the
+    /// transposition table, from which the search recovers the deepest completed iteration
+    /// and its principal variation; the history heuristic isn't saved, so move ordering starts
+    /// over.  Nothing is saved automatically: to checkpoint an interrupted search, call this
+    /// once the search returns.
+    pub fn save_checkpoint<P>(&self, path: P, state: &S) -> Result<(), PersistError> where P: AsRef<Path> {
+        let file = File::create(path)?;
+        let mut writer = Writer::new(BufWriter::new(file), CHECKPOINT_MAGIC, CHECKPOINT_VERSION)?;
 
-        self.history.lock().unwrap().clear();
-        self.interrupted = false;
+        let mut bytes = Vec::new();
+        state.encode(&mut bytes);
+        writer.write_section(&bytes)?;
 
-        let precalculated = match self.transposition_table.get(&state) {
-            Some(entry) => {
-                if entry.bound == Bound::Exact {
-                    principal_variation.append(&mut entry.principal_variation.clone());
-                    entry.depth
-                } else {
-                    0
-                }
+        self.transposition_table.write_sections(&mut writer)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Loads a checkpoint saved by `save_checkpoint` into the transposition table, and
+    /// returns the state it was saved for.
+    pub fn load_checkpoint<P>(&mut self, path: P) -> Result<S, PersistError> where P: AsRef<Path> {
+        let file = File::open(path)?;
+        let mut reader = Reader::new(BufReader::new(file), CHECKPOINT_MAGIC, CHECKPOINT_VERSION)?;
+
+        let state = match reader.read_section()? {
+            Some(bytes) => {
+                let mut decoder = Decoder::new(&bytes);
+                let state = S::decode(&mut decoder)?;
+                if !decoder.is_empty() {
+                    return Err(PersistError::Malformed(String::from("Unexpected data after state")));
+                }
+                state
             },
-            None => 0,
+            None => return Err(PersistError::Malformed(String::from("Checkpoint without a state"))),
         };
 
-        for depth in 1..precalculated + 1 {
-            statistics.push(vec![StatisticsLevel::new(); depth as usize]);
+        self.transposition_table.read_sections(&mut reader)?;
+        Ok(state)
+    }
+}
+
+impl<S, E> PvSearch<S, E> where
+    S: 'static + State + Extrapolatable<<S as State>::Ply> + Persistable,
+    <S as State>::Ply: Persistable,
+    E: 'static + Evaluator<State = S>,
+    <E as Evaluator>::Evaluation: Persistable {
+    /// Loads a checkpoint saved by `save_checkpoint` and continues searching its state,
+    /// beginning with the iteration after the deepest one the checkpoint completed.  This
+    /// reuses the checkpoint's entry for the state even if `set_reuse_precalculated` is off.
+    pub fn resume<P>(&mut self, path: P, interrupt: Option<Receiver<()>>) -> Result<Box<Analysis>, PersistError> where P: AsRef<Path> {
+        let state = self.load_checkpoint(path)?;
+
+        let reuse = self.reuse_precalculated;
+        self.reuse_precalculated = true;
+        let analysis = self.search(&state, interrupt);
+        self.reuse_precalculated = reuse;
+
+        Ok(analysis)
+    }
+}
+
+impl<S, E> PvSearch<S, E> where
+    S: State + Extrapolatable<<S as State>::Ply> + Symmetric,
+    E: Evaluator<State = S> {
+    /// Sets whether states are reduced to their canonical forms before being stored in
+    /// the transposition table, so that symmetric states are treated as transpositions.
+    /// Changing this setting clears the transposition table.
+    pub fn set_symmetric(&mut self, symmetric: bool) {
+        self.transposition_table.set_symmetry(if symmetric {
+            Some(Symmetry::new())
+        } else {
+            None
+        });
+    }
+}
+
+impl<S, E> PvSearch<S, E> where
+    S: State + Extrapolatable<<S as State>::Ply>,
+    E: IncrementalEvaluator<State = S> {
+    /// Sets whether the search drives the evaluator incrementally, notifying it of each ply
+    /// executed and reverted during the search.
+    pub fn set_incremental(&mut self, incremental: bool) {
+        self.incremental = if incremental {
+            Some(Incremental::new())
+        } else {
+            None
+        };
+    }
+}
+
+impl<S, E> PvSearch<S, E> where
+    S: State + StagedExtrapolatable<<S as State>::Ply>,
+    E: Evaluator<State = S> {
+    /// Sets whether plies are generated in the stages offered by the state, rather than all
+    /// at once.  Later stages are only generated if the search gets to them.
+    pub fn set_staged(&mut self, staged: bool) {
+        self.staged = if staged {
+            Some(<S as StagedExtrapolatable<<S as State>::Ply>>::extrapolate_stage)
+        } else {
+            None
+        };
+    }
+}
+
+impl<S, E> PvSearch<S, E> where
+    S: State + Extrapolatable<<S as State>::Ply> + PositionKey,
+    E: Evaluator<State = S> {
+    /// Sets whether the transposition table keys states by their position keys, rather than
+    /// by their hashes.  Changing this setting clears the transposition table.
+    pub fn set_position_keys(&mut self, position_keys: bool) {
+        fn key<S>(state: &S) -> u64 where S: PositionKey {
+            state.position_key()
         }
 
-        // Purge transposition table
-        {
-            let mut forget = Vec::with_capacity(self.transposition_table.len() / 5);
+        self.transposition_table.set_key(if position_keys {
+            Some(key::<S>)
+        } else {
+            None
+        });
+    }
+}
+
+impl<S, E> PvSearch<S, E> where
+    S: State + Extrapolatable<<S as State>::Ply>,
+    <S as State>::Ply: ButterflyIndex,
+    E: Evaluator<State = S> {
+    /// Sets whether the history heuristic scores plies by their butterfly indices, so that a
+    /// cutoff is credited to every ply sharing the index of the one that caused it.  Changing
+    /// this setting clears the history.
+    pub fn set_butterfly_history(&mut self, butterfly: bool) {
+        fn key<P>(ply: &P) -> u64 where P: ButterflyIndex {
+            ply.butterfly_index() as u64
+        }
 
-            for (key, entry) in self.transposition_table.iter_mut() {
-                if entry.lifetime > 0 {
-                    entry.lifetime -= 1;
+        self.history.lock().unwrap().set_key(if butterfly {
+            Some(key::<<S as State>::Ply>)
+        } else {
+            None
+        });
+    }
+}
+
+impl<S, E> PvSearch<S, E> where
+    S: State + ThreatDetector<<S as State>::Ply>,
+    E: Evaluator<State = S> {
+    /// Sets whether plies that the state recognizes as forcing are searched one ply deeper.
+    pub fn set_threat_extensions(&mut self, threat_extensions: bool) {
+        self.threats = if threat_extensions {
+            Some(<S as ThreatDetector<<S as State>::Ply>>::is_forcing)
+        } else {
+            None
+        };
+    }
+}
+
+impl<S, E> Search<S> for PvSearch<S, E> where
+    S: 'static + State + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S> {
+    fn search(&mut self, state: &S, interrupt: Option<Receiver<()>>) -> Box<Analysis> {
+        let mut state = state.clone();
+        let mut eval = <E as Evaluator>::Evaluation::null();
+        let mut principal_variation = Vec::new();
+        let mut statistics = Vec::new();
+        let mut root_plies = Vec::new();
+        let mut seldepth = Vec::new();
+        let mut stable_depth = 0;
+        let mut explanation = None;
+        let mut root_scores = None;
+        let mut interrupted = false;
+
+        let max_depth = if self.depth == 0 {
+            u8::MAX - 1
+        } else {
+            self.depth
+        };
+
+        let start_move = Instant::now();
+
+        self.history.lock().unwrap().clear();
+        self.interrupted = false;
+        self.nodes = 0;
+
+        if let Some(ref incremental) = self.incremental {
+            (incremental.reset)(&mut self.evaluator, &state);
+        }
+
+        let mut precalculated = match self.transposition_table.get(&state) {
+            Some(entry) if self.reuse_precalculated => {
+                if entry.bound == Bound::Exact {
+                    principal_variation.append(&mut self.transposition_table.get_principal_variation(&state, entry));
+                    eval = entry.value;
+                    entry.depth
                 } else {
-                    forget.push(key.clone());
+                    0
                 }
-            }
+            },
+            _ => 0,
+        };
 
-            for key in forget {
-                self.transposition_table.remove(&key);
-            }
+        self.transposition_table.new_search();
+
+        let mut precalculation_rejected = false;
+        if precalculated > 0 && !self.verify_precalculated(&mut state, &principal_variation, eval, precalculated, interrupt.as_ref()) {
+            precalculated = 0;
+            precalculation_rejected = true;
+            principal_variation.clear();
+            eval = <E as Evaluator>::Evaluation::null();
+        }
+
+        for depth in 1..precalculated + 1 {
+            statistics.push(vec![StatisticsLevel::new(); depth as usize]);
+            root_plies.push(0);
+            seldepth.push(0);
         }
 
+        if precalculated > 0 && !principal_variation.is_empty() {
+            stable_depth = precalculated;
+        }
+
+        let mut best_ply = principal_variation.first().cloned();
+        let mut last_search_time = 0.0;
+
+        // The results of the last completed iteration, which an interrupted one doesn't replace
+        let mut completed = (precalculated, eval, principal_variation.clone());
+
         for depth in 1..max_depth + 1 - precalculated {
             let search_depth = depth + precalculated;
 
             statistics.push(vec![StatisticsLevel::new(); search_depth as usize]);
 
+            if self.history_aging && depth > 1 {
+                self.history.lock().unwrap().age();
+            }
+
+            // Only search the most promising root plies until the final iteration
+            self.root_limit = if self.root_widening > 0 {
+                let final_iteration = search_depth >= max_depth || (
+                    self.goal != 0 && last_search_time > 0.0 &&
+                    seconds(start_move.elapsed()) + last_search_time * self.branching_factor * (1.0 + self.branching_factor) > self.goal as f32
+                );
+
+                if final_iteration {
+                    0
+                } else {
+                    self.root_widening.saturating_mul(1 << cmp::min(depth - 1, 20))
+                }
+            } else {
+                0
+            };
+            self.root_searched = 0;
+            self.seldepth = 0;
+
             let start_search = Instant::now();
 
-            eval = self.minimax(
-                &mut state,
-                &mut principal_variation,
-                search_depth, search_depth,
-                <E as Evaluator>::Evaluation::min(), <E as Evaluator>::Evaluation::max(),
-                &mut statistics.last_mut().unwrap(),
-                interrupt.as_ref(),
-                true,
-            );
+            // Search a window around the previous evaluation, widening it on failure
+            let mut window = self.aspiration_window;
+            let (mut alpha, mut beta) = if window > 0 && (depth > 1 || precalculated > 0) && !eval.is_end() {
+                let (jitter_low, jitter_high) = if self.seeded {
+                    (self.rng.gen_range(0, window / 2 + 1), self.rng.gen_range(0, window / 2 + 1))
+                } else {
+                    (0, 0)
+                };
+                (
+                    lower_bound(eval, <E as Evaluator>::Evaluation::granularity_multiple(window + jitter_low)),
+                    upper_bound(eval, <E as Evaluator>::Evaluation::granularity_multiple(window + jitter_high)),
+                )
+            } else {
+                (<E as Evaluator>::Evaluation::min(), <E as Evaluator>::Evaluation::max())
+            };
+
+            loop {
+                self.root_reports.clear();
+                self.root_scores.clear();
+                if self.pv_lines.is_empty() {
+                    self.pv_lines.push(Vec::new());
+                    self.pv_saved.push(Vec::new());
+                }
+                self.pv_lines[0].clone_from(&principal_variation);
+                eval = self.minimax(
+                    &mut state,
+                    None,
+                    0,
+                    search_depth, search_depth,
+                    alpha, beta,
+                    &mut statistics.last_mut().unwrap(),
+                    interrupt.as_ref(),
+                    true,
+                    self.forced_move_extensions,
+                );
+                principal_variation.clone_from(&self.pv_lines[0]);
+
+                let failed_low = eval <= alpha && alpha != <E as Evaluator>::Evaluation::min();
+                let failed_high = eval >= beta && beta != <E as Evaluator>::Evaluation::max();
+
+                if !(failed_low || failed_high) || self.is_interrupted(&interrupt.as_ref()) {
+                    break;
+                }
+
+                window = window.saturating_mul(4);
+                if failed_low {
+                    alpha = if window < ASPIRATION_LIMIT && !eval.is_end() {
+                        lower_bound(eval, <E as Evaluator>::Evaluation::granularity_multiple(window))
+                    } else {
+                        <E as Evaluator>::Evaluation::min()
+                    };
+                } else {
+                    beta = if window < ASPIRATION_LIMIT && !eval.is_end() {
+                        upper_bound(eval, <E as Evaluator>::Evaluation::granularity_multiple(window))
+                    } else {
+                        <E as Evaluator>::Evaluation::max()
+                    };
+                }
 
-            let elapsed_search = start_search.elapsed();
-            let elapsed_search = elapsed_search.as_secs() as f32 + elapsed_search.subsec_nanos() as f32 / 1_000_000_000.0;
-            let elapsed_move = start_move.elapsed();
-            let elapsed_move = elapsed_move.as_secs() as f32 + elapsed_move.subsec_nanos() as f32 / 1_000_000_000.0;
+                if let Some(ref mut logger) = self.logger {
+                    logger.aspiration_re_search(search_depth, alpha, beta);
+                }
+            }
+
+            let elapsed_search = seconds(start_search.elapsed());
+            let elapsed_move = seconds(start_move.elapsed());
 
             statistics.last_mut().unwrap()[0].time = elapsed_search;
+            root_plies.push(self.root_searched);
+            seldepth.push(self.seldepth);
+            last_search_time = elapsed_search;
 
             if self.is_interrupted(&interrupt.as_ref()) {
+                interrupted = true;
+                break;
+            }
+
+            completed = (search_depth, eval, principal_variation.clone());
+
+            if stable_depth == 0 || principal_variation.first() != best_ply.as_ref() {
+                stable_depth = search_depth;
+            }
+            best_ply = principal_variation.first().cloned();
+
+            if let Some(ref mut logger) = self.logger {
+                logger.iteration_finished(search_depth, eval, &principal_variation, elapsed_search);
+            }
+
+            if self.record_root_scores {
+                root_scores = Some(mem::replace(&mut self.root_scores, Vec::new()));
+            }
+
+            if self.explain {
+                let root_plies = mem::replace(&mut self.root_reports, Vec::new());
+                explanation = Some(Explanation {
+                    depth: search_depth,
+                    chosen: principal_variation.first().and_then(|first| {
+                        root_plies.iter().position(|report| report.ply == *first)
+                    }),
+                    skipped: state.extrapolate().len().saturating_sub(self.root_searched),
+                    root_plies: root_plies,
+                });
+            }
+
+            // Without a ply to make, there's nothing for a deeper search to choose between
+            if principal_variation.is_empty() {
                 break;
             }
 
@@ -423,15 +1541,183 @@ impl<S, E> Search<S> for PvSearch<S, E> where
             }
         }
 
+        let completed_depth = completed.0;
+        if completed_depth > 0 {
+            eval = completed.1;
+            principal_variation = completed.2;
+        }
+
+        let resolution = {
+            let mut result = state.clone();
+            if result.execute_plies(&principal_variation).is_ok() {
+                result.check_resolution().or_else(|| no_plies_resolution(&result))
+            } else {
+                None
+            }
+        };
+
         Box::new(PvSearchAnalysis::<S, E> {
             state: state.clone(),
             evaluation: eval,
+            win_probability: self.evaluator.score_model().map(|model| model.win_probability(eval)),
             principal_variation: principal_variation,
+            resolution: resolution,
+            explanation: explanation,
+            root_scores: root_scores,
+            interrupted: interrupted,
+            completed_depth: completed_depth,
             statistics: Statistics {
                 depth: statistics,
+                tt_entries: self.transposition_table.len(),
+                root_plies: root_plies,
+                seldepth: seldepth,
+                stable_depth: stable_depth,
+                node_count: self.nodes,
+                precalculated: precalculated,
+                precalculation_rejected: precalculation_rejected,
             },
         })
     }
+
+    fn get_principal_variation(&self, analysis: &Analysis) -> Vec<<S as State>::Ply> {
+        match analysis.as_any().downcast_ref::<PvSearchAnalysis<S, E>>() {
+            Some(analysis) => analysis.principal_variation.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.transposition_table.new_game();
+        self.history.lock().unwrap().clear();
+    }
+}
+
+impl<S, E> PvSearch<S, E> where
+    S: 'static + State + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S> {
+    /// Continues `analysis` from the state `pv_index` plies along its principal variation,
+    /// searching it `extra_depth` plies deeper than the original search completed.  The
+    /// transposition table carries over, so the shallower iterations cost little.  The time
+    /// goal, if any, is ignored for the extended search.
+    ///
+    /// Returns an error if the principal variation is shorter than `pv_index`.
+    pub fn extend_analysis(
+        &mut self,
+        analysis: &PvSearchAnalysis<S, E>,
+        pv_index: usize,
+        extra_depth: u8,
+        interrupt: Option<Receiver<()>>,
+    ) -> Result<Box<Analysis>, String> {
+        if pv_index > analysis.principal_variation.len() {
+            return Err(format!(
+                "Cannot extend from ply {} of a principal variation of {} plies.",
+                pv_index, analysis.principal_variation.len(),
+            ));
+        }
+
+        let mut state = analysis.state.clone();
+        if let Err(error) = state.execute_plies(&analysis.principal_variation[..pv_index]) {
+            return Err(format!(
+                "Error executing plies: {}, {}",
+                analysis.principal_variation[error.index], error.error,
+            ));
+        }
+
+        let reached = (analysis.completed_depth as usize).saturating_sub(pv_index);
+        let depth = cmp::min(reached + extra_depth as usize, u8::MAX as usize - 1);
+
+        let (depth, goal) = (
+            mem::replace(&mut self.depth, cmp::max(depth, 1) as u8),
+            mem::replace(&mut self.goal, 0),
+        );
+        let extended = self.search(&state, interrupt);
+        self.depth = depth;
+        self.goal = goal;
+
+        Ok(extended)
+    }
+}
+
+/// Evaluates a game that ended in `resolution` on `state`, for the player to move, in the way
+/// that the provided evaluators do.
+fn resolution_evaluation<S, E>(state: &S, resolution: &<S as State>::Resolution) -> <E as Evaluator>::Evaluation where
+    S: State,
+    E: Evaluator<State = S> {
+    let ply_count = if <E as Evaluator>::Evaluation::encodes_win_distance() {
+        state.get_ply_count() as i32
+    } else {
+        0
+    };
+
+    match resolution.get_winner() {
+        Some(winner) if winner as usize == state.get_ply_count() % 2 => <E as Evaluator>::Evaluation::win().shift(-ply_count),
+        Some(_) => <E as Evaluator>::Evaluation::lose().shift(ply_count),
+        None => <E as Evaluator>::Evaluation::null(),
+    }
+}
+
+/// Returns the resolution of `state` if its player to move has no legal plies and that ends the
+/// game, as with `State::no_plies_resolution`.
+fn no_plies_resolution<S>(state: &S) -> Option<<S as State>::Resolution> where
+    S: State + Extrapolatable<<S as State>::Ply> {
+    let mut state = state.clone();
+    for ply in state.extrapolate() {
+        if state.execute_ply(Some(&ply)).is_ok() {
+            return None;
+        }
+    }
+    state.no_plies_resolution()
+}
+
+/// Returns `eval - margin`, or the minimum evaluation if that's lower.
+fn lower_bound<V>(eval: V, margin: V) -> V where V: Evaluation {
+    if eval >= V::min() + margin {
+        eval - margin
+    } else {
+        V::min()
+    }
+}
+
+/// Returns `eval + margin`, or the maximum evaluation if that's higher.
+fn upper_bound<V>(eval: V, margin: V) -> V where V: Evaluation {
+    if eval <= V::max() - margin {
+        eval + margin
+    } else {
+        V::max()
+    }
+}
+
+/// Returns the totals of every level of `stats`.
+fn total_statistics(stats: &[StatisticsLevel]) -> StatisticsLevel {
+    let mut total = StatisticsLevel::new();
+    for level in stats {
+        total.add(level);
+    }
+    total
+}
+
+/// Returns the counts accumulated in `after` since `before` was taken.
+fn statistics_since(after: &StatisticsLevel, before: &StatisticsLevel) -> StatisticsLevel {
+    let mut since = StatisticsLevel::new();
+    since.visited = after.visited - before.visited;
+    since.evaluated = after.evaluated - before.evaluated;
+    since.tt_saves = after.tt_saves - before.tt_saves;
+    since.tt_hits = after.tt_hits - before.tt_hits;
+    since.tt_stores = after.tt_stores - before.tt_stores;
+    for (i, cutoffs) in since.cutoffs.iter_mut().enumerate() {
+        *cutoffs = after.cutoffs[i] - before.cutoffs[i];
+    }
+    since.null_cutoffs = after.null_cutoffs - before.null_cutoffs;
+    since.win_distance_cutoffs = after.win_distance_cutoffs - before.win_distance_cutoffs;
+    since.reverse_futility_cutoffs = after.reverse_futility_cutoffs - before.reverse_futility_cutoffs;
+    since.futility_pruned = after.futility_pruned - before.futility_pruned;
+    since.threat_extensions = after.threat_extensions - before.threat_extensions;
+    since.singular_extensions = after.singular_extensions - before.singular_extensions;
+    since
+}
+
+fn seconds(duration: Duration) -> f32 {
+    duration.as_secs() as f32 + duration.subsec_nanos() as f32 / 1_000_000_000.0
 }
 
 impl<S, E> fmt::Display for PvSearchAnalysis<S, E> where
@@ -442,21 +1728,36 @@ impl<S, E> fmt::Display for PvSearchAnalysis<S, E> where
         let mut result = self.state.clone();
         if result.execute_plies(&self.principal_variation).is_ok() {
             try!(write!(f, "Resultant State: {}\n", result));
-            // XXX Make Resolution require Display and print the resolution if any
-        }
-        try!(write!(f, "Evaluation: {}{}", self.evaluation, if self.evaluation.is_end() {
-            if self.evaluation.is_win() {
-                " (Win)\n"
-            } else {
-                " (Lose)\n"
+            if let Some(ref resolution) = self.resolution {
+                try!(write!(f, "Resolution: {}\n", resolution));
             }
-        } else {
-            "\n"
-        }));
+        }
+        try!(write!(f, "Evaluation: {}", self.evaluation));
+        if self.evaluation.is_end() {
+            try!(write!(f, " ({})", if self.evaluation.is_win() { "Win" } else { "Lose" }));
+        } else if let Some(win_probability) = self.win_probability {
+            try!(write!(f, " ({:.0}%)", win_probability * 100.0));
+        }
+        try!(write!(f, "\n"));
+        try!(write!(f, "Depth: {} (selective {}, first ply stable since {})\n",
+            self.statistics.search_depth(), self.statistics.selective_depth(), self.statistics.stable_depth));
+        try!(write!(f, "Nodes: {}\n", self.statistics.node_count));
+        if self.interrupted {
+            try!(write!(f, "Interrupted: results are from depth {}\n", self.completed_depth));
+        }
         try!(write!(f, "Principal Variation:"));
         for ply in &self.principal_variation {
             try!(write!(f, "\n  {}", ply));
         }
+        if let Some(ref explanation) = self.explanation {
+            try!(write!(f, "\nExplanation:\n{}", explanation));
+        }
+        if let Some(ref root_scores) = self.root_scores {
+            try!(write!(f, "\nRoot Plies:"));
+            for root_score in root_scores {
+                try!(write!(f, "\n  {}", root_score));
+            }
+        }
         try!(write!(f, "\nStatistics:\n{}", self.statistics));
         Ok(())
     }
@@ -468,11 +1769,805 @@ impl<S, E> Analysis for PvSearchAnalysis<S, E> where
     fn as_any(&self) -> &Any {
         self
     }
+
+    fn report(&self) -> AnalysisReport {
+        AnalysisReport {
+            best_move: self.principal_variation.first().map(|ply| ply.to_string()),
+            score: self.evaluation.to_string(),
+            pv: self.principal_variation.iter().map(|ply| ply.to_string()).collect(),
+            depth: self.completed_depth as usize,
+            nodes: self.statistics.node_count,
+            time: self.statistics.calculate_totals().time,
+            resolution: self.resolution.as_ref().map(|resolution| resolution.to_string()),
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        !self.interrupted
+    }
 }
 
-pub use self::statistics::{Statistics, StatisticsLevel};
+pub use self::explanation::{Explanation, RootPlyReport, RootScore, EXPLAINED_PLIES};
+pub use self::history::{ButterflyIndex, History};
+pub use self::options::{OptionError, PvSearchOptions};
+pub use self::move_ordering::{HistoryOrdering, MoveOrdering};
+pub use self::statistics::{Statistics, StatisticsLevel, CUTOFF_BUCKETS};
+pub use self::transposition_table::{Bound, StoreHook, TranspositionTableEntry};
 
+mod explanation;
 mod history;
+mod move_ordering;
+mod options;
 mod ply_generator;
 mod statistics;
 mod transposition_table;
+
+#[cfg(test)]
+mod test {
+    use std::fmt;
+    #[cfg(feature = "with_connect_four")]
+    use std::sync::mpsc;
+
+    use analysis::{Evaluation, Evaluator, Extrapolatable, StagedExtrapolatable, ThreatDetector};
+    use analysis::search::{Bound, PvSearch, PvSearchAnalysis, Search, TranspositionTableEntry};
+    #[cfg(feature = "with_connect_four")]
+    use analysis::search::{History, HistoryOrdering, MoveOrdering, OptionError, PvSearchOptions};
+    #[cfg(feature = "with_connect_four")]
+    use impls::connect_four::{self, Board};
+    use ply::Ply;
+    use resolution::Resolution;
+    use state::{PlyError, State};
+
+    /// Players take one or two stones in turn.  Without the `pass` rule, a player with no
+    /// stones to take loses; with it, they pass.  With the `dead` rule, fewer than three
+    /// stones is a dead draw.
+    #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+    struct Nim {
+        stones: u8,
+        ply_count: u8,
+        pass: bool,
+        dead: bool,
+    }
+
+    #[derive(Clone, Debug, Hash, PartialEq)]
+    struct Take(u8);
+
+    struct Win(u8);
+
+    struct NimEvaluator;
+
+    #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+    struct Eval(i16);
+
+    ::prepare_evaluation_tuple!(Eval);
+
+    impl Evaluation for Eval {
+        fn null() -> Eval { Eval(0) }
+        fn shift(self, steps: i32) -> Eval { Eval(self.0 + steps as i16) }
+        fn win() -> Eval { Eval(1000) }
+        fn max() -> Eval { Eval(i16::MAX) }
+        fn is_win(&self) -> bool { self.0 >= 500 }
+        fn encodes_win_distance() -> bool { true }
+    }
+
+    impl Ply for Take { }
+
+    impl Resolution for Win {
+        fn get_winner(&self) -> Option<u8> { Some(self.0) }
+        fn is_draw(&self) -> bool { false }
+    }
+
+    impl State for Nim {
+        type Ply = Take;
+        type Resolution = Win;
+
+        fn get_ply_count(&self) -> usize {
+            self.ply_count as usize
+        }
+
+        fn execute_ply(&mut self, ply: Option<&Take>) -> Result<(), PlyError> {
+            match ply {
+                Some(&Take(count)) if count > self.stones => return Err(PlyError::InsufficientPieces),
+                Some(&Take(count)) => self.stones -= count,
+                None => (),
+            }
+            self.ply_count += 1;
+            Ok(())
+        }
+
+        fn revert_ply(&mut self, ply: Option<&Take>) -> Result<(), PlyError> {
+            if let Some(&Take(count)) = ply {
+                self.stones += count;
+            }
+            self.ply_count -= 1;
+            Ok(())
+        }
+
+        fn check_resolution(&self) -> Option<Win> {
+            None
+        }
+
+        fn no_plies_resolution(&self) -> Option<Win> {
+            if self.pass {
+                None
+            } else {
+                Some(Win((self.ply_count + 1) % 2))
+            }
+        }
+
+        fn is_dead_draw(&self) -> bool {
+            self.dead && self.stones < 3
+        }
+    }
+
+    impl Extrapolatable<Take> for Nim {
+        fn extrapolate(&self) -> Vec<Take> {
+            vec![Take(1), Take(2)]
+        }
+    }
+
+    // Taking two stones comes first, then taking one
+    impl StagedExtrapolatable<Take> for Nim {
+        fn extrapolate_stage(&self, stage: usize, plies: &mut Vec<Take>) -> bool {
+            plies.clear();
+            match stage {
+                0 => plies.push(Take(2)),
+                1 => plies.push(Take(1)),
+                _ => return false,
+            }
+            true
+        }
+    }
+
+    // Taking two stones is forcing, so that threat extensions search it without consuming depth
+    impl ThreatDetector<Take> for Nim {
+        fn is_forcing(&self, ply: &Take) -> bool {
+            ply.0 == 2
+        }
+    }
+
+    impl Evaluator for NimEvaluator {
+        type State = Nim;
+        type Evaluation = Eval;
+
+        fn evaluate(&self, _: &Nim) -> Eval {
+            Eval::null()
+        }
+    }
+
+    impl fmt::Display for Take {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.0) }
+    }
+
+    impl fmt::Display for Win {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "Player {} wins", self.0 + 1) }
+    }
+
+    impl fmt::Display for Nim {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{} stones", self.stones) }
+    }
+
+    /// Returns the principal variation, the evaluation, and the winner of a search of `stones`.
+    fn search(stones: u8, pass: bool) -> (Vec<Take>, Eval, Option<u8>) {
+        let state = Nim {
+            stones: stones,
+            ply_count: 0,
+            pass: pass,
+            dead: false,
+        };
+        let analysis = PvSearch::new(NimEvaluator).search(&state, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+        (
+            analysis.principal_variation.clone(),
+            analysis.evaluation,
+            analysis.resolution.as_ref().and_then(|resolution| resolution.get_winner()),
+        )
+    }
+
+    #[test]
+    fn test_no_plies_resolution() {
+        let (principal_variation, evaluation, winner) = search(4, false);
+        assert_eq!(principal_variation[0], Take(1));
+        assert_eq!(principal_variation.len(), 3);
+        assert_eq!(evaluation, Eval::win().shift(-3));
+        assert_eq!(winner, Some(0));
+
+        assert!(search(3, false).1.is_lose());
+    }
+
+    #[test]
+    fn test_no_plies_at_root() {
+        assert_eq!(search(0, false), (Vec::new(), Eval::lose(), Some(1)));
+        assert_eq!(search(0, true), (Vec::new(), Eval::null(), None));
+    }
+
+    #[test]
+    fn test_dead_draw() {
+        // Every ply from four stones leaves three or fewer, and the reply leaves a dead draw
+        let state = Nim {
+            stones: 4,
+            ply_count: 0,
+            pass: false,
+            dead: true,
+        };
+        let analysis = PvSearch::with_depth(NimEvaluator, 6).search(&state, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+        assert_eq!(analysis.evaluation, Eval::null());
+        assert!(!analysis.principal_variation.is_empty());
+    }
+
+    #[test]
+    fn test_forced_move_extensions() {
+        // Taking one stone at a time, every ply is forced, and the last player to move wins
+        fn take_one(state: &Nim, stage: usize, plies: &mut Vec<Take>) -> bool {
+            plies.clear();
+            if stage > 0 {
+                return false;
+            }
+            if state.stones > 0 {
+                plies.push(Take(1));
+            }
+            true
+        }
+
+        let state = Nim {
+            stones: 4,
+            ply_count: 0,
+            pass: false,
+            dead: false,
+        };
+        let search = |extensions: u8| {
+            let mut search = PvSearch::with_depth(NimEvaluator, 1);
+            search.set_forced_move_extensions(extensions);
+            search.staged = Some(take_one);
+            let analysis = search.search(&state, None);
+            let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+            (analysis.principal_variation.len(), analysis.evaluation)
+        };
+
+        // The whole line is searched from a depth of one
+        assert_eq!(search(4), (4, Eval::lose().shift(4)));
+        assert_eq!(search(3), (4, Eval::null()));
+        assert_eq!(search(0), (1, Eval::null()));
+    }
+
+    #[test]
+    fn test_extend_analysis() {
+        let state = Nim {
+            stones: 10,
+            ply_count: 0,
+            pass: false,
+            dead: false,
+        };
+        let mut search = PvSearch::with_depth(NimEvaluator, 2);
+        let analysis = search.search(&state, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+        assert!(!analysis.evaluation.is_end());
+
+        // Only a deeper search can see how the game ends
+        let extended = search.extend_analysis(analysis, 1, 10, None).unwrap();
+        let extended = extended.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+        let mut expected = state.clone();
+        expected.execute_ply(Some(&analysis.principal_variation[0])).unwrap();
+        assert_eq!(extended.state, expected);
+        assert!(extended.evaluation.is_end());
+        assert!(extended.resolution.is_some());
+
+        assert!(search.extend_analysis(analysis, 3, 1, None).is_err());
+    }
+
+    #[test]
+    fn test_extend_interrupted_analysis() {
+        let state = Nim {
+            stones: 10,
+            ply_count: 0,
+            pass: false,
+            dead: false,
+        };
+        let mut search = PvSearch::with_depth(NimEvaluator, 6);
+        search.set_node_limit(20);
+        let analysis = search.search(&state, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+        assert!(analysis.interrupted);
+        assert!(analysis.statistics.depth.len() > analysis.completed_depth as usize);
+
+        // The unfinished iteration doesn't count toward the depth that's extended
+        search.set_node_limit(0);
+        let extended = search.extend_analysis(analysis, 0, 1, None).unwrap();
+        let extended = extended.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+        assert_eq!(extended.completed_depth, analysis.completed_depth + 1);
+    }
+
+    #[test]
+    fn test_depth_reporting() {
+        let state = Nim {
+            stones: 10,
+            ply_count: 0,
+            pass: false,
+            dead: false,
+        };
+        let analysis = PvSearch::with_depth(NimEvaluator, 4).search(&state, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+        let statistics = &analysis.statistics;
+        assert_eq!(statistics.search_depth(), 4);
+        assert_eq!(statistics.seldepth, vec![1, 2, 3, 4]);
+        assert!(statistics.stable_depth >= 1 && statistics.stable_depth <= 4);
+        assert!(analysis.to_string().contains("Depth: 4 (selective 4"));
+    }
+
+    #[test]
+    fn test_precalculated_verification() {
+        let state = Nim {
+            stones: 10,
+            ply_count: 0,
+            pass: false,
+            dead: false,
+        };
+        let shallow = PvSearch::with_depth(NimEvaluator, 2).search(&state, None);
+        let shallow = shallow.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+        let entry = TranspositionTableEntry {
+            depth: 2,
+            value: shallow.evaluation,
+            bound: Bound::Exact,
+            principal_variation: shallow.principal_variation.clone(),
+            generation: 0,
+        };
+
+        let search = |entry: TranspositionTableEntry<Take, Eval>, reuse: bool| {
+            let mut search = PvSearch::with_depth(NimEvaluator, 4);
+            search.set_reuse_precalculated(reuse);
+            search.ingest(state.clone(), entry);
+            let analysis = search.search(&state, None);
+            let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+            (analysis.evaluation, analysis.statistics.precalculated, analysis.statistics.precalculation_rejected)
+        };
+
+        let (evaluation, precalculated, rejected) = search(entry.clone(), true);
+        assert_eq!((precalculated, rejected), (2, false));
+
+        assert_eq!(search(entry.clone(), false), (evaluation, 0, false));
+
+        // A root entry claiming a win that the table below it doesn't support is searched again
+        let mut tampered = entry.clone();
+        tampered.value = Eval::win();
+        assert_eq!(search(tampered, true), (evaluation, 0, true));
+
+        let mut tampered = entry;
+        tampered.principal_variation = vec![Take(3)];
+        assert_eq!(search(tampered, true), (evaluation, 0, true));
+    }
+
+    #[test]
+    fn test_root_plies_with_extensions() {
+        let state = Nim {
+            stones: 10,
+            ply_count: 0,
+            pass: false,
+            dead: false,
+        };
+        let mut search = PvSearch::with_depth(NimEvaluator, 2);
+        search.set_threat_extensions(true);
+        search.set_root_scores(true);
+        search.set_explain(true);
+
+        let analysis = search.search(&state, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+        assert!(analysis.statistics.calculate_totals().threat_extensions > 0);
+
+        // An extended root ply is searched at the root's depth, but its replies aren't root plies
+        let root_scores = analysis.root_scores.as_ref().unwrap();
+        let root_plies = state.extrapolate();
+        assert_eq!(root_scores.len(), root_plies.len());
+        assert!(root_scores.iter().all(|score| root_plies.contains(&score.ply)));
+
+        let explanation = analysis.explanation.as_ref().unwrap();
+        assert_eq!(explanation.root_plies.len(), root_plies.len());
+        assert!(explanation.root_plies.iter().all(|report| root_plies.contains(&report.ply)));
+    }
+
+    #[test]
+    fn test_root_widening() {
+        let state = Nim {
+            stones: 7,
+            ply_count: 0,
+            pass: false,
+            dead: false,
+        };
+        let search = |width: usize| {
+            let mut search = PvSearch::with_depth(NimEvaluator, 7);
+            search.set_threat_extensions(true);
+            search.set_root_widening(width);
+            let analysis = search.search(&state, None);
+            let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+            (analysis.principal_variation[0].clone(), analysis.evaluation, analysis.statistics.root_plies.clone())
+        };
+
+        let (best, evaluation, _) = search(0);
+        let (widened_best, widened_evaluation, root_plies) = search(1);
+        assert_eq!((widened_best, widened_evaluation), (best, evaluation));
+        assert!(evaluation.is_win());
+
+        // The first iteration searches only the first root ply, and the last to search the root
+        // at all, rather than answering from the table, searches them all
+        assert_eq!(root_plies.first(), Some(&1));
+        assert_eq!(root_plies.iter().rev().find(|&&plies| plies > 0), Some(&2));
+        assert!(root_plies.iter().all(|&plies| plies <= 2));
+    }
+
+    #[test]
+    #[cfg(feature = "with_connect_four")]
+    fn test_node_limit() {
+        let board = Board::from_columns(&[4, 4, 3, 5]).unwrap();
+        let analyze = || {
+            let mut search = PvSearch::with_node_limit(connect_four::Evaluator, 5000);
+            search.set_seed(0);
+            let analysis = search.search(&board, None);
+            let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap();
+            (analysis.principal_variation.clone(), analysis.statistics.node_count)
+        };
+
+        // The same nodes are searched every time, and only the nodes underway pass the limit
+        let (principal_variation, nodes) = analyze();
+        assert!(nodes >= 5000 && nodes < 5100, "{}", nodes);
+        assert_eq!(analyze(), (principal_variation, nodes));
+    }
+
+    #[test]
+    #[cfg(feature = "with_connect_four")]
+    fn test_interrupted() {
+        let board = Board::from_columns(&[4, 4, 3, 5]).unwrap();
+
+        // The results come from the last iteration completed before the limit
+        let mut search = PvSearch::with_node_limit(connect_four::Evaluator, 5000);
+        let analysis = search.search(&board, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap();
+        assert!(analysis.interrupted);
+        assert!(analysis.completed_depth > 0 && (analysis.completed_depth as usize) < analysis.statistics.search_depth());
+        assert!(analysis.to_string().contains(&format!("Interrupted: results are from depth {}", analysis.completed_depth)));
+
+        let mut search = PvSearch::with_depth(connect_four::Evaluator, analysis.completed_depth);
+        let completed = search.search(&board, None);
+        let completed = completed.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap();
+        assert!(!completed.interrupted);
+        assert_eq!(completed.completed_depth, analysis.completed_depth);
+        assert_eq!(completed.principal_variation, analysis.principal_variation);
+        assert!(completed.evaluation == analysis.evaluation);
+
+        // Interrupted before finishing anything, the search still offers what it found
+        let (sender, receiver) = mpsc::channel();
+        sender.send(()).unwrap();
+        let analysis = PvSearch::with_depth(connect_four::Evaluator, 4).search(&board, Some(receiver));
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap();
+        assert!(analysis.interrupted);
+        assert_eq!(analysis.completed_depth, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "with_connect_four")]
+    fn test_win_distance_pruning() {
+        let mut search = PvSearch::with_depth(connect_four::Evaluator, 6);
+
+        // Red wins in three plies by opening a row of three on the bottom, and once a line
+        // finds that, no other can end soon enough to matter
+        let board = Board::from_columns(&[4, 4, 3, 3]).unwrap();
+        let analysis = search.search(&board, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap();
+        assert_eq!(analysis.principal_variation.len(), 3);
+        assert!(analysis.evaluation == connect_four::Evaluation::win().shift(-7));
+        assert!(analysis.statistics.calculate_totals().win_distance_cutoffs > 0);
+    }
+
+    #[test]
+    #[cfg(feature = "with_connect_four")]
+    fn test_report() {
+        let mut search = PvSearch::with_depth(connect_four::Evaluator, 6);
+
+        let board = Board::from_columns(&[4, 4, 3, 3]).unwrap();
+        let analysis = search.search(&board, None);
+        let report = analysis.report();
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap();
+
+        assert_eq!(report.best_move, Some(analysis.principal_variation[0].to_string()));
+        assert_eq!(report.pv.len(), 3);
+        assert_eq!(report.score, analysis.evaluation.to_string());
+        assert_eq!(report.nodes, analysis.statistics.node_count);
+        assert_eq!(report.resolution, analysis.resolution.as_ref().map(|resolution| resolution.to_string()));
+        assert!(report.resolution.is_some());
+        assert!(report.to_string().starts_with(&format!("Best Move: {}\nScore: {}\n", report.pv[0], report.score)));
+    }
+
+    #[test]
+    #[cfg(feature = "with_connect_four")]
+    fn test_futility_pruning() {
+        let mut search = PvSearch::with_depth(connect_four::Evaluator, 6);
+        search.set_futility_margin(8);
+        search.set_reverse_futility_margin(8);
+
+        // Pruning near the leaves must not hide the threat in column 4
+        let board = Board::from_columns(&[4, 1, 4, 1, 4]).unwrap();
+        let analysis = search.search(&board, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap();
+        let totals = analysis.statistics.calculate_totals();
+        assert_eq!(analysis.principal_variation[0].column, 3);
+        assert!(totals.futility_pruned > 0);
+        assert!(totals.reverse_futility_cutoffs > 0);
+    }
+
+    #[test]
+    #[cfg(feature = "with_tic_tac_toe")]
+    fn test_wide_margins() {
+        use impls::tic_tac_toe;
+
+        let analyze = |margin: u32| {
+            let mut search = PvSearch::with_depth(tic_tac_toe::Evaluator, 9);
+            search.set_futility_margin(margin);
+            search.set_reverse_futility_margin(margin);
+            search.set_singular_margin(margin);
+            search.set_seed(0);
+            let analysis = search.search(&tic_tac_toe::Board::new(), None);
+            let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<tic_tac_toe::Board, tic_tac_toe::Evaluator>>().unwrap();
+            (analysis.evaluation, analysis.principal_variation.clone())
+        };
+
+        // Margins wider than a narrow evaluation's range saturate instead of overflowing, and
+        // so prune nothing
+        assert!(analyze(100) == analyze(0));
+    }
+
+    #[test]
+    #[cfg(feature = "with_connect_four")]
+    fn test_singular_extensions() {
+        let mut search = PvSearch::with_depth(connect_four::Evaluator, 6);
+        search.set_singular_margin(4);
+
+        // Yellow's block in column 4 is the only ply that doesn't lose
+        let board = Board::from_columns(&[4, 1, 4, 1, 4]).unwrap();
+        let analysis = search.search(&board, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap();
+        assert_eq!(analysis.principal_variation[0].column, 3);
+        assert!(analysis.statistics.calculate_totals().singular_extensions > 0);
+    }
+
+    #[test]
+    #[cfg(feature = "with_connect_four")]
+    fn test_move_ordering_options() {
+        let board = Board::from_columns(&[4, 4, 3, 5]).unwrap();
+        let evaluation = |search: &mut PvSearch<Board, connect_four::Evaluator>| {
+            search.set_seed(0);
+            let analysis = search.search(&board, None);
+            analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap().evaluation
+        };
+
+        // Move ordering changes how much of the tree is searched, but not the result
+        let expected = evaluation(&mut PvSearch::with_depth(connect_four::Evaluator, 7));
+
+        let mut search = PvSearch::with_depth(connect_four::Evaluator, 7);
+        search.set_countermoves(true);
+        search.set_butterfly_history(true);
+        search.set_history_per_color(true);
+        search.set_history_aging(true);
+        assert_eq!(evaluation(&mut search), expected);
+    }
+
+    #[test]
+    #[cfg(feature = "with_connect_four")]
+    fn test_custom_move_ordering() {
+        // Tries the plies the default ordering likes least first
+        struct Reversed;
+
+        impl MoveOrdering<Board> for Reversed {
+            fn order_plies(&self, state: &Board, previous_ply: Option<&connect_four::Ply>, history: &History<connect_four::Ply>, plies: &mut [connect_four::Ply]) {
+                HistoryOrdering.order_plies(state, previous_ply, history, plies);
+                plies.reverse();
+            }
+        }
+
+        let board = Board::from_columns(&[4, 4, 3, 5]).unwrap();
+        let analyze = |search: &mut PvSearch<Board, connect_four::Evaluator>| {
+            search.set_seed(0);
+            let analysis = search.search(&board, None);
+            let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap();
+            (analysis.evaluation, analysis.statistics.nodes())
+        };
+
+        let (expected, default_nodes) = analyze(&mut PvSearch::with_depth(connect_four::Evaluator, 7));
+
+        let mut search = PvSearch::with_depth(connect_four::Evaluator, 7);
+        search.set_move_ordering(Box::new(Reversed));
+        let (evaluation, reversed_nodes) = analyze(&mut search);
+        assert_eq!(evaluation, expected);
+        assert!(reversed_nodes > default_nodes);
+    }
+
+    #[test]
+    #[cfg(feature = "with_connect_four")]
+    fn test_explain() {
+        let mut search = PvSearch::with_depth(connect_four::Evaluator, 4);
+        search.set_explain(true);
+
+        let board = Board::from_columns(&[4, 1, 4, 1, 4]).unwrap();
+        let analysis = search.search(&board, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap();
+        let explanation = analysis.explanation.as_ref().unwrap();
+
+        assert_eq!(explanation.depth, 4);
+        assert_eq!(explanation.root_plies.len(), 7);
+        assert!(explanation.ranked()[0].ply == analysis.principal_variation[0]);
+        assert!(explanation.ranked()[1..].iter().all(|report| report.bound == Bound::Upper));
+        assert!(explanation.to_string().starts_with("At depth 4, searched 7 root plies.\nChose Y, 4,"));
+    }
+
+    #[test]
+    #[cfg(feature = "with_connect_four")]
+    fn test_root_scores() {
+        let mut search = PvSearch::with_depth(connect_four::Evaluator, 4);
+        search.set_root_scores(true);
+
+        let board = Board::from_columns(&[4, 1, 4, 1, 4]).unwrap();
+        let analysis = search.search(&board, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap();
+        let root_scores = analysis.root_scores.as_ref().unwrap();
+
+        // Every ply but the block is only known to be no better than it
+        assert_eq!(root_scores.len(), 7);
+        let best = root_scores.iter().find(|score| score.ply == analysis.principal_variation[0]).unwrap();
+        assert_eq!(best.value, analysis.evaluation);
+        assert!(root_scores.iter().filter(|score| score.ply != best.ply).all(|score| score.bound == Bound::Upper && score.value <= best.value));
+        assert!(analysis.to_string().contains("Root Plies:\n  Y, 4"));
+
+        search.set_root_scores(false);
+        let analysis = search.search(&board, None);
+        assert!(analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, connect_four::Evaluator>>().unwrap().root_scores.is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "with_connect_four")]
+    fn test_options() {
+        let board = Board::from_columns(&[4, 1, 4, 1]).unwrap();
+        let summary = |search: &mut PvSearch<Board, connect_four::Evaluator>| {
+            let report = search.search(&board, None).report();
+            (report.pv, report.score, report.nodes)
+        };
+        let options = PvSearchOptions::new()
+            .depth(6)
+            .aspiration_window(10)
+            .history_per_color(true)
+            .countermoves(true)
+            .null_move(false)
+            .seed(7);
+
+        let mut search = PvSearch::new(connect_four::Evaluator);
+        search.set_depth(6);
+        search.set_aspiration_window(10);
+        search.set_history_per_color(true);
+        search.set_countermoves(true);
+        search.set_null_move(false);
+        search.set_seed(7);
+        let expected = summary(&mut search);
+
+        assert_eq!(summary(&mut PvSearch::with_options(connect_four::Evaluator, &options)), expected);
+
+        let mut search = PvSearch::new(connect_four::Evaluator);
+        for &(name, value) in &[("depth", "6"), ("aspiration_window", " 10"), ("history_per_color", "true"),
+                                ("countermoves", "true"), ("null_move", "false"), ("seed", "7")] {
+            search.set_option(name, value).unwrap();
+        }
+        assert_eq!(summary(&mut search), expected);
+
+        assert_eq!(search.set_option("depthh", "6"), Err(OptionError::Unknown(String::from("depthh"))));
+        assert_eq!(
+            search.set_option("depth", "-1"),
+            Err(OptionError::InvalidValue { name: String::from("depth"), value: String::from("-1") }),
+        );
+        assert!(search.set_option("null_move", "yes").is_err());
+
+        assert!(search.reuse_precalculated);
+        search.set_option("reuse_precalculated", "false").unwrap();
+        assert!(!search.reuse_precalculated);
+        search.set_options(&options);
+        assert!(search.reuse_precalculated);
+        assert!(!PvSearch::<Board, _>::with_options(connect_four::Evaluator, &options.reuse_precalculated(false)).reuse_precalculated);
+    }
+
+    #[test]
+    fn test_staged() {
+        let state = Nim {
+            stones: 8,
+            ply_count: 0,
+            pass: false,
+            dead: false,
+        };
+        let search = |staged: bool| {
+            let mut search = PvSearch::with_depth(NimEvaluator, 9);
+            search.set_staged(staged);
+            search.set_seed(0);
+            let analysis = search.search(&state, None);
+            let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Nim, NimEvaluator>>().unwrap();
+            (analysis.principal_variation.clone(), analysis.evaluation)
+        };
+
+        let expected = search(false);
+        assert!(expected.1.is_win());
+        assert_eq!(search(true), expected);
+    }
+
+    #[test]
+    #[cfg(feature = "with_tak")]
+    fn test_staged_tak() {
+        use impls::tak;
+
+        // With the capstone played, only a flatstone completes the road along the bottom row
+        let state = tak::State::from_tps("[TPS \"x5/x5/2,x3,1C/2,2,x3/1,1,1,1,x 1 6\"]").unwrap();
+        let search = |state: &tak::State, staged: bool| {
+            let mut search = PvSearch::with_depth(tak::evaluator::StaticEvaluator::new(), 3);
+            search.set_staged(staged);
+            search.set_seed(0);
+            search.search(state, None).report()
+        };
+
+        let expected = search(&state, false);
+        let report = search(&state, true);
+        assert_eq!((report.pv, report.score), (expected.pv, expected.score));
+
+        // Generating the slides only when the placements are exhausted doesn't change the result
+        let state = tak::State::from_tps("[TPS \"x5/x5/x,2,1,x2/x,1,2,x2/x5 1 3\"]").unwrap();
+        let expected = search(&state, false);
+        let report = search(&state, true);
+        assert_eq!((report.pv, report.score), (expected.pv, expected.score));
+    }
+
+    #[test]
+    #[cfg(feature = "with_tic_tac_toe")]
+    fn test_narrow_aspiration_windows() {
+        use impls::tic_tac_toe;
+        type Narrow = <tic_tac_toe::Evaluator as Evaluator>::Evaluation;
+
+        // Its evaluations are single bytes
+        assert!(Narrow::granularity_multiple(100) == Narrow::null().shift(100));
+        assert!(Narrow::granularity_multiple(1000) == Narrow::max());
+        assert!(Narrow::granularity_multiple(u32::max_value()) == Narrow::max());
+
+        // Windows, whether set wide or widened on failure, don't overflow the evaluation
+        let state = tic_tac_toe::Board::new();
+        let expected = PvSearch::with_depth(tic_tac_toe::Evaluator, 9).search(&state, None).report();
+        for &window in &[1, 10, 1000, u32::max_value()] {
+            let mut search = PvSearch::with_depth(tic_tac_toe::Evaluator, 9);
+            search.set_aspiration_window(window);
+            let report = search.search(&state, None).report();
+            assert_eq!(report.score, expected.score);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "with_tic_tac_toe")]
+    fn test_symmetric_search() {
+        use impls::tic_tac_toe;
+
+        // Symmetric positions are transpositions, so less of the tree is searched for the same result
+        let mut state = tic_tac_toe::Board::new();
+        for _ in 0..3 {
+            let analyze = |symmetric: bool| {
+                let mut search = PvSearch::with_depth(tic_tac_toe::Evaluator, 9);
+                search.set_symmetric(symmetric);
+                search.set_seed(0);
+                let analysis = search.search(&state, None);
+                let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<tic_tac_toe::Board, tic_tac_toe::Evaluator>>().unwrap();
+                (analysis.evaluation, analysis.principal_variation.clone(), analysis.statistics.nodes())
+            };
+
+            let (evaluation, _, nodes) = analyze(false);
+            let (symmetric_evaluation, principal_variation, symmetric_nodes) = analyze(true);
+            assert!(symmetric_evaluation == evaluation);
+            assert!(symmetric_nodes < nodes);
+
+            // The principal variation is played on the real board, not the canonical one
+            let mut line = state.clone();
+            for ply in &principal_variation {
+                assert!(line.extrapolate().contains(ply));
+                line.execute_ply(Some(ply)).unwrap();
+            }
+
+            state.execute_ply(Some(&principal_variation[0])).unwrap();
+        }
+    }
+}
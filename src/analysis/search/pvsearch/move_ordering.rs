@@ -0,0 +1,66 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+use analysis::search::pvsearch::history::History;
+use state::State;
+
+/// Decides the order in which `PvSearch` tries a state's plies.
+///
+/// The principal ply, usually from the transposition table, is always tried first, before the
+/// rest of the plies are ordered.  Implementations only need to order what remains.
+pub trait MoveOrdering<S>: Send where
+    S: State {
+    /// Sorts `plies`, the unsearched plies of `state`, so that the most promising plies come
+    /// *last*.  `previous_ply` is the ply that reached `state`, if any, and `history` holds the
+    /// search's history heuristic scores and countermoves.
+    fn order_plies(
+        &self,
+        state: &S,
+        previous_ply: Option<&<S as State>::Ply>,
+        history: &History<<S as State>::Ply>,
+        plies: &mut [<S as State>::Ply],
+    );
+}
+
+/// The default ordering.  Plies are sorted by their history heuristic scores, and the last
+/// refutation of the previous ply, if countermoves are enabled, is tried before all others.
+pub struct HistoryOrdering;
+
+impl<S> MoveOrdering<S> for HistoryOrdering where
+    S: State {
+    fn order_plies(
+        &self,
+        state: &S,
+        previous_ply: Option<&<S as State>::Ply>,
+        history: &History<<S as State>::Ply>,
+        plies: &mut [<S as State>::Ply],
+    ) {
+        history.sort_plies(state.get_ply_count(), plies);
+
+        let countermove = match previous_ply {
+            Some(previous_ply) => history.get_countermove(previous_ply),
+            None => None,
+        };
+
+        if let Some(countermove) = countermove {
+            if let Some(index) = plies.iter().position(|ply| ply == countermove) {
+                plies[index..].rotate_left(1);
+            }
+        }
+    }
+}
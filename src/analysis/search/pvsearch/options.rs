@@ -0,0 +1,208 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::error::Error;
+use std::fmt;
+
+/// The settings of a `PvSearch`, gathered in one place, as for reading a configuration or
+/// setting up several searches alike.  Each field corresponds to the `PvSearch` setter of
+/// the same name, and defaults to the same value.  See `PvSearch::with_options`.
+///
+/// # Example
+///
+/// ```rust
+/// # use zero_sum::analysis::search::PvSearchOptions;
+/// let options = PvSearchOptions::new()
+///     .depth(8)
+///     .node_limit(1_000_000)
+///     .aspiration_window(25)
+///     .seed(7);
+/// assert_eq!(options.depth, 8);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "with_serde", serde(default))]
+pub struct PvSearchOptions {
+    /// The maximum depth, or 0 for none.
+    pub depth: u8,
+    /// The time goal in seconds, or 0 for none.
+    pub goal: u16,
+    /// The branching factor used to predict the time of the next depth against the goal.
+    pub branching_factor: f32,
+    pub node_limit: u64,
+    pub forced_move_extensions: u8,
+    pub aspiration_window: u32,
+    pub futility_margin: u32,
+    pub reverse_futility_margin: u32,
+    pub singular_margin: u32,
+    pub null_move: bool,
+    pub root_widening: usize,
+    pub history_per_color: bool,
+    pub history_aging: bool,
+    pub countermoves: bool,
+    pub explain: bool,
+    pub root_scores: bool,
+    pub reuse_precalculated: bool,
+    /// The seed of the search's random number generator, or `None` to leave it unseeded.
+    pub seed: Option<u64>,
+}
+
+impl PvSearchOptions {
+    pub fn new() -> PvSearchOptions {
+        PvSearchOptions {
+            depth: 0,
+            goal: 0,
+            branching_factor: 0.0,
+            node_limit: 0,
+            forced_move_extensions: 4,
+            aspiration_window: 0,
+            futility_margin: 0,
+            reverse_futility_margin: 0,
+            singular_margin: 0,
+            null_move: true,
+            root_widening: 0,
+            history_per_color: false,
+            history_aging: false,
+            countermoves: false,
+            explain: false,
+            root_scores: false,
+            reuse_precalculated: true,
+            seed: None,
+        }
+    }
+
+    pub fn depth(mut self, depth: u8) -> PvSearchOptions {
+        self.depth = depth;
+        self
+    }
+
+    pub fn goal(mut self, goal: u16, branching_factor: f32) -> PvSearchOptions {
+        self.goal = goal;
+        self.branching_factor = branching_factor;
+        self
+    }
+
+    pub fn node_limit(mut self, nodes: u64) -> PvSearchOptions {
+        self.node_limit = nodes;
+        self
+    }
+
+    pub fn forced_move_extensions(mut self, extensions: u8) -> PvSearchOptions {
+        self.forced_move_extensions = extensions;
+        self
+    }
+
+    pub fn aspiration_window(mut self, units: u32) -> PvSearchOptions {
+        self.aspiration_window = units;
+        self
+    }
+
+    pub fn futility_margin(mut self, units: u32) -> PvSearchOptions {
+        self.futility_margin = units;
+        self
+    }
+
+    pub fn reverse_futility_margin(mut self, units: u32) -> PvSearchOptions {
+        self.reverse_futility_margin = units;
+        self
+    }
+
+    pub fn singular_margin(mut self, units: u32) -> PvSearchOptions {
+        self.singular_margin = units;
+        self
+    }
+
+    pub fn null_move(mut self, null_move: bool) -> PvSearchOptions {
+        self.null_move = null_move;
+        self
+    }
+
+    pub fn root_widening(mut self, width: usize) -> PvSearchOptions {
+        self.root_widening = width;
+        self
+    }
+
+    pub fn history_per_color(mut self, per_color: bool) -> PvSearchOptions {
+        self.history_per_color = per_color;
+        self
+    }
+
+    pub fn history_aging(mut self, aging: bool) -> PvSearchOptions {
+        self.history_aging = aging;
+        self
+    }
+
+    pub fn countermoves(mut self, countermoves: bool) -> PvSearchOptions {
+        self.countermoves = countermoves;
+        self
+    }
+
+    pub fn explain(mut self, explain: bool) -> PvSearchOptions {
+        self.explain = explain;
+        self
+    }
+
+    pub fn root_scores(mut self, root_scores: bool) -> PvSearchOptions {
+        self.root_scores = root_scores;
+        self
+    }
+
+    pub fn reuse_precalculated(mut self, reuse: bool) -> PvSearchOptions {
+        self.reuse_precalculated = reuse;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> PvSearchOptions {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl Default for PvSearchOptions {
+    fn default() -> PvSearchOptions {
+        PvSearchOptions::new()
+    }
+}
+
+/// The ways that `PvSearch::set_option` can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptionError {
+    /// There is no option by this name.
+    Unknown(String),
+    /// The value can't be parsed as the option's type.
+    InvalidValue {
+        name: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OptionError::Unknown(ref name) => write!(f, "Unknown option: {}", name),
+            OptionError::InvalidValue { ref name, ref value } => write!(f, "Invalid value for {}: {}", name, value),
+        }
+    }
+}
+
+impl Error for OptionError {
+    fn description(&self) -> &str {
+        "Invalid search option"
+    }
+}
@@ -24,46 +24,86 @@ use rand::Rng;
 
 use analysis::Extrapolatable;
 use analysis::search::pvsearch::history::History;
+use analysis::search::pvsearch::move_ordering::MoveOrdering;
 use ply::Ply;
+use state::State;
 use util::JKiss32Rng;
 
-lazy_static! {
-    pub static ref RNG: Mutex<JKiss32Rng> = Mutex::new(JKiss32Rng::new());
-}
+/// Generates a state's stages of plies, as with `StagedExtrapolatable::extrapolate_stage`.
+pub type StageFn<X, P> = fn(&X, usize, &mut Vec<P>) -> bool;
 
 pub struct PlyGenerator<X, P> where
     X: Extrapolatable<P>,
     P: Ply {
     principal_ply: Option<P>,
-    history: Arc<Mutex<History>>,
+    history: Arc<Mutex<History<P>>>,
     plies: Vec<P>,
     operation: u8,
+    staged: Option<StageFn<X, P>>,
+    next_stage: usize,
+    stages_remaining: bool,
     phantom: PhantomData<X>,
 }
 
 impl<X, P> PlyGenerator<X, P> where
     X: Extrapolatable<P>,
     P: Ply {
-    pub fn new(state: &X, principal_ply: Option<P>, history: Arc<Mutex<History>>) -> PlyGenerator<X, P> {
-        let mut plies = state.extrapolate();
-        RNG.lock().unwrap().shuffle(&mut plies);
+    /// Creates a generator for the plies of `state`, storing them in `plies`.  Any
+    /// existing contents of `plies` are discarded; only its allocation is reused.  If
+    /// `staged` is given, only the first stage is generated up front, and the rest are
+    /// generated as earlier stages are exhausted.
+    pub fn new(
+        state: &X,
+        principal_ply: Option<P>,
+        history: Arc<Mutex<History<P>>>,
+        rng: &mut JKiss32Rng,
+        mut plies: Vec<P>,
+        staged: Option<StageFn<X, P>>,
+    ) -> PlyGenerator<X, P> {
+        let stages_remaining = if let Some(staged) = staged {
+            staged(state, 0, &mut plies)
+        } else {
+            state.extrapolate_into(&mut plies);
+            false
+        };
+        rng.shuffle(&mut plies);
 
         PlyGenerator {
             principal_ply: principal_ply,
             history: history,
             plies: plies,
             operation: 0,
+            staged: staged,
+            next_stage: 1,
+            stages_remaining: stages_remaining,
             phantom: PhantomData,
         }
     }
-}
 
-impl<X, P> Iterator for PlyGenerator<X, P> where
-    X: Extrapolatable<P>,
-    P: Ply {
-    type Item = P;
+    /// Returns true if the state offered only a single ply.  With staged generation, this
+    /// may generate later stages early, until it's clear there is more than one ply.
+    pub fn is_forced(&mut self, state: &X, rng: &mut JKiss32Rng) -> bool {
+        if self.operation != 0 {
+            return false;
+        }
 
-    fn next(&mut self) -> Option<P> {
+        while self.plies.len() < 2 && self.generate_stage(state, rng, true) { }
+
+        self.plies.len() == 1
+    }
+
+    /// Returns the next ply to search, or `None` when all plies have been returned.  `state`
+    /// must be the state that the generator was created with, and `previous_ply` the ply that
+    /// reached it.  `ordering` orders the plies that follow the principal ply.
+    pub fn next<O>(
+        &mut self,
+        state: &X,
+        previous_ply: Option<&P>,
+        rng: &mut JKiss32Rng,
+        ordering: &O,
+    ) -> Option<P> where
+        X: State<Ply = P>,
+        O: MoveOrdering<X> + ?Sized {
         loop {
             if self.operation == 0 {
                 self.operation += 1;
@@ -76,20 +116,209 @@ impl<X, P> Iterator for PlyGenerator<X, P> where
             if self.operation == 1 {
                 self.operation += 1;
 
-                {
-                    let history = self.history.lock().unwrap();
+                let history = self.history.lock().unwrap();
 
-                    history.sort_plies(&mut self.plies);
-                }
+                ordering.order_plies(state, previous_ply, &history, &mut self.plies);
             }
 
             if self.operation == 2 {
                 let ply = self.plies.pop();
 
-                if ply != self.principal_ply || ply.is_none() {
+                if ply.is_none() {
+                    if self.generate_stage(state, rng, false) {
+                        self.operation = 1;
+                        continue;
+                    }
+                    return None;
+                }
+
+                if ply != self.principal_ply {
                     return ply;
                 }
             }
         }
     }
+
+    /// Consumes the generator, returning its ply buffer for reuse.
+    pub fn into_plies(self) -> Vec<P> {
+        self.plies
+    }
+
+    // Generates the next stage, either appending it to the current plies or replacing them.
+    // Returns false if there are no more stages.
+    fn generate_stage(&mut self, state: &X, rng: &mut JKiss32Rng, append: bool) -> bool {
+        let staged = match self.staged {
+            Some(staged) if self.stages_remaining => staged,
+            _ => return false,
+        };
+
+        let stage = self.next_stage;
+        self.next_stage += 1;
+
+        if append {
+            let mut plies = Vec::new();
+            self.stages_remaining = staged(state, stage, &mut plies);
+            rng.shuffle(&mut plies);
+            self.plies.append(&mut plies);
+        } else {
+            self.stages_remaining = staged(state, stage, &mut self.plies);
+            rng.shuffle(&mut self.plies);
+        }
+
+        self.stages_remaining
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    use analysis::search::pvsearch::history::History;
+    use analysis::search::pvsearch::move_ordering::HistoryOrdering;
+    use ply;
+    use resolution::Resolution;
+    use state::PlyError;
+    use util::JKiss32Rng;
+    use super::*;
+
+    /// A state that offers only the plies of the stage functions below.
+    #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+    struct Board;
+
+    #[derive(Clone, Debug, Hash, PartialEq)]
+    struct Ply {
+        column: usize,
+    }
+
+    enum Never { }
+
+    impl ply::Ply for Ply { }
+
+    impl Resolution for Never {
+        fn get_winner(&self) -> Option<u8> { match *self { } }
+        fn is_draw(&self) -> bool { match *self { } }
+    }
+
+    impl State for Board {
+        type Ply = Ply;
+        type Resolution = Never;
+
+        fn get_ply_count(&self) -> usize { 0 }
+        fn execute_ply(&mut self, _: Option<&Ply>) -> Result<(), PlyError> { Ok(()) }
+        fn revert_ply(&mut self, _: Option<&Ply>) -> Result<(), PlyError> { Ok(()) }
+        fn check_resolution(&self) -> Option<Never> { None }
+    }
+
+    impl Extrapolatable<Ply> for Board {
+        fn extrapolate(&self) -> Vec<Ply> {
+            Vec::new()
+        }
+    }
+
+    impl fmt::Display for Board {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "Board") }
+    }
+
+    impl fmt::Display for Ply {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.column) }
+    }
+
+    impl fmt::Display for Never {
+        fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result { match *self { } }
+    }
+
+    fn ply(column: usize) -> Ply {
+        Ply {
+            column: column,
+        }
+    }
+
+    fn three_stages(_: &Board, stage: usize, plies: &mut Vec<Ply>) -> bool {
+        plies.clear();
+        match stage {
+            0 => plies.push(ply(0)),
+            1 => plies.extend(vec![ply(1), ply(2), ply(3)]),
+            2 => plies.push(ply(4)),
+            _ => return false,
+        }
+        true
+    }
+
+    fn empty_first_stage(_: &Board, stage: usize, plies: &mut Vec<Ply>) -> bool {
+        plies.clear();
+        match stage {
+            0 => (),
+            1 => plies.push(ply(1)),
+            _ => return false,
+        }
+        true
+    }
+
+    fn empty_first_two_stages(_: &Board, stage: usize, plies: &mut Vec<Ply>) -> bool {
+        plies.clear();
+        match stage {
+            0 | 1 => (),
+            2 => plies.push(ply(2)),
+            3 => plies.push(ply(3)),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Returns every ply the generator offers, in order.
+    fn generate(staged: StageFn<Board, Ply>, principal_ply: Option<Ply>, check_forced: Option<bool>) -> Vec<Ply> {
+        let board = Board;
+        let mut rng = JKiss32Rng::with_seed(0);
+        let mut generator = PlyGenerator::new(
+            &board,
+            principal_ply,
+            Arc::new(Mutex::new(History::new())),
+            &mut rng,
+            Vec::new(),
+            Some(staged),
+        );
+
+        if let Some(forced) = check_forced {
+            assert_eq!(generator.is_forced(&board, &mut rng), forced);
+        }
+
+        let mut plies = Vec::new();
+        while let Some(ply) = generator.next(&board, None, &mut rng, &HistoryOrdering) {
+            plies.push(ply);
+        }
+        plies
+    }
+
+    fn columns(plies: &[Ply]) -> Vec<usize> {
+        let mut columns = plies.iter().map(|ply| ply.column).collect::<Vec<_>>();
+        columns.sort();
+        columns
+    }
+
+    #[test]
+    fn test_stages() {
+        // Every stage's plies are returned exactly once, in stage order
+        let plies = generate(three_stages, None, None);
+        assert_eq!(columns(&plies), vec![0, 1, 2, 3, 4]);
+        assert_eq!(plies[0].column, 0);
+        assert_eq!(plies[4].column, 4);
+
+        // The principal ply comes first, and isn't returned again with its stage
+        let plies = generate(three_stages, Some(ply(2)), None);
+        assert_eq!(plies[0].column, 2);
+        assert_eq!(columns(&plies), vec![0, 1, 2, 3, 4]);
+
+        // Generating stages early to check for a forced ply doesn't lose or repeat any
+        assert_eq!(columns(&generate(three_stages, None, Some(false))), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_forced_after_empty_stage() {
+        assert_eq!(columns(&generate(empty_first_stage, None, Some(true))), vec![1]);
+        assert_eq!(columns(&generate(empty_first_stage, None, None)), vec![1]);
+
+        assert_eq!(columns(&generate(empty_first_two_stages, None, Some(false))), vec![2, 3]);
+        assert_eq!(columns(&generate(empty_first_two_stages, None, None)), vec![2, 3]);
+    }
 }
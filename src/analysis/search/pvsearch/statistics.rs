@@ -20,8 +20,13 @@
 use std::cmp;
 use std::fmt;
 
+/// The number of buckets in the beta-cutoff histogram.  The last bucket counts every cutoff
+/// that happened at or beyond its index.
+pub const CUTOFF_BUCKETS: usize = 8;
+
 /// Represents statistics for the search at a single depth.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub struct StatisticsLevel {
     /// The number of nodes visited on this level.
     pub visited: u32,
@@ -35,6 +40,23 @@ pub struct StatisticsLevel {
     pub tt_stores: u32,
     /// The amount of time we spent searching this depth.
     pub time: f32,
+    /// The number of beta cutoffs, indexed by the position of the cutoff ply in the move
+    /// ordering.  A well-ordered search has most of its cutoffs in the first bucket.
+    pub cutoffs: [u32; CUTOFF_BUCKETS],
+    /// The number of nodes pruned by a null move search.
+    pub null_cutoffs: u32,
+    /// The number of nodes pruned because no line through them could end soon enough to
+    /// improve on a win already found.
+    pub win_distance_cutoffs: u32,
+    /// The number of nodes pruned because their static evaluation was far above beta.
+    pub reverse_futility_cutoffs: u32,
+    /// The number of plies skipped because the static evaluation of the state they were
+    /// made from was far below alpha.
+    pub futility_pruned: u32,
+    /// The number of plies searched deeper because they were recognized as forcing.
+    pub threat_extensions: u32,
+    /// The number of plies searched deeper because no other ply came close to them.
+    pub singular_extensions: u32,
 }
 
 impl StatisticsLevel {
@@ -46,14 +68,78 @@ impl StatisticsLevel {
             tt_hits: 0,
             tt_stores: 0,
             time: 0.0,
+            cutoffs: [0; CUTOFF_BUCKETS],
+            null_cutoffs: 0,
+            win_distance_cutoffs: 0,
+            reverse_futility_cutoffs: 0,
+            futility_pruned: 0,
+            threat_extensions: 0,
+            singular_extensions: 0,
+        }
+    }
+
+    /// Adds the counts and time of `other` to this level.
+    pub fn add(&mut self, other: &StatisticsLevel) {
+        self.visited += other.visited;
+        self.evaluated += other.evaluated;
+        self.tt_saves += other.tt_saves;
+        self.tt_hits += other.tt_hits;
+        self.tt_stores += other.tt_stores;
+        self.time += other.time;
+        for (cutoffs, other) in self.cutoffs.iter_mut().zip(other.cutoffs.iter()) {
+            *cutoffs += *other;
         }
+        self.null_cutoffs += other.null_cutoffs;
+        self.win_distance_cutoffs += other.win_distance_cutoffs;
+        self.reverse_futility_cutoffs += other.reverse_futility_cutoffs;
+        self.futility_pruned += other.futility_pruned;
+        self.threat_extensions += other.threat_extensions;
+        self.singular_extensions += other.singular_extensions;
+    }
+
+    /// Records a beta cutoff caused by the ply at `index` in the move ordering.
+    pub fn record_cutoff(&mut self, index: usize) {
+        self.cutoffs[cmp::min(index, CUTOFF_BUCKETS - 1)] += 1;
+    }
+
+    /// Returns the number of nodes visited or evaluated.
+    pub fn nodes(&self) -> u64 {
+        self.visited as u64 + self.evaluated as u64
     }
 }
 
 /// Full statistics for this search.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub struct Statistics {
     /// Statistics for each depth of the search.
     pub depth: Vec<Vec<StatisticsLevel>>,
+    /// The number of entries in the transposition table when the search finished.
+    pub tt_entries: usize,
+    /// The number of root plies searched in each iteration, which is less than the number
+    /// available when root widening is enabled.
+    pub root_plies: Vec<usize>,
+    /// The selective depth of each iteration: the greatest number of plies from the root that
+    /// it searched, which extensions can take past the iteration's depth.
+    #[cfg_attr(feature = "with_serde", serde(default))]
+    pub seldepth: Vec<usize>,
+    /// The depth of the earliest iteration since which every completed iteration has chosen
+    /// the same first ply, or 0 if no iteration completed.
+    #[cfg_attr(feature = "with_serde", serde(default))]
+    pub stable_depth: u8,
+    /// The exact number of nodes the search entered.  Unlike `nodes`, this includes the
+    /// nodes at the final depth of the first iteration and those settled without search, as
+    /// dead draws or by a tablebase or experience.  Node limits are measured against it.
+    #[cfg_attr(feature = "with_serde", serde(default))]
+    pub node_count: u64,
+    /// The depth reused from an exact transposition table entry for the root, which the
+    /// search continued from rather than searching again, or 0 if none was reused.
+    #[cfg_attr(feature = "with_serde", serde(default))]
+    pub precalculated: u8,
+    /// True if an exact entry for the root failed verification, so that the search started
+    /// over from the first depth.  See `PvSearch::set_reuse_precalculated`.
+    #[cfg_attr(feature = "with_serde", serde(default))]
+    pub precalculation_rejected: bool,
 }
 
 impl Statistics {
@@ -62,30 +148,114 @@ impl Statistics {
         let mut totals = vec![StatisticsLevel::new(); self.depth.len()];
         for (i, max_depth) in self.depth.iter().enumerate() {
             for depth in max_depth {
-                totals[i].visited += depth.visited;
-                totals[i].evaluated += depth.evaluated;
-                totals[i].tt_saves += depth.tt_saves;
-                totals[i].tt_hits += depth.tt_hits;
-                totals[i].tt_stores += depth.tt_stores;
-                totals[i].time += depth.time;
+                totals[i].add(depth);
             }
         }
         totals
     }
 
+    /// Returns the depth of the deepest iteration, complete or not.
+    pub fn search_depth(&self) -> usize {
+        self.depth.len()
+    }
+
+    /// Returns the greatest selective depth over all iterations.
+    pub fn selective_depth(&self) -> usize {
+        self.seldepth.iter().cloned().max().unwrap_or(0)
+    }
+
     /// Calculate the totals for all search iterations.
     pub fn calculate_totals(&self) -> StatisticsLevel {
         let mut final_totals = StatisticsLevel::new();
         for total in &self.calculate_depth_totals() {
-            final_totals.visited += total.visited;
-            final_totals.evaluated += total.evaluated;
-            final_totals.tt_saves += total.tt_saves;
-            final_totals.tt_hits += total.tt_hits;
-            final_totals.tt_stores += total.tt_stores;
-            final_totals.time += total.time;
+            final_totals.add(total);
         }
         final_totals
     }
+
+    /// Returns the number of nodes visited or evaluated over the whole search.
+    pub fn nodes(&self) -> u64 {
+        self.calculate_totals().nodes()
+    }
+
+    /// Returns the number of nodes searched per second, or 0 if no time was recorded.
+    pub fn nodes_per_second(&self) -> f64 {
+        let totals = self.calculate_totals();
+        if totals.time > 0.0 {
+            totals.nodes() as f64 / totals.time as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the mean ratio of the nodes searched by each iteration of iterative deepening
+    /// to the nodes searched by the iteration before it, or `None` if fewer than two
+    /// iterations searched any nodes.
+    pub fn effective_branching_factor(&self) -> Option<f64> {
+        let nodes = self.calculate_depth_totals().iter()
+            .map(|total| total.nodes())
+            .filter(|&nodes| nodes > 0)
+            .collect::<Vec<_>>();
+
+        if nodes.len() < 2 {
+            return None;
+        }
+
+        let ratios = nodes.windows(2).map(|pair| pair[1] as f64 / pair[0] as f64).collect::<Vec<_>>();
+        Some(ratios.iter().sum::<f64>() / ratios.len() as f64)
+    }
+
+    /// Returns the number of beta cutoffs over the whole search, indexed by the position of
+    /// the cutoff ply in the move ordering.
+    pub fn cutoff_histogram(&self) -> [u32; CUTOFF_BUCKETS] {
+        self.calculate_totals().cutoffs
+    }
+
+    /// Combines the statistics of another search of the same position into these, as from
+    /// another thread.  Counts are summed, while times, transposition table sizes, and
+    /// selective depths take the larger of the two, since the searches are assumed to have
+    /// run concurrently.  The stable depth of these statistics is kept.
+    pub fn merge(&mut self, other: &Statistics) {
+        for (i, other_max_depth) in other.depth.iter().enumerate() {
+            if i >= self.depth.len() {
+                self.depth.push(other_max_depth.clone());
+                continue;
+            }
+
+            let max_depth = &mut self.depth[i];
+            for (j, other_depth) in other_max_depth.iter().enumerate() {
+                if j >= max_depth.len() {
+                    max_depth.push(*other_depth);
+                    continue;
+                }
+
+                let time = max_depth[j].time.max(other_depth.time);
+                max_depth[j].add(other_depth);
+                max_depth[j].time = time;
+            }
+        }
+
+        self.tt_entries = cmp::max(self.tt_entries, other.tt_entries);
+        self.node_count += other.node_count;
+        self.precalculated = cmp::max(self.precalculated, other.precalculated);
+        self.precalculation_rejected |= other.precalculation_rejected;
+
+        for (i, &other_root_plies) in other.root_plies.iter().enumerate() {
+            if i < self.root_plies.len() {
+                self.root_plies[i] = cmp::max(self.root_plies[i], other_root_plies);
+            } else {
+                self.root_plies.push(other_root_plies);
+            }
+        }
+
+        for (i, &other_seldepth) in other.seldepth.iter().enumerate() {
+            if i < self.seldepth.len() {
+                self.seldepth[i] = cmp::max(self.seldepth[i], other_seldepth);
+            } else {
+                self.seldepth.push(other_seldepth);
+            }
+        }
+    }
 }
 
 impl fmt::Display for Statistics {
@@ -197,3 +367,56 @@ impl fmt::Display for Statistics {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn level(visited: u32, time: f32) -> StatisticsLevel {
+        let mut level = StatisticsLevel::new();
+        level.visited = visited;
+        level.time = time;
+        level
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = Statistics {
+            depth: vec![vec![level(10, 1.0)]],
+            tt_entries: 5,
+            root_plies: vec![4],
+            seldepth: vec![3],
+            stable_depth: 1,
+            node_count: 12,
+            precalculated: 0,
+            precalculation_rejected: false,
+        };
+        a.depth[0][0].record_cutoff(0);
+
+        let mut b = Statistics {
+            depth: vec![vec![level(20, 2.0)], vec![level(40, 4.0), level(80, 0.0)]],
+            tt_entries: 3,
+            root_plies: vec![2, 8],
+            seldepth: vec![1, 5],
+            stable_depth: 2,
+            node_count: 130,
+            precalculated: 1,
+            precalculation_rejected: true,
+        };
+        b.depth[0][0].record_cutoff(100);
+
+        a.merge(&b);
+
+        assert!(a.depth.len() == 2);
+        assert!(a.depth[0][0].visited == 30 && a.depth[0][0].time == 2.0);
+        assert!(a.nodes() == 150 && a.node_count == 142);
+        assert!(a.precalculated == 1 && a.precalculation_rejected);
+        assert!(a.tt_entries == 5);
+        assert!(a.root_plies == vec![4, 8]);
+        assert!(a.seldepth == vec![3, 5] && a.selective_depth() == 5);
+        assert!(a.search_depth() == 2 && a.stable_depth == 1);
+        assert!(a.cutoff_histogram()[0] == 1 && a.cutoff_histogram()[CUTOFF_BUCKETS - 1] == 1);
+        assert!(a.effective_branching_factor() == Some(4.0));
+        assert!(a.nodes_per_second() == 25.0);
+    }
+}
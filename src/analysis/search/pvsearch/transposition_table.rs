@@ -18,22 +18,26 @@
 //
 
 use std::collections::HashMap;
-use std::collections::hash_map::IterMut;
-use std::hash::BuildHasherDefault;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::io::{Read, Write};
 
 use fnv::FnvHasher;
 
 use analysis::Evaluation;
+use persist::{Decoder, Encode, Persistable, PersistError, Reader, Writer};
 use ply::Ply;
 use state::State;
+use symmetric::Symmetric;
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub enum Bound {
     Lower,
     Exact,
     Upper,
 }
 
+#[derive(Clone, Debug)]
 pub struct TranspositionTableEntry<P, E> where
     P: Ply,
     E: Evaluation {
@@ -41,41 +45,479 @@ pub struct TranspositionTableEntry<P, E> where
     pub value: E,
     pub bound: Bound,
     pub principal_variation: Vec<P>,
-    pub lifetime: u8,
+    /// The generation of the table when the entry was stored.  The table sets this itself.
+    pub generation: u8,
 }
 
+/// The canonicalization functions of a `Symmetric` state, stored so that the table
+/// doesn't require every state to be `Symmetric`.
+pub struct Symmetry<S> where
+    S: State {
+    canonicalize: fn(&S) -> S,
+    to_canonical: fn(&S, &[<S as State>::Ply]) -> Vec<<S as State>::Ply>,
+    from_canonical: fn(&S, &[<S as State>::Ply]) -> Vec<<S as State>::Ply>,
+}
+
+impl<S> Symmetry<S> where
+    S: Symmetric {
+    pub fn new() -> Symmetry<S> {
+        fn canonicalize<S>(state: &S) -> S where S: Symmetric {
+            state.canonicalize()
+        }
+
+        fn to_canonical<S>(state: &S, plies: &[<S as State>::Ply]) -> Vec<<S as State>::Ply> where S: Symmetric {
+            let transform = state.canonical_transform();
+            plies.iter().map(|ply| state.transform_ply(ply, &transform)).collect()
+        }
+
+        fn from_canonical<S>(state: &S, plies: &[<S as State>::Ply]) -> Vec<<S as State>::Ply> where S: Symmetric {
+            let transform = state.canonical_transform();
+            let canonical = state.transform(&transform);
+            let inverse = canonical.invert_transform(&transform);
+            plies.iter().map(|ply| canonical.transform_ply(ply, &inverse)).collect()
+        }
+
+        Symmetry {
+            canonicalize: canonicalize::<S>,
+            to_canonical: to_canonical::<S>,
+            from_canonical: from_canonical::<S>,
+        }
+    }
+}
+
+/// Computes the key of a state in the table.
+pub type KeyFn<S> = fn(&S) -> u64;
+
+/// A function called with each entry stored by a search, and the state it was stored for.
+pub type StoreHook<S, E> = Box<FnMut(&S, &TranspositionTableEntry<<S as State>::Ply, E>) + Send>;
+
 pub struct TranspositionTable<S, E> where
     S: State,
     E: Evaluation {
-    map: HashMap<S, TranspositionTableEntry<<S as State>::Ply, E>, BuildHasherDefault<FnvHasher>>,
+    map: HashMap<u64, (S, TranspositionTableEntry<<S as State>::Ply, E>), BuildHasherDefault<FnvHasher>>,
+    key: Option<KeyFn<S>>,
+    symmetry: Option<Symmetry<S>>,
+    store_hook: Option<StoreHook<S, E>>,
+    generation: u8,
 }
 
+/// The number of searches an entry survives without being stored again.
+const MAX_AGE: u8 = 2;
+
 impl<S, E> TranspositionTable<S, E> where
     S: State,
     E: Evaluation {
     pub fn new() -> TranspositionTable<S, E> {
         TranspositionTable {
             map: HashMap::default(),
+            key: None,
+            symmetry: None,
+            store_hook: None,
+            generation: 0,
+        }
+    }
+
+    /// Sets whether states are canonicalized before they're used as keys.  The table is
+    /// cleared if this changes, since existing keys and principal variations would no
+    /// longer be consistent.
+    pub fn set_symmetry(&mut self, symmetry: Option<Symmetry<S>>) {
+        if self.symmetry.is_some() != symmetry.is_some() {
+            self.map.clear();
         }
+        self.symmetry = symmetry;
+    }
+
+    /// Sets the function that keys states in the table, or `None` to key them by their
+    /// hashes.  Clears the table.
+    pub fn set_key(&mut self, key: Option<KeyFn<S>>) {
+        self.key = key;
+        self.map.clear();
+    }
+
+    pub fn set_store_hook(&mut self, store_hook: Option<StoreHook<S, E>>) {
+        self.store_hook = store_hook;
     }
 
     pub fn len(&self) -> usize {
         self.map.len()
     }
 
+    /// Advances the generation, and forgets entries that haven't been stored or refreshed in
+    /// the last `MAX_AGE` generations.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+
+        let generation = self.generation;
+        self.map.retain(|_, &mut (_, ref entry)| generation.wrapping_sub(entry.generation) <= MAX_AGE);
+    }
+
+    /// Forgets every entry, since nothing from a previous game is expected to recur.
+    pub fn new_game(&mut self) {
+        self.map.clear();
+        self.generation = 0;
+    }
+
     pub fn get(&self, state: &S) -> Option<&TranspositionTableEntry<<S as State>::Ply, E>> {
-        self.map.get(state)
+        if let Some(ref symmetry) = self.symmetry {
+            self.lookup(&(symmetry.canonicalize)(state))
+        } else {
+            self.lookup(state)
+        }
     }
 
-    pub fn insert(&mut self, state: S, entry: TranspositionTableEntry<<S as State>::Ply, E>) -> Option<TranspositionTableEntry<<S as State>::Ply, E>> {
-        self.map.insert(state, entry)
+    /// Returns the entry stored for `state`, which has already been canonicalized if needed.
+    /// Another state with the same key doesn't match.
+    fn lookup(&self, state: &S) -> Option<&TranspositionTableEntry<<S as State>::Ply, E>> {
+        match self.map.get(&self.key(state)) {
+            Some(&(ref stored, ref entry)) if stored == state => Some(entry),
+            _ => None,
+        }
+    }
+
+    /// Moves the entry stored for `state`, if any, into the current generation, as if it had
+    /// been stored again, so that entries a search keeps using aren't forgotten.
+    pub fn refresh(&mut self, state: &S) {
+        let canonical;
+        let state = if let Some(ref symmetry) = self.symmetry {
+            canonical = (symmetry.canonicalize)(state);
+            &canonical
+        } else {
+            state
+        };
+
+        let key = self.key(state);
+        let generation = self.generation;
+        if let Some(&mut (ref stored, ref mut entry)) = self.map.get_mut(&key) {
+            if stored == state {
+                entry.generation = generation;
+            }
+        }
+    }
+
+    /// Returns the principal variation of `entry`, oriented to `state`.  `entry` must have
+    /// been retrieved with `state`.
+    pub fn get_principal_variation(&self, state: &S, entry: &TranspositionTableEntry<<S as State>::Ply, E>) -> Vec<<S as State>::Ply> {
+        if let Some(ref symmetry) = self.symmetry {
+            (symmetry.from_canonical)(state, &entry.principal_variation)
+        } else {
+            entry.principal_variation.clone()
+        }
     }
 
-    pub fn remove(&mut self, state: &S) -> Option<TranspositionTableEntry<<S as State>::Ply, E>> {
-        self.map.remove(state)
+    pub fn insert(&mut self, state: S, mut entry: TranspositionTableEntry<<S as State>::Ply, E>) -> Option<TranspositionTableEntry<<S as State>::Ply, E>> {
+        entry.generation = self.generation;
+        if let Some(ref mut store_hook) = self.store_hook {
+            store_hook(&state, &entry);
+        }
+        self.store(state, entry)
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<S, TranspositionTableEntry<<S as State>::Ply, E>> {
-        self.map.iter_mut()
+    /// Merges an entry received from elsewhere, e.g. another machine's store hook, into the
+    /// current generation.  The entry replaces an existing one if that one is from an earlier
+    /// generation, or if it was searched deeper, or to the same depth with an exact bound
+    /// where the existing one has none.  The store hook isn't called, so replicated entries
+    /// aren't echoed back.  Entries without a principal variation are never stored, as the
+    /// search can't use them.  Returns true if the entry was stored.
+    pub fn ingest(&mut self, state: S, mut entry: TranspositionTableEntry<<S as State>::Ply, E>) -> bool {
+        if entry.principal_variation.is_empty() {
+            return false;
+        }
+
+        entry.generation = self.generation;
+        let replace = match self.get(&state) {
+            Some(existing) => existing.generation != self.generation || entry.depth > existing.depth || (
+                entry.depth == existing.depth &&
+                entry.bound == Bound::Exact &&
+                existing.bound != Bound::Exact
+            ),
+            None => true,
+        };
+
+        if replace {
+            self.store(state, entry);
+        }
+        replace
+    }
+
+    /// Stores `entry` for `state`, replacing the entry of any other state with the same key.
+    fn store(&mut self, state: S, mut entry: TranspositionTableEntry<<S as State>::Ply, E>) -> Option<TranspositionTableEntry<<S as State>::Ply, E>> {
+        let state = if let Some(ref symmetry) = self.symmetry {
+            entry.principal_variation = (symmetry.to_canonical)(&state, &entry.principal_variation);
+            (symmetry.canonicalize)(&state)
+        } else {
+            state
+        };
+
+        let key = self.key(&state);
+        match self.map.insert(key, (state, entry)) {
+            Some((ref replaced, _)) if *replaced != self.map[&key].0 => None,
+            replaced => replaced.map(|(_, entry)| entry),
+        }
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a S, &'a TranspositionTableEntry<<S as State>::Ply, E>)> + 'a {
+        self.map.values().map(|&(ref state, ref entry)| (state, entry))
+    }
+
+    fn key(&self, state: &S) -> u64 {
+        if let Some(key) = self.key {
+            return key(state);
+        }
+
+        let mut hasher = FnvHasher::default();
+        state.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+const TABLE_MAGIC: &'static [u8; 4] = b"ZSTT";
+const TABLE_VERSION: u32 = 2;
+
+/// The number of entries written to each section of a saved table.
+const SECTION_ENTRIES: usize = 4096;
+
+impl<S, E> TranspositionTable<S, E> where
+    S: State + Persistable,
+    <S as State>::Ply: Persistable,
+    E: Evaluation + Persistable {
+    /// Writes every entry to `writer`.
+    pub fn save<W>(&self, writer: W) -> Result<(), PersistError> where W: Write {
+        let mut writer = Writer::new(writer, TABLE_MAGIC, TABLE_VERSION)?;
+        self.write_sections(&mut writer)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Writes every entry to `writer` as sections of a larger file.
+    pub fn write_sections<W>(&self, writer: &mut Writer<W>) -> Result<(), PersistError> where W: Write {
+        let entries = self.iter().collect::<Vec<_>>();
+
+        for section in entries.chunks(SECTION_ENTRIES) {
+            let mut bytes = Vec::new();
+            bytes.write_u32(section.len() as u32);
+
+            for &(state, entry) in section {
+                state.encode(&mut bytes);
+                bytes.write_u8(entry.depth);
+                entry.value.encode(&mut bytes);
+                bytes.write_u8(match entry.bound {
+                    Bound::Lower => 0,
+                    Bound::Exact => 1,
+                    Bound::Upper => 2,
+                });
+                bytes.write_u32(entry.principal_variation.len() as u32);
+                for ply in &entry.principal_variation {
+                    ply.encode(&mut bytes);
+                }
+            }
+
+            writer.write_section(&bytes)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads entries written by `save` from `reader`, merging them into the current generation
+    /// as with `ingest`.  Returns the number of entries that were stored.
+    ///
+    /// If the data is damaged, the entries of the sections before the damage are still
+    /// merged before the error is returned.
+    pub fn load<R>(&mut self, reader: R) -> Result<usize, PersistError> where R: Read {
+        let mut reader = Reader::new(reader, TABLE_MAGIC, TABLE_VERSION)?;
+        self.read_sections(&mut reader)
+    }
+
+    /// Reads the remaining sections of `reader` as entries written by `write_sections`,
+    /// merging them as with `load`.
+    pub fn read_sections<R>(&mut self, reader: &mut Reader<R>) -> Result<usize, PersistError> where R: Read {
+        let mut stored = 0;
+
+        while let Some(bytes) = reader.read_section()? {
+            let mut decoder = Decoder::new(&bytes);
+            let count = decoder.read_u32()?;
+
+            for _ in 0..count {
+                let state = S::decode(&mut decoder)?;
+                let depth = decoder.read_u8()?;
+                let value = E::decode(&mut decoder)?;
+                let bound = match decoder.read_u8()? {
+                    0 => Bound::Lower,
+                    1 => Bound::Exact,
+                    2 => Bound::Upper,
+                    b => return Err(PersistError::Malformed(format!("Invalid bound: {}", b))),
+                };
+                let length = decoder.read_u32()?;
+                let mut principal_variation = Vec::new();
+                for _ in 0..length {
+                    principal_variation.push(<S as State>::Ply::decode(&mut decoder)?);
+                }
+
+                // Entries are only stored with a principal variation
+                if principal_variation.is_empty() {
+                    return Err(PersistError::Malformed(String::from("Entry without a principal variation")));
+                }
+
+                let entry = TranspositionTableEntry {
+                    depth: depth,
+                    value: value,
+                    bound: bound,
+                    principal_variation: principal_variation,
+                    generation: 0,
+                };
+                if self.ingest(state, entry) {
+                    stored += 1;
+                }
+            }
+
+            if !decoder.is_empty() {
+                return Err(PersistError::Malformed(String::from("Unexpected data after entries")));
+            }
+        }
+
+        Ok(stored)
+    }
+}
+
+#[cfg(all(test, feature = "with_tic_tac_toe"))]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use analysis::Evaluator as EvaluatorTrait;
+    use analysis::Extrapolatable;
+    use impls::tic_tac_toe::{Board, Evaluator};
+    use symmetric::Symmetric;
+    use super::*;
+
+    type Value = <Evaluator as EvaluatorTrait>::Evaluation;
+
+    fn entry(depth: u8, bound: Bound) -> TranspositionTableEntry<<Board as State>::Ply, Value> {
+        TranspositionTableEntry {
+            depth: depth,
+            value: Value::null(),
+            bound: bound,
+            principal_variation: vec![Board::new().extrapolate()[0].clone()],
+            generation: 0,
+        }
+    }
+
+    #[test]
+    fn test_store_hook_and_ingest() {
+        let stored = Arc::new(Mutex::new(Vec::new()));
+        let mut table = TranspositionTable::<Board, Value>::new();
+        {
+            let stored = stored.clone();
+            table.set_store_hook(Some(Box::new(move |_: &Board, entry: &TranspositionTableEntry<_, _>| {
+                stored.lock().unwrap().push(entry.depth);
+            })));
+        }
+
+        table.insert(Board::new(), entry(3, Bound::Lower));
+        assert_eq!(*stored.lock().unwrap(), vec![3]);
+
+        assert!(!table.ingest(Board::new(), entry(2, Bound::Exact)));
+        assert!(!table.ingest(Board::new(), entry(3, Bound::Upper)));
+        assert!(table.ingest(Board::new(), entry(3, Bound::Exact)));
+        assert!(table.ingest(Board::new(), entry(4, Bound::Upper)));
+        assert_eq!(table.get(&Board::new()).unwrap().depth, 4);
+        assert_eq!(*stored.lock().unwrap(), vec![3]);
+
+        // An entry without a principal variation would leave the search with nothing to play
+        let mut empty = entry(9, Bound::Exact);
+        empty.principal_variation.clear();
+        assert!(!table.ingest(Board::new(), empty));
+        assert_eq!(table.get(&Board::new()).unwrap().depth, 4);
+    }
+
+    #[test]
+    fn test_key_collisions() {
+        fn key(_: &Board) -> u64 {
+            0
+        }
+
+        let mut table = TranspositionTable::<Board, Value>::new();
+        let mut other = Board::new();
+        other.execute_ply(Some(&Board::new().extrapolate()[0])).unwrap();
+
+        table.insert(Board::new(), entry(3, Bound::Lower));
+        table.set_key(Some(key));
+        assert_eq!(table.len(), 0);
+
+        // Every state shares a key, so each store replaces the last, and lookups don't mistake one for the other
+        table.insert(Board::new(), entry(3, Bound::Lower));
+        assert!(table.get(&other).is_none());
+        assert!(table.insert(other.clone(), entry(2, Bound::Upper)).is_none());
+        assert!(table.get(&Board::new()).is_none());
+        assert_eq!(table.get(&other).unwrap().depth, 2);
+        assert_eq!(table.insert(other.clone(), entry(4, Bound::Exact)).unwrap().depth, 2);
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_generations() {
+        let mut table = TranspositionTable::<Board, Value>::new();
+        let mut other = Board::new();
+        other.execute_ply(Some(&Board::new().extrapolate()[0])).unwrap();
+
+        table.insert(Board::new(), entry(3, Bound::Lower));
+        table.new_search();
+        table.insert(other.clone(), entry(1, Bound::Upper));
+        assert_eq!(table.get(&other).unwrap().generation, 1);
+
+        // Entries from earlier searches give way to shallower ones from this one
+        table.new_search();
+        assert!(table.ingest(Board::new(), entry(2, Bound::Upper)));
+        assert_eq!(table.get(&Board::new()).unwrap().depth, 2);
+
+        table.new_search();
+        table.new_search();
+        assert!(table.get(&Board::new()).is_some() && table.get(&other).is_none());
+
+        // An entry the search keeps using lasts as if it were stored again
+        table.refresh(&Board::new());
+        table.new_search();
+        table.new_search();
+        assert!(table.get(&Board::new()).is_some());
+        table.new_search();
+        assert!(table.get(&Board::new()).is_none());
+
+        table.new_game();
+        assert_eq!(table.len(), 0);
+    }
+
+    #[test]
+    fn test_symmetry() {
+        // X in a corner, O on an edge: only the identity leaves this unchanged
+        let mut state = Board::new();
+        for &index in &[0, 1] {
+            let ply = state.extrapolate().into_iter().find(|ply| ply.coordinates == (index % 3, index / 3)).unwrap();
+            state.execute_ply(Some(&ply)).unwrap();
+        }
+
+        let canonical = state.canonicalize();
+        for transform in state.symmetries() {
+            let transformed = state.transform(&transform);
+            assert!(transformed.canonicalize() == canonical);
+            assert!(transformed.transform(&transformed.invert_transform(&transform)) == state);
+        }
+
+        // Plies translate into the canonical state and back
+        let symmetry = Symmetry::<Board>::new();
+        let plies = state.extrapolate();
+        let canonical_plies = (symmetry.to_canonical)(&state, &plies);
+        let legal = canonical.extrapolate();
+        assert!(canonical_plies.iter().all(|ply| legal.contains(ply)));
+        assert_eq!((symmetry.from_canonical)(&state, &canonical_plies), plies);
+
+        // An entry stored for one state serves its reflection, with its plies reflected
+        let mut table = TranspositionTable::<Board, Value>::new();
+        table.set_symmetry(Some(symmetry));
+        let mut stored = entry(3, Bound::Exact);
+        stored.principal_variation = vec![plies[0].clone()];
+        table.insert(state.clone(), stored);
+
+        let transform = state.symmetries()[4].clone();
+        let reflected = state.transform(&transform);
+        let found = table.get(&reflected).unwrap();
+        assert_eq!(table.get_principal_variation(&reflected, found), vec![state.transform_ply(&plies[0], &transform)]);
     }
 }
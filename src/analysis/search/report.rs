@@ -0,0 +1,60 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::fmt;
+
+/// The essentials of an analysis as plain values, for frontends that present results from any
+/// search without downcasting or parsing its `Display` output.  Plies, scores, and
+/// resolutions are given in their `Display` forms.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub struct AnalysisReport {
+    /// The ply the search recommends, if it found one.
+    pub best_move: Option<String>,
+    /// The evaluation of the state after the principal variation.
+    pub score: String,
+    /// The principal variation, beginning with `best_move`.
+    pub pv: Vec<String>,
+    /// The depth of the last iteration the search completed.
+    pub depth: usize,
+    /// The number of nodes searched.
+    pub nodes: u64,
+    /// The time spent searching, in seconds.
+    pub time: f32,
+    /// The resolution of the state after the principal variation, if the game ends there.
+    pub resolution: Option<String>,
+}
+
+impl fmt::Display for AnalysisReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.best_move {
+            Some(ref best_move) => try!(write!(f, "Best Move: {}\n", best_move)),
+            None => try!(write!(f, "Best Move: None\n")),
+        }
+        try!(write!(f, "Score: {}\n", self.score));
+        try!(write!(f, "Depth: {}\n", self.depth));
+        try!(write!(f, "Nodes: {}\n", self.nodes));
+        try!(write!(f, "Time: {:.3}s\n", self.time));
+        try!(write!(f, "Principal Variation: {}", self.pv.join(" ")));
+        if let Some(ref resolution) = self.resolution {
+            try!(write!(f, "\nResolution: {}", resolution));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,322 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use analysis::Extrapolatable;
+use analysis::search::{Analysis, Search};
+use state::State;
+
+/// A clock with this much time remaining is budgeted this fraction of it for each ply, plus
+/// most of the increment.
+const CLOCK_DIVISOR: u32 = 20;
+
+/// How long the engine may think about a ply.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeControl {
+    /// The search stops on its own, at its depth limit or time goal.
+    Unlimited,
+    /// The search is interrupted after the given time.
+    MoveTime(Duration),
+    /// The search is interrupted after a share of the engine's `remaining` time on its clock,
+    /// which gains `increment` after each of its plies.
+    Clock {
+        remaining: Duration,
+        increment: Duration,
+    },
+}
+
+impl TimeControl {
+    /// Returns the time to spend on the next ply, or `None` if it's unlimited.
+    pub fn budget(&self) -> Option<Duration> {
+        match *self {
+            TimeControl::Unlimited => None,
+            TimeControl::MoveTime(time) => Some(time),
+            TimeControl::Clock { remaining, increment } => Some(cmp::min(
+                remaining / CLOCK_DIVISOR + increment * 3 / 4,
+                remaining / 2,
+            )),
+        }
+    }
+}
+
+/// A ply chosen by an `EngineSession`.
+pub struct EngineMove<P> {
+    /// The chosen ply.
+    pub ply: P,
+    /// The reply that the search expects, which is worth pondering on.
+    pub expected_reply: Option<P>,
+    /// The analysis that the ply was chosen from, or `None` if it came from the opening book.
+    pub analysis: Option<Box<Analysis>>,
+}
+
+/// The engine's side of an entire game.
+///
+/// The session keeps the search, and whatever it carries between searches, such as a
+/// transposition table, alive from one ply to the next.  It tracks the game as plies are
+/// `play`ed, answers `go` from its opening book or with a search, and can `ponder` on the
+/// position it expects to face next while the opponent thinks.
+pub struct EngineSession<S, X> where
+    S: State + Extrapolatable<<S as State>::Ply>,
+    X: Search<S> {
+    search: X,
+    start: S,
+    state: S,
+    history: Vec<<S as State>::Ply>,
+    book: HashMap<S, <S as State>::Ply>,
+    expected: Vec<<S as State>::Ply>,
+}
+
+impl<S, X> EngineSession<S, X> where
+    S: State + Extrapolatable<<S as State>::Ply>,
+    X: Search<S> {
+    /// Creates a session for a game starting at `state`.
+    pub fn new(search: X, state: S) -> EngineSession<S, X> {
+        EngineSession {
+            search: search,
+            start: state.clone(),
+            state: state,
+            history: Vec::new(),
+            book: HashMap::new(),
+            expected: Vec::new(),
+        }
+    }
+
+    /// Returns the current state of the game.
+    pub fn get_state(&self) -> &S {
+        &self.state
+    }
+
+    /// Returns the plies played since the game began.
+    pub fn get_history(&self) -> &[<S as State>::Ply] {
+        &self.history
+    }
+
+    /// Returns the search, i.e. to change its settings between plies.
+    pub fn get_search_mut(&mut self) -> &mut X {
+        &mut self.search
+    }
+
+    /// Sets the opening book, which maps states to the ply the engine should make from them
+    /// without searching.
+    pub fn set_book(&mut self, book: HashMap<S, <S as State>::Ply>) {
+        self.book = book;
+    }
+
+    /// Starts a new game at `state`, clearing the history and anything the search has carried
+    /// over from the previous game.  The opening book is kept.
+    pub fn new_game(&mut self, state: S) {
+        self.start = state.clone();
+        self.state = state;
+        self.history.clear();
+        self.expected.clear();
+        self.search.clear();
+    }
+
+    /// Plays `ply`, made by either side, on the current state.
+    pub fn play(&mut self, ply: <S as State>::Ply) -> Result<(), String> {
+        self.state.execute_ply(Some(&ply))?;
+
+        if self.expected.first() == Some(&ply) {
+            self.expected.remove(0);
+        } else {
+            self.expected.clear();
+        }
+
+        self.history.push(ply);
+        Ok(())
+    }
+
+    /// Takes back the last ply played, returning it, or `None` if the game is at its start.
+    pub fn undo(&mut self) -> Result<Option<<S as State>::Ply>, String> {
+        match self.history.last() {
+            Some(ply) => self.state.revert_ply(Some(ply))?,
+            None => return Ok(None),
+        }
+        self.expected.clear();
+        Ok(self.history.pop())
+    }
+
+    /// Replays the game from its start, i.e. after the state has been modified through
+    /// something other than the session.
+    pub fn replay(&mut self) -> Result<(), String> {
+        let mut state = self.start.clone();
+        if let Err(error) = state.execute_plies(&self.history) {
+            return Err(format!("Error executing plies: {}, {}", self.history[error.index], error.error));
+        }
+        self.state = state;
+        self.expected.clear();
+        Ok(())
+    }
+
+    /// Chooses a ply for the side to move, within `time_control`.  The ply is not played; pass
+    /// it to `play` if it's accepted.  Returns `None` if the game is over or the search didn't
+    /// find a ply.
+    pub fn go(&mut self, time_control: TimeControl) -> Option<EngineMove<<S as State>::Ply>> {
+        if self.state.check_resolution().is_some() {
+            return None;
+        }
+
+        if let Some(ply) = self.book.get(&self.state).cloned() {
+            self.expected = vec![ply.clone()];
+            return Some(EngineMove {
+                ply: ply,
+                expected_reply: None,
+                analysis: None,
+            });
+        }
+
+        let state = self.state.clone();
+        let analysis = self.search_within(&state, time_control.budget());
+
+        self.expected = self.search.get_principal_variation(&*analysis);
+        match self.expected.first().cloned() {
+            Some(ply) => Some(EngineMove {
+                ply: ply,
+                expected_reply: self.expected.get(1).cloned(),
+                analysis: Some(analysis),
+            }),
+            None => None,
+        }
+    }
+
+    /// Searches the state the engine expects to face after the opponent replies to its last
+    /// ply, or the current state if it has no expectation, until `interrupt` receives a
+    /// message or the search stops on its own.  What the search learns is kept for `go`.
+    ///
+    /// Pondering is usually run on its own thread while waiting for the opponent, with the
+    /// interrupt sent when the opponent's ply arrives.
+    pub fn ponder(&mut self, interrupt: mpsc::Receiver<()>) -> Box<Analysis> {
+        let mut state = self.state.clone();
+        if let Some(reply) = self.expected.first() {
+            if state.execute_ply(Some(reply)).is_err() {
+                state = self.state.clone();
+            }
+        }
+        self.search.search(&state, Some(interrupt))
+    }
+
+    fn search_within(&mut self, state: &S, budget: Option<Duration>) -> Box<Analysis> {
+        let budget = match budget {
+            Some(budget) => budget,
+            None => return self.search.search(state, None),
+        };
+
+        let (interrupt_sender, interrupt_receiver) = mpsc::channel();
+        let (finished_sender, finished_receiver) = mpsc::channel::<()>();
+
+        // Interrupt the search when the budget runs out, unless it finishes first
+        let timer = thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = finished_receiver.recv_timeout(budget) {
+                interrupt_sender.send(()).ok();
+            }
+        });
+
+        let analysis = self.search.search(state, Some(interrupt_receiver));
+        drop(finished_sender);
+        timer.join().ok();
+        analysis
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        assert_eq!(TimeControl::Unlimited.budget(), None);
+        assert_eq!(TimeControl::MoveTime(Duration::from_secs(3)).budget(), Some(Duration::from_secs(3)));
+        assert_eq!(TimeControl::Clock {
+            remaining: Duration::from_secs(60),
+            increment: Duration::from_secs(4),
+        }.budget(), Some(Duration::from_secs(6)));
+        assert_eq!(TimeControl::Clock {
+            remaining: Duration::from_secs(4),
+            increment: Duration::from_secs(8),
+        }.budget(), Some(Duration::from_secs(2)));
+    }
+
+    #[cfg(feature = "with_tic_tac_toe")]
+    #[test]
+    fn test_session() {
+        use std::collections::HashMap;
+
+        use analysis::search::{PvSearch, PvSearchAnalysis, Search};
+        use impls::tic_tac_toe::{Board, Evaluator, Mark, Ply};
+
+        let mut session = EngineSession::new(PvSearch::with_depth(Evaluator, 9), Board::new());
+
+        let corner = Ply { mark: Mark::X, coordinates: (0, 0) };
+        let mut book = HashMap::new();
+        book.insert(Board::new(), corner);
+        session.set_book(book);
+
+        let book_move = session.go(TimeControl::Unlimited).unwrap();
+        assert_eq!(book_move.ply, corner);
+        assert!(book_move.analysis.is_none());
+        session.play(book_move.ply).unwrap();
+
+        let reply = session.go(TimeControl::Unlimited).unwrap();
+        assert!(reply.expected_reply.is_some());
+        session.play(reply.ply).unwrap();
+        session.play(reply.expected_reply.unwrap()).unwrap();
+        assert_eq!(session.get_history().len(), 3);
+
+        // The table carried over from the previous search saves work
+        let visited = |analysis: &Analysis| {
+            let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, Evaluator>>().unwrap();
+            analysis.statistics.calculate_depth_totals().iter().map(|level| level.visited).sum::<u32>()
+        };
+        let fresh = PvSearch::with_depth(Evaluator, 9).search(session.get_state(), None);
+        let next = session.go(TimeControl::Unlimited).unwrap();
+        assert!(visited(&*next.analysis.unwrap()) < visited(&*fresh));
+
+        assert_eq!(session.undo().unwrap(), reply.expected_reply);
+        session.new_game(Board::new());
+        assert!(session.get_history().is_empty());
+        assert_eq!(session.go(TimeControl::Unlimited).unwrap().ply, corner);
+    }
+
+    #[cfg(feature = "with_connect_four")]
+    #[test]
+    fn test_time_control() {
+        use std::sync::mpsc;
+        use std::time::Instant;
+
+        use analysis::search::PvSearch;
+        use impls::connect_four::{Board, Evaluator};
+
+        let mut session = EngineSession::new(PvSearch::new(Evaluator), Board::new());
+
+        let start = Instant::now();
+        assert!(session.go(TimeControl::MoveTime(Duration::from_millis(100))).is_some());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let (interrupt_sender, interrupt_receiver) = mpsc::channel();
+        interrupt_sender.send(()).unwrap();
+        session.ponder(interrupt_receiver);
+    }
+}
@@ -0,0 +1,412 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hasher;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+use fnv::FnvHasher;
+
+use analysis::{Evaluation, Extrapolatable};
+use persist::{Decoder, Encode, PersistError, Reader, Writer};
+use resolution::Resolution;
+use state::State;
+
+const TABLEBASE_MAGIC: &'static [u8; 4] = b"ZSTB";
+const TABLEBASE_VERSION: u32 = 1;
+
+/// The value of a state under perfect play, from the perspective of the player to move.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TablebaseValue {
+    /// The player to move wins in the given number of plies.
+    Win(u32),
+    /// The player to move loses in the given number of plies.
+    Loss(u32),
+    /// Neither player can force a win.
+    Draw,
+}
+
+impl TablebaseValue {
+    /// Returns the evaluation a search should give a state with this value, reached after
+    /// `ply_count` plies.  Like the evaluation of a resolved state, wins and losses are shifted
+    /// by the ply on which the game ends, if `E` encodes win distance.
+    pub fn evaluation<E>(&self, ply_count: usize) -> E where
+        E: Evaluation {
+        let end = |distance: u32| if E::encodes_win_distance() {
+            (ply_count + distance as usize) as i32
+        } else {
+            0
+        };
+
+        match *self {
+            TablebaseValue::Win(distance) => E::win().shift(-end(distance)),
+            TablebaseValue::Loss(distance) => E::lose().shift(end(distance)),
+            TablebaseValue::Draw => E::null(),
+        }
+    }
+
+    /// The value of the state from which a ply led to a state of this value.
+    fn parent(&self) -> TablebaseValue {
+        match *self {
+            TablebaseValue::Win(distance) => TablebaseValue::Loss(distance + 1),
+            TablebaseValue::Loss(distance) => TablebaseValue::Win(distance + 1),
+            TablebaseValue::Draw => TablebaseValue::Draw,
+        }
+    }
+
+    /// Orders values from the worst for the player to move to the best.
+    fn rank(&self) -> (i8, i64) {
+        match *self {
+            TablebaseValue::Loss(distance) => (-1, distance as i64),
+            TablebaseValue::Draw => (0, 0),
+            TablebaseValue::Win(distance) => (1, -(distance as i64)),
+        }
+    }
+}
+
+/// The solved values of every state reachable from a root state, built by retrograde analysis.
+///
+/// Building enumerates the states reachable from the root with `Extrapolatable::extrapolate`,
+/// scores the resolved ones, and works backward from them: a state with a ply to a lost state
+/// is won, and a state whose every ply leads to a won state is lost.  Whatever remains
+/// undecided is drawn.  Wins are found at their shortest distance, and losses at their
+/// longest.  Every reachable state is kept in memory while building, so this is only
+/// practical for small games, or endgames with few enough pieces left to play.
+///
+/// States are keyed by their hashes, so states that hash equally, like those that differ only
+/// in their ply counts, share an entry.  A search can probe the table at its leaves with
+/// `PvSearch::set_tablebase`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let tablebase = Tablebase::build(&Board::new(), 10_000).unwrap();
+/// assert_eq!(tablebase.get(&Board::new()), Some(TablebaseValue::Draw));
+/// ```
+pub struct Tablebase<S> where
+    S: State {
+    values: HashMap<u64, TablebaseValue>,
+    state: PhantomData<S>,
+}
+
+impl<S> Tablebase<S> where
+    S: State + Extrapolatable<<S as State>::Ply> {
+    /// Solves every state reachable from `root`.  Returns an error if there are more than
+    /// `max_states` of them.
+    pub fn build(root: &S, max_states: usize) -> Result<Tablebase<S>, String> {
+        let mut indices = HashMap::new();
+        let mut keys = Vec::new();
+        let mut values = Vec::new();
+        let mut parents: Vec<Vec<usize>> = Vec::new();
+        let mut remaining = Vec::new();
+        let mut queue = VecDeque::new();
+
+        let mut pending = vec![(0, root.clone())];
+        indices.insert(hash(root), 0);
+        keys.push(hash(root));
+        values.push(None);
+        parents.push(Vec::new());
+        remaining.push(0);
+
+        while let Some((index, state)) = pending.pop() {
+            let value = match state.check_resolution() {
+                Some(resolution) => Some(resolution_value(&state, &resolution)),
+                None if state.is_dead_draw() => Some(TablebaseValue::Draw),
+                None => None,
+            };
+            if let Some(value) = value {
+                values[index] = Some(value);
+                if value != TablebaseValue::Draw {
+                    queue.push_back(index);
+                }
+                continue;
+            }
+
+            let mut children = Vec::new();
+            for ply in state.extrapolate() {
+                let mut child = state.clone();
+                if child.execute_ply(Some(&ply)).is_ok() {
+                    children.push(child);
+                }
+            }
+            if children.is_empty() {
+                match state.no_plies_resolution() {
+                    Some(resolution) => {
+                        let value = resolution_value(&state, &resolution);
+                        values[index] = Some(value);
+                        if value != TablebaseValue::Draw {
+                            queue.push_back(index);
+                        }
+                        continue;
+                    },
+                    None => {
+                        let mut child = state.clone();
+                        child.execute_ply(None)?;
+                        children.push(child);
+                    },
+                }
+            }
+
+            for child in children {
+                let key = hash(&child);
+                let child_index = match indices.get(&key) {
+                    Some(&child_index) => child_index,
+                    None => {
+                        let child_index = keys.len();
+                        if child_index >= max_states {
+                            return Err(format!("More than {} states are reachable", max_states));
+                        }
+                        indices.insert(key, child_index);
+                        keys.push(key);
+                        values.push(None);
+                        parents.push(Vec::new());
+                        remaining.push(0);
+                        pending.push((child_index, child));
+                        child_index
+                    },
+                };
+                parents[child_index].push(index);
+                remaining[index] += 1;
+            }
+        }
+
+        // Values leave the queue in order of distance, so the first win found for a state is
+        // its shortest, and the last of its plies to be found losing is its longest loss.
+        while let Some(index) = queue.pop_front() {
+            let value = values[index].unwrap();
+            for &parent in &parents[index] {
+                if values[parent].is_some() {
+                    continue;
+                }
+
+                match value {
+                    TablebaseValue::Loss(_) => {
+                        values[parent] = Some(value.parent());
+                        queue.push_back(parent);
+                    },
+                    _ => {
+                        remaining[parent] -= 1;
+                        if remaining[parent] == 0 {
+                            values[parent] = Some(value.parent());
+                            queue.push_back(parent);
+                        }
+                    },
+                }
+            }
+        }
+
+        Ok(Tablebase {
+            values: keys.into_iter().zip(values).map(|(key, value)| {
+                (key, value.unwrap_or(TablebaseValue::Draw))
+            }).collect(),
+            state: PhantomData,
+        })
+    }
+
+    /// Returns the value of `state`, or `None` if it isn't in the table.
+    pub fn get(&self, state: &S) -> Option<TablebaseValue> {
+        self.values.get(&hash(state)).cloned()
+    }
+
+    /// Returns the ply that best preserves the value of `state`: the quickest win, or failing
+    /// that a draw, or failing that the slowest loss.  Returns `None` if `state` isn't in the
+    /// table or has no plies.
+    pub fn get_best_ply(&self, state: &S) -> Option<<S as State>::Ply> {
+        let mut best: Option<(<S as State>::Ply, TablebaseValue)> = None;
+        for ply in state.extrapolate() {
+            let mut child = state.clone();
+            if child.execute_ply(Some(&ply)).is_err() {
+                continue;
+            }
+
+            if let Some(value) = self.get(&child).map(|value| value.parent()) {
+                if best.as_ref().map_or(true, |&(_, best_value)| value.rank() > best_value.rank()) {
+                    best = Some((ply, value));
+                }
+            }
+        }
+        best.map(|(ply, _)| ply)
+    }
+}
+
+impl<S> Tablebase<S> where
+    S: State {
+    /// Returns the number of states in the table.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if the table holds no states.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Writes the table to `writer`.
+    pub fn save<W>(&self, writer: W) -> Result<(), PersistError> where
+        W: Write {
+        let mut writer = Writer::new(writer, TABLEBASE_MAGIC, TABLEBASE_VERSION)?;
+
+        let mut bytes = Vec::with_capacity(self.values.len() * 13);
+        for (&key, value) in &self.values {
+            bytes.write_u64(key);
+            match *value {
+                TablebaseValue::Win(distance) => {
+                    bytes.write_u8(0);
+                    bytes.write_u32(distance);
+                },
+                TablebaseValue::Loss(distance) => {
+                    bytes.write_u8(1);
+                    bytes.write_u32(distance);
+                },
+                TablebaseValue::Draw => {
+                    bytes.write_u8(2);
+                    bytes.write_u32(0);
+                },
+            }
+        }
+        writer.write_section(&bytes)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Reads a table written by `save`.
+    pub fn load<R>(reader: R) -> Result<Tablebase<S>, PersistError> where
+        R: Read {
+        let mut reader = Reader::new(reader, TABLEBASE_MAGIC, TABLEBASE_VERSION)?;
+        let bytes = match reader.read_section()? {
+            Some(bytes) => bytes,
+            None => return Err(PersistError::Malformed(String::from("Missing tablebase section"))),
+        };
+
+        let mut decoder = Decoder::new(&bytes);
+        let mut values = HashMap::new();
+        while !decoder.is_empty() {
+            let key = decoder.read_u64()?;
+            let kind = decoder.read_u8()?;
+            let distance = decoder.read_u32()?;
+            let value = match kind {
+                0 => TablebaseValue::Win(distance),
+                1 => TablebaseValue::Loss(distance),
+                2 => TablebaseValue::Draw,
+                _ => return Err(PersistError::Malformed(format!("Invalid tablebase value {}", kind))),
+            };
+            values.insert(key, value);
+        }
+
+        Ok(Tablebase {
+            values: values,
+            state: PhantomData,
+        })
+    }
+}
+
+/// Returns the value of a resolved state for the player to move.
+fn resolution_value<S>(state: &S, resolution: &<S as State>::Resolution) -> TablebaseValue where
+    S: State {
+    match resolution.get_winner() {
+        Some(winner) if winner as usize == state.get_ply_count() % 2 => TablebaseValue::Win(0),
+        Some(_) => TablebaseValue::Loss(0),
+        None => TablebaseValue::Draw,
+    }
+}
+
+fn hash<S>(state: &S) -> u64 where
+    S: State {
+    let mut hasher = FnvHasher::default();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(all(test, feature = "with_tic_tac_toe"))]
+mod test {
+    use std::sync::Arc;
+
+    use analysis::Evaluator as EvaluatorTrait;
+    use analysis::search::{PvSearch, PvSearchAnalysis, Search};
+    use impls::tic_tac_toe::{Board, Evaluator, Mark};
+    use state::State;
+    use super::*;
+
+    type Value = <Evaluator as EvaluatorTrait>::Evaluation;
+
+    #[test]
+    fn test_tic_tac_toe() {
+        let tablebase = Tablebase::build(&Board::new(), 10_000).unwrap();
+        assert_eq!(tablebase.len(), 5478);
+        assert_eq!(tablebase.get(&Board::new()), Some(TablebaseValue::Draw));
+        assert!(Tablebase::build(&Board::new(), 5477).is_err());
+
+        // X takes a corner, and O answers on an edge, which loses
+        let board = Board([Some(Mark::X), Some(Mark::O), None, None, None, None, None, None, None], 2);
+        let value = tablebase.get(&board).unwrap();
+        assert_eq!(value, TablebaseValue::Win(5));
+
+        let mut next = board.clone();
+        next.execute_ply(Some(&tablebase.get_best_ply(&board).unwrap())).unwrap();
+        assert_eq!(tablebase.get(&next), Some(TablebaseValue::Loss(4)));
+
+        // A single ply of search sees as far as a full one with the tablebase below it
+        let full = PvSearch::with_depth(Evaluator, 9);
+        let mut probed = PvSearch::with_depth(Evaluator, 1);
+        probed.set_tablebase(Some(Arc::new(tablebase)));
+
+        let expected = value.evaluation::<Value>(board.get_ply_count());
+        for search in &mut [full, probed] {
+            let analysis = search.search(&board, None);
+            let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, Evaluator>>().unwrap();
+            assert_eq!(analysis.evaluation, expected);
+        }
+    }
+
+    #[test]
+    fn test_save_load() {
+        let tablebase = Tablebase::build(&Board::new(), 10_000).unwrap();
+
+        let mut bytes = Vec::new();
+        tablebase.save(&mut bytes).unwrap();
+        let loaded = Tablebase::<Board>::load(&bytes[..]).unwrap();
+        assert_eq!(loaded.len(), tablebase.len());
+        assert_eq!(loaded.values, tablebase.values);
+
+        bytes[0] = b'X';
+        assert!(Tablebase::<Board>::load(&bytes[..]).is_err());
+    }
+
+    #[cfg(feature = "with_tak")]
+    #[test]
+    fn test_tak() {
+        use impls::tak::{Color, Resolution, StateBuilder};
+
+        // With one flatstone left apiece, whoever places first ends the game on flats
+        let mut builder = StateBuilder::from_tps("x3/x,1,x/x,2,x 1 2").unwrap();
+        builder.set_reserves(Color::White, 1, 0);
+        builder.set_reserves(Color::Black, 1, 0);
+        let state = builder.build().unwrap();
+
+        let tablebase = Tablebase::build(&state, 10_000).unwrap();
+        assert_eq!(tablebase.get(&state), Some(TablebaseValue::Win(1)));
+
+        let mut next = state.clone();
+        next.execute_ply(Some(&tablebase.get_best_ply(&state).unwrap())).unwrap();
+        match next.check_resolution() {
+            Some(Resolution::Flat(Color::White)) => (),
+            resolution => panic!("Unexpected resolution: {:?}", resolution),
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+//! Property checks for game and evaluator implementations.
+//!
+//! These are meant to be called from the test suites of implementations, including those
+//! outside of this crate.  Each returns a description of the first violation it finds, so a
+//! test can simply `unwrap` the result:
+//!
+//! ```rust,ignore
+//! #[test]
+//! fn test_properties() {
+//!     let start = Board::new();
+//!     for seed in 0..100 {
+//!         test_utils::check_round_trip(&start, 20, seed).unwrap();
+//!         for state in test_utils::random_states(&start, 20, seed) {
+//!             test_utils::check_antisymmetry(&Evaluator, &state).unwrap();
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::hash::{Hash, Hasher};
+
+use fnv::FnvHasher;
+use rand::Rng;
+
+use analysis::{Evaluator, Extrapolatable};
+use state::State;
+use util::JKiss32Rng;
+
+/// Returns the states along a random line of up to `length` legal plies from `state`,
+/// beginning with `state` itself.  The line ends early if the game is resolved or there are no
+/// legal plies.  The same `seed` always produces the same line.
+pub fn random_states<S>(state: &S, length: usize, seed: u64) -> Vec<S> where
+    S: State + Extrapolatable<<S as State>::Ply> {
+    let mut rng = JKiss32Rng::with_seed(seed);
+    let mut states = vec![state.clone()];
+
+    for _ in 0..length {
+        let mut state = states.last().unwrap().clone();
+        if state.check_resolution().is_some() {
+            break;
+        }
+
+        let mut plies = state.extrapolate();
+        rng.shuffle(&mut plies);
+        if !plies.iter().any(|ply| state.execute_ply(Some(ply)).is_ok()) {
+            break;
+        }
+        states.push(state);
+    }
+
+    states
+}
+
+/// Checks that executing a random line of up to `length` plies from `state`, and then
+/// reverting them, passes back through the same states, as compared by both `Eq` and `Hash`.
+pub fn check_round_trip<S>(state: &S, length: usize, seed: u64) -> Result<(), String> where
+    S: State + Extrapolatable<<S as State>::Ply> {
+    let mut rng = JKiss32Rng::with_seed(seed);
+    let mut states = vec![state.clone()];
+    let mut plies = Vec::new();
+
+    let mut current = state.clone();
+    for _ in 0..length {
+        if current.check_resolution().is_some() {
+            break;
+        }
+
+        let mut candidates = current.extrapolate();
+        rng.shuffle(&mut candidates);
+        match candidates.into_iter().find(|ply| current.execute_ply(Some(ply)).is_ok()) {
+            Some(ply) => plies.push(ply),
+            None => break,
+        }
+        states.push(current.clone());
+    }
+
+    while let Some(ply) = plies.pop() {
+        if let Err(error) = current.revert_ply(Some(&ply)) {
+            return Err(format!("Error reverting {}: {}\n{}", ply, error, current));
+        }
+
+        let expected = &states[plies.len()];
+        if current != *expected {
+            return Err(format!("Reverting {} produced\n{}\ninstead of\n{}", ply, current, expected));
+        }
+        if hash(&current) != hash(expected) {
+            return Err(format!("Reverting {} produced a state with a different hash than before\n{}", ply, current));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that any states in `states` that are equal have equal hashes.
+pub fn check_hash_consistency<S>(states: &[S]) -> Result<(), String> where
+    S: State {
+    let hashes = states.iter().map(hash).collect::<Vec<_>>();
+
+    for (i, a) in states.iter().enumerate() {
+        for (j, b) in states.iter().enumerate().skip(i + 1) {
+            if a == b && hashes[i] != hashes[j] {
+                return Err(format!("Equal states have different hashes\n{}", a));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the evaluation of `state` is the negation of the evaluation for the other player,
+/// as seen after a null move, apart from the tempo that each receives for having the move.  That
+/// is, `evaluate(state) + evaluate(null move) == 2 * tempo`.  Resolved states, and states that
+/// don't allow a null move to be executed, pass trivially.
+pub fn check_antisymmetry<E>(evaluator: &E, state: &<E as Evaluator>::State) -> Result<(), String> where
+    E: Evaluator {
+    if state.check_resolution().is_some() {
+        return Ok(());
+    }
+
+    let mut passed = state.clone();
+    if passed.execute_ply(None).is_err() {
+        return Ok(());
+    }
+
+    let evaluation = evaluator.evaluate(state);
+    let passed_evaluation = evaluator.evaluate(&passed);
+    let tempo = evaluator.tempo();
+
+    if evaluation + passed_evaluation != tempo + tempo {
+        return Err(format!(
+            "The evaluation {} becomes {} for the other player, expected {}\n{}",
+            evaluation, passed_evaluation, tempo + tempo - evaluation, state,
+        ));
+    }
+
+    Ok(())
+}
+
+fn hash<S>(state: &S) -> u64 where
+    S: Hash {
+    let mut hasher = FnvHasher::default();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(all(test, any(feature = "with_connect_four", feature = "with_tak", feature = "with_tic_tac_toe")))]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "with_connect_four")]
+    #[test]
+    fn test_connect_four() {
+        use impls::connect_four::{Board, Evaluator};
+
+        for seed in 0..20 {
+            check_round_trip(&Board::new(), 42, seed).unwrap();
+
+            let states = random_states(&Board::new(), 42, seed);
+            check_hash_consistency(&states).unwrap();
+            for state in &states {
+                check_antisymmetry(&Evaluator, state).unwrap();
+            }
+        }
+    }
+
+    #[cfg(feature = "with_tak")]
+    #[test]
+    fn test_tak() {
+        use impls::tak::{evaluator, State};
+
+        let evaluator = evaluator::StaticEvaluator::new();
+        for seed in 0..20 {
+            check_round_trip(&State::new(5), 60, seed).unwrap();
+
+            let states = random_states(&State::new(5), 60, seed);
+            check_hash_consistency(&states).unwrap();
+            for state in &states {
+                check_antisymmetry(&evaluator, state).unwrap();
+            }
+        }
+    }
+
+    #[cfg(feature = "with_tic_tac_toe")]
+    #[test]
+    fn test_tic_tac_toe() {
+        use impls::tic_tac_toe::{Board, Evaluator};
+
+        for seed in 0..20 {
+            check_round_trip(&Board::new(), 9, seed).unwrap();
+
+            let states = random_states(&Board::new(), 9, seed);
+            check_hash_consistency(&states).unwrap();
+            for state in &states {
+                check_antisymmetry(&Evaluator, state).unwrap();
+            }
+        }
+    }
+
+    #[cfg(feature = "with_connect_four")]
+    #[test]
+    fn test_violations() {
+        use analysis::Evaluator as EvaluatorTrait;
+        use impls::connect_four::{Board, Evaluation};
+
+        // Favors whoever is to move late in the game, regardless of the board
+        struct Biased;
+
+        impl EvaluatorTrait for Biased {
+            type State = Board;
+            type Evaluation = Evaluation;
+
+            fn evaluate(&self, board: &Board) -> Evaluation {
+                Evaluation(board.get_ply_count() as i16)
+            }
+        }
+
+        let states = random_states(&Board::new(), 3, 0);
+        assert_eq!(states.len(), 4);
+        assert!(check_antisymmetry(&Biased, &states[0]).is_err());
+        assert!(check_hash_consistency(&[states[1].clone(), states[1].clone()]).is_ok());
+    }
+}
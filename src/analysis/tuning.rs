@@ -0,0 +1,517 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+//! Tools for tuning the parameters of an evaluator.
+//!
+//! Two methods are provided:
+//!
+//! * `TexelTuner` fits an evaluator to a corpus of positions labeled with their outcomes
+//!   (or scores from a deeper search) by minimizing the error of a logistic mapping from
+//!   evaluation to expected result.
+//! * `Spsa` performs simultaneous perturbation stochastic approximation, where the quality
+//!   of a pair of parameter vectors is measured by some noisy comparison, usually the
+//!   result of games played between them (see `play_match`).
+//!
+//! Static evaluations of tactical positions are poor labels, so `is_quiet` and `retain_quiet` are
+//! provided to filter them out of training data.
+
+use std::sync::Arc;
+
+use rand::Rng;
+
+use analysis::{Evaluation, Evaluator, Experience, Extrapolatable, RepetitionTracker};
+use analysis::search::{PvSearch, PvSearchAnalysis, Search};
+use resolution::Resolution;
+use state::State;
+use util::JKiss32Rng;
+
+/// An evaluator whose behavior is controlled by a vector of numeric parameters.
+pub trait Tunable: Evaluator {
+    /// Returns the current parameters of the evaluator.
+    fn parameters(&self) -> Vec<f64>;
+
+    /// Sets the parameters of the evaluator.  `parameters` will have the same length as the
+    /// vector returned by `parameters`.
+    fn set_parameters(&mut self, parameters: &[f64]);
+
+    /// Returns `evaluation` as a plain number, on the same scale as the parameters.
+    fn evaluation_value(&self, evaluation: Self::Evaluation) -> f64;
+}
+
+/// A state and its expected result for the player to move: 1.0 for a win, 0.5 for a draw,
+/// and 0.0 for a loss.  Fractional labels, i.e. from a search score, are also allowed.
+#[derive(Clone, Debug)]
+pub struct LabeledPosition<S> where
+    S: State {
+    pub state: S,
+    pub label: f64,
+}
+
+impl<S> LabeledPosition<S> where
+    S: State {
+    /// Labels `state` with the eventual resolution of the game it was taken from.
+    pub fn from_resolution(state: S, resolution: &<S as State>::Resolution) -> LabeledPosition<S> {
+        let label = match resolution.get_winner() {
+            Some(winner) => if winner as usize == state.get_ply_count() % 2 {
+                1.0
+            } else {
+                0.0
+            },
+            None => 0.5,
+        };
+
+        LabeledPosition {
+            state: state,
+            label: label,
+        }
+    }
+}
+
+/// Returns true if a shallow search of `state` to `depth` neither finds a forced result nor
+/// moves the evaluation more than `margin` units of granularity away from the static
+/// evaluation.  A position that isn't quiet has tactics pending, and its static evaluation
+/// is unlikely to reflect its value.
+pub fn is_quiet<S, E>(state: &S, evaluator: &E, depth: u8, margin: u32) -> bool where
+    S: 'static + State + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S> + Clone {
+    if state.check_resolution().is_some() {
+        return false;
+    }
+
+    let static_evaluation = evaluator.evaluate(state);
+
+    let mut search = PvSearch::with_depth(evaluator.clone(), depth);
+    let analysis = search.search(state, None);
+    let searched = analysis.as_any().downcast_ref::<PvSearchAnalysis<S, E>>().unwrap().evaluation;
+
+    let margin = <E as Evaluator>::Evaluation::granularity_multiple(margin);
+    !searched.is_end() && searched - static_evaluation <= margin && static_evaluation - searched <= margin
+}
+
+/// Removes the positions that aren't quiet according to `is_quiet`, as judged by `evaluator`.
+pub fn retain_quiet<S, E>(positions: &mut Vec<LabeledPosition<S>>, evaluator: &E, depth: u8, margin: u32) where
+    S: 'static + State + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S> + Clone {
+    positions.retain(|position| is_quiet(&position.state, evaluator, depth, margin));
+}
+
+/// Texel-style tuning: local search over the parameters that minimizes the mean squared
+/// error between each position's label and `sigmoid(scale * evaluation)`.
+pub struct TexelTuner {
+    /// The scale of the logistic mapping.  If this is `None`, the scale is fitted to the
+    /// initial parameters before tuning begins.
+    pub scale: Option<f64>,
+    /// The amount by which each parameter is nudged, relative to its magnitude.  Parameters
+    /// are always nudged by at least 1.
+    pub step: f64,
+    /// The maximum number of passes over the parameters.
+    pub max_iterations: usize,
+}
+
+impl TexelTuner {
+    pub fn new() -> TexelTuner {
+        TexelTuner {
+            scale: None,
+            step: 0.05,
+            max_iterations: 100,
+        }
+    }
+
+    /// Returns the mean squared error of `evaluator` over `positions`.
+    pub fn error<E>(&self, evaluator: &E, positions: &[LabeledPosition<<E as Evaluator>::State>], scale: f64) -> f64 where
+        E: Tunable {
+        if positions.is_empty() {
+            return 0.0;
+        }
+
+        positions.iter().map(|position| {
+            let value = evaluator.evaluation_value(evaluator.evaluate(&position.state));
+            let error = position.label - sigmoid(scale * value);
+            error * error
+        }).sum::<f64>() / positions.len() as f64
+    }
+
+    /// Finds the scale that minimizes the error of `evaluator` over `positions`.
+    pub fn fit_scale<E>(&self, evaluator: &E, positions: &[LabeledPosition<<E as Evaluator>::State>]) -> f64 where
+        E: Tunable {
+        // Golden-section search over the logarithm of the scale
+        let ratio = (5.0f64.sqrt() - 1.0) / 2.0;
+        let error = |log_scale: f64| self.error(evaluator, positions, log_scale.exp());
+
+        let (mut low, mut high) = (-20.0f64, 5.0f64);
+        let mut a = high - ratio * (high - low);
+        let mut b = low + ratio * (high - low);
+        let (mut error_a, mut error_b) = (error(a), error(b));
+
+        while high - low > 1e-3 {
+            if error_a < error_b {
+                high = b;
+                b = a;
+                error_b = error_a;
+                a = high - ratio * (high - low);
+                error_a = error(a);
+            } else {
+                low = a;
+                a = b;
+                error_a = error_b;
+                b = low + ratio * (high - low);
+                error_b = error(b);
+            }
+        }
+
+        ((low + high) / 2.0).exp()
+    }
+
+    /// Tunes `evaluator` against `positions`, leaving it with the best parameters found.
+    /// Returns the final error.
+    pub fn tune<E>(&self, evaluator: &mut E, positions: &[LabeledPosition<<E as Evaluator>::State>]) -> f64 where
+        E: Tunable {
+        let scale = match self.scale {
+            Some(scale) => scale,
+            None => self.fit_scale(evaluator, positions),
+        };
+
+        let mut parameters = evaluator.parameters();
+        let mut best_error = self.error(evaluator, positions, scale);
+
+        for _ in 0..self.max_iterations {
+            let mut improved = false;
+
+            for i in 0..parameters.len() {
+                let step = (parameters[i].abs() * self.step).max(1.0);
+                let original = parameters[i];
+
+                for &direction in &[1.0, -1.0] {
+                    parameters[i] = original + direction * step;
+                    evaluator.set_parameters(&parameters);
+
+                    let error = self.error(evaluator, positions, scale);
+                    if error < best_error {
+                        best_error = error;
+                        improved = true;
+                        break;
+                    }
+
+                    parameters[i] = original;
+                }
+
+                evaluator.set_parameters(&parameters);
+            }
+
+            if !improved {
+                break;
+            }
+        }
+
+        best_error
+    }
+}
+
+/// Simultaneous perturbation stochastic approximation.
+///
+/// Each iteration perturbs every parameter at once in a random direction, compares the
+/// positively and negatively perturbed vectors, and moves the parameters toward the better
+/// of the two.  The gain sequences follow Spall's recommendations:
+/// `a_k = a / (k + 1 + big_a)^alpha` and `c_k = c / (k + 1)^gamma`.
+pub struct Spsa {
+    pub a: f64,
+    pub big_a: f64,
+    pub alpha: f64,
+    pub c: f64,
+    pub gamma: f64,
+    pub iterations: usize,
+    /// Seeds the perturbations, for reproducible runs.
+    pub seed: Option<u64>,
+}
+
+impl Spsa {
+    /// Creates an SPSA tuner with standard exponents, where `c` is the size of the
+    /// perturbations and `a` controls the size of the steps taken.
+    pub fn new(a: f64, c: f64, iterations: usize) -> Spsa {
+        Spsa {
+            a: a,
+            big_a: iterations as f64 / 10.0,
+            alpha: 0.602,
+            c: c,
+            gamma: 0.101,
+            iterations: iterations,
+            seed: None,
+        }
+    }
+
+    /// Tunes `parameters`.  `compare` is given the positively and negatively perturbed
+    /// parameters, and should return a score in `[-1, 1]`, positive if the first is
+    /// stronger; for instance, the result of `play_match`.
+    pub fn tune<F>(&self, parameters: &[f64], mut compare: F) -> Vec<f64> where
+        F: FnMut(&[f64], &[f64]) -> f64 {
+        let mut rng = if let Some(seed) = self.seed {
+            JKiss32Rng::with_seed(seed)
+        } else {
+            JKiss32Rng::new()
+        };
+        let mut theta = parameters.to_vec();
+
+        for k in 0..self.iterations {
+            let a_k = self.a / (k as f64 + 1.0 + self.big_a).powf(self.alpha);
+            let c_k = self.c / (k as f64 + 1.0).powf(self.gamma);
+
+            let delta = (0..theta.len()).map(|_| if rng.gen() { 1.0 } else { -1.0 }).collect::<Vec<f64>>();
+
+            let plus = theta.iter().zip(&delta).map(|(t, d)| t + c_k * d).collect::<Vec<_>>();
+            let minus = theta.iter().zip(&delta).map(|(t, d)| t - c_k * d).collect::<Vec<_>>();
+
+            let result = compare(&plus, &minus);
+
+            for (t, d) in theta.iter_mut().zip(&delta) {
+                *t += a_k * result / (2.0 * c_k * d);
+            }
+        }
+
+        theta
+    }
+}
+
+/// Plays a pair of games from each of `openings`, with each evaluator taking each side
+/// once, searching to `depth`.  Games that last longer than `max_plies`, that reach a dead
+/// draw, or that reach the same state for the third time are scored as draws.
+/// Because each opening is played from both sides, uneven openings, like games with a
+/// handicap, can be used to compare how well each evaluator handles the imbalance.
+///
+/// Returns the average score of `first` in `[-1, 1]`.
+pub fn play_match<S, E>(openings: &[S], first: E, second: E, depth: u8, max_plies: usize) -> f64 where
+    S: 'static + State + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S> + Clone {
+    play_games(openings, first, second, depth, max_plies, None)
+}
+
+/// Plays a match as with `play_match`, in which `first` consults `experience` and learns from
+/// each game, as with `Experience::learn`, recording the root states whose evaluations fell
+/// by more than `margin`.  Each game consults the experience learned before it began.
+///
+/// Returns the average score of `first` in `[-1, 1]`.
+pub fn play_match_with_experience<S, E>(
+    openings: &[S],
+    first: E,
+    second: E,
+    depth: u8,
+    max_plies: usize,
+    experience: &mut Experience<S, <E as Evaluator>::Evaluation>,
+    margin: <E as Evaluator>::Evaluation,
+) -> f64 where
+    S: 'static + State + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S> + Clone {
+    play_games(openings, first, second, depth, max_plies, Some((experience, margin)))
+}
+
+fn play_games<S, E>(
+    openings: &[S],
+    first: E,
+    second: E,
+    depth: u8,
+    max_plies: usize,
+    mut experience: Option<(&mut Experience<S, <E as Evaluator>::Evaluation>, <E as Evaluator>::Evaluation)>,
+) -> f64 where
+    S: 'static + State + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S> + Clone {
+    if openings.is_empty() {
+        return 0.0;
+    }
+
+    let mut total = 0.0;
+
+    for opening in openings {
+        for first_side in 0..2 {
+            let mut searches = [
+                PvSearch::with_depth(first.clone(), depth),
+                PvSearch::with_depth(second.clone(), depth),
+            ];
+            if let Some((ref experience, _)) = experience {
+                searches[0].set_experience(Some(Arc::new((*experience).clone())));
+            }
+            let mut roots = Vec::new();
+
+            // The first evaluator plays the player to move in the opening in the first game,
+            // and the other player in the second
+            let first_player = (opening.get_ply_count() + first_side) % 2;
+
+            let mut state = opening.clone();
+            let mut plies = 0;
+            let mut repetitions = RepetitionTracker::new(3);
+            repetitions.push(&state);
+
+            let winner = loop {
+                if let Some(resolution) = state.check_resolution() {
+                    break resolution.get_winner();
+                }
+
+                if plies >= max_plies || state.is_dead_draw() || repetitions.is_draw() {
+                    break None;
+                }
+
+                let mover = if state.get_ply_count() % 2 == first_player { 0 } else { 1 };
+                let ply = {
+                    let analysis = searches[mover].search(&state, None);
+                    let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<S, E>>().unwrap();
+                    if mover == 0 {
+                        roots.push((state.clone(), analysis.evaluation));
+                    }
+                    match analysis.principal_variation.first() {
+                        Some(ply) => ply.clone(),
+                        None => break None,
+                    }
+                };
+
+                if state.execute_ply(Some(&ply)).is_err() {
+                    break None;
+                }
+                repetitions.push(&state);
+                plies += 1;
+            };
+
+            if let Some((ref mut experience, margin)) = experience {
+                experience.learn(&roots, winner, state.get_ply_count(), depth, margin);
+            }
+
+            if let Some(winner) = winner {
+                total += if winner as usize == first_player { 1.0 } else { -1.0 };
+            }
+        }
+    }
+
+    total / (openings.len() * 2) as f64
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+#[cfg(all(test, feature = "with_connect_four"))]
+mod test {
+    use analysis::Evaluator;
+    use impls::connect_four::{self, Board, Evaluation};
+    use super::*;
+
+    /// Weighs the player to move's advantage in the center column, deferring to the connect four
+    /// evaluator for finished games.
+    #[derive(Clone)]
+    struct CenterEvaluator {
+        weight: f64,
+    }
+
+    fn center_advantage(state: &Board) -> i16 {
+        let mover = state.next_disc();
+        (0..6).filter_map(|row| state.get(3, row)).map(|disc| if disc == mover { 1 } else { -1 }).sum()
+    }
+
+    impl Evaluator for CenterEvaluator {
+        type State = Board;
+        type Evaluation = Evaluation;
+
+        fn evaluate(&self, state: &Board) -> Evaluation {
+            if state.check_resolution().is_some() {
+                return connect_four::Evaluator.evaluate(state);
+            }
+            Evaluation(self.weight as i16 * center_advantage(state))
+        }
+    }
+
+    impl Tunable for CenterEvaluator {
+        fn parameters(&self) -> Vec<f64> {
+            vec![self.weight]
+        }
+
+        fn set_parameters(&mut self, parameters: &[f64]) {
+            self.weight = parameters[0].round();
+        }
+
+        fn evaluation_value(&self, evaluation: Evaluation) -> f64 {
+            evaluation.0 as f64
+        }
+    }
+
+    #[test]
+    fn test_texel() {
+        // Labeled as if by an evaluator with a weight of 10
+        let positions = [&[4][..], &[4, 1], &[4, 4, 4], &[4, 1, 4, 1], &[1, 4, 1, 4], &[4, 4, 4, 1, 4]].iter().map(|columns| {
+            let state = Board::from_columns(columns).unwrap();
+            let label = sigmoid(0.1 * 10.0 * center_advantage(&state) as f64);
+            LabeledPosition {
+                state: state,
+                label: label,
+            }
+        }).collect::<Vec<_>>();
+
+        let mut tuner = TexelTuner::new();
+        tuner.scale = Some(0.1);
+        let mut evaluator = CenterEvaluator { weight: 1.0 };
+        let error = tuner.tune(&mut evaluator, &positions);
+        assert_eq!(evaluator.weight, 10.0);
+        assert!(error < 1e-12, "{}", error);
+
+        // The scale is fitted from the evaluator's own weight
+        assert!((tuner.fit_scale(&CenterEvaluator { weight: 20.0 }, &positions) - 0.05).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_spsa() {
+        // The parameters closer to the target win
+        let target = [3.0, -2.0];
+        let distance = |parameters: &[f64]| parameters.iter().zip(&target).map(|(p, t)| (p - t) * (p - t)).sum::<f64>();
+        let compare = |plus: &[f64], minus: &[f64]| (distance(minus) - distance(plus)).max(-1.0).min(1.0);
+
+        let mut spsa = Spsa::new(1.0, 0.5, 500);
+        spsa.seed = Some(0);
+        let tuned = spsa.tune(&[0.0, 0.0], compare);
+        assert!(distance(&tuned) < 0.1, "{:?}", tuned);
+        assert_eq!(spsa.tune(&[0.0, 0.0], compare), tuned);
+    }
+
+    #[test]
+    fn test_play_match() {
+        // Red wins the second opening immediately, whichever evaluator plays it, and the first is
+        // too short for anyone to win, so an evaluator playing itself scores even
+        let evaluator = CenterEvaluator { weight: 1.0 };
+        let openings = [Board::new(), Board::from_columns(&[4, 1, 4, 1, 4, 2]).unwrap()];
+        assert_eq!(play_match(&openings, evaluator.clone(), evaluator.clone(), 2, 4), 0.0);
+        assert_eq!(play_match(&openings[1..], evaluator.clone(), evaluator.clone(), 2, 4), 0.0);
+        assert_eq!(play_match(&[], evaluator.clone(), evaluator, 2, 4), 0.0);
+    }
+
+    #[test]
+    fn test_is_quiet() {
+        let evaluator = CenterEvaluator { weight: 1.0 };
+
+        // Red can complete column 4, which the static evaluation can't see
+        let tactical = Board::from_columns(&[4, 1, 4, 1, 4, 2]).unwrap();
+        let quiet = Board::from_columns(&[4, 4]).unwrap();
+        assert!(!is_quiet(&tactical, &evaluator, 2, 1));
+        assert!(is_quiet(&quiet, &evaluator, 2, 1));
+
+        // Neither is a finished game
+        assert!(!is_quiet(&Board::from_columns(&[4, 1, 4, 1, 4, 1, 4]).unwrap(), &evaluator, 2, 1));
+
+        let mut positions = vec![
+            LabeledPosition { state: tactical, label: 1.0 },
+            LabeledPosition { state: quiet.clone(), label: 0.5 },
+        ];
+        retain_quiet(&mut positions, &evaluator, 2, 1);
+        assert_eq!(positions.len(), 1);
+        assert!(positions[0].state == quiet);
+    }
+}
@@ -25,11 +25,12 @@ use std::io::{BufRead, BufReader, Write};
 use std::str::FromStr;
 
 use zero_sum::analysis::Evaluator;
-use zero_sum::impls::tak::evaluator::AnnEvaluator;
+use zero_sum::impls::tak::evaluator::{AnnConfig, AnnEvaluator};
 use zero_sum::impls::tak::State;
 
 fn main() {
     let network_file = String::from("evaluator_bootstrap");
+    let config_file = String::from("evaluator_config");
     let positions_file = String::from("bootstrap_positions");
     let labels_file = String::from("bootstrap_labels");
     let progress_file = String::from("progress_bootstrap");
@@ -37,10 +38,17 @@ fn main() {
     let serialize_interval = 1000;
     let progress_interval = 1000;
 
-    let mut evaluator = if let Ok(evaluator) = AnnEvaluator::from_file(&network_file) {
+    // Use the network topology from the config file, if there is one
+    let config = if let Ok(file) = OpenOptions::new().read(true).open(&config_file) {
+        AnnConfig::read(&mut BufReader::new(file)).unwrap()
+    } else {
+        AnnConfig::default()
+    };
+
+    let mut evaluator = if let Ok(evaluator) = AnnEvaluator::from_file_with_config(&network_file, &config) {
         evaluator
     } else {
-        AnnEvaluator::new()
+        AnnEvaluator::with_config(&config).unwrap()
     };
 
     println!("Reading positions...");
@@ -96,7 +104,10 @@ fn main() {
         }
 
         if iteration % serialize_interval == 0 {
-            evaluator.to_file(&format!("{}_{:06}", &network_file, iteration));
+            let filename = format!("{}_{:06}", &network_file, iteration);
+            if let Err(error) = evaluator.to_binary_file(&filename) {
+                println!("Cannot write file: {}: {}", filename, error);
+            }
         }
     }
 
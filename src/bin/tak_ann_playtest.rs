@@ -20,13 +20,13 @@
 extern crate zero_sum;
 
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::io::{BufReader, Write};
 use std::sync::{Arc, mpsc, Mutex};
 use std::thread;
 
 use zero_sum::analysis::Extrapolatable;
 use zero_sum::analysis::search::{PvSearch, PvSearchAnalysis, Search};
-use zero_sum::impls::tak::evaluator::{AnnEvaluator, StaticEvaluator};
+use zero_sum::impls::tak::evaluator::{AnnConfig, AnnEvaluator, StaticEvaluator};
 use zero_sum::impls::tak::{Piece, Ply, Resolution, State};
 use zero_sum::Resolution as ResolutionTrait;
 use zero_sum::State as StateTrait;
@@ -35,19 +35,28 @@ fn main() {
     let start_iteration = 10;
     let interval = 50;
 
+    let config_file = String::from("evaluator_config");
+
+    // Use the network topology from the config file, if there is one
+    let config = if let Ok(file) = OpenOptions::new().read(true).open(&config_file) {
+        AnnConfig::read(&mut BufReader::new(file)).unwrap()
+    } else {
+        AnnConfig::default()
+    };
+
     for iteration in (0..).map(|x| start_iteration + x * interval) {
         let network_file = format!("evaluator_{:06}", iteration);
         let games = 200;
         let threads = 4;
         let search_depth = 3;
 
-        let ann_evaluator = if let Ok(evaluator) = AnnEvaluator::from_file(&network_file) {
+        let ann_evaluator = if let Ok(evaluator) = AnnEvaluator::from_file_with_config(&network_file, &config) {
             evaluator
         } else {
             panic!("Cannot read network file: {}", network_file);
         };
 
-        let static_evaluator = StaticEvaluator;
+        let static_evaluator = StaticEvaluator::new();
 
         let ann_wins = Arc::new(Mutex::new(0));
         let static_wins = Arc::new(Mutex::new(0));
@@ -24,7 +24,7 @@ use std::fs::OpenOptions;
 use std::io::{BufRead, BufReader, Write};
 
 use zero_sum::analysis::Evaluator;
-use zero_sum::impls::tak::evaluator::{AnnEvaluator, StaticEvaluator};
+use zero_sum::impls::tak::evaluator::{AnnConfig, AnnEvaluator, StaticEvaluator};
 use zero_sum::impls::tak::State;
 
 fn main() {
@@ -43,6 +43,7 @@ fn main() {
     };
 
     let output_file = String::from("sample");
+    let config_file = String::from("evaluator_config");
 
     println!("Reading positions...");
     let positions = if let Ok(file) = OpenOptions::new().read(true).open(&positions_file) {
@@ -54,7 +55,14 @@ fn main() {
     println!("  Done. Read {} positions.", positions.len());
 
     if let Some(network_file) = network_file {
-        let evaluator = if let Ok(evaluator) = AnnEvaluator::from_file(&format!("{}", &network_file)) {
+        // Use the network topology from the config file, if there is one
+        let config = if let Ok(file) = OpenOptions::new().read(true).open(&config_file) {
+            AnnConfig::read(&mut BufReader::new(file)).unwrap()
+        } else {
+            AnnConfig::default()
+        };
+
+        let evaluator = if let Ok(evaluator) = AnnEvaluator::from_file_with_config(&format!("{}", &network_file), &config) {
             evaluator
         } else {
             panic!("Cannot read file: {}", network_file);
@@ -69,7 +77,7 @@ fn main() {
             println!("Cannot write file: {}", output_file);
         }
     } else {
-        let evaluator = StaticEvaluator;
+        let evaluator = StaticEvaluator::new();
 
         println!("Writing evaluations...");
         if let Ok(mut file) = OpenOptions::new().write(true).truncate(true).create(true).open(&output_file) {
@@ -24,11 +24,12 @@ use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
 use std::time::Instant;
 
-use zero_sum::impls::tak::evaluator::AnnEvaluator;
+use zero_sum::impls::tak::evaluator::{AnnConfig, AnnEvaluator};
 use zero_sum::impls::tak::State;
 
 fn main() {
     let network_prefix = String::from("evaluator");
+    let config_file = String::from("evaluator_config");
     let positions_file = String::from("training_positions");
     let progress_file = String::from("progress");
     let batch_size = 100;
@@ -37,6 +38,13 @@ fn main() {
     let resume_iteration = None;
     let threads = 4;
 
+    // Use the network topology from the config file, if there is one
+    let config = if let Ok(file) = OpenOptions::new().read(true).open(&config_file) {
+        AnnConfig::read(&mut BufReader::new(file)).unwrap()
+    } else {
+        AnnConfig::default()
+    };
+
     println!("Reading positions...");
     let mut positions = if let Ok(file) = OpenOptions::new().read(true).open(&positions_file) {
         BufReader::new(file)
@@ -51,7 +59,7 @@ fn main() {
         let mut evaluator = None;
 
         if let Some(resume_iteration) = resume_iteration {
-            if let Ok(read) = AnnEvaluator::from_file(&format!("{}_{:06}", &network_prefix, resume_iteration)) {
+            if let Ok(read) = AnnEvaluator::from_file_with_config(&format!("{}_{:06}", &network_prefix, resume_iteration), &config) {
                 resume = resume_iteration;
                 evaluator = Some(read);
             }
@@ -60,7 +68,7 @@ fn main() {
                 if Path::new(&format!("{}_{:06}", &network_prefix, iteration)).exists() {
                     resume = iteration;
                 } else {
-                    if let Ok(read) = AnnEvaluator::from_file(&format!("{}_{:06}", &network_prefix, resume)) {
+                    if let Ok(read) = AnnEvaluator::from_file_with_config(&format!("{}_{:06}", &network_prefix, resume), &config) {
                         evaluator = Some(read);
                     }
                     break;
@@ -71,13 +79,13 @@ fn main() {
         if let Some(evaluator) = evaluator {
             (resume + 1, evaluator)
         } else {
-            (1, AnnEvaluator::new())
+            (1, AnnEvaluator::with_config(&config).unwrap())
         }
     };
 
     // If we're starting at the beginning, look for a bootstrapped network file
     if start_iteration == 1 {
-        if let Ok(read) = AnnEvaluator::from_file(&format!("{}_bootstrap", &network_prefix)) {
+        if let Ok(read) = AnnEvaluator::from_file_with_config(&format!("{}_bootstrap", &network_prefix), &config) {
             println!("  Done. No resume network file found. Found bootstrap network file.");
             evaluator = read;
         } else {
@@ -126,7 +134,10 @@ fn main() {
         println!("Iteration {}: Error: {:.6}, Time: {:.2}s/position", iteration, error, elapsed_batch / batch.len() as f32);
 
         if iteration % serialize_interval == 0 {
-            evaluator.to_file(&format!("{}_{:06}", &network_prefix, iteration));
+            let filename = format!("{}_{:06}", &network_prefix, iteration);
+            if let Err(error) = evaluator.to_binary_file(&filename) {
+                println!("Cannot write file: {}: {}", filename, error);
+            }
         }
 
         if iteration % progress_interval == 0 {
@@ -31,6 +31,7 @@ use rand::{Rng, thread_rng};
 
 use zero_sum::State as StateTrait;
 use zero_sum::analysis::{Evaluation, Evaluator, Extrapolatable};
+use zero_sum::analysis::tuning::is_quiet;
 use zero_sum::impls::tak::{Color, Direction, Piece, Ply, State};
 use zero_sum::impls::tak::evaluator::{AnnEvaluator, StaticEvaluator};
 
@@ -56,6 +57,7 @@ fn main() {
     let maximum_positions = 500000;
     let label = false;
     let normalize_range = true;
+    let quiet_search = Some((2, 25)); // Depth and margin of the quiet check, or None to keep every position
     let file_prefix = String::from("training");
 
     let connection = rusqlite::Connection::open("games_anon.db").unwrap();
@@ -110,6 +112,13 @@ fn main() {
     }
     println!("  Done. Generated {} training positions.", states.len());
 
+    if let Some((depth, margin)) = quiet_search {
+        println!("Removing positions that aren't quiet...");
+        let evaluator = StaticEvaluator::new();
+        states.retain(|state| is_quiet(state, &evaluator, depth, margin));
+        println!("  Done. Kept {} training positions.", states.len());
+    }
+
     println!("Writing to file...");
     if let Ok(mut file) = OpenOptions::new().write(true).truncate(true).create(true).open(format!("{}_positions", file_prefix)) {
         for state in &states {
@@ -122,7 +131,7 @@ fn main() {
 
     if label {
         println!("Labeling training positions...");
-        let evaluator = StaticEvaluator;
+        let evaluator = StaticEvaluator::new();
         let mut labels = states.iter().map(|s| <AnnEvaluator as Evaluator>::Evaluation::new(
             evaluator.evaluate(s).0 as f32 / <StaticEvaluator as Evaluator>::Evaluation::win().0 as f32
         )).collect::<Vec<_>>();
@@ -0,0 +1,247 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+//! A game loop for playing against the engine on the terminal.
+//!
+//! A `Game` shows the state, reads plies from the players that the engine doesn't control,
+//! and answers with a search for the ones it does.  Besides plies, a person can enter:
+//!
+//! * `hint` to see what the engine would play in their place.
+//! * `undo` to take back their last ply, along with the engine's reply to it.
+//! * `help` to list the commands and the notation that plies are entered in.
+//! * `quit` to end the game.
+//!
+//! States are made playable by implementing `Interactive`, which the provided games do when
+//! this feature is enabled alongside them.
+
+use std::io::{self, BufRead, Write};
+
+use analysis::{Evaluator, Extrapolatable};
+use analysis::search::{PvSearch, PvSearchAnalysis, Search};
+use state::State;
+
+/// A state that can be played on the terminal.
+pub trait Interactive: State {
+    /// Parses a ply for the player to move, written in the notation a person would enter.
+    fn parse_ply(&self, input: &str) -> Result<Self::Ply, String>;
+
+    /// Writes `ply`, made from this state, in the notation accepted by `parse_ply`.
+    fn format_ply(&self, ply: &Self::Ply) -> String;
+
+    /// A short description of the notation accepted by `parse_ply`.
+    fn notation() -> &'static str;
+
+    /// The name shown for `player`, where player 0 moves first.
+    fn player_name(player: u8) -> String {
+        format!("Player {}", player + 1)
+    }
+}
+
+/// A game in progress between people, the engine, or both.
+pub struct Game<S, E> where
+    S: 'static + Interactive + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S> {
+    state: S,
+    history: Vec<<S as State>::Ply>,
+    search: PvSearch<S, E>,
+    engine: [bool; 2],
+}
+
+impl<S, E> Game<S, E> where
+    S: 'static + Interactive + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S> {
+    /// Creates a game starting from `state`.  The engine plays `engine_player`, if any,
+    /// choosing its plies with `search`, which also provides hints.
+    pub fn new(state: S, search: PvSearch<S, E>, engine_player: Option<u8>) -> Game<S, E> {
+        let mut game = Game {
+            state: state,
+            history: Vec::new(),
+            search: search,
+            engine: [false; 2],
+        };
+        if let Some(player) = engine_player {
+            game.set_engine_player(player, true);
+        }
+        game
+    }
+
+    /// Sets whether the engine plays for `player`.  The engine may play both sides.
+    pub fn set_engine_player(&mut self, player: u8, engine: bool) {
+        self.engine[player as usize % 2] = engine;
+    }
+
+    /// Returns the current state of the game.
+    pub fn get_state(&self) -> &S {
+        &self.state
+    }
+
+    /// Returns the plies made since the game began.
+    pub fn get_history(&self) -> &[<S as State>::Ply] {
+        &self.history
+    }
+
+    /// Plays on standard input and output.  See `play_with`.
+    pub fn play(&mut self) -> io::Result<Option<<S as State>::Resolution>> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        self.play_with(stdin.lock(), stdout.lock())
+    }
+
+    /// Plays until the game is resolved, a player quits, or `input` runs out.  Returns the
+    /// resolution, if the game reached one.
+    pub fn play_with<R, W>(&mut self, mut input: R, mut output: W) -> io::Result<Option<<S as State>::Resolution>> where
+        R: BufRead,
+        W: Write {
+        'game: loop {
+            writeln!(output, "{}\n", self.state)?;
+
+            if let Some(resolution) = self.state.check_resolution() {
+                writeln!(output, "{}", resolution)?;
+                return Ok(Some(resolution));
+            }
+
+            let player = self.get_player();
+
+            if self.engine[player as usize] {
+                let (ply, evaluation) = self.analyze()?;
+                writeln!(output, "{} plays {} (evaluation {})", S::player_name(player), self.state.format_ply(&ply), evaluation)?;
+                if let Err(error) = self.make_ply(ply) {
+                    return Err(io::Error::new(io::ErrorKind::Other, error));
+                }
+                continue;
+            }
+
+            loop {
+                write!(output, "{}> ", S::player_name(player))?;
+                output.flush()?;
+
+                let mut line = String::new();
+                if input.read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+
+                match line.trim() {
+                    "" => (),
+                    "quit" => return Ok(None),
+                    "help" => writeln!(output, "Enter a ply ({}), or one of: hint, undo, help, quit", S::notation())?,
+                    "hint" => {
+                        let (ply, evaluation) = self.analyze()?;
+                        writeln!(output, "Hint: {} (evaluation {})", self.state.format_ply(&ply), evaluation)?;
+                    },
+                    "undo" => if self.undo()? {
+                        continue 'game;
+                    } else {
+                        writeln!(output, "Nothing to undo")?;
+                    },
+                    input => match self.state.parse_ply(input) {
+                        Ok(ply) => match self.make_ply(ply) {
+                            Ok(()) => continue 'game,
+                            Err(error) => writeln!(output, "Illegal ply: {}", error)?,
+                        },
+                        Err(error) => writeln!(output, "Invalid ply: {}", error)?,
+                    },
+                }
+            }
+        }
+    }
+
+    fn get_player(&self) -> u8 {
+        (self.state.get_ply_count() % 2) as u8
+    }
+
+    /// Searches the current state, returning the best ply and its evaluation.
+    fn analyze(&mut self) -> io::Result<(<S as State>::Ply, <E as Evaluator>::Evaluation)> {
+        let analysis = self.search.search(&self.state, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<S, E>>().unwrap();
+        match analysis.principal_variation.first() {
+            Some(ply) => Ok((ply.clone(), analysis.evaluation)),
+            None => Err(io::Error::new(io::ErrorKind::Other, "The search found no plies")),
+        }
+    }
+
+    /// Executes `ply`, which fails only for an illegal ply entered by a person.
+    fn make_ply(&mut self, ply: <S as State>::Ply) -> Result<(), String> {
+        self.state.execute_ply(Some(&ply))?;
+        self.history.push(ply);
+        Ok(())
+    }
+
+    /// Reverts plies until one made by a person has been taken back and it's a person's turn
+    /// again.  Returns false if there was no such ply.
+    fn undo(&mut self) -> io::Result<bool> {
+        let player_plies = (0..self.history.len()).filter(|&index| {
+            !self.engine[(self.state.get_ply_count() - self.history.len() + index) % 2]
+        }).collect::<Vec<_>>();
+
+        let target = match player_plies.last() {
+            Some(&index) => index,
+            None => return Ok(false),
+        };
+
+        while self.history.len() > target {
+            let ply = self.history.pop().unwrap();
+            if let Err(error) = self.state.revert_ply(Some(&ply)) {
+                return Err(io::Error::new(io::ErrorKind::Other, error));
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(all(test, feature = "with_tic_tac_toe"))]
+mod test {
+    use std::io::Cursor;
+
+    use analysis::search::PvSearch;
+    use impls::tic_tac_toe::{Board, Evaluator, Mark};
+    use resolution::Resolution;
+    use super::*;
+
+    #[test]
+    fn test_commands() {
+        let mut game = Game::new(Board::new(), PvSearch::with_depth(Evaluator, 9), Some(1));
+        let input = "2 2\nundo\nundo\n4 1\n1\nhint\n1 1\n1 1\nquit\n";
+        let mut output = Vec::new();
+
+        assert!(game.play_with(Cursor::new(input), &mut output).unwrap().is_none());
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("O plays").count(), 2);
+        assert!(output.contains("Nothing to undo"));
+        assert!(output.contains("Invalid ply: Invalid coordinate: 4"));
+        assert!(output.contains("Invalid ply: Expected two coordinates"));
+        assert!(output.contains("Hint: "));
+        assert!(output.contains("Illegal ply"));
+
+        // The undone ply and the engine's reply are gone
+        assert_eq!(game.get_history().len(), 2);
+        assert_eq!(game.get_history()[0].coordinates, (0, 0));
+        assert_eq!(game.get_state().0[0], Some(Mark::X));
+    }
+
+    #[test]
+    fn test_engine_against_itself() {
+        let mut game = Game::new(Board::new(), PvSearch::with_depth(Evaluator, 9), Some(0));
+        game.set_engine_player(1, true);
+
+        let resolution = game.play_with(Cursor::new(""), Vec::new()).unwrap().unwrap();
+        assert!(resolution.is_draw());
+        assert_eq!(game.get_history().len(), 9);
+    }
+}
@@ -0,0 +1,45 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use cli::Interactive;
+use impls::connect_four::{Board, Ply, WIDTH};
+
+impl Interactive for Board {
+    fn parse_ply(&self, input: &str) -> Result<Ply, String> {
+        match input.parse::<usize>() {
+            Ok(column) if column >= 1 && column <= WIDTH => Ok(Ply {
+                disc: self.next_disc(),
+                column: column - 1,
+            }),
+            _ => Err(format!("Invalid column: {}", input)),
+        }
+    }
+
+    fn format_ply(&self, ply: &Ply) -> String {
+        format!("{}", ply.column + 1)
+    }
+
+    fn notation() -> &'static str {
+        "the column from 1 to 7"
+    }
+
+    fn player_name(player: u8) -> String {
+        String::from(if player == 0 { "Red" } else { "Yellow" })
+    }
+}
@@ -0,0 +1,66 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::fmt;
+
+use impls::connect_four::{Board, Disc, Ply, Resolution, HEIGHT, WIDTH};
+
+impl fmt::Display for Disc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            Disc::Red => "R",
+            Disc::Yellow => "Y",
+        })
+    }
+}
+
+impl fmt::Display for Ply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, {}", self.disc, self.column + 1)
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Resolution::Win(Disc::Red) => write!(f, "Red wins"),
+            Resolution::Win(Disc::Yellow) => write!(f, "Yellow wins"),
+            Resolution::Draw => write!(f, "Draw"),
+        }
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in (0..HEIGHT).rev() {
+            writeln!(f)?;
+            for column in 0..WIDTH {
+                match self.get(column, row) {
+                    Some(disc) => write!(f, "[{}]", disc)?,
+                    None => write!(f, "[ ]")?,
+                }
+            }
+        }
+        writeln!(f)?;
+        for column in 0..WIDTH {
+            write!(f, " {} ", column + 1)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+//! The game of connect four, on the standard board of seven columns and six rows.
+//!
+//! The game is solved as a win for the first player, who must open in the center column.
+
+use std::hash::{Hash, Hasher};
+
+/// The number of columns on the board.
+pub const WIDTH: usize = 7;
+
+/// The number of rows on the board.
+pub const HEIGHT: usize = 6;
+
+/// Either red, who moves first, or yellow.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub enum Disc {
+    Red,
+    Yellow,
+}
+
+impl Disc {
+    pub fn flip(&self) -> Disc {
+        match *self {
+            Disc::Red => Disc::Yellow,
+            Disc::Yellow => Disc::Red,
+        }
+    }
+}
+
+/// The dropping of a disc into a column.  It comes to rest on the lowest empty row.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub struct Ply {
+    pub disc: Disc,
+    pub column: usize,
+}
+
+impl Hash for Ply {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        let mut hash = match self.disc {
+            Disc::Red => 1 as u64,
+            Disc::Yellow => 0 as u64,
+        };
+        hash = (hash << 8) | self.column as u64;
+        state.write_u64(hash);
+    }
+}
+
+/// Either a win or a draw.
+#[derive(Debug)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub enum Resolution {
+    Win(Disc),
+    Draw,
+}
+
+/// The game board, stored as a bitboard for each disc.  Each column occupies `HEIGHT + 1`
+/// bits, lowest row first, with the extra bit left empty so that lines can't wrap from one
+/// column to the next.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub struct Board {
+    discs: [u64; 2],
+    heights: [u8; WIDTH],
+    ply_count: u8,
+}
+
+impl Board {
+    /// Creates an empty board.
+    pub fn new() -> Board {
+        Board {
+            discs: [0; 2],
+            heights: [0; WIDTH],
+            ply_count: 0,
+        }
+    }
+
+    /// Creates a board by dropping discs, alternating from red, into each of `columns` in turn.
+    /// Columns are numbered from 1, so that a game can be read from the common notation,
+    /// i.e. "4453".
+    pub fn from_columns(columns: &[usize]) -> Result<Board, String> {
+        use state::State;
+
+        let mut board = Board::new();
+        for &column in columns {
+            if column == 0 {
+                return Err(String::from("Columns are numbered from 1"));
+            }
+
+            let ply = Ply {
+                disc: board.next_disc(),
+                column: column - 1,
+            };
+            if let Err(error) = board.execute_ply(Some(&ply)) {
+                return Err(format!("Error executing ply: {}, {}", ply, error));
+            }
+        }
+        Ok(board)
+    }
+
+    /// Returns the disc that will make the next move.
+    pub fn next_disc(&self) -> Disc {
+        if self.ply_count % 2 == 0 {
+            Disc::Red
+        } else {
+            Disc::Yellow
+        }
+    }
+
+    /// Returns the disc at `column` and `row`, counting rows from the bottom.
+    pub fn get(&self, column: usize, row: usize) -> Option<Disc> {
+        if column >= WIDTH || row >= HEIGHT {
+            return None;
+        }
+
+        let bit = 1 << bit_index(column, row);
+        if self.discs[0] & bit != 0 {
+            Some(Disc::Red)
+        } else if self.discs[1] & bit != 0 {
+            Some(Disc::Yellow)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of discs in `column`.
+    pub fn get_height(&self, column: usize) -> usize {
+        self.heights[column] as usize
+    }
+
+    /// Returns true if `disc` has four in a row.
+    pub fn has_won(&self, disc: Disc) -> bool {
+        let discs = self.discs[disc_index(disc)];
+
+        // Vertical, horizontal, and the two diagonals
+        for &shift in &[1, HEIGHT + 1, HEIGHT, HEIGHT + 2] {
+            let pairs = discs & (discs >> shift);
+            if pairs & (pairs >> (2 * shift)) != 0 {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+fn bit_index(column: usize, row: usize) -> usize {
+    column * (HEIGHT + 1) + row
+}
+
+fn disc_index(disc: Disc) -> usize {
+    match disc {
+        Disc::Red => 0,
+        Disc::Yellow => 1,
+    }
+}
+
+/// Either the identity, or a reflection of the board across its center column.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Transform {
+    pub mirror: bool,
+}
+
+impl Transform {
+    /// Transforms the index of a column.
+    pub fn apply(&self, column: usize) -> usize {
+        if self.mirror {
+            WIDTH - 1 - column
+        } else {
+            column
+        }
+    }
+}
+
+pub use self::zero_sum::{Evaluation, Evaluator, Features};
+
+#[cfg(feature = "with_cli")]
+mod cli;
+
+mod display;
+mod zero_sum;
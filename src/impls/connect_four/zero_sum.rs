@@ -0,0 +1,348 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::i16;
+
+use analysis::{self, Evaluation as EvaluationTrait};
+use analysis::search::ButterflyIndex;
+use impls::connect_four::{bit_index, disc_index, Board, Disc, Ply, Resolution, Transform, HEIGHT, WIDTH};
+use ply;
+use resolution;
+use state::{self, PlyError, State};
+use symmetric;
+
+impl ply::Ply for Ply { }
+
+impl ButterflyIndex for Ply {
+    fn butterfly_index(&self) -> usize {
+        self.column
+    }
+}
+
+impl resolution::Resolution for Resolution {
+    fn get_winner(&self) -> Option<u8> {
+        match *self {
+            Resolution::Win(Disc::Red) => Some(0),
+            Resolution::Win(Disc::Yellow) => Some(1),
+            _ => None,
+        }
+    }
+
+    fn is_draw(&self) -> bool {
+        if let Resolution::Draw = *self { true } else { false }
+    }
+}
+
+impl state::State for Board {
+    type Ply = Ply;
+    type Resolution = Resolution;
+
+    fn get_ply_count(&self) -> usize {
+        self.ply_count as usize
+    }
+
+    fn execute_ply(&mut self, ply: Option<&Ply>) -> Result<(), PlyError> {
+        let ply = if let Some(ply) = ply {
+            ply
+        } else {
+            self.ply_count += 1;
+            return Ok(());
+        };
+
+        if ply.column >= WIDTH {
+            return Err(PlyError::OutOfBounds);
+        }
+
+        let height = self.heights[ply.column] as usize;
+        if height >= HEIGHT {
+            return Err(PlyError::Occupied);
+        }
+
+        self.discs[disc_index(ply.disc)] |= 1 << bit_index(ply.column, height);
+        self.heights[ply.column] += 1;
+        self.ply_count += 1;
+        Ok(())
+    }
+
+    fn revert_ply(&mut self, ply: Option<&Ply>) -> Result<(), PlyError> {
+        let ply = if let Some(ply) = ply {
+            ply
+        } else {
+            self.ply_count -= 1;
+            return Ok(());
+        };
+
+        if ply.column >= WIDTH {
+            return Err(PlyError::OutOfBounds);
+        }
+
+        let height = self.heights[ply.column] as usize;
+        if height == 0 {
+            return Err(PlyError::Empty);
+        }
+
+        let bit = 1 << bit_index(ply.column, height - 1);
+        if self.discs[disc_index(ply.disc)] & bit == 0 {
+            return Err(PlyError::WrongColor);
+        }
+
+        self.discs[disc_index(ply.disc)] &= !bit;
+        self.heights[ply.column] -= 1;
+        self.ply_count -= 1;
+        Ok(())
+    }
+
+    fn check_resolution(&self) -> Option<Resolution> {
+        if self.has_won(Disc::Red) {
+            Some(Resolution::Win(Disc::Red))
+        } else if self.has_won(Disc::Yellow) {
+            Some(Resolution::Win(Disc::Yellow))
+        } else if self.heights.iter().all(|&height| height as usize == HEIGHT) {
+            Some(Resolution::Draw)
+        } else {
+            None
+        }
+    }
+}
+
+/// Columns in the order they're extrapolated.  Central columns take part in more lines,
+/// so they're more likely to be good moves.
+const COLUMN_ORDER: [usize; WIDTH] = [3, 2, 4, 1, 5, 0, 6];
+
+impl analysis::Extrapolatable<Ply> for Board {
+    fn extrapolate(&self) -> Vec<Ply> {
+        let next_disc = self.next_disc();
+        COLUMN_ORDER.iter()
+            .filter(|&&column| (self.heights[column] as usize) < HEIGHT)
+            .map(|&column| Ply {
+                disc: next_disc,
+                column: column,
+            })
+            .collect::<Vec<Ply>>()
+    }
+}
+
+impl symmetric::Symmetric for Board {
+    type Transform = Transform;
+
+    fn symmetries(&self) -> Vec<Transform> {
+        vec![Transform { mirror: false }, Transform { mirror: true }]
+    }
+
+    fn transform(&self, transform: &Transform) -> Board {
+        let mut board = Board::new();
+        board.ply_count = self.ply_count;
+
+        let column_mask = (1 << HEIGHT) - 1;
+        for column in 0..WIDTH {
+            let target = transform.apply(column);
+            for disc in 0..2 {
+                let bits = (self.discs[disc] >> bit_index(column, 0)) & column_mask;
+                board.discs[disc] |= bits << bit_index(target, 0);
+            }
+            board.heights[target] = self.heights[column];
+        }
+        board
+    }
+
+    fn transform_ply(&self, ply: &Ply, transform: &Transform) -> Ply {
+        Ply {
+            disc: ply.disc,
+            column: transform.apply(ply.column),
+        }
+    }
+
+    fn invert_transform(&self, transform: &Transform) -> Transform {
+        *transform
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub struct Evaluation(pub i16);
+
+prepare_evaluation_tuple!(Evaluation); // Implements arithmetic operators and display in terms of the inner type
+
+impl analysis::Evaluation for Evaluation {
+    fn null() -> Evaluation { Evaluation(0) }
+    fn shift(self, steps: i32) -> Evaluation { Evaluation(self.0 + steps as i16) }
+    fn win() -> Evaluation { Evaluation(1000) }
+    fn max() -> Evaluation { Evaluation(i16::MAX) }
+    fn is_win(&self) -> bool { self.0 >= 500 }
+    fn encodes_win_distance() -> bool { true }
+}
+
+/// The value of a line of four that is open to only one player, by the number of that
+/// player's discs in it.
+const LINE_WEIGHTS: [i16; 4] = [0, 1, 4, 16];
+
+/// The value of each disc in the center column.
+const CENTER_WEIGHT: i16 = 3;
+
+/// A simple evaluator.  Counts the lines of four that each player could still complete,
+/// weighted by how close they are to completion, and favors the center column.
+pub struct Evaluator;
+
+impl analysis::Evaluator for Evaluator {
+    type State = Board;
+    type Evaluation = Evaluation;
+
+    fn evaluate(&self, state: &Board) -> Evaluation {
+        let next_disc = state.next_disc();
+
+        match state.check_resolution() {
+            Some(Resolution::Win(disc)) => {
+                let value = Evaluation(Evaluation::win().0 - state.ply_count as i16);
+                return if disc == next_disc { value } else { -value };
+            },
+            Some(Resolution::Draw) => return Evaluation::null(),
+            None => (),
+        }
+
+        let mut red = 0;
+        let mut yellow = 0;
+
+        for &(dx, dy) in &[(1, 0), (0, 1), (1, 1), (1, -1)] {
+            for column in 0..WIDTH as isize {
+                for row in 0..HEIGHT as isize {
+                    let (end_column, end_row) = (column + 3 * dx, row + 3 * dy);
+                    if end_column >= WIDTH as isize || end_row < 0 || end_row >= HEIGHT as isize {
+                        continue;
+                    }
+
+                    let mut red_count = 0;
+                    let mut yellow_count = 0;
+                    for i in 0..4 {
+                        match state.get((column + i * dx) as usize, (row + i * dy) as usize) {
+                            Some(Disc::Red) => red_count += 1,
+                            Some(Disc::Yellow) => yellow_count += 1,
+                            None => (),
+                        }
+                    }
+
+                    if yellow_count == 0 {
+                        red += LINE_WEIGHTS[red_count];
+                    } else if red_count == 0 {
+                        yellow += LINE_WEIGHTS[yellow_count];
+                    }
+                }
+            }
+        }
+
+        for row in 0..HEIGHT {
+            match state.get(WIDTH / 2, row) {
+                Some(Disc::Red) => red += CENTER_WEIGHT,
+                Some(Disc::Yellow) => yellow += CENTER_WEIGHT,
+                None => (),
+            }
+        }
+
+        if next_disc == Disc::Red {
+            Evaluation(red - yellow)
+        } else {
+            Evaluation(yellow - red)
+        }
+    }
+}
+
+/// Describes the board with one feature per space for each disc: 1.0 if the space holds that
+/// disc, and 0.0 otherwise.  Spaces are ordered by row from the bottom, then by column, and the
+/// red features come first.
+pub struct Features;
+
+impl analysis::FeatureExtractor<Board> for Features {
+    fn dimension(&self) -> usize {
+        2 * WIDTH * HEIGHT
+    }
+
+    fn features(&self, state: &Board, out: &mut [f32]) {
+        for row in 0..HEIGHT {
+            for column in 0..WIDTH {
+                let space = row * WIDTH + column;
+                let disc = state.get(column, row);
+                out[space] = if disc == Some(Disc::Red) { 1.0 } else { 0.0 };
+                out[WIDTH * HEIGHT + space] = if disc == Some(Disc::Yellow) { 1.0 } else { 0.0 };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use analysis::{Evaluation, FeatureExtractor};
+    use analysis::search::{PvSearch, PvSearchAnalysis, Search};
+    use impls::connect_four::*;
+    use resolution::{Outcome, Resolution as ResolutionTrait};
+    use state::State;
+    use symmetric::Symmetric;
+
+    fn winner(board: &Board) -> Option<u8> {
+        board.check_resolution().and_then(|resolution| resolution.get_winner())
+    }
+
+    #[test]
+    fn test_resolution() {
+        // Vertical, horizontal, and both diagonals
+        let wins = ["4343434", "1122334", "12234334644", "76654554244"];
+        for columns in wins.iter() {
+            let columns = columns.bytes().map(|c| (c - b'0') as usize).collect::<Vec<_>>();
+            let board = Board::from_columns(&columns).unwrap();
+            assert_eq!(winner(&board), Some(0), "{:?}", columns);
+            assert_eq!(winner(&board.transform(&Transform { mirror: true })), Some(0), "{:?}", columns);
+        }
+
+        let resolution = Board::from_columns(&[4, 3, 4, 3, 4, 3, 4]).unwrap().check_resolution().unwrap();
+        assert_eq!(resolution.score_for(0), Some(Outcome::Win));
+        assert_eq!(resolution.score_for(1), Some(Outcome::Loss));
+        assert_eq!(resolution.to_string(), "Red wins");
+
+        assert_eq!(winner(&Board::from_columns(&[1, 2, 3, 5, 6, 7]).unwrap()), None);
+        assert!(Board::from_columns(&[1; 7]).is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let mut search = PvSearch::with_depth(Evaluator, 4);
+
+        // Yellow must block red's three in column 4
+        let board = Board::from_columns(&[4, 1, 4, 1, 4]).unwrap();
+        let analysis = search.search(&board, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, Evaluator>>().unwrap();
+        assert_eq!(analysis.principal_variation[0].column, 3);
+
+        // Otherwise red wins there
+        let board = Board::from_columns(&[4, 1, 4, 1, 4, 2]).unwrap();
+        let analysis = search.search(&board, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<Board, Evaluator>>().unwrap();
+        assert_eq!(analysis.principal_variation[0].column, 3);
+        assert!(analysis.evaluation.is_win());
+    }
+
+    #[test]
+    fn test_features() {
+        let board = Board::from_columns(&[4, 4, 1]).unwrap();
+        let features = Features.feature_vector(&board);
+
+        assert_eq!(features.len(), Features.dimension());
+        assert_eq!(features.iter().sum::<f32>(), 3.0);
+        assert_eq!(features[3], 1.0);
+        assert_eq!(features[0], 1.0);
+        assert_eq!(features[WIDTH * HEIGHT + WIDTH + 3], 1.0);
+    }
+}
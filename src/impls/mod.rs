@@ -19,6 +19,9 @@
 
 //! Contains implementations of the library for a few zero-sum games.
 
+#[cfg(feature = "with_connect_four")]
+pub mod connect_four;
+
 #[cfg(feature = "with_tak")]
 pub mod tak;
 
@@ -0,0 +1,141 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::slice;
+
+lazy_static! {
+    static ref SLIDE_TABLE: Vec<Vec<Vec<u8>>> = generate_slide_table(MAX_CARRY as u8);
+}
+
+/// The most stones that can be carried in a slide, on the largest board.
+pub const MAX_CARRY: usize = 8;
+
+/// Returns an iterator over the drop patterns of a slide that picks up at most `max_carry`
+/// stones and has `distance` open spaces (empty, or topped by flatstones) in its direction.
+/// If `crush` is set, the space beyond those is a standing stone that the slide may flatten,
+/// with a lone capstone as its last drop; this should only be set when a capstone tops the
+/// stack.
+///
+/// Each pattern is yielded once, so every slide of a stack in a direction is described by
+/// exactly one pattern.
+///
+/// # Panics
+/// Will panic if `max_carry` is greater than `MAX_CARRY`.
+pub fn drop_patterns(max_carry: usize, distance: usize, crush: bool) -> DropPatterns {
+    assert!(max_carry <= MAX_CARRY, "Can't carry more than {} stones", MAX_CARRY);
+
+    DropPatterns {
+        patterns: SLIDE_TABLE[max_carry].iter(),
+        distance: distance,
+        crush: crush,
+        pending: if crush && distance == 0 && max_carry > 0 {
+            Some(vec![1])
+        } else {
+            None
+        },
+    }
+}
+
+/// An iterator over drop patterns, created by `drop_patterns`.
+pub struct DropPatterns {
+    patterns: slice::Iter<'static, Vec<u8>>,
+    distance: usize,
+    crush: bool,
+    pending: Option<Vec<u8>>,
+}
+
+impl Iterator for DropPatterns {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        if let Some(drops) = self.pending.take() {
+            return Some(drops);
+        }
+
+        for drops in self.patterns.by_ref() {
+            if drops.len() > self.distance {
+                continue;
+            }
+
+            // Flatten the standing stone by splitting the capstone off the last drop
+            if self.crush && drops.len() == self.distance && *drops.last().unwrap() > 1 {
+                let mut crush = drops.clone();
+                *crush.last_mut().unwrap() -= 1;
+                crush.push(1);
+                self.pending = Some(crush);
+            }
+
+            return Some(drops.clone());
+        }
+
+        None
+    }
+}
+
+/// Generates, for each carry limit up to `size`, every sequence of positive drops whose
+/// sum is no more than the limit.
+fn generate_slide_table(size: u8) -> Vec<Vec<Vec<u8>>> {
+    let mut result: Vec<Vec<Vec<u8>>> = Vec::with_capacity(size as usize);
+    result.push(Vec::new());
+
+    for stack in 1..(size + 1) {
+        let mut out = Vec::with_capacity((2 as usize).pow(stack as u32) - 1);
+
+        for i in 1..(stack + 1) {
+            out.push(vec![i]);
+
+            for sub in &result[(stack - i) as usize] {
+                let mut t = vec![0; sub.len() + 1];
+                t[0] = i;
+                t[1..].clone_from_slice(sub);
+
+                out.push(t);
+            }
+        }
+
+        result.push(out);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_drop_patterns() {
+        // Every composition of 1 to 3 stones: 1 + 2 + 4
+        assert_eq!(drop_patterns(3, 3, false).count(), 7);
+        assert_eq!(drop_patterns(3, 1, false).collect::<Vec<_>>(), vec![vec![1], vec![2], vec![3]]);
+        assert_eq!(drop_patterns(3, 0, false).count(), 0);
+
+        assert_eq!(drop_patterns(3, 0, true).collect::<Vec<_>>(), vec![vec![1]]);
+        assert_eq!(drop_patterns(0, 0, true).count(), 0);
+
+        let crushes = drop_patterns(3, 1, true).filter(|drops| drops.len() == 2).collect::<Vec<_>>();
+        assert_eq!(crushes, vec![vec![1, 1], vec![2, 1]]);
+
+        for drops in drop_patterns(MAX_CARRY, 4, true) {
+            assert!(drops.iter().all(|&drop| drop > 0));
+            assert!(drops.iter().map(|&drop| drop as usize).sum::<usize>() <= MAX_CARRY);
+            assert!(drops.len() <= 4 || (drops.len() == 5 && *drops.last().unwrap() == 1));
+        }
+    }
+}
@@ -21,6 +21,7 @@
 
 /// The colors of the players.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub enum Color {
     White,
     Black,
@@ -37,6 +38,7 @@ impl Color {
 
 /// The types of pieces.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub enum Piece {
     Flatstone(Color),
     StandingStone(Color),
@@ -55,6 +57,7 @@ impl Piece {
 
 /// The slidable directions.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub enum Direction {
     North,
     East,
@@ -73,10 +76,93 @@ impl Direction {
     }
 }
 
-pub use self::ply::Ply;
+/// One of the eight symmetries of the square board.  The board is first mirrored
+/// east-to-west if `reflect` is set, then rotated counter-clockwise by a quarter turn
+/// `rotations` times.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Transform {
+    pub rotations: u8,
+    pub reflect: bool,
+}
+
+impl Transform {
+    /// Returns all eight symmetries of the board, starting with the identity.
+    pub fn all() -> Vec<Transform> {
+        let mut transforms = Vec::with_capacity(8);
+        for &reflect in &[false, true] {
+            for rotations in 0..4 {
+                transforms.push(Transform {
+                    rotations: rotations,
+                    reflect: reflect,
+                });
+            }
+        }
+        transforms
+    }
+
+    /// Returns the transform that undoes this one.
+    pub fn invert(&self) -> Transform {
+        if self.reflect {
+            *self
+        } else {
+            Transform {
+                rotations: (4 - self.rotations % 4) % 4,
+                reflect: false,
+            }
+        }
+    }
+
+    /// Transforms the coordinates of a space on a board of size `board_size`.
+    pub fn apply(&self, x: usize, y: usize, board_size: usize) -> (usize, usize) {
+        let (mut x, mut y) = if self.reflect {
+            (board_size - 1 - x, y)
+        } else {
+            (x, y)
+        };
+
+        for _ in 0..self.rotations % 4 {
+            let rx = board_size - 1 - y;
+            y = x;
+            x = rx;
+        }
+
+        (x, y)
+    }
+
+    /// Transforms a slide direction.
+    pub fn apply_direction(&self, direction: Direction) -> Direction {
+        let mut direction = if self.reflect {
+            match direction {
+                Direction::East => Direction::West,
+                Direction::West => Direction::East,
+                other => other,
+            }
+        } else {
+            direction
+        };
+
+        for _ in 0..self.rotations % 4 {
+            direction = match direction {
+                Direction::North => Direction::West,
+                Direction::East => Direction::North,
+                Direction::South => Direction::East,
+                Direction::West => Direction::South,
+            };
+        }
+
+        direction
+    }
+}
+
+pub use self::drops::{drop_patterns, DropPatterns, MAX_CARRY};
+pub use self::ply::{Annotation, Ply};
 pub use self::resolution::Resolution;
-pub use self::state::{State, evaluator};
+pub use self::state::{FEATURE_COUNT, Features, Handicap, IllegalReason, Rules, SetupError, State, StateBuilder, TpsError, evaluator, gather_features};
+pub use self::tei::{TeiAnalysis, TeiEngine, TeiError};
 
+mod drops;
+mod persist;
 mod ply;
 mod resolution;
 mod state;
+mod tei;
@@ -0,0 +1,181 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use impls::tak::{Color, Direction, Piece, Ply};
+use persist::{Decoder, Encode, Persistable, PersistError};
+
+impl Persistable for Piece {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        let (kind, color) = match *self {
+            Piece::Flatstone(color) => (0, color),
+            Piece::StandingStone(color) => (1, color),
+            Piece::Capstone(color) => (2, color),
+        };
+        bytes.write_u8(kind << 1 | if color == Color::White { 0 } else { 1 });
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Piece, PersistError> {
+        let code = decoder.read_u8()?;
+        let color = if code & 1 == 0 { Color::White } else { Color::Black };
+        match code >> 1 {
+            0 => Ok(Piece::Flatstone(color)),
+            1 => Ok(Piece::StandingStone(color)),
+            2 => Ok(Piece::Capstone(color)),
+            _ => Err(PersistError::Malformed(format!("Invalid piece: {}", code))),
+        }
+    }
+}
+
+impl Persistable for Ply {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match *self {
+            Ply::Place { x, y, ref piece } => {
+                bytes.write_u8(0);
+                bytes.write_u8(x as u8);
+                bytes.write_u8(y as u8);
+                piece.encode(bytes);
+            },
+            Ply::Slide { x, y, direction, ref drops } => {
+                bytes.write_u8(1);
+                bytes.write_u8(x as u8);
+                bytes.write_u8(y as u8);
+                bytes.write_u8(match direction {
+                    Direction::North => 0,
+                    Direction::East => 1,
+                    Direction::South => 2,
+                    Direction::West => 3,
+                });
+                bytes.write_u8(drops.len() as u8);
+                bytes.extend_from_slice(drops);
+            },
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Ply, PersistError> {
+        let kind = decoder.read_u8()?;
+        let x = decoder.read_u8()? as usize;
+        let y = decoder.read_u8()? as usize;
+
+        match kind {
+            0 => Ok(Ply::Place {
+                x: x,
+                y: y,
+                piece: Piece::decode(decoder)?,
+            }),
+            1 => {
+                let direction = match decoder.read_u8()? {
+                    0 => Direction::North,
+                    1 => Direction::East,
+                    2 => Direction::South,
+                    3 => Direction::West,
+                    d => return Err(PersistError::Malformed(format!("Invalid direction: {}", d))),
+                };
+                let count = decoder.read_u8()? as usize;
+
+                Ok(Ply::Slide {
+                    x: x,
+                    y: y,
+                    direction: direction,
+                    drops: decoder.read_bytes(count)?.to_vec(),
+                })
+            },
+            _ => Err(PersistError::Malformed(format!("Invalid ply kind: {}", kind))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs;
+
+    use analysis::search::{PvSearch, PvSearchAnalysis, Search};
+    use impls::tak::*;
+    use persist::{Decoder, Persistable};
+    use state::State as StateTrait;
+
+    fn round_trip<T>(value: &T) -> T where T: Persistable {
+        let mut bytes = Vec::new();
+        value.encode(&mut bytes);
+        let mut decoder = Decoder::new(&bytes);
+        let decoded = T::decode(&mut decoder).unwrap();
+        assert!(decoder.is_empty());
+        decoded
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let state = State::from_tps("[TPS \"x5/x5/x,2S,x3/x,121C,x3/1,x4 1 3\"]").unwrap();
+        let crush = Ply::from_ptn("b2+", Color::White).unwrap();
+        let mut crushed = state.clone();
+        crushed.execute_ply(Some(&crush)).unwrap();
+
+        for state in &[state, crushed, State::with_reserves(6, (28, 1), (30, 0))] {
+            assert!(round_trip(state) == *state);
+        }
+
+        for ptn in &["a1", "Sb2", "Cc3", "3d4>12", "e5-"] {
+            let ply = Ply::from_ptn(ptn, Color::Black).unwrap();
+            assert!(round_trip(&ply) == ply);
+        }
+    }
+
+    #[test]
+    fn test_save_table() {
+        let state = State::from_tps("[TPS \"x5/x5/x,2,1,x2/x,1,2,x2/x5 1 3\"]").unwrap();
+        let path = env::temp_dir().join(format!("zero_sum_test_table_{}", ::std::process::id()));
+
+        let mut search = PvSearch::with_depth(evaluator::StaticEvaluator::new(), 3);
+        search.search(&state, None);
+        search.save_table(&path).unwrap();
+
+        let mut resumed = PvSearch::with_depth(evaluator::StaticEvaluator::new(), 3);
+        let loaded = resumed.load_table(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(loaded > 0);
+
+        // The root's entry answers the whole search
+        let analysis = resumed.search(&state, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<State, evaluator::StaticEvaluator>>().unwrap();
+        assert_eq!(analysis.statistics.nodes(), 0);
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let state = State::from_tps("[TPS \"x5/x5/x,2,1,x2/x,1,2,x2/x5 1 3\"]").unwrap();
+        let path = env::temp_dir().join(format!("zero_sum_test_checkpoint_{}", ::std::process::id()));
+
+        let mut search = PvSearch::with_depth(evaluator::StaticEvaluator::new(), 2);
+        search.search(&state, None);
+        search.save_checkpoint(&path, &state).unwrap();
+
+        // A deeper search picks up after the iterations the checkpoint completed, even one
+        // that wouldn't otherwise reuse a precalculated root
+        let mut resumed = PvSearch::with_depth(evaluator::StaticEvaluator::new(), 3);
+        resumed.set_reuse_precalculated(false);
+        let analysis = resumed.resume(&path, None).unwrap();
+        fs::remove_file(&path).unwrap();
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<State, evaluator::StaticEvaluator>>().unwrap();
+        assert!(analysis.state == state);
+        assert_eq!(analysis.statistics.search_depth(), 3);
+        assert!(analysis.statistics.depth[..2].iter().all(|levels| levels.iter().all(|level| level.nodes() == 0)));
+        assert!(analysis.statistics.nodes() > 0);
+        assert!(!analysis.principal_variation.is_empty());
+    }
+}
@@ -19,12 +19,15 @@
 
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 
+use analysis::search::ButterflyIndex;
 use impls::tak::{Color, Direction, Piece};
 use ply;
 
 /// Represents either a piece placement or a slide.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub enum Ply {
     /// Placement of a stone in an empty space.
     Place {
@@ -42,116 +45,22 @@ pub enum Ply {
 }
 
 impl Ply {
-    pub fn from_ptn(ptn: &str, color: Color) -> Option<Ply> { // XXX Return Result<Ply, String>
-        let mut chars = ptn.chars();
-
-        let mut next = chars.next();
-
-        let mut new_piece = match next {
-            Some('S') => {
-                next = chars.next();
-                Some(Piece::StandingStone(color))
-            },
-            Some('C') => {
-                next = chars.next();
-                Some(Piece::Capstone(color))
-            },
-            Some('F') => {
-                next = chars.next();
-                Some(Piece::Flatstone(color))
-            },
-            None => return None,
-            _ => None,
-        };
-
-        let grab = match next {
-            Some(c) => if c.is_digit(10) {
-                next = chars.next();
-                Some(c as u8 - 48)
-            } else {
-                None
-            },
-            None => return None,
-        };
-
-        let x = match next {
-            Some(c) => if c.is_alphabetic() && c.is_lowercase() {
-                (c as u8 - 97) as usize
-            } else {
-                return None;
-            },
-            None => return None,
-        };
-
-        let y = match chars.next() {
-            Some(c) => if c.is_digit(10) && c != '0' {
-                (c as u8 - 49) as usize
-            } else {
-                return None;
-            },
-            None => return None,
-        };
-
-        let direction = match chars.next() {
-            Some('+') => Some(Direction::North),
-            Some('>') => Some(Direction::East),
-            Some('-') => Some(Direction::South),
-            Some('<') => Some(Direction::West),
-            None => {
-                if new_piece.is_none() {
-                    new_piece = Some(Piece::Flatstone(color));
-                }
-                None
-            },
-            _ => return None,
-        };
-
-        let mut drops = Vec::new();
-        for c in chars {
-            if c.is_digit(10) {
-                drops.push(c as u8 - 48);
-            } else {
-                return None;
-            }
-        }
-
-        if new_piece.is_some() {
-            if grab.is_some() || direction.is_some() || !drops.is_empty() {
-                return None;
-            }
-
-            Some(Ply::Place {
-                x: x,
-                y: y,
-                piece: new_piece.unwrap(),
-            })
-        } else if direction.is_some() {
-            if drops.is_empty() {
-                if grab.is_some() {
-                    drops.push(grab.unwrap());
-                } else {
-                    drops.push(1);
-                }
-            } else {
-                if grab.is_none() {
-                    return None;
-                }
-                if grab.unwrap() != drops.iter().fold(0, |acc, x| acc + x) {
-                    return None;
-                }
-            }
+    /// Parses a ply in PTN, such as `Sc3`, `a1`, or `3b2>21`, made by `color`.  Counts and
+    /// drops may be omitted where PTN allows it, and any annotation after the ply is ignored.
+    pub fn from_ptn(ptn: &str, color: Color) -> Result<Ply, String> {
+        Ply::from_annotated_ptn(ptn, color).map(|(ply, _)| ply)
+    }
 
-            Some(Ply::Slide {
-                x: x,
-                y: y,
-                direction: direction.unwrap(),
-                drops: drops
-            })
-        } else {
-            None
-        }
+    /// Parses a ply in PTN made by `color`, along with any annotation that follows it.
+    pub fn from_annotated_ptn(ptn: &str, color: Color) -> Result<(Ply, Annotation), String> {
+        let split = ptn.find(|c| ANNOTATION_CHARACTERS.contains(c)).unwrap_or(ptn.len());
+        let ply = parse_ply(&ptn[..split], color).map_err(|error| format!("Invalid ply \"{}\": {}", ptn, error))?;
+        let annotation = ptn[split..].parse::<Annotation>().map_err(|error| format!("Invalid ply \"{}\": {}", ptn, error))?;
+        Ok((ply, annotation))
     }
 
+    /// Returns the ply in PTN, in its shortest form: flatstone placements have no prefix, and
+    /// counts and drops are omitted where they are implied.
     pub fn to_ptn(&self) -> String {
         let mut ptn = String::new();
 
@@ -193,10 +102,192 @@ impl Ply {
 
         ptn
     }
+
+    /// Returns the ply in PTN, as with `to_ptn`, followed by `annotation`.
+    pub fn to_annotated_ptn(&self, annotation: &Annotation) -> String {
+        format!("{}{}", self.to_ptn(), annotation)
+    }
+
+    /// Rewrites a ply in PTN in the form that `to_ptn` gives, keeping its annotation.
+    pub fn normalize_ptn(ptn: &str) -> Result<String, String> {
+        Ply::from_annotated_ptn(ptn, Color::White).map(|(ply, annotation)| ply.to_annotated_ptn(&annotation))
+    }
+}
+
+/// The characters that may begin an annotation.
+const ANNOTATION_CHARACTERS: &'static str = "*'\"!?";
+
+fn parse_ply(ptn: &str, color: Color) -> Result<Ply, String> {
+    fn parse_count(c: char) -> Result<u8, String> {
+        if c >= '1' && c <= '8' {
+            Ok(c as u8 - 48)
+        } else {
+            Err(format!("'{}' is not a count from 1 to 8", c))
+        }
+    }
+
+    let mut chars = ptn.chars().peekable();
+
+    let piece = match chars.peek() {
+        Some(&'F') => Some(Piece::Flatstone(color)),
+        Some(&'S') => Some(Piece::StandingStone(color)),
+        Some(&'C') => Some(Piece::Capstone(color)),
+        _ => None,
+    };
+    if piece.is_some() {
+        chars.next();
+    }
+
+    let grab = match chars.peek() {
+        Some(&c) if c.is_digit(10) => Some(parse_count(c)?),
+        _ => None,
+    };
+    if grab.is_some() {
+        chars.next();
+    }
+
+    let x = match chars.next() {
+        Some(c) if c >= 'a' && c <= 'h' => (c as u8 - 97) as usize,
+        Some(c) => return Err(format!("'{}' is not a column from a to h", c)),
+        None => return Err(String::from("Missing a space")),
+    };
+
+    let y = match chars.next() {
+        Some(c) if c >= '1' && c <= '8' => (c as u8 - 49) as usize,
+        Some(c) => return Err(format!("'{}' is not a row from 1 to 8", c)),
+        None => return Err(String::from("Missing a row")),
+    };
+
+    let direction = match chars.next() {
+        Some('+') => Direction::North,
+        Some('>') => Direction::East,
+        Some('-') => Direction::South,
+        Some('<') => Direction::West,
+        Some(c) => return Err(format!("'{}' is not a direction", c)),
+        None => {
+            if grab.is_some() {
+                return Err(String::from("A placement can't have a count"));
+            }
+
+            return Ok(Ply::Place {
+                x: x,
+                y: y,
+                piece: piece.unwrap_or(Piece::Flatstone(color)),
+            });
+        },
+    };
+
+    if piece.is_some() {
+        return Err(String::from("A slide can't name a piece"));
+    }
+
+    let mut drops = Vec::new();
+    for c in chars {
+        drops.push(parse_count(c)?);
+    }
+
+    let grab = grab.unwrap_or(1);
+    if drops.is_empty() {
+        drops.push(grab);
+    } else if drops.iter().fold(0, |acc, x| acc + x) != grab {
+        return Err(format!("The drops don't add up to the count of {}", grab));
+    }
+
+    Ok(Ply::Slide {
+        x: x,
+        y: y,
+        direction: direction,
+        drops: drops,
+    })
+}
+
+/// The marks that may follow a ply in PTN.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub struct Annotation {
+    /// The ply flattens a standing stone with a capstone, marked with `*`.
+    pub flattens: bool,
+    /// The ply threatens to complete a road next turn, marked with `'`.
+    pub tak: bool,
+    /// The ply leaves the opponent unable to stop a road, marked with `''` or `"`.
+    pub tinue: bool,
+    /// A judgement of the ply: one of `!`, `?`, `!!`, `??`, `!?`, or `?!`.
+    pub judgement: Option<String>,
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.flattens {
+            write!(f, "*")?;
+        }
+        if self.tinue {
+            write!(f, "''")?;
+        } else if self.tak {
+            write!(f, "'")?;
+        }
+        if let Some(ref judgement) = self.judgement {
+            write!(f, "{}", judgement)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Annotation {
+    type Err = String;
+
+    /// Parses an annotation: optionally `*`, then optionally `'`, `''`, or `"`, then optionally
+    /// a judgement.
+    fn from_str(s: &str) -> Result<Annotation, String> {
+        let mut annotation = Annotation::default();
+        let mut rest = s;
+
+        if rest.starts_with('*') {
+            annotation.flattens = true;
+            rest = &rest[1..];
+        }
+
+        if rest.starts_with("''") {
+            annotation.tinue = true;
+            rest = &rest[2..];
+        } else if rest.starts_with('"') {
+            annotation.tinue = true;
+            rest = &rest[1..];
+        } else if rest.starts_with('\'') {
+            annotation.tak = true;
+            rest = &rest[1..];
+        }
+        annotation.tak = annotation.tak || annotation.tinue;
+
+        if !rest.is_empty() {
+            match rest {
+                "!" | "?" | "!!" | "??" | "!?" | "?!" => annotation.judgement = Some(String::from(rest)),
+                _ => return Err(format!("\"{}\" is not an annotation", s)),
+            }
+        }
+
+        Ok(annotation)
+    }
 }
 
 impl ply::Ply for Ply { }
 
+/// Placements are indexed by square and type of stone, and slides by square, direction, and
+/// distance, so slides differing only in their drops share an index.
+impl ButterflyIndex for Ply {
+    fn butterfly_index(&self) -> usize {
+        match *self {
+            Ply::Place { x, y, ref piece } => (x * 8 + y) * 3 + match *piece {
+                Piece::Flatstone(_) => 0,
+                Piece::StandingStone(_) => 1,
+                Piece::Capstone(_) => 2,
+            },
+            Ply::Slide { x, y, direction, ref drops } => {
+                192 + ((x * 8 + y) * 4 + direction as usize) * 8 + drops.len() - 1
+            },
+        }
+    }
+}
+
 impl fmt::Display for Ply {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.to_ptn())
@@ -228,3 +319,49 @@ impl Hash for Ply {
         state.write_u64(hash);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use analysis::Extrapolatable;
+    use impls::tak::*;
+    use state::State as StateTrait;
+
+    #[test]
+    fn test_round_trip() {
+        let mut state = State::from_tps("[TPS \"21,22221C,1,12212S,x/2121,2S,2,1S,2/x2,2,2,x/1,2111112C,2,x,21/x,1,21,x2 1 32\"]").unwrap();
+        state.execute_ply(None).unwrap();
+
+        for &color in &[Color::White, Color::Black] {
+            state.execute_ply(None).unwrap();
+            for ply in state.extrapolate() {
+                assert_eq!(Ply::from_ptn(&ply.to_ptn(), color), Ok(ply.clone()), "{}", ply);
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        let cases = [
+            ("Fa1", "a1"),
+            ("1a1>", "a1>"),
+            ("a1>1", "a1>"),
+            ("3b2+3", "3b2+"),
+            ("3b2+21", "3b2+21"),
+            ("c3-*'", "c3-*'"),
+            ("Cd4\"!?", "Cd4''!?"),
+        ];
+
+        for &(ptn, normalized) in cases.iter() {
+            assert_eq!(Ply::normalize_ptn(ptn), Ok(String::from(normalized)), "{}", ptn);
+        }
+
+        let (_, annotation) = Ply::from_annotated_ptn("2a3>11''??", Color::Black).unwrap();
+        assert!(annotation.tak && annotation.tinue && !annotation.flattens);
+        assert_eq!(annotation.judgement, Some(String::from("??")));
+
+        let invalid = ["", "a", "a0", "i1", "2a1", "Sa1>", "3a1>13", "9a1>", "a1>0", "a1x", "a1!!!", "a1'*"];
+        for ptn in invalid.iter() {
+            assert!(Ply::from_ptn(ptn, Color::White).is_err(), "{}", ptn);
+        }
+    }
+}
@@ -17,11 +17,14 @@
 // Copyright 2016-2017 Chris Foster
 //
 
+use std::fmt;
+
 use impls::tak::Color;
 use resolution;
 
 /// The ways a game can end.
 #[derive(Debug)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub enum Resolution {
     /// One player has completed a road.
     Road(Color),
@@ -53,3 +56,13 @@ impl resolution::Resolution for Resolution {
         }
     }
 }
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Resolution::Road(color) => write!(f, "{:?} wins by road", color),
+            Resolution::Flat(color) => write!(f, "{:?} wins by flats", color),
+            Resolution::Draw => write!(f, "Draw"),
+        }
+    }
+}
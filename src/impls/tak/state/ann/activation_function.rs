@@ -19,15 +19,26 @@
 
 use std::any::Any;
 
-pub trait ActivationFunction {
+use impls::tak::state::ann::{HeInitializer, Initializer, ZeroInitializer};
+
+pub trait ActivationFunction: Send + 'static {
     fn new() -> Self;
 
+    /// Returns the function's name, by which networks exchanged with other tools identify it.
+    fn name() -> &'static str;
+
     /// Calculates the activation function at `x`.
     fn f(x: f32) -> f32;
 
     /// Calculates the derivative of the activation function at `x`.
     fn f_prime(x: f32) -> f32;
 
+    /// Returns the initializer used for layers of this activation function when none is
+    /// given.  Defaults to `ZeroInitializer`.
+    fn initializer() -> Box<Initializer> {
+        Box::new(ZeroInitializer)
+    }
+
     fn as_any(&self) -> &Any;
 }
 
@@ -39,6 +50,10 @@ impl ActivationFunction for ReLuActivationFunction {
         ReLuActivationFunction
     }
 
+    fn name() -> &'static str {
+        "relu"
+    }
+
     fn f(x: f32) -> f32 {
         x.max(0.0)
     }
@@ -51,6 +66,10 @@ impl ActivationFunction for ReLuActivationFunction {
         }
     }
 
+    fn initializer() -> Box<Initializer> {
+        Box::new(HeInitializer)
+    }
+
     fn as_any(&self) -> &Any {
         self
     }
@@ -64,6 +83,10 @@ impl ActivationFunction for TanHActivationFunction {
         TanHActivationFunction
     }
 
+    fn name() -> &'static str {
+        "tanh"
+    }
+
     fn f(x: f32) -> f32 {
         x.tanh()
     }
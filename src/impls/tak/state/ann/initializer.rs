@@ -0,0 +1,224 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::fs::File;
+use std::io::BufReader;
+
+use rand::distributions::{Normal, Range, Sample};
+use rand::thread_rng;
+
+use impls::tak::state::ann::{
+    ActivationFunction,
+    Ann,
+    GradientDescent,
+    MatrixCm,
+    SimpleGradientDescent,
+    TanHActivationFunction,
+    read_network,
+};
+
+/// Chooses the initial weights of a network's layers.
+pub trait Initializer: Send {
+    /// Returns the initial weights of layer `layer`, which has `inputs` inputs and `outputs`
+    /// neurons, in column-major order.
+    fn initialize(&self, layer: usize, inputs: usize, outputs: usize) -> Vec<f32>;
+}
+
+/// Initializes every weight to zero.
+#[derive(Clone, Debug)]
+pub struct ZeroInitializer;
+
+impl Initializer for ZeroInitializer {
+    fn initialize(&self, _layer: usize, inputs: usize, outputs: usize) -> Vec<f32> {
+        vec![0.0; inputs * outputs]
+    }
+}
+
+/// Draws weights uniformly from `low` to `high`.
+#[derive(Clone, Debug)]
+pub struct UniformInitializer {
+    pub low: f32,
+    pub high: f32,
+}
+
+impl UniformInitializer {
+    pub fn new(low: f32, high: f32) -> UniformInitializer {
+        assert!(low < high, "Invalid range!");
+
+        UniformInitializer {
+            low: low,
+            high: high,
+        }
+    }
+}
+
+impl Initializer for UniformInitializer {
+    fn initialize(&self, _layer: usize, inputs: usize, outputs: usize) -> Vec<f32> {
+        let mut distribution = Range::new(self.low, self.high);
+        (0..inputs * outputs).map(|_| distribution.sample(&mut thread_rng())).collect()
+    }
+}
+
+/// Xavier (Glorot) initialization: normally distributed weights with a variance of
+/// 2 / (inputs + outputs), suited to TanH layers.
+#[derive(Clone, Debug)]
+pub struct XavierInitializer;
+
+impl Initializer for XavierInitializer {
+    fn initialize(&self, _layer: usize, inputs: usize, outputs: usize) -> Vec<f32> {
+        normal(inputs * outputs, (2.0 / (inputs + outputs) as f64).sqrt())
+    }
+}
+
+/// He initialization: normally distributed weights with a variance of 2 / inputs, suited to
+/// ReLU layers.
+#[derive(Clone, Debug)]
+pub struct HeInitializer;
+
+impl Initializer for HeInitializer {
+    fn initialize(&self, _layer: usize, inputs: usize, outputs: usize) -> Vec<f32> {
+        normal(inputs * outputs, (2.0 / inputs as f64).sqrt())
+    }
+}
+
+/// Takes the weights of another network, layer by layer, e.g. to start training from a saved
+/// network with a different activation function or gradient descent.
+///
+/// # Panics
+/// `initialize` will panic if the layer doesn't match the dimensions of the other network's.
+#[derive(Clone, Debug)]
+pub struct FileInitializer {
+    weights: Vec<MatrixCm>,
+}
+
+impl FileInitializer {
+    /// Takes the weights of `network`.
+    pub fn from_network<A, F, G>(network: &Ann<A, F, G>) -> FileInitializer where
+        A: ActivationFunction,
+        F: ActivationFunction,
+        G: GradientDescent {
+        FileInitializer {
+            weights: network.weights.clone(),
+        }
+    }
+
+    /// Reads the weights of a network with the given topology, written by `write_network` or
+    /// `write_network_binary`, from the file at `filename`.
+    pub fn from_file(filename: &str, inputs: usize, hidden_layers: &[usize], outputs: usize) -> Result<FileInitializer, String> {
+        let mut network = Ann::<TanHActivationFunction, TanHActivationFunction, SimpleGradientDescent>::new(
+            inputs,
+            hidden_layers,
+            outputs,
+            &[],
+            SimpleGradientDescent,
+        );
+
+        match File::open(filename) {
+            Ok(file) => read_network(&mut BufReader::new(file), &mut network)?,
+            Err(_) => return Err(format!("Cannot open file: {}", filename)),
+        }
+
+        Ok(FileInitializer::from_network(&network))
+    }
+}
+
+impl Initializer for FileInitializer {
+    fn initialize(&self, layer: usize, inputs: usize, outputs: usize) -> Vec<f32> {
+        assert!(layer < self.weights.len(), "No weights for layer {}!", layer);
+
+        let weights = &self.weights[layer];
+        assert!(weights.rows == inputs && weights.columns == outputs, "Incorrect matrix dimensions!");
+        weights.values.clone()
+    }
+}
+
+fn normal(count: usize, standard_deviation: f64) -> Vec<f32> {
+    let mut distribution = Normal::new(0.0, standard_deviation);
+    (0..count).map(|_| distribution.sample(&mut thread_rng()) as f32).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use impls::tak::state::ann::ReLuActivationFunction;
+    use super::*;
+
+    // Returns the mean and standard deviation of `values`
+    fn statistics(values: &[f32]) -> (f32, f32) {
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let variance = values.iter().map(|value| (value - mean) * (value - mean)).sum::<f32>() / values.len() as f32;
+        (mean, variance.sqrt())
+    }
+
+    #[test]
+    fn test_shapes() {
+        let initializers: Vec<Box<Initializer>> = vec![
+            Box::new(ZeroInitializer),
+            Box::new(UniformInitializer::new(-1.0, 1.0)),
+            Box::new(XavierInitializer),
+            Box::new(HeInitializer),
+        ];
+        for initializer in &initializers {
+            assert_eq!(initializer.initialize(0, 3, 5).len(), 15);
+            assert_eq!(initializer.initialize(2, 7, 1).len(), 7);
+        }
+    }
+
+    #[test]
+    fn test_statistics() {
+        assert!(ZeroInitializer.initialize(0, 100, 100).iter().all(|&weight| weight == 0.0));
+
+        let weights = UniformInitializer::new(0.5, 1.5).initialize(0, 100, 100);
+        assert!(weights.iter().all(|&weight| weight >= 0.5 && weight < 1.5));
+        let (mean, deviation) = statistics(&weights);
+        assert!((mean - 1.0).abs() < 0.02, "{}", mean);
+        assert!((deviation - 1.0 / 12.0f32.sqrt()).abs() < 0.02, "{}", deviation);
+
+        // Xavier scales by the inputs and outputs; He by the inputs alone
+        let (mean, deviation) = statistics(&XavierInitializer.initialize(0, 300, 100));
+        assert!(mean.abs() < 0.01, "{}", mean);
+        assert!((deviation - (2.0 / 400.0f32).sqrt()).abs() < 0.005, "{}", deviation);
+
+        let (mean, deviation) = statistics(&HeInitializer.initialize(0, 300, 100));
+        assert!(mean.abs() < 0.01, "{}", mean);
+        assert!((deviation - (2.0 / 300.0f32).sqrt()).abs() < 0.005, "{}", deviation);
+    }
+
+    #[test]
+    fn test_per_layer() {
+        let mask = MatrixCm::from_vec(2, 3, vec![1.0, 0.0, 1.0, 0.0, 1.0, 0.0]);
+        let initializers: Vec<Box<Initializer>> = vec![Box::new(UniformInitializer::new(1.0, 2.0)), Box::new(ZeroInitializer)];
+        let network = Ann::<ReLuActivationFunction, TanHActivationFunction, _>::with_initializers(
+            2, &[3, 4], 1, &[Some(mask.clone())], &initializers, SimpleGradientDescent,
+        );
+
+        // The mask applies to the initial weights, and layers past the initializers use the default
+        for (weight, mask) in network.weights[0].values.iter().zip(&mask.values) {
+            assert!(if *mask == 0.0 { *weight == 0.0 } else { *weight >= 1.0 });
+        }
+        assert!(network.weights[1].values.iter().all(|&weight| weight == 0.0));
+        assert_eq!((network.weights[2].rows, network.weights[2].columns), (4, 1));
+        assert!(network.weights[2].values.iter().any(|&weight| weight != 0.0));
+
+        // A file initializer copies another network's weights exactly
+        let copy = Ann::<ReLuActivationFunction, TanHActivationFunction, _>::with_initializers(
+            2, &[3, 4], 1, &[], &[Box::new(FileInitializer::from_network(&network)) as Box<Initializer>], SimpleGradientDescent,
+        );
+        assert_eq!(copy.weights[0].values, network.weights[0].values);
+    }
+}
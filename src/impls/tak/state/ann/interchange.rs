@@ -0,0 +1,429 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::io::{self, Read, Write};
+use std::str::{self, FromStr};
+
+use impls::tak::state::ann::{ActivationFunction, Ann, GradientDescent, MatrixCm, MatrixRm};
+use impls::tak::state::ann::serialization::Matrix;
+
+/// The value of the `format` field of a network in the interchange format.
+pub const INTERCHANGE_FORMAT: &str = "zero_sum-ann";
+pub const INTERCHANGE_VERSION: u32 = 1;
+
+/// Writes the network as JSON, in a format meant for moving networks to and from other tools:
+///
+/// ```text
+/// {
+///   "format": "zero_sum-ann",
+///   "version": 1,
+///   "activation": "relu",
+///   "output_activation": "tanh",
+///   "inputs": 264,
+///   "layers": [
+///     {
+///       "weights": [[0.1, -0.2, ...], ...],
+///       "mask": null,
+///       "biases": [0.0, ...],
+///       "residual": false
+///     },
+///     ...
+///   ]
+/// }
+/// ```
+///
+/// Each layer computes `activation(inputs × weights + biases)`, with `output_activation` in
+/// place of `activation` for the last layer.  `weights` has a row for each of the layer's
+/// inputs and a column for each of its neurons, the transpose of the `[neurons][inputs]`
+/// layout used by many other tools.  `mask`, if not null, has the same shape, and zeros the
+/// weights that are held out of training.  A `residual` layer adds its inputs to its
+/// activations, and is as large as the layer before it.
+pub fn write_network_json<A, F, G, W>(writer: &mut W, network: &Ann<A, F, G>) -> io::Result<()> where
+    A: ActivationFunction,
+    F: ActivationFunction,
+    G: GradientDescent,
+    W: Write {
+    write!(writer, "{{\n  \"format\": \"{}\",\n  \"version\": {},\n", INTERCHANGE_FORMAT, INTERCHANGE_VERSION)?;
+    write!(writer, "  \"activation\": \"{}\",\n  \"output_activation\": \"{}\",\n", A::name(), F::name())?;
+    write!(writer, "  \"inputs\": {},\n  \"layers\": [", network.weights[0].rows)?;
+
+    for layer in 0..network.weights.len() {
+        write!(writer, "{}\n    {{\n      \"weights\": ", if layer > 0 { "," } else { "" })?;
+        write_matrix(writer, &network.weights[layer])?;
+
+        write!(writer, ",\n      \"mask\": ")?;
+        if let Some(ref mask) = network.weight_masks[layer] {
+            write_matrix(writer, mask)?;
+        } else {
+            write!(writer, "null")?;
+        }
+
+        write!(writer, ",\n      \"biases\": ")?;
+        write_values(writer, &network.biases[layer].values)?;
+        write!(writer, ",\n      \"residual\": {}\n    }}", network.residual[layer])?;
+    }
+
+    write!(writer, "\n  ]\n}}\n")
+}
+
+/// Reads a network written by `write_network_json`, or by another tool in the same format,
+/// into `network`.  The network must have the same activation functions and layer sizes.
+/// Nothing is changed unless the whole network is read.
+pub fn read_network_json<A, F, G, R>(reader: &mut R, network: &mut Ann<A, F, G>) -> Result<(), String> where
+    A: ActivationFunction,
+    F: ActivationFunction,
+    G: GradientDescent,
+    R: Read {
+    let mut text = String::new();
+    if reader.read_to_string(&mut text).is_err() {
+        return Err(String::from("Cannot read network!"));
+    }
+
+    let json = Parser { bytes: text.as_bytes(), position: 0 }.parse()?;
+
+    if json.get("format")?.as_str()? != INTERCHANGE_FORMAT {
+        return Err(String::from("Not a network in the interchange format!"));
+    }
+    if json.get("version")?.as_number::<f64>()? > INTERCHANGE_VERSION as f64 {
+        return Err(String::from("Unsupported interchange version!"));
+    }
+    if json.get("activation")?.as_str()? != A::name() || json.get("output_activation")?.as_str()? != F::name() {
+        return Err(String::from("Incorrect activation functions!"));
+    }
+    if json.get("inputs")?.as_number::<f64>()? != network.weights[0].rows as f64 {
+        return Err(String::from("Incorrect number of inputs!"));
+    }
+
+    let layers = json.get("layers")?.as_array()?;
+    if layers.len() != network.weights.len() {
+        return Err(String::from("Incorrect number of layers!"));
+    }
+
+    let mut decoded = Vec::with_capacity(layers.len());
+    for (layer, json) in layers.iter().enumerate() {
+        let (rows, columns) = (network.weights[layer].rows, network.weights[layer].columns);
+
+        let weights = read_matrix(json.get("weights")?, rows, columns)?;
+        let weight_mask = match *json.get("mask")? {
+            Json::Null => None,
+            ref mask => Some(read_matrix(mask, rows, columns)?),
+        };
+        let biases = read_values(json.get("biases")?)?;
+        if biases.len() != columns {
+            return Err(String::from("Incorrect number of biases!"));
+        }
+
+        let residual = match *json.get("residual")? {
+            Json::Bool(residual) => residual,
+            _ => return Err(String::from("Expected a boolean for residual!")),
+        };
+        if residual && (layer == 0 || layer == layers.len() - 1 || rows != columns) {
+            return Err(format!("Layer {} can't be residual!", layer));
+        }
+
+        decoded.push((weights, weight_mask, MatrixRm::from_vec(1, columns, biases), residual));
+    }
+
+    for (layer, (weights, weight_mask, biases, residual)) in decoded.into_iter().enumerate() {
+        network.weights[layer] = weights;
+        network.weight_masks[layer] = weight_mask;
+        network.biases[layer] = biases;
+        network.residual[layer] = residual;
+    }
+
+    Ok(())
+}
+
+fn write_matrix<W>(writer: &mut W, matrix: &MatrixCm) -> io::Result<()> where W: Write {
+    write!(writer, "[")?;
+    for row in 0..matrix.rows {
+        if row > 0 {
+            write!(writer, ", ")?;
+        }
+        write_values(writer, &matrix.get_row(row))?;
+    }
+    write!(writer, "]")
+}
+
+fn write_values<W>(writer: &mut W, values: &[f32]) -> io::Result<()> where W: Write {
+    write!(writer, "[")?;
+    for (i, value) in values.iter().enumerate() {
+        if !value.is_finite() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "JSON can't represent a non-finite value"));
+        }
+        write!(writer, "{}{}", if i > 0 { ", " } else { "" }, value)?;
+    }
+    write!(writer, "]")
+}
+
+fn read_matrix(json: &Json, rows: usize, columns: usize) -> Result<MatrixCm, String> {
+    let json_rows = json.as_array()?;
+    if json_rows.len() != rows {
+        return Err(String::from("Incorrect matrix dimensions!"));
+    }
+
+    let mut values = Vec::with_capacity(rows * columns);
+    for row in json_rows {
+        let row = read_values(row)?;
+        if row.len() != columns {
+            return Err(String::from("Incorrect matrix dimensions!"));
+        }
+        values.extend(row);
+    }
+
+    Ok(MatrixCm::from_row_major_vec(rows, columns, values))
+}
+
+fn read_values(json: &Json) -> Result<Vec<f32>, String> {
+    json.as_array()?.iter().map(|value| value.as_number::<f32>()).collect()
+}
+
+/// The JSON values that a network is made of.
+#[derive(Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    /// Kept as written, so that values convert straight to `f32` without rounding twice.
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Result<&Json, String> {
+        match *self {
+            Json::Object(ref fields) => match fields.iter().find(|field| field.0 == key) {
+                Some(field) => Ok(&field.1),
+                None => Err(format!("Missing field: {}", key)),
+            },
+            _ => Err(format!("Expected an object with field: {}", key)),
+        }
+    }
+
+    fn as_str(&self) -> Result<&str, String> {
+        match *self {
+            Json::String(ref string) => Ok(string),
+            _ => Err(String::from("Expected a string!")),
+        }
+    }
+
+    fn as_number<T>(&self) -> Result<T, String> where T: FromStr {
+        match *self {
+            Json::Number(ref number) => T::from_str(number).map_err(|_| format!("Cannot parse number: {}", number)),
+            _ => Err(String::from("Expected a number!")),
+        }
+    }
+
+    fn as_array(&self) -> Result<&[Json], String> {
+        match *self {
+            Json::Array(ref values) => Ok(values),
+            _ => Err(String::from("Expected an array!")),
+        }
+    }
+}
+
+/// Parses the subset of JSON the format needs: everything but string escapes beyond the
+/// simple ones.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(mut self) -> Result<Json, String> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if self.position < self.bytes.len() {
+            return Err(self.error("the end"));
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => Ok(Json::String(self.parse_string()?)),
+            Some(b'n') => self.parse_literal("null", Json::Null),
+            Some(b't') => self.parse_literal("true", Json::Bool(true)),
+            Some(b'f') => self.parse_literal("false", Json::Bool(false)),
+            Some(b'-') | Some(b'0'..=b'9') => self.parse_number(),
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, String> {
+        let mut fields = Vec::new();
+        self.position += 1;
+
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(Json::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let name = self.parse_string()?;
+            self.expect(b':')?;
+            fields.push((name, self.parse_value()?));
+
+            self.skip_whitespace();
+            match self.read_byte() {
+                Some(b',') => (),
+                Some(b'}') => return Ok(Json::Object(fields)),
+                _ => return Err(self.error("',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, String> {
+        let mut values = Vec::new();
+        self.position += 1;
+
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Json::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.read_byte() {
+                Some(b',') => (),
+                Some(b']') => return Ok(Json::Array(values)),
+                _ => return Err(self.error("',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        if self.read_byte() != Some(b'"') {
+            return Err(self.error("a string"));
+        }
+
+        let mut bytes = Vec::new();
+        loop {
+            match self.read_byte() {
+                Some(b'"') => break,
+                Some(b'\\') => bytes.push(match self.read_byte() {
+                    Some(b'"') => b'"',
+                    Some(b'\\') => b'\\',
+                    Some(b'/') => b'/',
+                    Some(b'n') => b'\n',
+                    Some(b't') => b'\t',
+                    _ => return Err(self.error("a supported escape")),
+                }),
+                Some(byte) => bytes.push(byte),
+                None => return Err(self.error("the end of the string")),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| String::from("Invalid UTF-8 in string!"))
+    }
+
+    fn parse_number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+        while let Some(b'-') | Some(b'+') | Some(b'.') | Some(b'e') | Some(b'E') | Some(b'0'..=b'9') = self.peek() {
+            self.position += 1;
+        }
+
+        let text = str::from_utf8(&self.bytes[start..self.position]).unwrap();
+        match f64::from_str(text) {
+            Ok(_) => Ok(Json::Number(String::from(text))),
+            _ => Err(format!("Cannot parse number: {}", text)),
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: Json) -> Result<Json, String> {
+        if self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error(literal))
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.read_byte() == Some(byte) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}'", byte as char)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).cloned()
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        let byte = self.peek();
+        self.position += 1;
+        byte
+    }
+
+    fn error(&self, expected: &str) -> String {
+        format!("Expected {} at position {}!", expected, self.position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use impls::tak::state::ann::{ReLuActivationFunction, SimpleGradientDescent, TanHActivationFunction};
+    use super::*;
+
+    type Network = Ann<ReLuActivationFunction, TanHActivationFunction, SimpleGradientDescent>;
+
+    #[test]
+    fn test_round_trip() {
+        let mut network = Network::new(3, &[4, 4], 1, &[Some(MatrixCm::from_vec(3, 4, vec![1.0; 12]))], SimpleGradientDescent);
+        network.set_residual(1, true);
+        network.biases[0].values[2] = -0.25;
+
+        let mut json = Vec::new();
+        write_network_json(&mut json, &network).unwrap();
+
+        let mut read = Network::new(3, &[4, 4], 1, &[], SimpleGradientDescent);
+        read_network_json(&mut &json[..], &mut read).unwrap();
+        for layer in 0..3 {
+            assert_eq!(read.weights[layer].values, network.weights[layer].values);
+            assert_eq!(read.biases[layer].values, network.biases[layer].values);
+            assert_eq!(read.weight_masks[layer].is_some(), layer == 0);
+            assert_eq!(read.residual[layer], layer == 1);
+        }
+
+        // The shapes and activations must match
+        let mut wrong = Network::new(3, &[5, 4], 1, &[], SimpleGradientDescent);
+        assert!(read_network_json(&mut &json[..], &mut wrong).is_err());
+        let mut wrong = Ann::<TanHActivationFunction, TanHActivationFunction, _>::new(3, &[4, 4], 1, &[], SimpleGradientDescent);
+        assert!(read_network_json(&mut &json[..], &mut wrong).is_err());
+        assert!(read_network_json(&mut &json[..json.len() / 2], &mut read).is_err());
+    }
+}
@@ -17,6 +17,7 @@
 // Copyright 2016-2017 Chris Foster
 //
 
+use std::cmp;
 use std::ops::{Index, IndexMut};
 
 /// Column-major matrix
@@ -65,6 +66,42 @@ impl MatrixCm {
     pub fn same_size(&self, other: &MatrixCm) -> bool {
         self.rows == other.rows && self.columns == other.columns
     }
+
+    /// Resizes the matrix, keeping the values that are within both the old and new dimensions.
+    /// New values are zero.
+    pub fn resize_preserving(&mut self, rows: usize, columns: usize) {
+        self.values = resize_major(&self.values, self.columns, self.rows, columns, rows);
+        self.rows = rows;
+        self.columns = columns;
+    }
+
+    /// Inserts `values` as a new row before `index`.
+    pub fn insert_row(&mut self, index: usize, values: &[f32]) {
+        assert!(index <= self.rows && values.len() == self.columns, "Invalid row insertion!");
+        self.values = insert_minor(&self.values, self.rows, index, values);
+        self.rows += 1;
+    }
+
+    /// Inserts `values` as a new column before `index`.
+    pub fn insert_column(&mut self, index: usize, values: &[f32]) {
+        assert!(index <= self.columns && values.len() == self.rows, "Invalid column insertion!");
+        insert_major(&mut self.values, self.rows, index, values);
+        self.columns += 1;
+    }
+
+    /// Removes the row at `index`, moving the rows after it up.
+    pub fn remove_row(&mut self, index: usize) {
+        assert!(index < self.rows, "Invalid row removal!");
+        self.values = remove_minor(&self.values, self.rows, index);
+        self.rows -= 1;
+    }
+
+    /// Removes the column at `index`, moving the columns after it left.
+    pub fn remove_column(&mut self, index: usize) {
+        assert!(index < self.columns, "Invalid column removal!");
+        remove_major(&mut self.values, self.rows, index);
+        self.columns -= 1;
+    }
 }
 
 impl Index<usize> for MatrixCm {
@@ -116,6 +153,8 @@ impl MatrixRm {
         self.rows == other.rows && self.columns == other.columns
     }
 
+    /// Resizes the matrix without initializing or rearranging its values, so the contents are
+    /// garbage afterward.  Use `resize_preserving` to keep them.
     pub fn resize(&mut self, rows: usize, columns: usize) {
         self.rows = rows;
         self.columns = columns;
@@ -125,6 +164,42 @@ impl MatrixRm {
         }
         unsafe { self.values.set_len(self.rows * self.columns); }
     }
+
+    /// Resizes the matrix, keeping the values that are within both the old and new dimensions.
+    /// New values are zero.
+    pub fn resize_preserving(&mut self, rows: usize, columns: usize) {
+        self.values = resize_major(&self.values, self.rows, self.columns, rows, columns);
+        self.rows = rows;
+        self.columns = columns;
+    }
+
+    /// Inserts `values` as a new row before `index`.
+    pub fn insert_row(&mut self, index: usize, values: &[f32]) {
+        assert!(index <= self.rows && values.len() == self.columns, "Invalid row insertion!");
+        insert_major(&mut self.values, self.columns, index, values);
+        self.rows += 1;
+    }
+
+    /// Inserts `values` as a new column before `index`.
+    pub fn insert_column(&mut self, index: usize, values: &[f32]) {
+        assert!(index <= self.columns && values.len() == self.rows, "Invalid column insertion!");
+        self.values = insert_minor(&self.values, self.columns, index, values);
+        self.columns += 1;
+    }
+
+    /// Removes the row at `index`, moving the rows after it up.
+    pub fn remove_row(&mut self, index: usize) {
+        assert!(index < self.rows, "Invalid row removal!");
+        remove_major(&mut self.values, self.columns, index);
+        self.rows -= 1;
+    }
+
+    /// Removes the column at `index`, moving the columns after it left.
+    pub fn remove_column(&mut self, index: usize) {
+        assert!(index < self.columns, "Invalid column removal!");
+        self.values = remove_minor(&self.values, self.columns, index);
+        self.columns -= 1;
+    }
 }
 
 impl Index<usize> for MatrixRm {
@@ -144,3 +219,123 @@ impl IndexMut<usize> for MatrixRm {
         &mut self.values[index * self.columns..(index + 1) * self.columns]
     }
 }
+
+// The helpers below work in terms of the storage order: a "major" line is a row of a row-major
+// matrix or a column of a column-major one, and is contiguous in memory.
+
+fn resize_major(values: &[f32], majors: usize, length: usize, new_majors: usize, new_length: usize) -> Vec<f32> {
+    let mut resized = vec![0.0; new_majors * new_length];
+    let kept = cmp::min(length, new_length);
+    for major in 0..cmp::min(majors, new_majors) {
+        resized[major * new_length..major * new_length + kept].copy_from_slice(&values[major * length..major * length + kept]);
+    }
+    resized
+}
+
+fn insert_major(values: &mut Vec<f32>, length: usize, index: usize, line: &[f32]) {
+    let position = index * length;
+    values.splice(position..position, line.iter().cloned());
+}
+
+fn remove_major(values: &mut Vec<f32>, length: usize, index: usize) {
+    values.drain(index * length..(index + 1) * length);
+}
+
+fn insert_minor(values: &[f32], length: usize, index: usize, line: &[f32]) -> Vec<f32> {
+    let mut inserted = Vec::with_capacity(values.len() + line.len());
+    for (major, &value) in line.iter().enumerate() {
+        let start = major * length;
+        inserted.extend_from_slice(&values[start..start + index]);
+        inserted.push(value);
+        inserted.extend_from_slice(&values[start + index..start + length]);
+    }
+    inserted
+}
+
+fn remove_minor(values: &[f32], length: usize, index: usize) -> Vec<f32> {
+    let mut removed = Vec::with_capacity(values.len() - values.len() / cmp::max(length, 1));
+    for major in 0..values.len() / cmp::max(length, 1) {
+        let start = major * length;
+        removed.extend_from_slice(&values[start..start + index]);
+        removed.extend_from_slice(&values[start + index + 1..start + length]);
+    }
+    removed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The rows of a column-major matrix
+    fn cm_rows(matrix: &MatrixCm) -> Vec<Vec<f32>> {
+        (0..matrix.rows).map(|row| (0..matrix.columns).map(|column| matrix[column][row]).collect()).collect()
+    }
+
+    fn rm_rows(matrix: &MatrixRm) -> Vec<Vec<f32>> {
+        (0..matrix.rows).map(|row| matrix[row].to_vec()).collect()
+    }
+
+    #[test]
+    fn test_resize_preserving() {
+        let values = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let mut cm = MatrixCm::from_row_major_vec(2, 3, values.clone());
+        let mut rm = MatrixRm::from_vec(2, 3, values);
+
+        cm.resize_preserving(3, 2);
+        rm.resize_preserving(3, 2);
+        let expected = vec![vec![1.0, 2.0], vec![4.0, 5.0], vec![0.0, 0.0]];
+        assert_eq!(cm_rows(&cm), expected);
+        assert_eq!(rm_rows(&rm), expected);
+        assert_eq!((cm.values.len(), rm.values.len()), (6, 6));
+
+        cm.resize_preserving(1, 4);
+        rm.resize_preserving(1, 4);
+        assert_eq!(cm_rows(&cm), vec![vec![1.0, 2.0, 0.0, 0.0]]);
+        assert_eq!(rm_rows(&rm), vec![vec![1.0, 2.0, 0.0, 0.0]]);
+
+        cm.resize_preserving(0, 0);
+        rm.resize_preserving(0, 0);
+        assert!(cm.values.is_empty() && rm.values.is_empty());
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let values = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let mut cm = MatrixCm::from_row_major_vec(2, 3, values.clone());
+        let mut rm = MatrixRm::from_vec(2, 3, values);
+
+        cm.insert_row(1, &[7.0, 8.0, 9.0]);
+        rm.insert_row(1, &[7.0, 8.0, 9.0]);
+        let expected = vec![vec![1.0, 2.0, 3.0], vec![7.0, 8.0, 9.0], vec![4.0, 5.0, 6.0]];
+        assert_eq!(cm_rows(&cm), expected);
+        assert_eq!(rm_rows(&rm), expected);
+
+        cm.insert_column(3, &[10.0, 11.0, 12.0]);
+        rm.insert_column(3, &[10.0, 11.0, 12.0]);
+        cm.insert_column(0, &[0.0, 0.5, 1.0]);
+        rm.insert_column(0, &[0.0, 0.5, 1.0]);
+        let expected = vec![vec![0.0, 1.0, 2.0, 3.0, 10.0], vec![0.5, 7.0, 8.0, 9.0, 11.0], vec![1.0, 4.0, 5.0, 6.0, 12.0]];
+        assert_eq!(cm_rows(&cm), expected);
+        assert_eq!(rm_rows(&rm), expected);
+        assert_eq!((cm.rows, cm.columns, rm.rows, rm.columns), (3, 5, 3, 5));
+
+        cm.remove_row(0);
+        rm.remove_row(0);
+        cm.remove_column(2);
+        rm.remove_column(2);
+        let expected = vec![vec![0.5, 7.0, 9.0, 11.0], vec![1.0, 4.0, 6.0, 12.0]];
+        assert_eq!(cm_rows(&cm), expected);
+        assert_eq!(rm_rows(&rm), expected);
+
+        // Down to nothing and back
+        for _ in 0..2 {
+            cm.remove_row(0);
+            rm.remove_row(0);
+        }
+        assert_eq!((cm.rows, cm.columns, rm.rows, rm.columns), (0, 4, 0, 4));
+        cm.insert_row(0, &[1.0, 2.0, 3.0, 4.0]);
+        rm.insert_row(0, &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(cm_rows(&cm), vec![vec![1.0, 2.0, 3.0, 4.0]]);
+        assert_eq!(rm_rows(&rm), vec![vec![1.0, 2.0, 3.0, 4.0]]);
+    }
+}
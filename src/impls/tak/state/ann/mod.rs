@@ -20,16 +20,22 @@
 use std::cell::RefCell;
 use std::cmp;
 use std::marker::PhantomData;
+use std::thread;
 
 use blas::c as blas;
-use rand::distributions::{Normal, Sample};
-use rand::{thread_rng};
 
 pub use self::activation_function::{ActivationFunction, ReLuActivationFunction, TanHActivationFunction};
-pub use self::feature_representation::gather_features;
+pub use impls::tak::state::{FEATURE_COUNT, gather_features};
 pub use self::gradient_descent::{AdadeltaGradientDescent, GradientDescent, SimpleGradientDescent};
+pub use self::initializer::{FileInitializer, HeInitializer, Initializer, UniformInitializer, XavierInitializer, ZeroInitializer};
+pub use self::interchange::{read_network_json, write_network_json};
 pub use self::matrix::{MatrixCm, MatrixRm};
-pub use self::serialization::{read_network, write_network};
+pub use self::serialization::{read_network, write_network, write_network_binary};
+pub use self::trainer::{EpochReport, LearningRateSchedule, Trainer, TrainingReport};
+
+/// The fewest rows each thread is given when a batch is split across threads.  Smaller batches are
+/// run on the calling thread, since copying the network would cost more than it saves.
+pub const MIN_ROWS_PER_THREAD: usize = 16;
 
 /// Artificial neural network
 #[derive(Clone, Debug)]
@@ -43,23 +49,42 @@ pub struct Ann<A, F, G> where
     weights: Vec<MatrixCm>,
     weight_masks: Vec<Option<MatrixCm>>,
     biases: Vec<MatrixRm>,
+    residual: Vec<bool>,
 
     gradient_descent: G,
 
     pre_activations_buffer: RefCell<MatrixRm>,
     activations_buffer: RefCell<MatrixRm>,
+
+    threads: usize,
 }
 
 impl<A, F, G> Ann<A, F, G> where
     A: ActivationFunction,
     F: ActivationFunction,
     G: GradientDescent {
+    /// Creates a network whose weights are chosen by the hidden-layer activation function's
+    /// default initializer.
     pub fn new(
         inputs: usize,
         hidden_layers: &[usize],
         outputs: usize,
         weight_masks: &[Option<MatrixCm>],
         gradient_descent: G,
+    ) -> Ann<A, F, G> {
+        Ann::with_initializers(inputs, hidden_layers, outputs, weight_masks, &[], gradient_descent)
+    }
+
+    /// Creates a network whose weights are chosen layer by layer by `initializers`.  Layers
+    /// beyond the end of `initializers` use the hidden-layer activation function's default.
+    /// Weight masks are applied to the initial weights.
+    pub fn with_initializers(
+        inputs: usize,
+        hidden_layers: &[usize],
+        outputs: usize,
+        weight_masks: &[Option<MatrixCm>],
+        initializers: &[Box<Initializer>],
+        gradient_descent: G,
     ) -> Ann<A, F, G> {
         assert!(inputs > 0, "Invalid number of inputs!");
         assert!(hidden_layers.iter().find(|&&l| l == 0).is_none(), "Invalid number of hidden-layer neurons!");
@@ -81,9 +106,11 @@ impl<A, F, G> Ann<A, F, G> where
             weights: Vec::new(),
             weight_masks: Vec::new(),
             biases: Vec::new(),
+            residual: Vec::new(),
             gradient_descent: gradient_descent,
             pre_activations_buffer: activations_buffer.clone(),
             activations_buffer: activations_buffer,
+            threads: 1,
         };
 
         for layer in 0..hidden_layers.len() + 1 {
@@ -105,30 +132,73 @@ impl<A, F, G> Ann<A, F, G> where
                 None
             };
 
-            ann.weights.push(MatrixCm::from_vec(
-                input_size, output_size,
-                if A::new().as_any().is::<ReLuActivationFunction>() {
-                    let mut distribution = Normal::new(0.0, (2.0 / output_size as f64).sqrt());
-                    (0..input_size * output_size).map(|i|
-                        distribution.sample(&mut thread_rng()) as f32 * if let Some(ref mask) = weight_mask {
-                            mask.values[i]
-                        } else {
-                            1.0
-                        }
-                    ).collect::<Vec<_>>()
-                } else {
-                    vec![0.0; input_size * output_size]
-                },
-            ));
+            let mut weights = if layer < initializers.len() {
+                initializers[layer].initialize(layer, input_size, output_size)
+            } else {
+                A::initializer().initialize(layer, input_size, output_size)
+            };
+            if let Some(ref mask) = weight_mask {
+                for (weight, mask) in weights.iter_mut().zip(&mask.values) {
+                    *weight *= *mask;
+                }
+            }
+
+            ann.weights.push(MatrixCm::from_vec(input_size, output_size, weights));
 
             ann.weight_masks.push(weight_mask);
 
             ann.biases.push(MatrixRm::zeros(1, output_size));
+            ann.residual.push(false);
         }
 
         ann
     }
 
+    /// Sets whether hidden layer `layer` adds its inputs to its outputs, so that it learns a residual
+    /// on top of the previous layer's activations.  The layer must have as many neurons as the
+    /// previous layer, and the first and output layers can't be residual.
+    pub fn set_residual(&mut self, layer: usize, residual: bool) {
+        assert!(layer > 0 && layer < self.weights.len() - 1, "Invalid residual layer!");
+        assert!(self.weights[layer].rows == self.weights[layer].columns, "Residual layers must have as many neurons as their inputs!");
+        self.residual[layer] = residual;
+    }
+
+    pub fn is_residual(&self, layer: usize) -> bool {
+        self.residual[layer]
+    }
+
+    /// Sets the number of threads that `propagate_forward_simple` and `train` split the rows of a
+    /// batch across.  Each thread works on its own copy of the network.
+    pub fn set_threads(&mut self, threads: usize) {
+        assert!(threads > 0, "Invalid number of threads!");
+        self.threads = threads;
+    }
+
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Returns the number of threads a batch of `rows` rows should be split across.
+    fn threads_for(&self, rows: usize) -> usize {
+        cmp::min(self.threads, rows / MIN_ROWS_PER_THREAD).max(1)
+    }
+
+    /// Returns a single-threaded copy of the network's parameters, for use on a worker thread.
+    fn worker(&self) -> Ann<A, F, SimpleGradientDescent> {
+        Ann {
+            activation_function: PhantomData,
+            final_activation_function: PhantomData,
+            weights: self.weights.clone(),
+            weight_masks: vec![None; self.weights.len()],
+            biases: self.biases.clone(),
+            residual: self.residual.clone(),
+            gradient_descent: SimpleGradientDescent,
+            pre_activations_buffer: RefCell::new(self.pre_activations_buffer.borrow().clone()),
+            activations_buffer: RefCell::new(self.activations_buffer.borrow().clone()),
+            threads: 1,
+        }
+    }
+
     pub fn allocate_activation_buffers(&self, inputs: usize) -> (Vec<MatrixRm>, Vec<MatrixRm>) {
         let buffer = self.weights.iter().map(|l| MatrixRm::zeros(inputs, l.columns)).collect::<Vec<_>>();
         (buffer.clone(), buffer)
@@ -199,6 +269,14 @@ impl<A, F, G> Ann<A, F, G> where
                     F::f(pre_activations.values[i])
                 };
             }
+
+            // activations += layer_inputs
+            if self.residual[layer] {
+                let (previous, current) = activations.split_at_mut(layer);
+                for (activation, input) in current[0].values.iter_mut().zip(&previous[layer - 1].values) {
+                    *activation += *input;
+                }
+            }
         }
     }
 
@@ -207,6 +285,31 @@ impl<A, F, G> Ann<A, F, G> where
         debug_assert!(outputs.columns == self.weights.last().unwrap().columns, "Incorrect number of outputs!");
         debug_assert!(inputs.rows == outputs.rows, "Row mismatch between inputs and outputs!");
 
+        let threads = self.threads_for(inputs.rows);
+        if threads == 1 {
+            self.propagate_forward_serial(inputs, outputs);
+            return;
+        }
+
+        let handles = split_rows(inputs, threads).into_iter().map(|chunk| {
+            let worker = self.worker();
+            let columns = outputs.columns;
+            thread::spawn(move || {
+                let mut chunk_outputs = MatrixRm::zeros(chunk.rows, columns);
+                worker.propagate_forward_serial(&chunk, &mut chunk_outputs);
+                chunk_outputs
+            })
+        }).collect::<Vec<_>>();
+
+        let mut offset = 0;
+        for handle in handles {
+            let chunk_outputs = handle.join().unwrap();
+            outputs.values[offset..offset + chunk_outputs.values.len()].copy_from_slice(&chunk_outputs.values);
+            offset += chunk_outputs.values.len();
+        }
+    }
+
+    fn propagate_forward_serial(&self, inputs: &MatrixRm, outputs: &mut MatrixRm) {
         for layer in 0..self.weights.len() {
             let mut pre_activations = self.pre_activations_buffer.borrow_mut();
             let mut activations = self.activations_buffer.borrow_mut();
@@ -246,7 +349,12 @@ impl<A, F, G> Ann<A, F, G> where
                 }
             }
 
-            if layer < self.weights.len() - 1 {
+            if self.residual[layer] {
+                // The layer's inputs are still in the buffer, and are the same size as its outputs
+                for i in 0..pre_activations.values.len() {
+                    activations.values[i] += A::f(pre_activations.values[i]);
+                }
+            } else if layer < self.weights.len() - 1 {
                 activations.resize(pre_activations.rows, pre_activations.columns);
 
                 for i in 0..pre_activations.values.len() {
@@ -313,6 +421,10 @@ impl<A, F, G> Ann<A, F, G> where
         let mut delta = error_derivatives.clone();
         let mut delta_tmp = delta.clone();
 
+        // The gradient of the error with respect to the current layer's activations, kept only when
+        // a residual connection passes it straight on to the previous layer
+        let mut activation_gradients = MatrixRm::zeros(0, 0);
+
         for layer in (0..self.weights.len()).rev() {
             // bias_gradients[layer] = delta, all inputs summed
             bias_gradients[layer].values.clone_from_slice(&delta[0]);
@@ -352,6 +464,20 @@ impl<A, F, G> Ann<A, F, G> where
                     0.0, delta_tmp.values.as_mut_slice(), n,
                 );
 
+                // A residual layer's inputs also reach the error directly, through its activations
+                if self.residual[layer] {
+                    blas::saxpy(
+                        delta_tmp.values.len() as i32,
+                        1.0,
+                        &activation_gradients.values, 1,
+                        delta_tmp.values.as_mut_slice(), 1,
+                    );
+                }
+
+                if self.residual[layer - 1] {
+                    activation_gradients.clone_from(&delta_tmp);
+                }
+
                 delta.resize(delta_tmp.rows, delta_tmp.columns);
 
                 for i in 0..delta_tmp.values.len() {
@@ -366,6 +492,40 @@ impl<A, F, G> Ann<A, F, G> where
         debug_assert!(inputs.rows == targets.rows, "Incorrect number of targets!");
         debug_assert!(targets.columns == self.weights.last().unwrap().columns, "Incorrect number of outputs!");
 
+        let threads = self.threads_for(inputs.rows);
+        let (weight_gradients, bias_gradients) = if threads == 1 {
+            self.calculate_gradients(inputs, targets)
+        } else {
+            // The gradients are sums over the rows, so each thread's share can simply be added together
+            let handles = split_rows(inputs, threads).into_iter().zip(split_rows(targets, threads)).map(|(chunk_inputs, chunk_targets)| {
+                let worker = self.worker();
+                thread::spawn(move || worker.calculate_gradients(&chunk_inputs, &chunk_targets))
+            }).collect::<Vec<_>>();
+
+            let (mut weight_gradients, mut bias_gradients) = self.allocate_gradient_buffers();
+            for handle in handles {
+                let (chunk_weight_gradients, chunk_bias_gradients) = handle.join().unwrap();
+                for (sum, chunk) in weight_gradients.iter_mut().zip(chunk_weight_gradients.iter()) {
+                    blas::saxpy(sum.values.len() as i32, 1.0, &chunk.values, 1, &mut sum.values, 1);
+                }
+                for (sum, chunk) in bias_gradients.iter_mut().zip(chunk_bias_gradients.iter()) {
+                    blas::saxpy(sum.values.len() as i32, 1.0, &chunk.values, 1, &mut sum.values, 1);
+                }
+            }
+            (weight_gradients, bias_gradients)
+        };
+
+        self.gradient_descent.descend(
+            &mut self.weights,
+            &self.weight_masks,
+            &mut self.biases,
+            &weight_gradients,
+            &bias_gradients,
+            rate,
+        );
+    }
+
+    fn calculate_gradients(&self, inputs: &MatrixRm, targets: &MatrixRm) -> (Vec<MatrixCm>, Vec<MatrixRm>) {
         let (mut pre_activations, mut activations) = self.allocate_activation_buffers(inputs.rows);
         self.propagate_forward(
             inputs,
@@ -384,17 +544,18 @@ impl<A, F, G> Ann<A, F, G> where
             &mut weight_gradients, &mut bias_gradients,
         );
 
-        self.gradient_descent.descend(
-            &mut self.weights,
-            &self.weight_masks,
-            &mut self.biases,
-            &weight_gradients,
-            &bias_gradients,
-            rate,
-        );
+        (weight_gradients, bias_gradients)
     }
 }
 
+/// Splits the rows of `matrix` into `parts` contiguous matrices of nearly equal size.
+fn split_rows(matrix: &MatrixRm, parts: usize) -> Vec<MatrixRm> {
+    let rows_per_part = (matrix.rows + parts - 1) / parts;
+    matrix.values.chunks(rows_per_part * matrix.columns).map(|values|
+        MatrixRm::from_vec(values.len() / matrix.columns, matrix.columns, values.to_vec())
+    ).collect()
+}
+
 /// Returns the total sum of the error.
 pub fn calculate_error<F>(outputs: &MatrixRm, targets: &MatrixRm, error: &mut MatrixRm) -> f32 where F: ActivationFunction {
     debug_assert!(outputs.same_size(targets), "outputs's dimensions are different than targets's!");
@@ -456,7 +617,44 @@ pub fn calculate_error_derivatives<F>(outputs: &MatrixRm, targets: &MatrixRm, er
 }
 
 mod activation_function;
-mod feature_representation;
 mod gradient_descent;
+mod initializer;
+mod interchange;
 mod matrix;
 mod serialization;
+mod trainer;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_residual_forward() {
+        let mut network = Ann::<ReLuActivationFunction, TanHActivationFunction, _>::new(2, &[2, 2], 1, &[], SimpleGradientDescent);
+        network.weights[0].values = vec![1.0, 0.0, 0.0, 1.0];
+        network.weights[1].values = vec![1.0, 0.0, 0.0, 1.0];
+        network.weights[2].values = vec![1.0, 1.0];
+
+        let inputs = MatrixRm::from_vec(1, 2, vec![0.1, 0.2]);
+        let forward = |network: &Ann<_, _, _>| {
+            let (mut pre_activations, mut activations) = network.allocate_activation_buffers(1);
+            network.propagate_forward(&inputs, &mut pre_activations, &mut activations);
+
+            let mut outputs = MatrixRm::zeros(1, 1);
+            network.propagate_forward_simple(&inputs, &mut outputs);
+            assert_eq!(outputs.values, activations[2].values);
+
+            activations
+        };
+
+        let activations = forward(&network);
+        assert_eq!(activations[1].values, vec![0.1, 0.2]);
+        assert_eq!(activations[2].values, vec![0.3f32.tanh()]);
+
+        // The residual layer's inputs are added to its activations
+        network.set_residual(1, true);
+        let activations = forward(&network);
+        assert_eq!(activations[1].values, vec![0.2, 0.4]);
+        assert_eq!(activations[2].values, vec![0.6f32.tanh()]);
+    }
+}
@@ -21,24 +21,35 @@ use std::fs::File;
 use std::io::{self, BufRead, BufReader, Write};
 use std::str::FromStr;
 
-use impls::tak::state::ann::{ActivationFunction, Ann, GradientDescent, MatrixCm, MatrixRm};
+use impls::tak::state::ann::{ActivationFunction, Ann, GradientDescent, MatrixCm, MatrixRm, read_network_json};
+use persist::{self, Decoder, Encode, PersistError};
+
+/// Identifies a network written by `write_network_binary`.
+pub const NETWORK_MAGIC: &[u8; 4] = b"ZSNN";
+pub const NETWORK_VERSION: u32 = 1;
 
 pub trait Matrix {
     fn from_vec(rows: usize, columns: usize, values: Vec<f32>) -> Self;
+    fn from_storage_vec(rows: usize, columns: usize, values: Vec<f32>) -> Self;
     fn get_dimensions(&self) -> (usize, usize);
     fn get_row(&self, row: usize) -> Vec<f32>;
+    fn get_storage(&self) -> &[f32];
 }
 
 impl Matrix for MatrixCm {
     fn from_vec(rows: usize, columns: usize, values: Vec<f32>) -> MatrixCm { MatrixCm::from_row_major_vec(rows, columns, values) }
+    fn from_storage_vec(rows: usize, columns: usize, values: Vec<f32>) -> MatrixCm { MatrixCm::from_vec(rows, columns, values) }
     fn get_dimensions(&self) -> (usize, usize) { (self.rows, self.columns) }
     fn get_row(&self, row: usize) -> Vec<f32> { let mut values = Vec::new(); for i in 0..self.columns { values.push(self.values[i * self.rows + row]); } values }
+    fn get_storage(&self) -> &[f32] { &self.values }
 }
 
 impl Matrix for MatrixRm {
     fn from_vec(rows: usize, columns: usize, values: Vec<f32>) -> MatrixRm { MatrixRm::from_vec(rows, columns, values) }
+    fn from_storage_vec(rows: usize, columns: usize, values: Vec<f32>) -> MatrixRm { MatrixRm::from_vec(rows, columns, values) }
     fn get_dimensions(&self) -> (usize, usize) { (self.rows, self.columns) }
     fn get_row(&self, row: usize) -> Vec<f32> { self[row].to_vec() }
+    fn get_storage(&self) -> &[f32] { &self.values }
 }
 
 fn read_line(file: &mut BufReader<File>) -> io::Result<Vec<String>> {
@@ -106,7 +117,33 @@ fn write_matrix<M: Matrix>(file: &mut File, matrix: &M, precision: usize) -> io:
     Ok(())
 }
 
+/// Reads a network written by `write_network`, `write_network_binary`, or `write_network_json`,
+/// detecting the format from the start of the file.
 pub fn read_network<A, F, G>(file: &mut BufReader<File>, network: &mut Ann<A, F, G>) -> Result<(), String> where
+    A: ActivationFunction,
+    F: ActivationFunction,
+    G: GradientDescent {
+    let (binary, json) = match file.fill_buf() {
+        Ok(buffer) => (
+            buffer.starts_with(NETWORK_MAGIC),
+            buffer.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{'),
+        ),
+        _ => return Err(String::from("Cannot read network!")),
+    };
+
+    if json {
+        read_network_json(file, network)
+    } else if binary {
+        match read_network_binary(file, network) {
+            Ok(()) => Ok(()),
+            Err(error) => Err(format!("{}", error)),
+        }
+    } else {
+        read_network_text(file, network)
+    }
+}
+
+fn read_network_text<A, F, G>(file: &mut BufReader<File>, network: &mut Ann<A, F, G>) -> Result<(), String> where
     A: ActivationFunction,
     F: ActivationFunction,
     G: GradientDescent {
@@ -164,6 +201,7 @@ pub fn read_network<A, F, G>(file: &mut BufReader<File>, network: &mut Ann<A, F,
     Ok(())
 }
 
+/// Writes the network in a readable text format.
 pub fn write_network<A, F, G>(file: &mut File, network: &Ann<A, F, G>) -> io::Result<()> where
     A: ActivationFunction,
     F: ActivationFunction,
@@ -188,3 +226,179 @@ pub fn write_network<A, F, G>(file: &mut File, network: &Ann<A, F, G>) -> io::Re
     Ok(())
 }
 
+
+/// Writes the network in a compact binary format: the header is followed by a section of layer
+/// sizes, then a section per layer holding its weights, weight mask, and biases as raw `f32`s.
+/// The text format stores each value as roughly a dozen characters; this stores four bytes.
+pub fn write_network_binary<A, F, G>(file: &mut File, network: &Ann<A, F, G>) -> io::Result<()> where
+    A: ActivationFunction,
+    F: ActivationFunction,
+    G: GradientDescent {
+    let mut writer = persist::Writer::new(file, NETWORK_MAGIC, NETWORK_VERSION)?;
+
+    let mut sizes = Vec::new();
+    sizes.write_u32(network.weights.len() as u32);
+    sizes.write_u32(network.weights[0].rows as u32);
+    for weights in &network.weights {
+        sizes.write_u32(weights.columns as u32);
+    }
+    writer.write_section(&sizes)?;
+
+    for layer in 0..network.weights.len() {
+        let mut bytes = Vec::new();
+        encode_matrix(&mut bytes, &network.weights[layer]);
+
+        if let Some(ref mask) = network.weight_masks[layer] {
+            bytes.write_u8(1);
+            encode_matrix(&mut bytes, mask);
+        } else {
+            bytes.write_u8(0);
+        }
+
+        encode_matrix(&mut bytes, &network.biases[layer]);
+        writer.write_section(&bytes)?;
+    }
+
+    writer.finish()?;
+    Ok(())
+}
+
+fn read_network_binary<A, F, G>(file: &mut BufReader<File>, network: &mut Ann<A, F, G>) -> Result<(), PersistError> where
+    A: ActivationFunction,
+    F: ActivationFunction,
+    G: GradientDescent {
+    let mut reader = persist::Reader::new(file, NETWORK_MAGIC, NETWORK_VERSION)?;
+
+    let sizes = match reader.read_section()? {
+        Some(sizes) => sizes,
+        None => return Err(PersistError::Malformed(String::from("Missing network dimensions"))),
+    };
+    let mut decoder = Decoder::new(&sizes);
+    if decoder.read_u32()? as usize != network.weights.len() {
+        return Err(PersistError::Malformed(String::from("Incorrect number of layers")));
+    }
+    if decoder.read_u32()? as usize != network.weights[0].rows {
+        return Err(PersistError::Malformed(String::from("Incorrect number of inputs")));
+    }
+    for weights in &network.weights {
+        if decoder.read_u32()? as usize != weights.columns {
+            return Err(PersistError::Malformed(String::from("Incorrect layer size")));
+        }
+    }
+
+    // Decode everything before touching the network, so a damaged file leaves it unchanged
+    let mut layers = Vec::with_capacity(network.weights.len());
+    for layer in 0..network.weights.len() {
+        let bytes = match reader.read_section()? {
+            Some(bytes) => bytes,
+            None => return Err(PersistError::Truncated { section: layer + 1 }),
+        };
+        let mut decoder = Decoder::new(&bytes);
+
+        let weights = decode_matrix::<MatrixCm>(&mut decoder)?;
+        let weight_mask = match decoder.read_u8()? {
+            0 => None,
+            1 => Some(decode_matrix::<MatrixCm>(&mut decoder)?),
+            _ => return Err(PersistError::Malformed(String::from("Invalid weight mask flag"))),
+        };
+        let biases = decode_matrix::<MatrixRm>(&mut decoder)?;
+
+        if !weights.same_size(&network.weights[layer]) ||
+           !weight_mask.as_ref().map_or(true, |mask| mask.same_size(&network.weights[layer])) ||
+           !biases.same_size(&network.biases[layer]) {
+            return Err(PersistError::Malformed(String::from("Incorrect matrix dimensions")));
+        }
+
+        layers.push((weights, weight_mask, biases));
+    }
+
+    for (layer, (weights, weight_mask, biases)) in layers.into_iter().enumerate() {
+        network.weights[layer] = weights;
+        network.weight_masks[layer] = weight_mask;
+        network.biases[layer] = biases;
+    }
+
+    Ok(())
+}
+
+fn encode_matrix<M: Matrix>(bytes: &mut Vec<u8>, matrix: &M) {
+    let (rows, columns) = matrix.get_dimensions();
+    bytes.write_u32(rows as u32);
+    bytes.write_u32(columns as u32);
+    for value in matrix.get_storage() {
+        bytes.write_u32(value.to_bits());
+    }
+}
+
+fn decode_matrix<M: Matrix>(decoder: &mut Decoder) -> Result<M, PersistError> {
+    let rows = decoder.read_u32()? as usize;
+    let columns = decoder.read_u32()? as usize;
+
+    // Take the whole matrix up front so a bad length can't cause a huge allocation
+    let mut values_decoder = Decoder::new(decoder.read_bytes(rows * columns * 4)?);
+    let values = (0..rows * columns).map(|_| values_decoder.read_u32().map(f32::from_bits)).collect::<Result<Vec<_>, _>>()?;
+
+    Ok(M::from_storage_vec(rows, columns, values))
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs::{self, OpenOptions};
+
+    use impls::tak::state::ann::{ReLuActivationFunction, SimpleGradientDescent, TanHActivationFunction};
+    use super::*;
+
+    type Network = Ann<ReLuActivationFunction, TanHActivationFunction, SimpleGradientDescent>;
+
+    fn read(path: &::std::path::Path, network: &mut Network) -> Result<(), String> {
+        let mut file = BufReader::new(File::open(path).unwrap());
+        read_network(&mut file, network)
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let path = env::temp_dir().join(format!("zero_sum_test_network_{}", ::std::process::id()));
+
+        let mut network = Network::new(3, &[4, 4], 1, &[Some(MatrixCm::from_vec(3, 4, vec![1.0; 12]))], SimpleGradientDescent);
+        network.biases[0].values[2] = -0.25;
+        network.weights[1].values[5] = 1.0e-9;
+        write_network_binary(&mut File::create(&path).unwrap(), &network).unwrap();
+
+        // Values are stored exactly, and the format is detected on load
+        let mut loaded = Network::new(3, &[4, 4], 1, &[], SimpleGradientDescent);
+        read(&path, &mut loaded).unwrap();
+        for layer in 0..3 {
+            assert_eq!(loaded.weights[layer].values, network.weights[layer].values);
+            assert_eq!(loaded.biases[layer].values, network.biases[layer].values);
+            assert_eq!(loaded.weight_masks[layer].as_ref().map(|mask| mask.values.clone()), network.weight_masks[layer].as_ref().map(|mask| mask.values.clone()));
+        }
+
+        let mut wrong = Network::new(3, &[5, 4], 1, &[], SimpleGradientDescent);
+        assert!(read(&path, &mut wrong).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_binary_truncated() {
+        let path = env::temp_dir().join(format!("zero_sum_test_truncated_network_{}", ::std::process::id()));
+
+        let network = Network::new(3, &[4, 4], 1, &[], SimpleGradientDescent);
+        write_network_binary(&mut File::create(&path).unwrap(), &network).unwrap();
+        let length = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(length - 10).unwrap();
+
+        // Reading stops before any layer is replaced
+        let mut loaded = Network::new(3, &[4, 4], 1, &[], SimpleGradientDescent);
+        loaded.biases[2].values[0] = 0.5;
+        let original = loaded.clone();
+        assert!(read(&path, &mut loaded).is_err());
+        for layer in 0..3 {
+            assert_eq!(loaded.weights[layer].values, original.weights[layer].values);
+            assert_eq!(loaded.biases[layer].values, original.biases[layer].values);
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+}
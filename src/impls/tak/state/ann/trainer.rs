@@ -0,0 +1,500 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::f32::consts::PI;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::str::FromStr;
+
+use rand::Rng;
+
+use impls::tak::state::ann::{ActivationFunction, Ann, GradientDescent, MatrixRm, calculate_error, write_network_binary};
+use util::JKiss32Rng;
+
+/// How the learning rate changes from one epoch to the next.
+///
+/// Schedules have a text format, read with `from_str` and written with `Display`:
+///
+/// ```text
+/// constant
+/// step 10 0.5
+/// exponential 0.95
+/// cosine 100 0.01
+/// warmup 5 cosine 100 0.01
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum LearningRateSchedule {
+    /// The rate never changes.
+    Constant,
+    /// The rate is multiplied by `factor` every `interval` epochs.
+    Step { interval: usize, factor: f32 },
+    /// The rate is multiplied by the given factor after every epoch.
+    Exponential(f32),
+    /// The rate follows half a cosine wave from the initial rate down to `minimum` times the
+    /// initial rate over `epochs` epochs, and stays there.
+    Cosine { epochs: usize, minimum: f32 },
+    /// The rate rises linearly to the initial rate over `epochs` epochs, after which `then`
+    /// takes over as if training had just begun.
+    Warmup { epochs: usize, then: Box<LearningRateSchedule> },
+}
+
+impl LearningRateSchedule {
+    /// Returns the learning rate to use in `epoch` (zero-based), given the initial rate.
+    pub fn rate(&self, initial: f32, epoch: usize) -> f32 {
+        match *self {
+            LearningRateSchedule::Constant => initial,
+            LearningRateSchedule::Step { interval, factor } => initial * factor.powi((epoch / interval.max(1)) as i32),
+            LearningRateSchedule::Exponential(factor) => initial * factor.powi(epoch as i32),
+            LearningRateSchedule::Cosine { epochs, minimum } => {
+                let progress = (epoch as f32 / epochs.max(1) as f32).min(1.0);
+                initial * (minimum + (1.0 - minimum) * (1.0 + (PI * progress).cos()) / 2.0)
+            },
+            LearningRateSchedule::Warmup { epochs, ref then } => if epoch < epochs {
+                initial * (epoch + 1) as f32 / epochs as f32
+            } else {
+                then.rate(initial, epoch - epochs)
+            },
+        }
+    }
+
+    fn parse(strings: &[&str]) -> Result<LearningRateSchedule, String> {
+        fn value<T>(string: &str) -> Result<T, String> where T: FromStr {
+            match string.parse() {
+                Ok(value) => Ok(value),
+                _ => Err(format!("Cannot parse schedule value: {}", string)),
+            }
+        }
+
+        let count = match strings.first() {
+            Some(&"constant") => 1,
+            Some(&"exponential") => 2,
+            Some(&"step") | Some(&"cosine") => 3,
+            Some(&"warmup") if strings.len() > 2 => strings.len(),
+            Some(&"warmup") => 3,
+            Some(name) => return Err(format!("Unknown schedule: {}", name)),
+            None => return Err(String::from("No schedule!")),
+        };
+        if strings.len() != count {
+            return Err(format!("Incorrect number of values for schedule: {}", strings.join(" ")));
+        }
+
+        Ok(match strings[0] {
+            "constant" => LearningRateSchedule::Constant,
+            "exponential" => LearningRateSchedule::Exponential(value(strings[1])?),
+            "step" => LearningRateSchedule::Step {
+                interval: value(strings[1])?,
+                factor: value(strings[2])?,
+            },
+            "cosine" => LearningRateSchedule::Cosine {
+                epochs: value(strings[1])?,
+                minimum: value(strings[2])?,
+            },
+            _ => LearningRateSchedule::Warmup {
+                epochs: value(strings[1])?,
+                then: Box::new(LearningRateSchedule::parse(&strings[2..])?),
+            },
+        })
+    }
+}
+
+impl FromStr for LearningRateSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LearningRateSchedule, String> {
+        LearningRateSchedule::parse(&s.split_whitespace().collect::<Vec<_>>())
+    }
+}
+
+impl fmt::Display for LearningRateSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LearningRateSchedule::Constant => write!(f, "constant"),
+            LearningRateSchedule::Step { interval, factor } => write!(f, "step {} {}", interval, factor),
+            LearningRateSchedule::Exponential(factor) => write!(f, "exponential {}", factor),
+            LearningRateSchedule::Cosine { epochs, minimum } => write!(f, "cosine {} {}", epochs, minimum),
+            LearningRateSchedule::Warmup { epochs, ref then } => write!(f, "warmup {} {}", epochs, then),
+        }
+    }
+}
+
+/// The results of a single training epoch.
+#[derive(Clone, Copy, Debug)]
+pub struct EpochReport {
+    pub rate: f32,
+    /// The average error per training row, measured after the epoch's last batch.
+    pub training_error: f32,
+    /// The average error per validation row, if a validation set was split off.
+    pub validation_error: Option<f32>,
+}
+
+/// The results of a call to `Trainer::train`.
+#[derive(Clone, Debug)]
+pub struct TrainingReport {
+    pub epochs: Vec<EpochReport>,
+    /// The epoch whose network was kept.  With a validation set, this is the epoch with the lowest
+    /// validation error; otherwise it is the last epoch.
+    pub best_epoch: Option<usize>,
+    pub stopped_early: bool,
+}
+
+/// Drives minibatch training of an `Ann` over a whole data set.
+///
+/// `Ann::train` performs a single step of gradient descent on one batch.  A `Trainer` owns the rest
+/// of the loop: it splits off a validation set, shuffles the training rows into minibatches each
+/// epoch, adjusts the learning rate, stops once the validation error stops improving, and
+/// periodically writes the network to a file with `write_network_binary`.
+#[derive(Clone, Debug)]
+pub struct Trainer {
+    epochs: usize,
+    batch_size: usize,
+    rate: f32,
+    schedule: LearningRateSchedule,
+    validation_fraction: f32,
+    patience: Option<usize>,
+    checkpoint: Option<(String, usize)>,
+    seed: Option<u64>,
+    start_epoch: usize,
+}
+
+impl Trainer {
+    /// Creates a trainer that runs `epochs` epochs of `batch_size` rows at a constant `rate`,
+    /// without a validation set or checkpoints.
+    pub fn new(epochs: usize, batch_size: usize, rate: f32) -> Trainer {
+        assert!(batch_size > 0, "Invalid batch size!");
+
+        Trainer {
+            epochs: epochs,
+            batch_size: batch_size,
+            rate: rate,
+            schedule: LearningRateSchedule::Constant,
+            validation_fraction: 0.0,
+            patience: None,
+            checkpoint: None,
+            seed: None,
+            start_epoch: 0,
+        }
+    }
+
+    pub fn set_schedule(&mut self, schedule: LearningRateSchedule) {
+        self.schedule = schedule;
+    }
+
+    /// Holds back `fraction` of the rows as a validation set.  The validation error is reported
+    /// every epoch and decides which network is kept.
+    pub fn set_validation_fraction(&mut self, fraction: f32) {
+        assert!(fraction >= 0.0 && fraction < 1.0, "Invalid validation fraction!");
+        self.validation_fraction = fraction;
+    }
+
+    /// Stops training once the validation error hasn't improved for `patience` epochs.  Has no
+    /// effect without a validation set.
+    pub fn set_patience(&mut self, patience: Option<usize>) {
+        self.patience = patience;
+    }
+
+    /// Writes the network to `filename` every `interval` epochs, and once more when training ends.
+    /// The training progress is written alongside it, to `filename` with `.progress` appended, for
+    /// `resume`.
+    pub fn set_checkpoint(&mut self, filename: &str, interval: usize) {
+        self.checkpoint = Some((String::from(filename), interval.max(1)));
+    }
+
+    /// Seeds the shuffling, for reproducible runs.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    /// Begins training at `epoch` (zero-based), so that the schedule continues from there.
+    pub fn set_start_epoch(&mut self, epoch: usize) {
+        self.start_epoch = epoch;
+    }
+
+    /// Continues the run that wrote the checkpoint `filename`, taking its next epoch, initial rate,
+    /// and schedule from the progress written alongside it.  The network itself is loaded
+    /// separately, with `read_network`.
+    pub fn resume(&mut self, filename: &str) -> Result<(), String> {
+        let progress = format!("{}.progress", filename);
+        let file = match File::open(&progress) {
+            Ok(file) => file,
+            Err(_) => return Err(format!("Cannot open file: {}", progress)),
+        };
+
+        let (mut epoch, mut rate, mut schedule): (Option<usize>, Option<f32>, Option<LearningRateSchedule>) = (None, None, None);
+        for line in BufReader::new(file).lines() {
+            let line = match line {
+                Ok(line) => line,
+                _ => return Err(format!("Cannot read file: {}", progress)),
+            };
+
+            let strings = line.split_whitespace().collect::<Vec<_>>();
+            match strings.first() {
+                Some(&"epoch") if strings.len() == 2 => epoch = strings[1].parse().ok(),
+                Some(&"rate") if strings.len() == 2 => rate = strings[1].parse().ok(),
+                Some(&"schedule") => schedule = Some(LearningRateSchedule::parse(&strings[1..])?),
+                None => (),
+                _ => return Err(format!("Cannot parse progress: {}", line.trim())),
+            }
+        }
+
+        match (epoch, rate, schedule) {
+            (Some(epoch), Some(rate), Some(schedule)) => {
+                self.start_epoch = epoch;
+                self.rate = rate;
+                self.schedule = schedule;
+                Ok(())
+            },
+            _ => Err(format!("Incomplete progress: {}", progress)),
+        }
+    }
+
+    /// Trains `network` on the rows of `inputs` against the corresponding rows of `targets`.
+    ///
+    /// If early stopping is triggered, `network` is left as it was after the best epoch.
+    pub fn train<A, F, G>(&self, network: &mut Ann<A, F, G>, inputs: &MatrixRm, targets: &MatrixRm) -> Result<TrainingReport, String> where
+        A: ActivationFunction + Clone,
+        F: ActivationFunction + Clone,
+        G: GradientDescent + Clone {
+        if inputs.rows != targets.rows {
+            return Err(String::from("Inputs and targets have a different number of rows!"));
+        }
+
+        let mut rng = if let Some(seed) = self.seed {
+            JKiss32Rng::with_seed(seed)
+        } else {
+            JKiss32Rng::new()
+        };
+
+        let mut rows = (0..inputs.rows).collect::<Vec<_>>();
+        rng.shuffle(&mut rows);
+
+        let validation_rows = (inputs.rows as f32 * self.validation_fraction) as usize;
+        let training_rows = inputs.rows - validation_rows;
+        if training_rows == 0 {
+            return Err(String::from("No rows left to train on!"));
+        }
+
+        let validation = rows.split_off(training_rows);
+        let mut training = rows;
+
+        let mut report = TrainingReport {
+            epochs: Vec::with_capacity(self.epochs.saturating_sub(self.start_epoch)),
+            best_epoch: None,
+            stopped_early: false,
+        };
+        let mut best: Option<(f32, Ann<A, F, G>)> = None;
+
+        let mut batch_inputs = MatrixRm::zeros(0, inputs.columns);
+        let mut batch_targets = MatrixRm::zeros(0, targets.columns);
+
+        for epoch in self.start_epoch..self.epochs {
+            let rate = self.schedule.rate(self.rate, epoch);
+
+            rng.shuffle(&mut training);
+            for batch in training.chunks(self.batch_size) {
+                gather_rows(inputs, batch, &mut batch_inputs);
+                gather_rows(targets, batch, &mut batch_targets);
+                network.train(&batch_inputs, &batch_targets, rate);
+            }
+
+            let training_error = self.average_error(network, inputs, targets, &training);
+            let validation_error = if validation.is_empty() {
+                None
+            } else {
+                Some(self.average_error(network, inputs, targets, &validation))
+            };
+
+            report.epochs.push(EpochReport {
+                rate: rate,
+                training_error: training_error,
+                validation_error: validation_error,
+            });
+
+            if let Some(error) = validation_error {
+                let improved = match best {
+                    Some((best_error, _)) => error < best_error,
+                    None => true,
+                };
+
+                if improved {
+                    best = Some((error, network.clone()));
+                    report.best_epoch = Some(epoch);
+                } else if let Some(patience) = self.patience {
+                    if epoch - report.best_epoch.unwrap() >= patience {
+                        report.stopped_early = true;
+                    }
+                }
+            } else {
+                report.best_epoch = Some(epoch);
+            }
+
+            if let Some((ref filename, interval)) = self.checkpoint {
+                if (epoch + 1) % interval == 0 && !report.stopped_early {
+                    write_checkpoint(filename, network)?;
+                    self.write_progress(filename, epoch + 1)?;
+                }
+            }
+
+            if report.stopped_early {
+                break;
+            }
+        }
+
+        if report.stopped_early {
+            if let Some((_, best_network)) = best {
+                *network = best_network;
+            }
+        }
+
+        if let Some((ref filename, _)) = self.checkpoint {
+            write_checkpoint(filename, network)?;
+            self.write_progress(filename, self.start_epoch + report.epochs.len())?;
+        }
+
+        Ok(report)
+    }
+
+    /// Writes the progress of the run checkpointed to `filename`, which continues at `epoch`.
+    fn write_progress(&self, filename: &str, epoch: usize) -> Result<(), String> {
+        let progress = format!("{}.progress", filename);
+        let mut file = match OpenOptions::new().write(true).truncate(true).create(true).open(&progress) {
+            Ok(file) => file,
+            Err(_) => return Err(format!("Cannot write file: {}", progress)),
+        };
+
+        match write!(file, "epoch {}\nrate {}\nschedule {}\n", epoch, self.rate, self.schedule) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(format!("Cannot write file: {}: {}", progress, error)),
+        }
+    }
+
+    /// Returns the average error per row over the given rows, evaluated a batch at a time.
+    fn average_error<A, F, G>(&self, network: &Ann<A, F, G>, inputs: &MatrixRm, targets: &MatrixRm, rows: &[usize]) -> f32 where
+        A: ActivationFunction,
+        F: ActivationFunction,
+        G: GradientDescent {
+        let mut batch_inputs = MatrixRm::zeros(0, inputs.columns);
+        let mut batch_targets = MatrixRm::zeros(0, targets.columns);
+        let mut outputs = MatrixRm::zeros(0, targets.columns);
+        let mut error = MatrixRm::zeros(0, targets.columns);
+        let mut total = 0.0;
+
+        for batch in rows.chunks(self.batch_size) {
+            gather_rows(inputs, batch, &mut batch_inputs);
+            gather_rows(targets, batch, &mut batch_targets);
+            outputs.resize(batch.len(), targets.columns);
+            error.resize(batch.len(), targets.columns);
+
+            network.propagate_forward_simple(&batch_inputs, &mut outputs);
+            calculate_error::<F>(&outputs, &batch_targets, &mut error);
+
+            // The linear error is signed, so sum magnitudes to keep errors from cancelling out
+            total += error.values.iter().map(|e| e.abs()).sum::<f32>();
+        }
+
+        total / rows.len() as f32
+    }
+}
+
+/// Copies the given rows of `source` into `destination`, resizing it to fit.
+fn gather_rows(source: &MatrixRm, rows: &[usize], destination: &mut MatrixRm) {
+    destination.resize(rows.len(), source.columns);
+
+    for (i, &row) in rows.iter().enumerate() {
+        let from = row * source.columns;
+        let to = i * source.columns;
+        destination.values[to..to + source.columns].copy_from_slice(&source.values[from..from + source.columns]);
+    }
+}
+
+fn write_checkpoint<A, F, G>(filename: &str, network: &Ann<A, F, G>) -> Result<(), String> where
+    A: ActivationFunction,
+    F: ActivationFunction,
+    G: GradientDescent {
+    let mut file = match OpenOptions::new().write(true).truncate(true).create(true).open(filename) {
+        Ok(file) => file,
+        Err(_) => return Err(format!("Cannot write file: {}", filename)),
+    };
+
+    match write_network_binary(&mut file, network) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!("Cannot write file: {}: {}", filename, error)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use impls::tak::state::ann::{ReLuActivationFunction, SimpleGradientDescent, TanHActivationFunction};
+    use super::*;
+
+    #[test]
+    fn test_schedules() {
+        assert_eq!(LearningRateSchedule::Step { interval: 2, factor: 0.5 }.rate(1.0, 5), 0.25);
+
+        let cosine = LearningRateSchedule::Cosine { epochs: 10, minimum: 0.1 };
+        assert_eq!(cosine.rate(1.0, 0), 1.0);
+        assert!((cosine.rate(1.0, 5) - 0.55).abs() < 1e-6);
+        assert!((cosine.rate(1.0, 20) - 0.1).abs() < 1e-6);
+
+        let warmup = LearningRateSchedule::Warmup { epochs: 4, then: Box::new(cosine.clone()) };
+        assert_eq!(warmup.rate(1.0, 0), 0.25);
+        assert_eq!(warmup.rate(1.0, 4), cosine.rate(1.0, 0));
+        assert_eq!(warmup.rate(1.0, 9), cosine.rate(1.0, 5));
+    }
+
+    #[test]
+    fn test_schedule_round_trip() {
+        for text in &["constant", "step 10 0.5", "exponential 0.95", "cosine 100 0.01", "warmup 5 step 10 0.5"] {
+            let schedule = LearningRateSchedule::from_str(text).unwrap();
+            assert_eq!(schedule.to_string(), *text);
+        }
+
+        assert!(LearningRateSchedule::from_str("").is_err());
+        assert!(LearningRateSchedule::from_str("step 10").is_err());
+        assert!(LearningRateSchedule::from_str("warmup 5").is_err());
+        assert!(LearningRateSchedule::from_str("linear 1").is_err());
+    }
+
+    #[test]
+    fn test_train() {
+        // The output is the difference of the two inputs, a quarter of the way to either end
+        let mut inputs = MatrixRm::zeros(16, 2);
+        let mut targets = MatrixRm::zeros(16, 1);
+        for row in 0..16 {
+            let (a, b) = ((row % 4) as f32 / 3.0, (row / 4) as f32 / 3.0);
+            inputs[row].clone_from_slice(&[a, b]);
+            targets[row].clone_from_slice(&[(a - b) / 4.0]);
+        }
+
+        // Fixed initial weights keep the run reproducible
+        let mut network = Ann::<ReLuActivationFunction, TanHActivationFunction, _>::new(2, &[4], 1, &[], SimpleGradientDescent);
+        network.weights[0].values = vec![0.5, -0.5, -0.3, 0.3, 0.2, 0.4, 0.4, 0.1];
+        network.weights[1].values = vec![0.5, -0.5, 0.3, -0.3];
+        let mut trainer = Trainer::new(50, 4, 0.05);
+        trainer.set_seed(Some(0));
+        let report = trainer.train(&mut network, &inputs, &targets).unwrap();
+
+        assert_eq!(report.epochs.len(), 50);
+        assert_eq!(report.best_epoch, Some(49));
+        assert!(!report.stopped_early);
+        assert!(report.epochs[49].training_error < report.epochs[0].training_error / 2.0);
+
+        assert!(trainer.train(&mut network, &inputs, &MatrixRm::zeros(15, 1)).is_err());
+    }
+}
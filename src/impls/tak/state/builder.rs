@@ -0,0 +1,329 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+use std::error::Error;
+use std::fmt;
+
+use impls::tak::{Color, Piece};
+use impls::tak::state::{Handicap, Rules, State};
+use impls::tak::state::metadata::Metadata;
+use impls::tak::state::tps::{format_tps, parse_tps, TpsError};
+
+/// The ways that a `StateBuilder` can describe a position that couldn't arise in a game.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SetupError {
+    /// The standing stone or capstone at `(x, y)` is covered by another piece.  Only
+    /// flatstones can be covered.
+    BuriedPiece {
+        x: usize,
+        y: usize,
+    },
+    /// No reserves were given for a player, and there are more of their pieces on the board
+    /// than they start with.
+    TooManyPieces(Color),
+    /// There are more pieces on the board than plies played to place them.
+    PlyCount {
+        pieces: usize,
+        ply_count: u16,
+    },
+    /// A player has no pieces on the board, though the opening ply that places one for them
+    /// has been played.
+    MissingOpening(Color),
+    /// The players' starting reserves, the pieces on the board plus those in reserve, differ
+    /// by more than a `Handicap` can describe.
+    ReserveImbalance,
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SetupError::BuriedPiece { x, y } => write!(f, "A standing stone or capstone is covered at ({}, {})", x, y),
+            SetupError::TooManyPieces(color) => write!(f, "Too many {:?} pieces on the board", color),
+            SetupError::PlyCount { pieces, ply_count } => write!(f, "{} pieces can't be placed in {} plies", pieces, ply_count),
+            SetupError::MissingOpening(color) => write!(f, "{:?} has no pieces on the board after the opening", color),
+            SetupError::ReserveImbalance => write!(f, "The players' reserves are too unequal"),
+        }
+    }
+}
+
+impl Error for SetupError {
+    fn description(&self) -> &str {
+        "Invalid tak position"
+    }
+}
+
+/// Sets up a `State` from an arbitrary position, optionally with explicit reserves, as
+/// for composing puzzles or adjudicating a game from a position alone.
+///
+/// Reserves that aren't given are inferred from the pieces on the board, as with
+/// `State::from_tps`.  When they are given, the players' starting reserves are taken to be
+/// the pieces on the board plus those in reserve, and any imbalance between them becomes
+/// the state's `Handicap`.
+///
+/// The built state has no history, so plies made before the position can't be reverted.
+///
+/// # Example
+///
+/// ```rust
+/// # use zero_sum::impls::tak::{Color, StateBuilder};
+/// let mut builder = StateBuilder::from_tps("[TPS \"x5/x5/x,2,1,x2/x,1,2,x2/x5 1 3\"]").unwrap();
+/// builder.set_reserves(Color::White, 3, 0);
+/// let state = builder.build().unwrap();
+/// assert_eq!(state.p1_flatstones, 3);
+/// ```
+#[derive(Clone, Debug)]
+pub struct StateBuilder {
+    board: Vec<Vec<Vec<Piece>>>,
+    ply_count: u16,
+    reserves: [Option<(u8, u8)>; 2],
+    rules: Rules,
+}
+
+impl StateBuilder {
+    /// Creates a builder for an empty board of the specified size, before the first ply.
+    ///
+    /// # Panics
+    /// This function panics if it is passed a board size less than 3 or greater than 8.
+    pub fn new(board_size: usize) -> StateBuilder {
+        StateBuilder::from_state(&State::new(board_size))
+    }
+
+    /// Creates a builder for the position of `state`, with its reserves and rules.
+    pub fn from_state(state: &State) -> StateBuilder {
+        StateBuilder {
+            board: state.board.clone(),
+            ply_count: state.ply_count,
+            reserves: [
+                Some((state.p1_flatstones, state.p1_capstones)),
+                Some((state.p2_flatstones, state.p2_capstones)),
+            ],
+            rules: state.rules,
+        }
+    }
+
+    /// Creates a builder for a position in TPS format, leaving the reserves to be inferred.
+    /// Unlike `State::from_tps`, the board isn't checked against the standard reserves
+    /// until the state is built, so reserves for larger positions can be set first.
+    pub fn from_tps(tps: &str) -> Result<StateBuilder, TpsError> {
+        let (rows, ply_count) = parse_tps(tps)?;
+
+        let board_size = rows.len();
+        let mut board = vec![vec![Vec::new(); board_size]; board_size];
+        for (i, row) in rows.into_iter().enumerate() {
+            for (x, stack) in row.into_iter().enumerate() {
+                board[x][board_size - 1 - i] = stack;
+            }
+        }
+
+        Ok(StateBuilder {
+            board: board,
+            ply_count: ply_count,
+            reserves: [None, None],
+            rules: Rules::default(),
+        })
+    }
+
+    /// Returns the position in TPS format.  TPS doesn't record reserves or rules.
+    pub fn to_tps(&self) -> String {
+        format_tps(&self.board, self.ply_count)
+    }
+
+    /// Replaces the stack at `(x, y)`, listed from the bottom up.
+    ///
+    /// # Panics
+    /// This function panics if `(x, y)` is off the board.
+    pub fn set_stack(&mut self, x: usize, y: usize, stack: Vec<Piece>) {
+        self.board[x][y] = stack;
+    }
+
+    /// Sets the number of plies played before the position.
+    pub fn set_ply_count(&mut self, ply_count: u16) {
+        self.ply_count = ply_count;
+    }
+
+    /// Sets the flatstones and capstones remaining in `color`'s reserve.
+    pub fn set_reserves(&mut self, color: Color, flatstones: u8, capstones: u8) {
+        self.reserves[color as usize] = Some((flatstones, capstones));
+    }
+
+    /// Sets the rules used to score flat wins.
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+    }
+
+    /// Checks the position and builds the state.
+    pub fn build(&self) -> Result<State, SetupError> {
+        let board_size = self.board.len();
+
+        let mut on_board = [(0u8, 0u8), (0u8, 0u8)];
+        for x in 0..board_size {
+            for y in 0..board_size {
+                let stack = &self.board[x][y];
+                for (z, piece) in stack.iter().enumerate() {
+                    match *piece {
+                        Piece::Flatstone(_) => (),
+                        _ if z + 1 < stack.len() => return Err(SetupError::BuriedPiece { x: x, y: y }),
+                        _ => (),
+                    }
+
+                    let count = &mut on_board[piece.get_color() as usize];
+                    match *piece {
+                        Piece::Capstone(_) => count.1 = count.1.saturating_add(1),
+                        _ => count.0 = count.0.saturating_add(1),
+                    }
+                }
+            }
+        }
+
+        let pieces = on_board.iter().map(|&(flatstones, capstones)| flatstones as usize + capstones as usize).sum::<usize>();
+        if pieces > self.ply_count as usize {
+            return Err(SetupError::PlyCount {
+                pieces: pieces,
+                ply_count: self.ply_count,
+            });
+        }
+
+        // Each player's first ply places one of the other's pieces, which can't be removed
+        for &(color, opening) in &[(Color::Black, 1), (Color::White, 2)] {
+            let count = on_board[color as usize];
+            if self.ply_count >= opening && count.0 == 0 && count.1 == 0 {
+                return Err(SetupError::MissingOpening(color));
+            }
+        }
+
+        let mut state = State::new(board_size);
+
+        let standard = (state.p1_flatstones, state.p1_capstones);
+        let mut reserves = [(0, 0), (0, 0)];
+        let mut starting = [(0i16, 0i16), (0i16, 0i16)];
+        for &color in &[Color::White, Color::Black] {
+            let i = color as usize;
+            reserves[i] = match self.reserves[i] {
+                Some(reserves) => reserves,
+                None => if on_board[i].0 <= standard.0 && on_board[i].1 <= standard.1 {
+                    (standard.0 - on_board[i].0, standard.1 - on_board[i].1)
+                } else {
+                    return Err(SetupError::TooManyPieces(color));
+                },
+            };
+            starting[i] = (
+                reserves[i].0 as i16 + on_board[i].0 as i16,
+                reserves[i].1 as i16 + on_board[i].1 as i16,
+            );
+        }
+
+        let imbalance = (starting[0].0 - starting[1].0, starting[0].1 - starting[1].1);
+        if imbalance.0.abs() > i8::max_value() as i16 || imbalance.1.abs() > i8::max_value() as i16 {
+            return Err(SetupError::ReserveImbalance);
+        }
+
+        state.p1_flatstones = reserves[0].0;
+        state.p1_capstones = reserves[0].1;
+        state.p2_flatstones = reserves[1].0;
+        state.p2_capstones = reserves[1].1;
+        state.board = self.board.clone();
+        state.ply_count = self.ply_count;
+        state.handicap = Handicap {
+            flatstones: imbalance.0 as i8,
+            capstones: imbalance.1 as i8,
+        };
+        state.rules = self.rules;
+        state.metadata = Metadata::from_state(&state);
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use impls::tak::*;
+    use state::State as StateTrait;
+
+    #[test]
+    fn test_reserves() {
+        let tps = "[TPS \"x5/x5/x,2,1,x2/x,1,2,x2/x5 1 3\"]";
+        let mut builder = StateBuilder::from_tps(tps).unwrap();
+        assert_eq!(builder.to_tps(), tps);
+        assert_eq!(builder.build().unwrap(), State::from_tps(tps).unwrap());
+
+        builder.set_reserves(Color::White, 3, 0);
+        let state = builder.build().unwrap();
+        assert_eq!((state.p1_flatstones, state.p1_capstones), (3, 0));
+        assert_eq!((state.p2_flatstones, state.p2_capstones), (19, 1));
+        assert_eq!(state.get_handicap(), Handicap { flatstones: -16, capstones: -1 });
+
+        // More pieces than the standard reserves allow, once reserves are given
+        let tps = "[TPS \"11111111111,2,x/x3/x3 1 20\"]";
+        assert_eq!(State::from_tps(tps), Err(TpsError::TooManyPieces(Color::White)));
+        let mut builder = StateBuilder::from_tps(tps).unwrap();
+        assert_eq!(builder.build(), Err(SetupError::TooManyPieces(Color::White)));
+        builder.set_reserves(Color::White, 0, 0);
+        let state = builder.build().unwrap();
+        assert_eq!(state.get_handicap(), Handicap { flatstones: 1, capstones: 0 });
+        assert!(state.check_resolution().is_some());
+    }
+
+    #[test]
+    fn test_from_state() {
+        let mut state = State::with_reserves(5, (21, 1), (19, 1));
+        state.execute_plies(&[
+            Ply::from_ptn("a1", Color::Black).unwrap(),
+            Ply::from_ptn("e5", Color::White).unwrap(),
+            Ply::from_ptn("Cc3", Color::White).unwrap(),
+        ]).unwrap();
+        state.set_rules(Rules {
+            half_komi: 5,
+            capstones_count: false,
+        });
+
+        let built = StateBuilder::from_state(&state).build().unwrap();
+        assert_eq!(built.to_tps(), state.to_tps());
+        assert_eq!(built.get_handicap(), state.get_handicap());
+        assert_eq!(built.get_rules(), state.get_rules());
+        assert_eq!(built.p1_capstones, 0);
+        assert_eq!(built.p2_flatstones, 18);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut builder = StateBuilder::new(3);
+        builder.set_ply_count(10);
+        builder.set_stack(0, 0, vec![Piece::Flatstone(Color::White), Piece::Flatstone(Color::Black)]);
+        builder.set_stack(1, 0, vec![Piece::StandingStone(Color::White), Piece::Flatstone(Color::Black)]);
+        assert_eq!(builder.build(), Err(SetupError::BuriedPiece { x: 1, y: 0 }));
+
+        builder.set_stack(1, 0, vec![Piece::Flatstone(Color::Black), Piece::StandingStone(Color::White)]);
+        assert!(builder.build().is_ok());
+
+        builder.set_ply_count(3);
+        assert_eq!(builder.build(), Err(SetupError::PlyCount { pieces: 4, ply_count: 3 }));
+
+        builder.set_reserves(Color::Black, 200, 0);
+        builder.set_ply_count(4);
+        assert_eq!(builder.build(), Err(SetupError::ReserveImbalance));
+
+        let cases = [
+            ("x3/x3/x3 1 2", SetupError::MissingOpening(Color::Black)),
+            ("x3/x3/2,x2 1 2", SetupError::MissingOpening(Color::White)),
+        ];
+        for &(tps, ref error) in cases.iter() {
+            assert_eq!(StateBuilder::from_tps(tps).unwrap().build().as_ref(), Err(error), "{}", tps);
+            assert!(!error.to_string().is_empty());
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use cli::Interactive;
+use impls::tak::{Color, Ply, State};
+
+impl Interactive for State {
+    fn parse_ply(&self, input: &str) -> Result<Ply, String> {
+        // Each player places the other's flatstone on their first turn
+        let color = match (self.ply_count % 2 == 0, self.is_opening_swap()) {
+            (true, false) | (false, true) => Color::White,
+            _ => Color::Black,
+        };
+        Ply::from_ptn(input, color)
+    }
+
+    fn format_ply(&self, ply: &Ply) -> String {
+        ply.to_ptn()
+    }
+
+    fn notation() -> &'static str {
+        "PTN, i.e. \"a1\", \"Sc3\", or \"3b2>21\""
+    }
+
+    fn player_name(player: u8) -> String {
+        String::from(if player == 0 { "White" } else { "Black" })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cli::Interactive;
+    use impls::tak::*;
+    use state::State as StateTrait;
+
+    #[test]
+    fn test_parse_ply() {
+        let mut state = State::new(5);
+        for &(ptn, color) in [("a1", Color::Black), ("e5", Color::White), ("b1", Color::White), ("c1", Color::Black)].iter() {
+            let ply = state.parse_ply(ptn).unwrap();
+            assert_eq!(ply, Ply::from_ptn(ptn, color).unwrap());
+            assert_eq!(state.format_ply(&ply), ptn);
+            state.execute_ply(Some(&ply)).unwrap();
+        }
+    }
+}
@@ -19,7 +19,7 @@
 
 use std::f32;
 use std::fs::OpenOptions;
-use std::io::BufReader;
+use std::io::{self, BufReader};
 use std::mem;
 use std::sync::{Arc, mpsc, Mutex};
 use std::thread;
@@ -28,9 +28,12 @@ use analysis::{self, Evaluation as EvaluationTrait};
 use analysis::search::{PvSearch, PvSearchAnalysis, Search};
 use impls::tak::{Color, Resolution, State};
 use impls::tak::state::ann::*;
+use impls::tak::state::evaluator::AnnConfig;
+use persist::{Decoder, Encode, Persistable, PersistError};
 use state::State as StateTrait;
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub struct Evaluation(pub f32);
 
 impl Evaluation {
@@ -41,6 +44,16 @@ impl Evaluation {
 
 prepare_evaluation_tuple!(Evaluation);
 
+impl Persistable for Evaluation {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.write_u32(self.0.to_bits());
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Evaluation, PersistError> {
+        Ok(Evaluation(f32::from_bits(decoder.read_u32()?)))
+    }
+}
+
 const USABLE_RANGE: f32 = 0.999;
 
 impl analysis::Evaluation for Evaluation {
@@ -61,6 +74,8 @@ impl analysis::Evaluation for Evaluation {
     fn win() -> Evaluation { Evaluation(1.0) }
     fn max() -> Evaluation { Evaluation(f32::MAX) }
     fn is_win(&self) -> bool { self.0 > USABLE_RANGE }
+    fn encodes_win_distance() -> bool { true }
+    fn granularity() -> Evaluation { Evaluation(0.001) }
 }
 
 fn scale_evaluation(evaluation: Evaluation) -> f32 {
@@ -71,9 +86,30 @@ fn unscale_evaluation(evaluation: f32) -> Evaluation {
     Evaluation(evaluation * USABLE_RANGE)
 }
 
-/// Uses an artificial neural network to evaluate the tak state.  The network has three hidden layers
-/// using ReLU activation, and uses TanH activation on the output.  It uses ADADELTA to perform gradient
-/// descent.
+/// Returns the features of `positions` and the scaled `labels`, as network inputs and targets.
+fn training_data(positions: &[State], labels: &[Evaluation]) -> (MatrixRm, MatrixRm) {
+    let mut inputs = MatrixRm::zeros(positions.len(), FEATURE_COUNT);
+    for i in 0..positions.len() {
+        inputs[i].clone_from_slice(&gather_features(&positions[i]));
+    }
+
+    // Label everything from white's point of view
+    let mut targets = MatrixRm::zeros(labels.len(), 1);
+    for i in 0..labels.len() {
+        targets[i].clone_from_slice(&[if positions[i].ply_count % 2 == 0 {
+            scale_evaluation(labels[i])
+        } else {
+            -scale_evaluation(labels[i])
+        }]);
+    }
+
+    (inputs, targets)
+}
+
+/// Uses an artificial neural network to evaluate the tak state.  By default, the network has three hidden
+/// layers using ReLU activation, and uses TanH activation on the output.  It uses ADADELTA to perform
+/// gradient descent.  The sizes of the hidden layers and the grouping of the input features can be
+/// changed with an `AnnConfig`.
 ///
 /// This is largely an experimental evaluator.  While it has demonstrated its potential as a stronger
 /// evaluator of tak positions than the `StaticEvaluator`, it is an order of magnitude slower, making
@@ -84,54 +120,54 @@ pub struct AnnEvaluator {
 }
 
 impl AnnEvaluator {
-    /// Creates a new evaluator, randomly initializing the network.
+    /// Creates a new evaluator with the default topology, randomly initializing the network.
     pub fn new() -> AnnEvaluator {
-        let input_count = 264;
-        let hidden_layers = [100, 64, 48];
-        let output_count = 1;
-
-        let mut weight_mask = MatrixCm::zeros(input_count, hidden_layers[0]);
-
-        // Global group
-        for column in 0..10 {
-            for row in 0..14 {
-                weight_mask[column][row] = 1.0;
-            }
-        }
-
-        // Stack positions and configuration
-        for column in 10..75 {
-            for row in 14..214 {
-                weight_mask[column][row] = 1.0;
-            }
-        }
+        AnnEvaluator::with_config(&AnnConfig::default()).unwrap()
+    }
 
-        // Influence
-        for column in 75..100 {
-            for row in 214..264 {
-                weight_mask[column][row] = 1.0;
-            }
-        }
+    /// Creates a new evaluator with the topology described by `config`, randomly initializing the network.
+    pub fn with_config(config: &AnnConfig) -> Result<AnnEvaluator, String> {
+        AnnEvaluator::with_initializers(config, &[])
+    }
 
-        AnnEvaluator {
-            ann: Ann::<ReLuActivationFunction, TanHActivationFunction, AdadeltaGradientDescent>::new(
-                input_count,
-                &hidden_layers,
-                output_count,
-                &[Some(weight_mask)],
+    /// Creates a new evaluator with the topology described by `config`, initializing each layer
+    /// of the network with the corresponding entry of `initializers`.  Layers without one use He
+    /// initialization.
+    pub fn with_initializers(config: &AnnConfig, initializers: &[Box<Initializer>]) -> Result<AnnEvaluator, String> {
+        config.validate()?;
+
+        let mut evaluator = AnnEvaluator {
+            ann: Ann::<ReLuActivationFunction, TanHActivationFunction, AdadeltaGradientDescent>::with_initializers(
+                FEATURE_COUNT,
+                &config.hidden_layers,
+                1,
+                &[config.weight_mask()],
+                initializers,
                 AdadeltaGradientDescent::new(
-                    input_count,
-                    &hidden_layers,
-                    output_count,
+                    FEATURE_COUNT,
+                    &config.hidden_layers,
+                    1,
                     0.000001,
                 ),
             ),
+        };
+
+        for &layer in &config.residual_layers {
+            evaluator.ann.set_residual(layer, true);
         }
+
+        Ok(evaluator)
     }
 
-    /// Loads in a network state previously serialized with the `to_file` method.
+    /// Loads in a network state previously serialized with the `to_file`, `to_binary_file`, or
+    /// `to_json_file` methods, or written in the JSON interchange format by another tool.
     pub fn from_file(filename: &str) -> Result<AnnEvaluator, String> {
-        let mut evaluator = AnnEvaluator::new();
+        AnnEvaluator::from_file_with_config(filename, &AnnConfig::default())
+    }
+
+    /// Loads in a network state with the topology described by `config`.
+    pub fn from_file_with_config(filename: &str, config: &AnnConfig) -> Result<AnnEvaluator, String> {
+        let mut evaluator = AnnEvaluator::with_config(config)?;
 
         if let Ok(file) = OpenOptions::new().read(true).open(filename) {
             let mut reader = BufReader::new(file);
@@ -153,23 +189,24 @@ impl AnnEvaluator {
         }
     }
 
+    /// Writes the current network state to a file in the compact binary format.  Much smaller and
+    /// faster than `to_file`, but not human-readable.
+    pub fn to_binary_file(&self, filename: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new().write(true).truncate(true).create(true).open(filename)?;
+        write_network_binary(&mut file, &self.ann)
+    }
+
+    /// Writes the current network state to a file in the JSON interchange format, for use by
+    /// other tools.  See `write_network_json` for the format.
+    pub fn to_json_file(&self, filename: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new().write(true).truncate(true).create(true).open(filename)?;
+        write_network_json(&mut file, &self.ann)
+    }
+
     /// Trains the network on `positions`, against `labels`.  Optionally will return the average amount of
     /// error per input in `error`.
     pub fn train_batch(&mut self, positions: &[State], labels: &[Evaluation], error: Option<&mut f32>) {
-        let mut inputs = MatrixRm::zeros(positions.len(), 264);
-        for i in 0..positions.len() {
-            inputs[i].clone_from_slice(&gather_features(&positions[i]));
-        }
-
-        // Label everything from white's point of view
-        let mut targets = MatrixRm::zeros(labels.len(), 1);
-        for i in 0..labels.len() {
-            targets[i].clone_from_slice(&[if positions[i].ply_count % 2 == 0 {
-                scale_evaluation(labels[i])
-            } else {
-                -scale_evaluation(labels[i])
-            }]);
-        }
+        let (inputs, targets) = training_data(positions, labels);
 
         if let Some(error) = error {
             let mut outputs = MatrixRm::zeros(targets.rows, targets.columns);
@@ -182,6 +219,12 @@ impl AnnEvaluator {
         self.ann.train(&inputs, &targets, 0.5);
     }
 
+    /// Trains the network on `positions`, against `labels`, for as many epochs as `trainer` calls for.
+    pub fn train(&mut self, trainer: &Trainer, positions: &[State], labels: &[Evaluation]) -> Result<TrainingReport, String> {
+        let (inputs, targets) = training_data(positions, labels);
+        trainer.train(&mut self.ann, &inputs, &targets)
+    }
+
     /// Use temporal difference learning (TD-Leaf algorithm) to train the system through self-play.
     /// `positions` are used as starting points for self-play.  Optionally returns the average amount of
     /// error per input in `error`.
@@ -190,7 +233,7 @@ impl AnnEvaluator {
 
         let total_error = Arc::new(Mutex::new(0.0));
 
-        let mut inputs = MatrixRm::zeros(positions.len(), 264);
+        let mut inputs = MatrixRm::zeros(positions.len(), FEATURE_COUNT);
         for i in 0..positions.len() {
             inputs[i].clone_from_slice(&gather_features(&positions[i]));
         }
@@ -329,6 +372,39 @@ impl analysis::Evaluator for AnnEvaluator {
             -unscale_evaluation(output.values[0])
         }
     }
+
+    /// Propagates every unresolved state through the network at once, as a single matrix.
+    fn evaluate_batch(&self, states: &[State]) -> Vec<Evaluation> {
+        let pending = states.iter().enumerate().filter(|&(_, state)| {
+            state.check_resolution().is_none()
+        }).map(|(i, _)| i).collect::<Vec<_>>();
+
+        let mut input = MatrixRm::zeros(pending.len(), FEATURE_COUNT);
+        for (row, &i) in pending.iter().enumerate() {
+            input[row].clone_from_slice(&gather_features(&states[i]));
+        }
+        let mut output = MatrixRm::zeros(pending.len(), 1);
+
+        if !pending.is_empty() {
+            self.ann.propagate_forward_simple(&input, &mut output);
+        }
+
+        let mut evaluations = Vec::with_capacity(states.len());
+        let mut row = 0;
+        for (i, state) in states.iter().enumerate() {
+            if row < pending.len() && pending[row] == i {
+                evaluations.push(if state.ply_count % 2 == 0 {
+                    unscale_evaluation(output.values[row])
+                } else {
+                    -unscale_evaluation(output.values[row])
+                });
+                row += 1;
+            } else {
+                evaluations.push(self.evaluate(state));
+            }
+        }
+        evaluations
+    }
 }
 
 fn decompose_f32(x: f32) -> (u8, u8, u32) {
@@ -411,7 +487,7 @@ fn next_f32(x: f32) -> f32 {
     compose_f32(sign, exponent, mantissa)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "with_nightly"))]
 mod test {
     use test::{self, Bencher};
 
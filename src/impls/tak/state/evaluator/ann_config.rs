@@ -0,0 +1,391 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::str::FromStr;
+
+use impls::tak::state::ann::{FEATURE_COUNT, MatrixCm};
+
+/// A contiguous range of input features that feed their own set of first-layer neurons.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeatureGroup {
+    pub name: String,
+    pub inputs: Range<usize>,
+    pub neurons: usize,
+}
+
+/// Describes the topology of an `AnnEvaluator`'s network.
+///
+/// If any feature groups are given, the first hidden layer is partitioned between them in order,
+/// and each neuron only sees the inputs of its group.  Otherwise, the first hidden layer is fully
+/// connected.  The activation functions are fixed by the evaluator's type: ReLU in the hidden
+/// layers and TanH on the output.
+///
+/// The text format has one setting per line, and ignores blank lines and lines beginning with `#`:
+///
+/// ```text
+/// hidden 100 64 48
+/// group global 0 14 10
+/// group stacks 14 214 65
+/// group influence 214 264 25
+/// ```
+///
+/// A `group` line gives the group's name, the start and end of its input range, and its number of
+/// neurons.  A `residual` line lists the hidden layers, counting from 0, that add their inputs to
+/// their outputs, as in `residual 2 3` for `hidden 100 64 64 64`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnnConfig {
+    pub hidden_layers: Vec<usize>,
+    pub feature_groups: Vec<FeatureGroup>,
+    /// The hidden layers with residual connections.  Each must be the same size as the layer
+    /// before it, so the first hidden layer can't be one.
+    pub residual_layers: Vec<usize>,
+}
+
+impl Default for AnnConfig {
+    fn default() -> AnnConfig {
+        AnnConfig {
+            hidden_layers: vec![100, 64, 48],
+            feature_groups: vec![
+                FeatureGroup { name: String::from("global"),    inputs:   0..14,  neurons: 10 },
+                FeatureGroup { name: String::from("stacks"),    inputs:  14..214, neurons: 65 },
+                FeatureGroup { name: String::from("influence"), inputs: 214..264, neurons: 25 },
+            ],
+            residual_layers: Vec::new(),
+        }
+    }
+}
+
+impl AnnConfig {
+    /// Reads a configuration in the text format from `reader`.
+    pub fn read<R>(reader: &mut R) -> Result<AnnConfig, String> where R: BufRead {
+        let mut config = AnnConfig {
+            hidden_layers: Vec::new(),
+            feature_groups: Vec::new(),
+            residual_layers: Vec::new(),
+        };
+
+        for line in reader.lines() {
+            let line = match line {
+                Ok(line) => line,
+                _ => return Err(String::from("Cannot read configuration!")),
+            };
+
+            let strings = line.split_whitespace().collect::<Vec<_>>();
+            if strings.is_empty() || strings[0].starts_with('#') {
+                continue;
+            }
+
+            match strings[0] {
+                "hidden" => {
+                    config.hidden_layers.clear();
+                    for string in &strings[1..] {
+                        match usize::from_str(string) {
+                            Ok(size) => config.hidden_layers.push(size),
+                            _ => return Err(format!("Cannot parse layer size: {}", string)),
+                        }
+                    }
+                },
+                "group" => {
+                    if strings.len() != 5 {
+                        return Err(format!("Incorrect number of values for group: {}", line.trim()));
+                    }
+
+                    let mut values = [0; 3];
+                    for (value, string) in values.iter_mut().zip(&strings[2..]) {
+                        match usize::from_str(string) {
+                            Ok(parsed) => *value = parsed,
+                            _ => return Err(format!("Cannot parse group value: {}", string)),
+                        }
+                    }
+
+                    config.feature_groups.push(FeatureGroup {
+                        name: String::from(strings[1]),
+                        inputs: values[0]..values[1],
+                        neurons: values[2],
+                    });
+                },
+                "residual" => {
+                    for string in &strings[1..] {
+                        match usize::from_str(string) {
+                            Ok(layer) => config.residual_layers.push(layer),
+                            _ => return Err(format!("Cannot parse residual layer: {}", string)),
+                        }
+                    }
+                },
+                name => return Err(format!("Unknown setting: {}", name)),
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Writes the configuration in the text format to `writer`.
+    pub fn write<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
+        write!(writer, "{}", self)
+    }
+
+    /// Checks that the configuration describes a network that can be built.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.hidden_layers.is_empty() {
+            return Err(String::from("No hidden layers!"));
+        }
+        if self.hidden_layers.contains(&0) {
+            return Err(String::from("Invalid number of hidden-layer neurons!"));
+        }
+
+        if !self.feature_groups.is_empty() {
+            for group in &self.feature_groups {
+                if group.inputs.start >= group.inputs.end || group.inputs.end > FEATURE_COUNT {
+                    return Err(format!("Invalid input range for group {}!", group.name));
+                }
+                if group.neurons == 0 {
+                    return Err(format!("Invalid number of neurons for group {}!", group.name));
+                }
+            }
+
+            let neurons = self.feature_groups.iter().map(|group| group.neurons).sum::<usize>();
+            if neurons != self.hidden_layers[0] {
+                return Err(format!(
+                    "Feature groups have {} neurons, but the first hidden layer has {}!",
+                    neurons, self.hidden_layers[0],
+                ));
+            }
+        }
+
+        for &layer in &self.residual_layers {
+            if layer == 0 || layer >= self.hidden_layers.len() {
+                return Err(format!("Invalid residual layer: {}!", layer));
+            }
+            if self.hidden_layers[layer] != self.hidden_layers[layer - 1] {
+                return Err(format!("Residual layer {} is a different size than the layer before it!", layer));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the weight mask for the first hidden layer, or `None` if it is fully connected.
+    pub fn weight_mask(&self) -> Option<MatrixCm> {
+        if self.feature_groups.is_empty() {
+            return None;
+        }
+
+        let mut weight_mask = MatrixCm::zeros(FEATURE_COUNT, self.hidden_layers[0]);
+        let mut first_neuron = 0;
+
+        for group in &self.feature_groups {
+            for column in first_neuron..first_neuron + group.neurons {
+                for row in group.inputs.clone() {
+                    weight_mask[column][row] = 1.0;
+                }
+            }
+            first_neuron += group.neurons;
+        }
+
+        Some(weight_mask)
+    }
+}
+
+/// Builds an `AnnConfig` one hidden layer at a time, inferring each layer's size where it
+/// follows from the layers before it, so that sizes don't have to be matched by hand.
+///
+/// ```rust,ignore
+/// let config = AnnConfigBuilder::new()
+///     .group("global", 0..14, 10)
+///     .group("stacks", 14..214, 65)
+///     .group("influence", 214..264, 25)
+///     .dense(64)
+///     .residual()
+///     .build()?;
+/// ```
+///
+/// The first error is kept and returned by `build`, which also validates the whole
+/// configuration.
+#[derive(Clone, Debug)]
+pub struct AnnConfigBuilder {
+    config: AnnConfig,
+    error: Option<String>,
+}
+
+impl AnnConfigBuilder {
+    pub fn new() -> AnnConfigBuilder {
+        AnnConfigBuilder {
+            config: AnnConfig {
+                hidden_layers: Vec::new(),
+                feature_groups: Vec::new(),
+                residual_layers: Vec::new(),
+            },
+            error: None,
+        }
+    }
+
+    /// Adds a group of input features to the first hidden layer, which is as large as all of
+    /// its groups together.  Groups must come before every other layer.
+    pub fn group(mut self, name: &str, inputs: Range<usize>, neurons: usize) -> AnnConfigBuilder {
+        if !self.config.hidden_layers.is_empty() {
+            self.fail(format!("Group {} follows a hidden layer!", name));
+        }
+
+        self.config.feature_groups.push(FeatureGroup {
+            name: String::from(name),
+            inputs: inputs,
+            neurons: neurons,
+        });
+        self
+    }
+
+    /// Adds a fully connected hidden layer of `neurons` neurons.
+    pub fn dense(mut self, neurons: usize) -> AnnConfigBuilder {
+        self.close_groups();
+        self.config.hidden_layers.push(neurons);
+        self
+    }
+
+    /// Adds a hidden layer the size of the one before it, which adds its inputs to its outputs.
+    pub fn residual(mut self) -> AnnConfigBuilder {
+        self.close_groups();
+
+        match self.config.hidden_layers.last().cloned() {
+            Some(neurons) => {
+                self.config.residual_layers.push(self.config.hidden_layers.len());
+                self.config.hidden_layers.push(neurons);
+            },
+            None => self.fail(String::from("A residual layer must follow another hidden layer!")),
+        }
+        self
+    }
+
+    /// Returns the configuration, or the first problem with it.
+    pub fn build(mut self) -> Result<AnnConfig, String> {
+        self.close_groups();
+
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        self.config.validate()?;
+        Ok(self.config)
+    }
+
+    /// Adds the first hidden layer for the feature groups, if it hasn't been added yet.
+    fn close_groups(&mut self) {
+        if self.config.hidden_layers.is_empty() && !self.config.feature_groups.is_empty() {
+            let neurons = self.config.feature_groups.iter().map(|group| group.neurons).sum::<usize>();
+            self.config.hidden_layers.push(neurons);
+        }
+    }
+
+    fn fail(&mut self, error: String) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+}
+
+impl Default for AnnConfigBuilder {
+    fn default() -> AnnConfigBuilder {
+        AnnConfigBuilder::new()
+    }
+}
+
+impl fmt::Display for AnnConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "hidden")?;
+        for size in &self.hidden_layers {
+            write!(f, " {}", size)?;
+        }
+        writeln!(f)?;
+
+        for group in &self.feature_groups {
+            writeln!(f, "group {} {} {} {}", group.name, group.inputs.start, group.inputs.end, group.neurons)?;
+        }
+
+        if !self.residual_layers.is_empty() {
+            write!(f, "residual")?;
+            for layer in &self.residual_layers {
+                write!(f, " {}", layer)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for AnnConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AnnConfig, String> {
+        AnnConfig::read(&mut s.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let config = AnnConfig::default();
+        assert_eq!(AnnConfig::from_str(&format!("{}", config)), Ok(config));
+
+        let config = AnnConfig::from_str("# Fully connected\nhidden 32 16\n").unwrap();
+        assert_eq!(config.hidden_layers, vec![32, 16]);
+        assert!(config.weight_mask().is_none());
+
+        let config = AnnConfig::from_str("hidden 32 16 16 16\nresidual 2 3\n").unwrap();
+        assert_eq!(config.residual_layers, vec![2, 3]);
+        assert_eq!(AnnConfig::from_str(&format!("{}", config)), Ok(config));
+    }
+
+    #[test]
+    fn test_builder() {
+        let config = AnnConfigBuilder::new()
+            .group("global", 0..14, 10)
+            .group("stacks", 14..214, 65)
+            .group("influence", 214..264, 25)
+            .dense(64)
+            .dense(48)
+            .build();
+        assert_eq!(config, Ok(AnnConfig::default()));
+
+        let config = AnnConfigBuilder::new().dense(32).dense(16).residual().residual().build().unwrap();
+        assert_eq!(config, AnnConfig::from_str("hidden 32 16 16 16\nresidual 2 3\n").unwrap());
+
+        assert!(AnnConfigBuilder::new().build().is_err());
+        assert!(AnnConfigBuilder::new().residual().dense(10).build().is_err());
+        assert!(AnnConfigBuilder::new().dense(10).group("all", 0..264, 10).build().is_err());
+        assert!(AnnConfigBuilder::new().group("all", 0..300, 10).dense(10).build().is_err());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(AnnConfig::from_str("").is_err());
+        assert!(AnnConfig::from_str("hidden 10 0").is_err());
+        assert!(AnnConfig::from_str("hidden 10\ngroup all 0 264 9").is_err());
+        assert!(AnnConfig::from_str("hidden 10\ngroup all 0 300 10").is_err());
+        assert!(AnnConfig::from_str("hidden 10\nlayers 3").is_err());
+        assert!(AnnConfig::from_str("hidden 10 10\nresidual 0").is_err());
+        assert!(AnnConfig::from_str("hidden 10 10\nresidual 2").is_err());
+        assert!(AnnConfig::from_str("hidden 10 12\nresidual 1").is_err());
+    }
+}
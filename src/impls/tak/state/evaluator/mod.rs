@@ -21,10 +21,18 @@
 
 #[cfg(feature = "with_tak_ann")]
 pub use self::ann::AnnEvaluator;
+#[cfg(feature = "with_tak_ann")]
+pub use self::ann_config::{AnnConfig, AnnConfigBuilder, FeatureGroup};
+#[cfg(feature = "with_tak_ann")]
+pub use impls::tak::state::ann::{FileInitializer, HeInitializer, Initializer, UniformInitializer, XavierInitializer, ZeroInitializer};
+#[cfg(feature = "with_tak_ann")]
+pub use impls::tak::state::ann::{EpochReport, LearningRateSchedule, Trainer, TrainingReport};
 
-pub use self::static_::StaticEvaluator;
+pub use self::static_::{StaticEvaluator, Weights};
 
 #[cfg(feature = "with_tak_ann")]
 mod ann;
+#[cfg(feature = "with_tak_ann")]
+mod ann_config;
 
 mod static_;
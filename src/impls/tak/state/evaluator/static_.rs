@@ -18,16 +18,22 @@
 //
 
 use std::cmp;
+use std::fmt;
 use std::i32;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
 
 use analysis::{self, Evaluation as EvaluationTrait};
+use analysis::tuning::Tunable;
 use impls::tak::Color;
 use impls::tak::resolution::Resolution;
 use impls::tak::state::State;
 use impls::tak::state::metadata::{Bitmap, BitmapInterface, BOARD, EDGE, Metadata};
+use persist::{Decoder, Encode, Persistable, PersistError};
 use state::State as StateTrait;
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub struct Evaluation(pub i32);
 
 impl Evaluation {
@@ -44,74 +50,223 @@ impl analysis::Evaluation for Evaluation {
     fn win() -> Evaluation { Evaluation(100_000) }
     fn max() -> Evaluation { Evaluation(i32::MAX) }
     fn is_win(&self) -> bool { self.0 >= 99_000 }
+    fn encodes_win_distance() -> bool { true }
+    fn granularity() -> Evaluation { Evaluation(4) } // A flatstone is worth about 100 units
+}
+
+impl Persistable for Evaluation {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.write_u32(self.0 as u32);
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Evaluation, PersistError> {
+        Ok(Evaluation(decoder.read_u32()? as i32))
+    }
 }
 
 const END_GAME_FLATSTONE_THRESHOLD: [i32; 9] = [0, 0, 0, 5, 8, 10, 15, 20, 25];
 
-struct Weights {
-    flatstone: (i32, i32),
-    standing_stone: i32,
-    capstone: i32,
+/// The slope of the score model, so that an early flatstone's advantage is worth about a 65%
+/// chance to win.  Refit with `TexelTuner::fit_scale` when the weights change substantially.
+const SCORE_SCALE: f64 = 0.0015;
+
+/// The names of the terms of the evaluation, other than tempo, as reported by `explain`.
+const TERMS: [&str; 8] = ["top_pieces", "stacked_flatstones", "road_groups", "threats", "road_distance", "influence", "handicap", "komi"];
+
+/// The weights of each of the terms considered by the `StaticEvaluator`.
+///
+/// Weights can be written and read in a simple text format, one term per line, i.e.
+///
+/// ```text
+/// flatstone 400 800
+/// standing_stone 200
+/// ```
+///
+/// Terms that are missing from the text keep their default values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weights {
+    /// The value of a top-level flatstone at the start and end of the game.
+    pub flatstone: (i32, i32),
+    /// The value of a standing stone.
+    pub standing_stone: i32,
+    /// The value of a capstone.
+    pub capstone: i32,
+
+    /// The value of a friendly flatstone captured beneath a flatstone, a standing stone, and a capstone.
+    pub hard_flat: (i32, i32, i32),
+    /// The value of an enemy flatstone captured beneath a flatstone, a standing stone, and a capstone.
+    pub soft_flat: (i32, i32, i32),
+
+    /// The value of a road that could be completed with a single placement.
+    pub threat: i32,
+
+    /// The value of a road that could be completed with one, two, and three placements at the
+    /// least, counting the empty spaces along the shortest path between opposite edges.
+    pub road_distance: (i32, i32, i32),
+
+    /// The value of influence over friendly flatstones, empty spaces, and enemy pieces.
+    pub influence: (i32, i32, i32),
+
+    /// The value of a road group, indexed by its width or height.
+    pub group: [i32; 8],
+
+    /// The bonus awarded to the side to move.
+    pub tempo: i32,
+
+    /// The value of each extra flatstone and capstone in a player's starting reserves, in
+    /// games played with a handicap.
+    pub handicap: (i32, i32),
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights {
+            flatstone:         (400, 800),
+            standing_stone:     200,
+            capstone:           300,
 
-    hard_flat: (i32, i32, i32),
-    soft_flat: (i32, i32, i32),
+            hard_flat:         (125, 125, 150),
+            soft_flat:         (-75, -50, -25),
 
-    threat: i32,
+            threat:             200,
 
-    influence: (i32, i32, i32),
+            road_distance:     (150,  60,  20),
 
-    group: [i32; 8],
+            influence:         ( 20,  15,  -5),
+
+            group: [0, 0, 100, 200, 400, 600, 0, 0],
+
+            tempo:                0,
+
+            handicap:          (  0,   0),
+        }
+    }
 }
 
-const WEIGHT: Weights = Weights {
-    flatstone:         (400, 800),
-    standing_stone:     200,
-    capstone:           300,
+impl Weights {
+    /// Reads weights in the text format from `reader`.
+    pub fn read<R>(reader: &mut R) -> Result<Weights, String> where R: BufRead {
+        let mut weights = Weights::default();
 
-    hard_flat:         (125, 125, 150),
-    soft_flat:         (-75, -50, -25),
+        for line in reader.lines() {
+            let line = match line {
+                Ok(line) => line,
+                _ => return Err(String::from("Cannot read weights!")),
+            };
 
-    threat:             200,
+            let strings = line.split_whitespace().collect::<Vec<_>>();
+            if strings.is_empty() || strings[0].starts_with('#') {
+                continue;
+            }
 
-    influence:         ( 20,  15,  -5),
+            let mut values = Vec::with_capacity(strings.len() - 1);
+            for string in &strings[1..] {
+                match i32::from_str(string) {
+                    Ok(value) => values.push(value),
+                    _ => return Err(format!("Cannot parse weight value: {}", string)),
+                }
+            }
+
+            let expected = match strings[0] {
+                "flatstone" | "handicap" => 2,
+                "standing_stone" | "capstone" | "threat" | "tempo" => 1,
+                "hard_flat" | "soft_flat" | "road_distance" | "influence" => 3,
+                "group" => 8,
+                name => return Err(format!("Unknown weight: {}", name)),
+            };
 
-    group: [0, 0, 100, 200, 400, 600, 0, 0],
-};
+            if values.len() != expected {
+                return Err(format!("Incorrect number of values for weight: {}", strings[0]));
+            }
+
+            match strings[0] {
+                "flatstone" => weights.flatstone = (values[0], values[1]),
+                "standing_stone" => weights.standing_stone = values[0],
+                "capstone" => weights.capstone = values[0],
+                "hard_flat" => weights.hard_flat = (values[0], values[1], values[2]),
+                "soft_flat" => weights.soft_flat = (values[0], values[1], values[2]),
+                "threat" => weights.threat = values[0],
+                "road_distance" => weights.road_distance = (values[0], values[1], values[2]),
+                "influence" => weights.influence = (values[0], values[1], values[2]),
+                "tempo" => weights.tempo = values[0],
+                "handicap" => weights.handicap = (values[0], values[1]),
+                _ => weights.group.clone_from_slice(&values),
+            }
+        }
+
+        Ok(weights)
+    }
+
+    /// Writes the weights in the text format to `writer`.
+    pub fn write<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
+        write!(writer, "{}", self)
+    }
+}
+
+impl fmt::Display for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "flatstone {} {}", self.flatstone.0, self.flatstone.1)?;
+        writeln!(f, "standing_stone {}", self.standing_stone)?;
+        writeln!(f, "capstone {}", self.capstone)?;
+        writeln!(f, "hard_flat {} {} {}", self.hard_flat.0, self.hard_flat.1, self.hard_flat.2)?;
+        writeln!(f, "soft_flat {} {} {}", self.soft_flat.0, self.soft_flat.1, self.soft_flat.2)?;
+        writeln!(f, "threat {}", self.threat)?;
+        writeln!(f, "road_distance {} {} {}", self.road_distance.0, self.road_distance.1, self.road_distance.2)?;
+        writeln!(f, "influence {} {} {}", self.influence.0, self.influence.1, self.influence.2)?;
+        write!(f, "group")?;
+        for value in &self.group {
+            write!(f, " {}", value)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "tempo {}", self.tempo)?;
+        writeln!(f, "handicap {} {}", self.handicap.0, self.handicap.1)
+    }
+}
+
+impl FromStr for Weights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Weights, String> {
+        Weights::read(&mut s.as_bytes())
+    }
+}
 
 /// Provides a static evaluation of a tak state.  This evaluator considers
 /// top-level pieces, stacked flatstones, road-group size, one-away threats,
-/// and stack influence.
+/// the placements needed to complete a road, and stack influence.
 #[derive(Clone)]
-pub struct StaticEvaluator;
+pub struct StaticEvaluator {
+    weights: Weights,
+}
 
-impl analysis::Evaluator for StaticEvaluator {
-    type State = State;
-    type Evaluation = Evaluation;
+impl Default for StaticEvaluator {
+    fn default() -> StaticEvaluator {
+        StaticEvaluator::new()
+    }
+}
 
-    fn evaluate(&self, state: &State) -> Evaluation {
-        let next_color = if state.ply_count % 2 == 0 {
-            Color::White
-        } else {
-            Color::Black
-        };
+impl StaticEvaluator {
+    /// Creates an evaluator with the default weights.
+    pub fn new() -> StaticEvaluator {
+        StaticEvaluator::with_weights(Weights::default())
+    }
 
-        match state.check_resolution() {
-            None => (),
-            Some(Resolution::Road(win_color)) |
-            Some(Resolution::Flat(win_color)) => {
-                if win_color == next_color {
-                    return Evaluation::win().shift(-(state.ply_count as i32));
-                } else {
-                    return Evaluation::lose().shift(state.ply_count as i32);
-                }
-            },
-            Some(Resolution::Draw) => return Evaluation::null(),
+    /// Creates an evaluator with the given weights.
+    pub fn with_weights(weights: Weights) -> StaticEvaluator {
+        StaticEvaluator {
+            weights: weights,
         }
+    }
 
-        let mut p1_eval = 0;
-        let mut p2_eval = 0;
+    /// Returns the weights used by the evaluator.
+    pub fn weights(&self) -> &Weights {
+        &self.weights
+    }
 
+    // Returns the value of each of the terms in `TERMS` for each player, without tempo.
+    fn terms(&self, state: &State) -> [(i32, i32); 8] {
         let m = &state.metadata;
+        let w = &self.weights;
 
         let total_pieces = m.p1_pieces | m.p2_pieces;
 
@@ -131,72 +286,200 @@ impl analysis::Evaluator for StaticEvaluator {
             let p2_position = cmp::min(state.p2_flatstones as i32, flatstone_threshold);
 
             (
-                WEIGHT.flatstone.0 * p1_position / flatstone_threshold +
-                WEIGHT.flatstone.1 * (flatstone_threshold - p1_position) / flatstone_threshold,
-                WEIGHT.flatstone.0 * p2_position / flatstone_threshold +
-                WEIGHT.flatstone.1 * (flatstone_threshold - p2_position) / flatstone_threshold,
+                w.flatstone.0 * p1_position / flatstone_threshold +
+                w.flatstone.1 * (flatstone_threshold - p1_position) / flatstone_threshold,
+                w.flatstone.0 * p2_position / flatstone_threshold +
+                w.flatstone.1 * (flatstone_threshold - p2_position) / flatstone_threshold,
             )
         };
 
-        // Top-level pieces
-        p1_eval += evaluate_top_pieces(m.p1_flatstone_count as i32, p1_flatstone_weight, p1_standing_stones, p1_capstones);
-        p2_eval += evaluate_top_pieces(m.p2_flatstone_count as i32, p2_flatstone_weight, p2_standing_stones, p2_capstones);
-
-        // Stacked flatstones
-        {
-            let stacked_flatstones_eval = evaluate_stacked_flatstones(
-                m,
-                p1_flatstones,
-                p2_flatstones,
-                p1_standing_stones,
-                p2_standing_stones,
-                p1_capstones,
-                p2_capstones,
-            );
-            p1_eval += stacked_flatstones_eval.0;
-            p2_eval += stacked_flatstones_eval.1;
-        }
+        let rules = state.get_rules();
 
-        // Road groups
-        p1_eval += evaluate_road_groups(m, &m.p1_road_groups);
-        p2_eval += evaluate_road_groups(m, &m.p2_road_groups);
+        let top_pieces = {
+            // Under rules where capstones are counted, they're worth a flatstone on top of their own value
+            let (p1_counted, p2_counted) = if rules.capstones_count {
+                (
+                    m.p1_flatstone_count as i32 + p1_capstones.get_population() as i32,
+                    m.p2_flatstone_count as i32 + p2_capstones.get_population() as i32,
+                )
+            } else {
+                (m.p1_flatstone_count as i32, m.p2_flatstone_count as i32)
+            };
 
-        // Threats
-        p1_eval += evaluate_threats(m, total_pieces, &m.p1_road_groups);
-        p2_eval += evaluate_threats(m, total_pieces, &m.p2_road_groups);
+            (
+                evaluate_top_pieces(w, p1_counted, p1_flatstone_weight, p1_standing_stones, p1_capstones),
+                evaluate_top_pieces(w, p2_counted, p2_flatstone_weight, p2_standing_stones, p2_capstones),
+            )
+        };
 
-        // Influence
-        p1_eval += evaluate_influence(
+        let stacked_flatstones = evaluate_stacked_flatstones(
+            w,
             m,
-            total_pieces,
-            m.p1_pieces,
-            &m.p1_flatstones,
             p1_flatstones,
-            m.p2_pieces,
-        );
-        p2_eval += evaluate_influence(
-            m,
-            total_pieces,
-            m.p2_pieces,
-            &m.p2_flatstones,
             p2_flatstones,
-            m.p1_pieces,
+            p1_standing_stones,
+            p2_standing_stones,
+            p1_capstones,
+            p2_capstones,
+        );
+
+        let road_groups = (
+            evaluate_road_groups(w, m, &m.p1_road_groups),
+            evaluate_road_groups(w, m, &m.p2_road_groups),
+        );
+
+        let threats = (
+            evaluate_threats(w, m, total_pieces, &m.p1_road_groups),
+            evaluate_threats(w, m, total_pieces, &m.p2_road_groups),
+        );
+
+        let road_distance = (
+            evaluate_road_distance(w, m, total_pieces, &m.p1_road_groups),
+            evaluate_road_distance(w, m, total_pieces, &m.p2_road_groups),
         );
 
+        let influence = (
+            evaluate_influence(w, m, total_pieces, m.p1_pieces, &m.p1_flatstones, p1_flatstones, m.p2_pieces),
+            evaluate_influence(w, m, total_pieces, m.p2_pieces, &m.p2_flatstones, p2_flatstones, m.p1_pieces),
+        );
+
+        // Starting reserve imbalance
+        let handicap = {
+            let handicap = state.get_handicap();
+            (handicap.flatstones as i32 * w.handicap.0 + handicap.capstones as i32 * w.handicap.1, 0)
+        };
+
+        // Komi is worth as much to player 2 as the flatstones it stands in for
+        let komi = (0, rules.half_komi as i32 * p2_flatstone_weight / 2);
+
+        [top_pieces, stacked_flatstones, road_groups, threats, road_distance, influence, handicap, komi]
+    }
+}
+
+impl Tunable for StaticEvaluator {
+    fn parameters(&self) -> Vec<f64> {
+        let w = &self.weights;
+        let mut parameters = vec![
+            w.flatstone.0, w.flatstone.1,
+            w.standing_stone,
+            w.capstone,
+            w.hard_flat.0, w.hard_flat.1, w.hard_flat.2,
+            w.soft_flat.0, w.soft_flat.1, w.soft_flat.2,
+            w.threat,
+            w.influence.0, w.influence.1, w.influence.2,
+        ];
+        parameters.extend_from_slice(&w.group);
+        parameters.push(w.tempo);
+        parameters.push(w.handicap.0);
+        parameters.push(w.handicap.1);
+        parameters.extend_from_slice(&[w.road_distance.0, w.road_distance.1, w.road_distance.2]);
+        parameters.into_iter().map(|p| p as f64).collect()
+    }
+
+    fn set_parameters(&mut self, parameters: &[f64]) {
+        let p = parameters.iter().map(|p| p.round() as i32).collect::<Vec<_>>();
+        let w = &mut self.weights;
+        w.flatstone = (p[0], p[1]);
+        w.standing_stone = p[2];
+        w.capstone = p[3];
+        w.hard_flat = (p[4], p[5], p[6]);
+        w.soft_flat = (p[7], p[8], p[9]);
+        w.threat = p[10];
+        w.influence = (p[11], p[12], p[13]);
+        w.group.clone_from_slice(&p[14..22]);
+        w.tempo = p[22];
+        w.handicap = (p[23], p[24]);
+        w.road_distance = (p[25], p[26], p[27]);
+    }
+
+    fn evaluation_value(&self, evaluation: Evaluation) -> f64 {
+        evaluation.0 as f64
+    }
+}
+
+impl analysis::Evaluator for StaticEvaluator {
+    type State = State;
+    type Evaluation = Evaluation;
+
+    fn evaluate(&self, state: &State) -> Evaluation {
+        let next_color = if state.ply_count % 2 == 0 {
+            Color::White
+        } else {
+            Color::Black
+        };
+
+        match state.check_resolution() {
+            None => (),
+            Some(Resolution::Road(win_color)) |
+            Some(Resolution::Flat(win_color)) => {
+                if win_color == next_color {
+                    return Evaluation::win().shift(-(state.ply_count as i32));
+                } else {
+                    return Evaluation::lose().shift(state.ply_count as i32);
+                }
+            },
+            Some(Resolution::Draw) => return Evaluation::null(),
+        }
+
+        let mut p1_eval = 0;
+        let mut p2_eval = 0;
+
+        for &(p1_term, p2_term) in &self.terms(state) {
+            p1_eval += p1_term;
+            p2_eval += p2_term;
+        }
+
         match next_color {
-            Color::White => Evaluation(p1_eval - p2_eval),
-            Color::Black => Evaluation(p2_eval - p1_eval),
+            Color::White => Evaluation(p1_eval - p2_eval + self.weights.tempo),
+            Color::Black => Evaluation(p2_eval - p1_eval + self.weights.tempo),
+        }
+    }
+
+    fn tempo(&self) -> Evaluation {
+        Evaluation(self.weights.tempo)
+    }
+
+    fn score_model(&self) -> Option<analysis::ScoreModel<Evaluation>> {
+        Some(analysis::ScoreModel::new(SCORE_SCALE, |evaluation| evaluation.0 as f64))
+    }
+}
+
+impl analysis::ExplainableEvaluator for StaticEvaluator {
+    fn explain(&self, state: &State) -> analysis::EvaluationBreakdown<Evaluation> {
+        let evaluation = analysis::Evaluator::evaluate(self, state);
+        if state.check_resolution().is_some() {
+            return analysis::EvaluationBreakdown {
+                terms: Vec::new(),
+                evaluation: evaluation,
+            };
+        }
+
+        let mut terms = TERMS.iter().zip(self.terms(state).iter()).map(|(&name, &(p1_term, p2_term))| {
+            (name, [Evaluation(p1_term), Evaluation(p2_term)])
+        }).collect::<Vec<_>>();
+
+        let tempo = Evaluation(self.weights.tempo);
+        terms.push(("tempo", if state.ply_count % 2 == 0 {
+            [tempo, Evaluation(0)]
+        } else {
+            [Evaluation(0), tempo]
+        }));
+
+        analysis::EvaluationBreakdown {
+            terms: terms,
+            evaluation: evaluation,
         }
     }
 }
 
-fn evaluate_top_pieces(flatstone_count: i32, flatstone_weight: i32, standing_stones: Bitmap, capstones: Bitmap) -> i32 {
+fn evaluate_top_pieces(w: &Weights, flatstone_count: i32, flatstone_weight: i32, standing_stones: Bitmap, capstones: Bitmap) -> i32 {
     flatstone_count as i32 * flatstone_weight +
-    standing_stones.get_population() as i32 * WEIGHT.standing_stone +
-    capstones.get_population() as i32 * WEIGHT.capstone
+    standing_stones.get_population() as i32 * w.standing_stone +
+    capstones.get_population() as i32 * w.capstone
 }
 
 fn evaluate_stacked_flatstones(
+    w: &Weights,
     m: &Metadata,
     p1_flatstones: Bitmap,
     p2_flatstones: Bitmap,
@@ -250,31 +533,31 @@ fn evaluate_stacked_flatstones(
     }
 
     let mut p1_eval = 0;
-    p1_eval += p1_flatstone_hard_flats * WEIGHT.hard_flat.0 + p2_flatstone_soft_flats * WEIGHT.soft_flat.0;
-    p1_eval += p1_standing_stone_hard_flats * WEIGHT.hard_flat.1 + p2_standing_stone_soft_flats * WEIGHT.soft_flat.1;
-    p1_eval += p1_capstone_hard_flats * WEIGHT.hard_flat.2 + p2_capstone_soft_flats * WEIGHT.soft_flat.2;
+    p1_eval += p1_flatstone_hard_flats * w.hard_flat.0 + p2_flatstone_soft_flats * w.soft_flat.0;
+    p1_eval += p1_standing_stone_hard_flats * w.hard_flat.1 + p2_standing_stone_soft_flats * w.soft_flat.1;
+    p1_eval += p1_capstone_hard_flats * w.hard_flat.2 + p2_capstone_soft_flats * w.soft_flat.2;
 
     let mut p2_eval = 0;
-    p2_eval += p2_flatstone_hard_flats * WEIGHT.hard_flat.0 + p1_flatstone_soft_flats * WEIGHT.soft_flat.0;
-    p2_eval += p2_standing_stone_hard_flats * WEIGHT.hard_flat.1 + p1_standing_stone_soft_flats * WEIGHT.soft_flat.1;
-    p2_eval += p2_capstone_hard_flats * WEIGHT.hard_flat.2 + p1_capstone_soft_flats * WEIGHT.soft_flat.2;
+    p2_eval += p2_flatstone_hard_flats * w.hard_flat.0 + p1_flatstone_soft_flats * w.soft_flat.0;
+    p2_eval += p2_standing_stone_hard_flats * w.hard_flat.1 + p1_standing_stone_soft_flats * w.soft_flat.1;
+    p2_eval += p2_capstone_hard_flats * w.hard_flat.2 + p1_capstone_soft_flats * w.soft_flat.2;
 
     (p1_eval, p2_eval)
 }
 
-fn evaluate_road_groups(m: &Metadata, groups: &[Bitmap]) -> i32 {
+fn evaluate_road_groups(w: &Weights, m: &Metadata, groups: &[Bitmap]) -> i32 {
     let mut eval = 0;
 
     for group in groups {
         let (width, height) = group.get_dimensions(m.board_size);
 
-        eval += WEIGHT.group[width] + WEIGHT.group[height];
+        eval += w.group[width] + w.group[height];
     }
 
     eval
 }
 
-fn evaluate_threats(m: &Metadata, total_pieces: Bitmap, groups: &[Bitmap]) -> i32 {
+fn evaluate_threats(w: &Weights, m: &Metadata, total_pieces: Bitmap, groups: &[Bitmap]) -> i32 {
     let mut expanded_groups = vec![0; groups.len()];
     let mut threats = 0;
 
@@ -311,10 +594,57 @@ fn evaluate_threats(m: &Metadata, total_pieces: Bitmap, groups: &[Bitmap]) -> i3
         }
     }
 
-    threats * WEIGHT.threat
+    threats * w.threat
+}
+
+fn evaluate_road_distance(w: &Weights, m: &Metadata, total_pieces: Bitmap, groups: &[Bitmap]) -> i32 {
+    use impls::tak::Direction::*;
+
+    if w.road_distance == (0, 0, 0) {
+        return 0;
+    }
+
+    let board = BOARD[m.board_size];
+    let empty = board & !total_pieces;
+
+    // Road groups are whole, so anything reached brings along the groups next to it
+    let flood = |seeds: Bitmap| {
+        let near = seeds.grow(board, m.board_size);
+        groups.iter().filter(|&&group| group & near != 0).fold(seeds, |reached, group| reached | group)
+    };
+
+    // Each step claims the empty spaces next to what's been reached, or along the first edge
+    let distance = |from: Bitmap, to: Bitmap| {
+        let mut reached = flood(from & groups.iter().fold(0, |pieces, group| pieces | group));
+        if reached & to != 0 {
+            return 0;
+        }
+
+        for placements in 1..4 {
+            reached = flood(reached | ((reached.grow(board, m.board_size) | from) & empty));
+            if reached & to != 0 {
+                return placements;
+            }
+        }
+        4
+    };
+
+    let edges = &EDGE[m.board_size];
+    let placements = cmp::min(
+        distance(edges[North as usize], edges[South as usize]),
+        distance(edges[West as usize], edges[East as usize]),
+    );
+
+    match placements {
+        1 => w.road_distance.0,
+        2 => w.road_distance.1,
+        3 => w.road_distance.2,
+        _ => 0,
+    }
 }
 
 fn evaluate_influence(
+    w: &Weights,
     m: &Metadata,
     total_pieces: Bitmap,
     own_pieces: Bitmap,
@@ -385,9 +715,9 @@ fn evaluate_influence(
     {
         let mut eval = 0;
         for (level, map) in influence.iter().enumerate() {
-            eval += (map & own_flatstones).get_population() as i32 * (WEIGHT.influence.0 * (level as i32 + 1));
-            eval += (map & !total_pieces).get_population() as i32 * (WEIGHT.influence.1 * (level as i32 + 1));
-            eval += (map & enemy_pieces).get_population() as i32 * (WEIGHT.influence.2 >> level);
+            eval += (map & own_flatstones).get_population() as i32 * (w.influence.0 * (level as i32 + 1));
+            eval += (map & !total_pieces).get_population() as i32 * (w.influence.1 * (level as i32 + 1));
+            eval += (map & enemy_pieces).get_population() as i32 * (w.influence.2 >> level);
         }
         eval
     }
@@ -395,20 +725,10 @@ fn evaluate_influence(
 
 #[cfg(test)]
 mod test {
-    use std::cmp;
-    use test::{self, Bencher};
-
-    use analysis::Evaluator;
+    use analysis::{Evaluation as EvaluationTrait, Evaluator, ExplainableEvaluator};
     use impls::tak::*;
-    use super::{
-        END_GAME_FLATSTONE_THRESHOLD,
-        evaluate_influence,
-        evaluate_road_groups,
-        evaluate_stacked_flatstones,
-        evaluate_threats,
-        evaluate_top_pieces,
-        WEIGHT,
-    };
+    use state::State as StateTrait;
+    use super::{evaluate_road_distance, StaticEvaluator, Weights};
 
     lazy_static! {
         static ref STATE: State = State::from_tps("[TPS \"21,22221C,1,12212S,x/2121,2S,2,1S,2/x2,2,2,x/1,2111112C,2,x,21/x,1,21,x2 1 32\"]").unwrap();
@@ -448,7 +768,7 @@ mod test {
             State::from_board(board, state.ply_count)
         }
 
-        let evaluator = evaluator::StaticEvaluator;
+        let evaluator = evaluator::StaticEvaluator::new();
         let original = STATE.clone();
         let original_evaluation = evaluator.evaluate(&original);
         println!("{}\n{}", original_evaluation, original);
@@ -489,136 +809,290 @@ mod test {
         assert!(transformed_evaluation == original_evaluation);
     }
 
-    #[bench]
-    fn bench_evaluate(b: &mut Bencher) {
-        let evaluator = evaluator::StaticEvaluator;
+    #[test]
+    fn test_weights_serialization() {
+        let mut weights = Weights::default();
+        weights.threat = 250;
+        weights.group[3] = 150;
+        weights.tempo = 30;
+        weights.handicap = (40, 200);
+
+        let parsed = weights.to_string().parse::<Weights>().unwrap();
+        assert!(parsed == weights);
+
+        let partial = "# Partial\nthreat 250\n".parse::<Weights>().unwrap();
+        assert!(partial.threat == 250);
+        assert!(partial.capstone == Weights::default().capstone);
+
+        assert!("threat 1 2".parse::<Weights>().is_err());
+        assert!("unknown 1".parse::<Weights>().is_err());
+    }
 
-        b.iter(|| {
-            evaluator.evaluate(test::black_box(&STATE))
+    #[test]
+    fn test_road_distance() {
+        let mut w = Weights::default();
+        w.road_distance = (300, 200, 100);
+
+        let distance = |tps: &str| {
+            let state = State::from_tps(tps).unwrap();
+            let m = &state.metadata;
+            evaluate_road_distance(&w, m, m.p1_pieces | m.p2_pieces, &m.p1_road_groups)
+        };
+
+        // Three in a row is two placements from a road, and one more if the row is blocked
+        assert_eq!(distance("1,1,1,x2/x5/x5/x5/2,2,x3 1 5"), 200);
+        assert_eq!(distance("1,1,1,2,x/x5/x5/x5/2,x4 1 4"), 100);
+        assert_eq!(distance("1,1,1,1,x/x5/x5/x5/2,2,2,x2 1 7"), 300);
+        assert_eq!(distance("x5/x5/x2,1,x2/x5/x5 2 1"), 0);
+
+        // Even friendly standing stones block roads
+        assert_eq!(distance("1,1,1,1S,x/x5/x5/x5/2,2,2,x2 1 7"), 100);
+    }
+
+    #[test]
+    fn test_explain() {
+        let evaluator = StaticEvaluator::new();
+
+        let breakdown = evaluator.explain(&STATE);
+        let totals = breakdown.totals();
+        let (to_move, waiting) = if STATE.ply_count % 2 == 0 { (0, 1) } else { (1, 0) };
+        assert_eq!(breakdown.evaluation, evaluator.evaluate(&STATE));
+        assert_eq!(breakdown.evaluation, totals[to_move] - totals[waiting]);
+        assert_eq!(breakdown.get("tempo").unwrap()[to_move], evaluator.tempo());
+        assert_eq!(breakdown.to_string().lines().count(), breakdown.terms.len() + 2);
+
+        // Komi counts for player 2
+        let mut komi = STATE.clone();
+        komi.set_rules(Rules {
+            half_komi: 5,
+            capstones_count: false,
+        });
+        let komi_value = evaluator.explain(&komi).get("komi").unwrap();
+        assert!(komi_value[0].0 == 0 && komi_value[1].0 > 0);
+        assert_eq!(evaluator.evaluate(&komi), if to_move == 0 {
+            breakdown.evaluation - komi_value[1]
+        } else {
+            breakdown.evaluation + komi_value[1]
         });
+
+        let road = State::from_tps("[TPS \"1,1,1,1,1/x5/x5/x5/2,2,2,2,x 2 5\"]").unwrap();
+        let breakdown = evaluator.explain(&road);
+        assert!(breakdown.terms.is_empty());
+        assert!(breakdown.evaluation.is_lose());
     }
 
-    #[bench]
-    fn bench_evaluate_top_pieces(b: &mut Bencher) {
-        let mut p1_eval = 0;
-        let mut p2_eval = 0;
+    #[test]
+    fn test_score_model() {
+        let evaluator = StaticEvaluator::new();
+        let model = evaluator.score_model().unwrap();
+
+        let evaluation = evaluator.evaluate(&STATE);
+        let probability = model.win_probability(evaluation);
+        assert!(probability > 0.0 && probability < 1.0);
+        assert!((probability + model.win_probability(-evaluation) - 1.0).abs() < 1e-9);
+        assert_eq!(model.win_probability(EvaluationTrait::null()), 0.5);
+
+        let road = State::from_tps("[TPS \"1,1,1,1,1/x5/x5/x5/2,2,2,2,x 2 5\"]").unwrap();
+        assert_eq!(model.win_probability(evaluator.evaluate(&road)), 0.0);
+    }
 
-        let m = &STATE.metadata;
+    #[test]
+    fn test_handicap() {
+        // Player 1 starts two flatstones and a capstone ahead
+        let mut state = State::with_reserves(5, (23, 1), (21, 0));
+        state.execute_plies(&[
+            Ply::from_ptn("a1", Color::Black).unwrap(),
+            Ply::from_ptn("e5", Color::White).unwrap(),
+        ]).unwrap();
+
+        let unweighted = StaticEvaluator::new();
+        assert_eq!(unweighted.explain(&state).get("handicap").unwrap()[0], EvaluationTrait::null());
+
+        let mut weights = Weights::default();
+        weights.handicap = (40, 200);
+        let weighted = StaticEvaluator::with_weights(weights);
+        let handicap = weighted.explain(&state).get("handicap").unwrap();
+        assert_eq!(handicap[0].0, 2 * 40 + 200);
+        assert_eq!(handicap[1].0, 0);
+
+        // Player 1 is to move, so their handicap counts for them
+        assert_eq!(weighted.evaluate(&state), unweighted.evaluate(&state) + handicap[0]);
+    }
 
-        let p1_standing_stones = m.p1_pieces & m.standing_stones;
-        let p2_standing_stones = m.p2_pieces & m.standing_stones;
+    #[cfg(feature = "with_nightly")]
+    mod bench {
+        use std::cmp;
+        use test::{self, Bencher};
+
+        use analysis::Evaluator;
+        use impls::tak::*;
+        use super::STATE;
+        use super::super::{
+            END_GAME_FLATSTONE_THRESHOLD,
+            evaluate_influence,
+            evaluate_road_distance,
+            evaluate_road_groups,
+            evaluate_stacked_flatstones,
+            evaluate_threats,
+            evaluate_top_pieces,
+            Weights,
+        };
 
-        let p1_capstones = m.p1_pieces & m.capstones;
-        let p2_capstones = m.p2_pieces & m.capstones;
+        #[bench]
+        fn bench_evaluate(b: &mut Bencher) {
+            let evaluator = evaluator::StaticEvaluator::new();
 
-        let (p1_flatstone_weight, p2_flatstone_weight) = {
-            let flatstone_threshold = END_GAME_FLATSTONE_THRESHOLD[m.board_size];
+            b.iter(|| {
+                evaluator.evaluate(test::black_box(&STATE))
+            });
+        }
 
-            let p1_position = cmp::min(STATE.p1_flatstones as i32, flatstone_threshold);
-            let p2_position = cmp::min(STATE.p2_flatstones as i32, flatstone_threshold);
+        #[bench]
+        fn bench_evaluate_top_pieces(b: &mut Bencher) {
+            let mut p1_eval = 0;
+            let mut p2_eval = 0;
 
-            (
-                WEIGHT.flatstone.0 * p1_position / flatstone_threshold +
-                WEIGHT.flatstone.1 * (flatstone_threshold - p1_position) / flatstone_threshold,
-                WEIGHT.flatstone.0 * p2_position / flatstone_threshold +
-                WEIGHT.flatstone.1 * (flatstone_threshold - p2_position) / flatstone_threshold,
-            )
-        };
+            let m = &STATE.metadata;
+            let w = &Weights::default();
 
-        b.iter(|| {
-            p1_eval += test::black_box(evaluate_top_pieces(m.p1_flatstone_count as i32, p1_flatstone_weight, p1_standing_stones, p1_capstones));
-            p2_eval += test::black_box(evaluate_top_pieces(m.p2_flatstone_count as i32, p2_flatstone_weight, p2_standing_stones, p2_capstones));
-        });
-    }
+            let p1_standing_stones = m.p1_pieces & m.standing_stones;
+            let p2_standing_stones = m.p2_pieces & m.standing_stones;
 
-    #[bench]
-    fn bench_evaluate_stacked_flatstones(b: &mut Bencher) {
-        let mut p1_eval = 0;
-        let mut p2_eval = 0;
+            let p1_capstones = m.p1_pieces & m.capstones;
+            let p2_capstones = m.p2_pieces & m.capstones;
 
-        let m = &STATE.metadata;
+            let (p1_flatstone_weight, p2_flatstone_weight) = {
+                let flatstone_threshold = END_GAME_FLATSTONE_THRESHOLD[m.board_size];
 
-        let p1_flatstones = m.p1_pieces & !m.standing_stones & !m.capstones;
-        let p2_flatstones = m.p2_pieces & !m.standing_stones & !m.capstones;
+                let p1_position = cmp::min(STATE.p1_flatstones as i32, flatstone_threshold);
+                let p2_position = cmp::min(STATE.p2_flatstones as i32, flatstone_threshold);
 
-        let p1_standing_stones = m.p1_pieces & m.standing_stones;
-        let p2_standing_stones = m.p2_pieces & m.standing_stones;
+                (
+                    w.flatstone.0 * p1_position / flatstone_threshold +
+                    w.flatstone.1 * (flatstone_threshold - p1_position) / flatstone_threshold,
+                    w.flatstone.0 * p2_position / flatstone_threshold +
+                    w.flatstone.1 * (flatstone_threshold - p2_position) / flatstone_threshold,
+                )
+            };
 
-        let p1_capstones = m.p1_pieces & m.capstones;
-        let p2_capstones = m.p2_pieces & m.capstones;
+            b.iter(|| {
+                p1_eval += test::black_box(evaluate_top_pieces(w, m.p1_flatstone_count as i32, p1_flatstone_weight, p1_standing_stones, p1_capstones));
+                p2_eval += test::black_box(evaluate_top_pieces(w, m.p2_flatstone_count as i32, p2_flatstone_weight, p2_standing_stones, p2_capstones));
+            });
+        }
 
-        b.iter(|| {
-            let stacked_flatstones_eval = test::black_box(evaluate_stacked_flatstones(
-                m,
-                p1_flatstones,
-                p2_flatstones,
-                p1_standing_stones,
-                p2_standing_stones,
-                p1_capstones,
-                p2_capstones,
-            ));
-            p1_eval += stacked_flatstones_eval.0;
-            p2_eval += stacked_flatstones_eval.1;
-        });
-    }
+        #[bench]
+        fn bench_evaluate_stacked_flatstones(b: &mut Bencher) {
+            let mut p1_eval = 0;
+            let mut p2_eval = 0;
+
+            let m = &STATE.metadata;
+            let w = &Weights::default();
+
+            let p1_flatstones = m.p1_pieces & !m.standing_stones & !m.capstones;
+            let p2_flatstones = m.p2_pieces & !m.standing_stones & !m.capstones;
+
+            let p1_standing_stones = m.p1_pieces & m.standing_stones;
+            let p2_standing_stones = m.p2_pieces & m.standing_stones;
+
+            let p1_capstones = m.p1_pieces & m.capstones;
+            let p2_capstones = m.p2_pieces & m.capstones;
+
+            b.iter(|| {
+                let stacked_flatstones_eval = test::black_box(evaluate_stacked_flatstones(
+                    w,
+                    m,
+                    p1_flatstones,
+                    p2_flatstones,
+                    p1_standing_stones,
+                    p2_standing_stones,
+                    p1_capstones,
+                    p2_capstones,
+                ));
+                p1_eval += stacked_flatstones_eval.0;
+                p2_eval += stacked_flatstones_eval.1;
+            });
+        }
 
-    #[bench]
-    fn bench_evaluate_road_groups(b: &mut Bencher) {
-        let mut p1_eval = 0;
-        let mut p2_eval = 0;
+        #[bench]
+        fn bench_evaluate_road_groups(b: &mut Bencher) {
+            let mut p1_eval = 0;
+            let mut p2_eval = 0;
 
-        let m = &STATE.metadata;
+            let m = &STATE.metadata;
+            let w = &Weights::default();
 
-        b.iter(|| {
-            p1_eval += test::black_box(evaluate_road_groups(m, &m.p1_road_groups));
-            p2_eval += test::black_box(evaluate_road_groups(m, &m.p2_road_groups));
-        });
-    }
+            b.iter(|| {
+                p1_eval += test::black_box(evaluate_road_groups(w, m, &m.p1_road_groups));
+                p2_eval += test::black_box(evaluate_road_groups(w, m, &m.p2_road_groups));
+            });
+        }
 
-    #[bench]
-    fn bench_evaluate_threats(b: &mut Bencher) {
-        let mut p1_eval = 0;
-        let mut p2_eval = 0;
+        #[bench]
+        fn bench_evaluate_threats(b: &mut Bencher) {
+            let mut p1_eval = 0;
+            let mut p2_eval = 0;
 
-        let m = &STATE.metadata;
+            let m = &STATE.metadata;
+            let w = &Weights::default();
 
-        let total_pieces = m.p1_pieces | m.p2_pieces;
+            let total_pieces = m.p1_pieces | m.p2_pieces;
 
-        b.iter(|| {
-            p1_eval += test::black_box(evaluate_threats(m, total_pieces, &m.p1_road_groups));
-            p2_eval += test::black_box(evaluate_threats(m, total_pieces, &m.p2_road_groups));
-        });
-    }
+            b.iter(|| {
+                p1_eval += test::black_box(evaluate_threats(w, m, total_pieces, &m.p1_road_groups));
+                p2_eval += test::black_box(evaluate_threats(w, m, total_pieces, &m.p2_road_groups));
+            });
+        }
 
-    #[bench]
-    fn bench_evaluate_influence(b: &mut Bencher) {
-        let mut p1_eval = 0;
-        let mut p2_eval = 0;
+        #[bench]
+        fn bench_evaluate_road_distance(b: &mut Bencher) {
+            let mut p1_eval = 0;
+            let mut p2_eval = 0;
 
-        let m = &STATE.metadata;
+            let m = &STATE.metadata;
+            let w = &Weights::default();
 
-        let total_pieces = m.p1_pieces | m.p2_pieces;
+            let total_pieces = m.p1_pieces | m.p2_pieces;
 
-        let p1_flatstones = m.p1_pieces & !m.standing_stones & !m.capstones;
-        let p2_flatstones = m.p2_pieces & !m.standing_stones & !m.capstones;
+            b.iter(|| {
+                p1_eval += test::black_box(evaluate_road_distance(w, m, total_pieces, &m.p1_road_groups));
+                p2_eval += test::black_box(evaluate_road_distance(w, m, total_pieces, &m.p2_road_groups));
+            });
+        }
 
-        b.iter(|| {
-            p1_eval += test::black_box(evaluate_influence(
-                m,
-                total_pieces,
-                m.p1_pieces,
-                &m.p1_flatstones,
-                p1_flatstones,
-                m.p2_pieces,
-            ));
-            p2_eval += test::black_box(evaluate_influence(
-                m,
-                total_pieces,
-                m.p2_pieces,
-                &m.p2_flatstones,
-                p2_flatstones,
-                m.p1_pieces,
-            ));
-        });
+        #[bench]
+        fn bench_evaluate_influence(b: &mut Bencher) {
+            let mut p1_eval = 0;
+            let mut p2_eval = 0;
+
+            let m = &STATE.metadata;
+            let w = &Weights::default();
+
+            let total_pieces = m.p1_pieces | m.p2_pieces;
+
+            let p1_flatstones = m.p1_pieces & !m.standing_stones & !m.capstones;
+            let p2_flatstones = m.p2_pieces & !m.standing_stones & !m.capstones;
+
+            b.iter(|| {
+                p1_eval += test::black_box(evaluate_influence(
+                    w,
+                    m,
+                    total_pieces,
+                    m.p1_pieces,
+                    &m.p1_flatstones,
+                    p1_flatstones,
+                    m.p2_pieces,
+                ));
+                p2_eval += test::black_box(evaluate_influence(
+                    w,
+                    m,
+                    total_pieces,
+                    m.p2_pieces,
+                    &m.p2_flatstones,
+                    p2_flatstones,
+                    m.p1_pieces,
+                ));
+            });
+        }
     }
 }
@@ -20,169 +20,404 @@
 use std::cmp;
 
 use analysis;
-use impls::tak::{Color, Direction, Piece};
+use impls::tak::{drop_patterns, Color, Direction, Piece};
 use impls::tak::ply::Ply;
 use impls::tak::state::State;
-
-lazy_static! {
-    static ref SLIDE_TABLE: Vec<Vec<Vec<u8>>> = generate_slide_table(8);
-}
+use impls::tak::state::metadata::{Bitmap, BitmapInterface, BOARD, EDGE};
 
 impl analysis::Extrapolatable<Ply> for State {
     fn extrapolate(&self) -> Vec<Ply> {
         let mut plies = Vec::new();
+        self.extrapolate_into(&mut plies);
+        plies
+    }
+
+    fn extrapolate_into(&self, plies: &mut Vec<Ply>) {
+        plies.clear();
+
+        if self.is_opening_swap() {
+            self.extrapolate_opening(plies);
+        } else {
+            self.extrapolate_placements(plies, true, true);
+            self.extrapolate_slides(plies);
+        }
+    }
+}
+
+/// Plies are generated in three stages: flatstone and capstone placements, then slides, then
+/// standing stone placements.
+impl analysis::StagedExtrapolatable<Ply> for State {
+    fn extrapolate_stage(&self, stage: usize, plies: &mut Vec<Ply>) -> bool {
+        plies.clear();
+
+        if self.is_opening_swap() {
+            if stage == 0 {
+                self.extrapolate_opening(plies);
+            }
+            return stage == 0;
+        }
+
+        match stage {
+            0 => self.extrapolate_placements(plies, true, false),
+            1 => self.extrapolate_slides(plies),
+            2 => self.extrapolate_placements(plies, false, true),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// A ply is forcing if it leaves its player able to complete a road by placing a single piece,
+/// along a road that passes through a space the ply changed.  Threats that the ply merely left
+/// standing don't count, so that a player with a standing threat doesn't extend every line.
+impl analysis::ThreatDetector<Ply> for State {
+    fn is_forcing(&self, ply: &Ply) -> bool {
+        if self.ply_count <= 2 {
+            return false;
+        }
+
+        let board_size = self.board.len();
+        let m = &self.metadata;
+
+        let (groups, reserves) = if self.ply_count % 2 == 1 {
+            (&m.p1_road_groups, self.p1_flatstones + self.p1_capstones)
+        } else {
+            (&m.p2_road_groups, self.p2_flatstones + self.p2_capstones)
+        };
+
+        if reserves == 0 {
+            return false;
+        }
+
+        let mut changed: Bitmap = 0;
+        match *ply {
+            Ply::Place { x, y, .. } => changed.set(x, y, board_size),
+            Ply::Slide { x, y, direction, ref drops } => {
+                let (dx, dy) = direction.to_offset();
+                for i in 0..drops.len() + 1 {
+                    changed.set(
+                        (x as i8 + dx * i as i8) as usize,
+                        (y as i8 + dy * i as i8) as usize,
+                        board_size,
+                    );
+                }
+            },
+        }
+
+        let is_road = |group: Bitmap| {
+            use impls::tak::Direction::*;
+
+            (group & EDGE[board_size][North as usize] != 0 &&
+             group & EDGE[board_size][South as usize] != 0) ||
+            (group & EDGE[board_size][West as usize] != 0 &&
+             group & EDGE[board_size][East as usize] != 0)
+        };
+
+        let expanded_groups = groups.iter()
+            .map(|group| group.grow(BOARD[board_size], board_size))
+            .collect::<Vec<_>>();
+
+        let empty = BOARD[board_size] & !(m.p1_pieces | m.p2_pieces);
+        let mut candidates = expanded_groups.iter().fold(0, |candidates, &expanded| candidates | expanded) & empty;
+
+        while candidates != 0 {
+            let space = candidates & candidates.wrapping_neg();
+            candidates &= !space;
+
+            let mut road = space;
+            for (group, expanded) in groups.iter().zip(expanded_groups.iter()) {
+                if expanded & space != 0 {
+                    road |= *group;
+                }
+            }
+
+            if road & changed != 0 && is_road(road) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+impl State {
+    /// Returns true if `ply` may be played from this state under the rules of tak: the
+    /// first ply of each player places an opponent's flatstone, placements need a free space
+    /// and a piece in reserve, and slides must carry no more than the board size from a
+    /// controlled stack, drop at least one stone on each space, and only flatten a standing
+    /// stone with a lone capstone.  This doesn't consider whether the game has already ended.
+    ///
+    /// Every ply returned by `extrapolate` is legal.
+    pub fn is_legal(&self, ply: &Ply) -> bool {
+        let board_size = self.board.len();
+        let next_color = self.next_color();
+
+        match *ply {
+            Ply::Place { x, y, ref piece } => {
+                if x >= board_size || y >= board_size || !self.board[x][y].is_empty() {
+                    return false;
+                }
+
+                if self.is_opening_swap() {
+                    return *piece == Piece::Flatstone(next_color.flip());
+                }
+
+                if piece.get_color() != next_color {
+                    return false;
+                }
+
+                let (flatstones, capstones) = match next_color {
+                    Color::White => (self.p1_flatstones, self.p1_capstones),
+                    Color::Black => (self.p2_flatstones, self.p2_capstones),
+                };
+
+                match *piece {
+                    Piece::Capstone(_) => capstones > 0,
+                    _ => flatstones > 0,
+                }
+            },
+            Ply::Slide { x, y, direction, ref drops } => {
+                if self.is_opening_swap() || x >= board_size || y >= board_size {
+                    return false;
+                }
+
+                let stack = &self.board[x][y];
+                match stack.last() {
+                    Some(piece) if piece.get_color() == next_color => (),
+                    _ => return false,
+                }
+
+                if drops.is_empty() || drops.iter().any(|&drop| drop == 0) {
+                    return false;
+                }
+
+                let carry = drops.iter().map(|&drop| drop as usize).sum::<usize>();
+                if carry > cmp::min(stack.len(), board_size) {
+                    return false;
+                }
 
-        let next_color = if self.ply_count % 2 == 0 {
+                let capstone_top = if let Some(&Piece::Capstone(_)) = stack.last() {
+                    true
+                } else {
+                    false
+                };
+
+                let (dx, dy) = direction.to_offset();
+                let (mut tx, mut ty) = (x as i8, y as i8);
+                for (i, &drop) in drops.iter().enumerate() {
+                    tx += dx;
+                    ty += dy;
+
+                    if tx < 0 || tx >= board_size as i8 || ty < 0 || ty >= board_size as i8 {
+                        return false;
+                    }
+
+                    match self.board[tx as usize][ty as usize].last() {
+                        Some(&Piece::Capstone(_)) => return false,
+                        Some(&Piece::StandingStone(_)) => if !(capstone_top && i == drops.len() - 1 && drop == 1) {
+                            return false;
+                        },
+                        _ => (),
+                    }
+                }
+
+                true
+            },
+        }
+    }
+
+    fn next_color(&self) -> Color {
+        if self.ply_count % 2 == 0 {
             Color::White
         } else {
             Color::Black
+        }
+    }
+
+    fn extrapolate_opening(&self, plies: &mut Vec<Ply>) {
+        let next_color = self.next_color();
+
+        for (x, column) in self.board.iter().enumerate() {
+            for (y, stack) in column.iter().enumerate() {
+                if stack.is_empty() {
+                    plies.push(Ply::Place {
+                        x: x,
+                        y: y,
+                        piece: Piece::Flatstone(next_color.flip()),
+                    });
+                }
+            }
+        }
+    }
+
+    fn extrapolate_placements(&self, plies: &mut Vec<Ply>, flatstones_and_capstones: bool, standing_stones: bool) {
+        let next_color = self.next_color();
+
+        let (flatstones, capstones) = match next_color {
+            Color::White => (self.p1_flatstones, self.p1_capstones),
+            Color::Black => (self.p2_flatstones, self.p2_capstones),
         };
 
-        if self.ply_count >= 2 {
-           for (x, column) in self.board.iter().enumerate() {
-                for (y, stack) in column.iter().enumerate() {
-                    if stack.is_empty() {
+        for (x, column) in self.board.iter().enumerate() {
+            for (y, stack) in column.iter().enumerate() {
+                if stack.is_empty() {
+                    if flatstones_and_capstones && flatstones > 0 {
                         plies.push(Ply::Place {
                             x: x,
                             y: y,
                             piece: Piece::Flatstone(next_color),
                         });
+                    }
+
+                    if standing_stones && flatstones > 0 {
                         plies.push(Ply::Place {
                             x: x,
                             y: y,
                             piece: Piece::StandingStone(next_color),
                         });
+                    }
 
-                        match next_color {
-                            Color::White => if self.p1_capstones > 0 {
-                                plies.push(Ply::Place {
-                                    x: x,
-                                    y: y,
-                                    piece: Piece::Capstone(next_color),
-                                });
-                            },
-                            Color::Black => if self.p2_capstones > 0 {
-                                plies.push(Ply::Place {
-                                    x: x,
-                                    y: y,
-                                    piece: Piece::Capstone(next_color),
-                                });
-                            },
-                        }
-                    } else if stack.last().unwrap().get_color() == next_color {
-                        let board_size = self.board.len();
-
-                        let capstone_top = if let Some(&Piece::Capstone(_)) = stack.last() {
-                            true
-                        } else {
-                            false
-                        };
-
-                        for &direction in &[
-                            Direction::North,
-                            Direction::East,
-                            Direction::South,
-                            Direction::West,
-                        ] {
-                            let (distance, standing_stone_target) = {
-                                let (dx, dy) = direction.to_offset();
-                                let (mut tx, mut ty) = (x as i8, y as i8);
-                                let mut distance = 0;
-                                let mut standing_stone_target = false;
-                                loop {
-                                    tx += dx;
-                                    ty += dy;
-
-                                    if tx >= 0 && tx < board_size as i8 && ty >= 0 && ty < board_size as i8 {
-                                        match self.board[tx as usize][ty as usize].last() {
-                                            Some(&Piece::StandingStone(_)) => { standing_stone_target = true; break; },
-                                            Some(&Piece::Capstone(_)) => break,
-                                            _ => distance += 1,
-                                        }
-                                    } else {
-                                        break;
-                                    }
-                                }
-                                (distance, standing_stone_target)
-                            };
-
-                            let max_grab = cmp::min(stack.len(), board_size);
-
-                            if distance > 0 {
-                                for drops in &SLIDE_TABLE[max_grab] {
-                                    if drops.len() <= distance {
-                                        plies.push(Ply::Slide {
-                                            x: x,
-                                            y: y,
-                                            direction: direction,
-                                            drops: drops.clone(),
-                                        });
-
-                                        if capstone_top && standing_stone_target && drops.len() == distance && *drops.last().unwrap() > 1 {
-                                            let mut drops = drops.clone();
-                                            *drops.last_mut().unwrap() -= 1;
-                                            drops.push(1);
-
-                                            plies.push(Ply::Slide {
-                                                x: x,
-                                                y: y,
-                                                direction: direction,
-                                                drops: drops,
-                                            });
-                                        }
-                                    }
-                                }
-                            } else if capstone_top && standing_stone_target {
-                                plies.push(Ply::Slide {
-                                    x: x,
-                                    y: y,
-                                    direction: direction,
-                                    drops: vec![1],
-                                });
-                            }
-                        }
+                    if flatstones_and_capstones && capstones > 0 {
+                        plies.push(Ply::Place {
+                            x: x,
+                            y: y,
+                            piece: Piece::Capstone(next_color),
+                        });
                     }
                 }
             }
-        } else {
-            for (x, column) in self.board.iter().enumerate() {
-                for (y, stack) in column.iter().enumerate() {
-                    if stack.is_empty() {
-                        plies.push(Ply::Place {
+        }
+    }
+
+    fn extrapolate_slides(&self, plies: &mut Vec<Ply>) {
+        let next_color = self.next_color();
+        let board_size = self.board.len();
+
+        for (x, column) in self.board.iter().enumerate() {
+            for (y, stack) in column.iter().enumerate() {
+                if stack.is_empty() || stack.last().unwrap().get_color() != next_color {
+                    continue;
+                }
+
+                let capstone_top = if let Some(&Piece::Capstone(_)) = stack.last() {
+                    true
+                } else {
+                    false
+                };
+
+                for &direction in &[
+                    Direction::North,
+                    Direction::East,
+                    Direction::South,
+                    Direction::West,
+                ] {
+                    let (distance, standing_stone_target) = {
+                        let (dx, dy) = direction.to_offset();
+                        let (mut tx, mut ty) = (x as i8, y as i8);
+                        let mut distance = 0;
+                        let mut standing_stone_target = false;
+                        loop {
+                            tx += dx;
+                            ty += dy;
+
+                            if tx >= 0 && tx < board_size as i8 && ty >= 0 && ty < board_size as i8 {
+                                match self.board[tx as usize][ty as usize].last() {
+                                    Some(&Piece::StandingStone(_)) => { standing_stone_target = true; break; },
+                                    Some(&Piece::Capstone(_)) => break,
+                                    _ => distance += 1,
+                                }
+                            } else {
+                                break;
+                            }
+                        }
+                        (distance, standing_stone_target)
+                    };
+
+                    let max_grab = cmp::min(stack.len(), board_size);
+
+                    for drops in drop_patterns(max_grab, distance, capstone_top && standing_stone_target) {
+                        plies.push(Ply::Slide {
                             x: x,
                             y: y,
-                            piece: Piece::Flatstone(next_color.flip()),
+                            direction: direction,
+                            drops: drops,
                         });
                     }
                 }
             }
         }
-
-        plies
     }
 }
 
-fn generate_slide_table(size: u8) -> Vec<Vec<Vec<u8>>> {
-    let mut result: Vec<Vec<Vec<u8>>> = Vec::with_capacity(size as usize);
-    result.push(Vec::new());
+#[cfg(test)]
+mod test {
+    use rand::Rng;
+
+    use analysis::{Extrapolatable, ThreatDetector};
+    use impls::tak::*;
+    use state::State as StateTrait;
+    use util::JKiss32Rng;
 
-    for stack in 1..(size + 1) {
-        let mut out = Vec::with_capacity((2 as usize).pow(stack as u32) - 1);
+    #[test]
+    fn test_extrapolate_legal() {
+        let mut rng = JKiss32Rng::with_seed(0);
 
-        for i in 1..(stack + 1) {
-            out.push(vec![i]);
+        for game in 0..50 {
+            let mut state = if game % 2 == 0 {
+                State::new(5)
+            } else {
+                State::with_reserves(4, (4, 1), (6, 0))
+            };
 
-            for sub in &result[(stack - i) as usize] {
-                let mut t = vec![0; sub.len() + 1];
-                t[0] = i;
-                t[1..].clone_from_slice(sub);
+            while state.check_resolution().is_none() {
+                let plies = state.extrapolate();
+                if plies.is_empty() {
+                    break;
+                }
+
+                for ply in &plies {
+                    assert!(state.is_legal(ply), "{:?} is not legal in {}", ply, state.to_tps());
+                    let mut next = state.clone();
+                    assert!(next.execute_ply(Some(ply)).is_ok());
+                }
 
-                out.push(t);
+                let ply = &plies[rng.gen_range(0, plies.len())];
+                state.execute_ply(Some(ply)).unwrap();
             }
         }
+    }
 
-        result.push(out);
+    #[test]
+    fn test_is_forcing() {
+        let state = State::from_tps("[TPS \"x5/x5/x5/2,2,2,x2/1,1,1,x2 1 4\"]").unwrap();
+
+        let mut threat = state.clone();
+        let ply = Ply::from_ptn("d1", Color::White).unwrap();
+        threat.execute_ply(Some(&ply)).unwrap();
+        assert!(threat.is_forcing(&ply));
+
+        let mut quiet = state.clone();
+        let ply = Ply::from_ptn("a5", Color::White).unwrap();
+        quiet.execute_ply(Some(&ply)).unwrap();
+        assert!(!quiet.is_forcing(&ply));
     }
 
-    result
+    #[test]
+    fn test_is_legal() {
+        let state = State::from_tps("[TPS \"x5/x5/x,2S,x3/x,121C,x3/1,x4 1 3\"]").unwrap();
+        let legal = ["b2+", "Sd4", "c1", "a1+", "3b2>", "2b2>11", "3b2>111"];
+        let illegal = ["Cd4", "b3", "b3+", "c3>", "4b2>", "2b2+2", "2b2<11", "2b2-11"];
+
+        for ptn in legal.iter() {
+            assert!(state.is_legal(&Ply::from_ptn(ptn, Color::White).unwrap()), "{}", ptn);
+        }
+        for ptn in illegal.iter() {
+            assert!(!state.is_legal(&Ply::from_ptn(ptn, Color::White).unwrap()), "{}", ptn);
+        }
+
+        let opening = State::new(5);
+        assert!(opening.is_legal(&Ply::from_ptn("a1", Color::Black).unwrap()));
+        assert!(!opening.is_legal(&Ply::from_ptn("a1", Color::White).unwrap()));
+        assert!(!opening.is_legal(&Ply::from_ptn("Ca1", Color::Black).unwrap()));
+    }
 }
@@ -19,11 +19,30 @@
 
 use std::cmp;
 
+use analysis::FeatureExtractor;
 use impls::tak::{Color, Direction, Piece, State};
 use impls::tak::state::metadata::{Bitmap, BitmapInterface, EDGE};
 
+/// The number of features returned by `gather_features`.
+pub const FEATURE_COUNT: usize = 264;
+
+/// Extracts the features that the ANN evaluator is trained on.  Only 5x5 boards are supported.
+#[derive(Clone, Copy, Debug)]
+pub struct Features;
+
+impl FeatureExtractor<State> for Features {
+    fn dimension(&self) -> usize {
+        FEATURE_COUNT
+    }
+
+    fn features(&self, state: &State, out: &mut [f32]) {
+        debug_assert!(state.board.len() == 5, "Only 5x5 boards are supported!");
+        out.copy_from_slice(&gather_features(state));
+    }
+}
+
 pub fn gather_features(state: &State) -> Vec<f32> {
-    let mut features = Vec::with_capacity(264);
+    let mut features = Vec::with_capacity(FEATURE_COUNT);
 
     // 1 - Side to move
     features.push((state.ply_count % 2) as f32);
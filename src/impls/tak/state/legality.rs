@@ -0,0 +1,250 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::error::Error;
+use std::fmt;
+
+use impls::tak::{Color, Direction, Piece};
+use impls::tak::ply::Ply;
+use impls::tak::state::State;
+use state::PlyError;
+
+/// The reasons a ply can be rejected.  Coordinates are board spaces, with `(0, 0)` being a1,
+/// so that an interface can point at the space responsible.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IllegalReason {
+    /// The placement space is already occupied.
+    Occupied {
+        x: usize,
+        y: usize,
+    },
+    /// The placement space, or the end of the slide from `(x, y)`, is off the board.
+    OutOfBounds {
+        x: usize,
+        y: usize,
+    },
+    /// In the first two plies, each player must place one of the opponent's flatstones.
+    OpeningSwap {
+        x: usize,
+        y: usize,
+    },
+    /// The player has none of `piece` left in reserve.
+    NoReserve {
+        piece: Piece,
+    },
+    /// The stack at `(x, y)` isn't controlled by the player to move.
+    OpponentsStack {
+        x: usize,
+        y: usize,
+    },
+    /// More than `limit` pieces were picked up from `(x, y)`.
+    CarryLimit {
+        x: usize,
+        y: usize,
+        carry: usize,
+        limit: usize,
+    },
+    /// The stack at `(x, y)` is shorter than the carry, or the drops are malformed.
+    NotEnoughPieces {
+        x: usize,
+        y: usize,
+        carry: usize,
+        height: usize,
+    },
+    /// The slide runs into a capstone at `(x, y)`.
+    BlockedByCapstone {
+        x: usize,
+        y: usize,
+    },
+    /// The slide runs into a standing stone at `(x, y)`.
+    BlockedByStandingStone {
+        x: usize,
+        y: usize,
+    },
+    /// The standing stone at `(x, y)` can only be flattened by a capstone moving alone.
+    CrushNeedsCapstone {
+        x: usize,
+        y: usize,
+    },
+}
+
+impl fmt::Display for IllegalReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IllegalReason::Occupied { x, y } => write!(f, "{} is already occupied", Square(x, y)),
+            IllegalReason::OutOfBounds { x, y } => write!(f, "The ply at {} leaves the board", Square(x, y)),
+            IllegalReason::OpeningSwap { x, y } => write!(f, "The first ply of each player must place an opponent's flatstone, not at {}", Square(x, y)),
+            IllegalReason::NoReserve { ref piece } => write!(f, "No {:?} left in reserve", piece),
+            IllegalReason::OpponentsStack { x, y } => write!(f, "The stack at {} belongs to the opponent", Square(x, y)),
+            IllegalReason::CarryLimit { x, y, carry, limit } => write!(f, "Cannot carry {} pieces from {}; the limit is {}", carry, Square(x, y), limit),
+            IllegalReason::NotEnoughPieces { x, y, carry, height } => write!(f, "Cannot carry {} pieces from {}, which has {}", carry, Square(x, y), height),
+            IllegalReason::BlockedByCapstone { x, y } => write!(f, "Cannot slide onto the capstone at {}", Square(x, y)),
+            IllegalReason::BlockedByStandingStone { x, y } => write!(f, "Cannot slide onto the standing stone at {}", Square(x, y)),
+            IllegalReason::CrushNeedsCapstone { x, y } => write!(f, "Only a lone capstone can flatten the standing stone at {}", Square(x, y)),
+        }
+    }
+}
+
+impl Error for IllegalReason {
+    fn description(&self) -> &str {
+        "Illegal ply"
+    }
+}
+
+impl From<IllegalReason> for PlyError {
+    fn from(reason: IllegalReason) -> PlyError {
+        match reason {
+            IllegalReason::Occupied { .. } => PlyError::Occupied,
+            IllegalReason::OutOfBounds { .. } => PlyError::OutOfBounds,
+            IllegalReason::OpeningSwap { .. } => PlyError::Illegal("The first ply of each player must place an opponent's flatstone"),
+            IllegalReason::NoReserve { .. } => PlyError::InsufficientPieces,
+            IllegalReason::OpponentsStack { .. } => PlyError::WrongColor,
+            IllegalReason::CarryLimit { .. } |
+            IllegalReason::NotEnoughPieces { .. } => PlyError::Illegal("Illegal carry amount"),
+            IllegalReason::BlockedByCapstone { .. } => PlyError::Illegal("Cannot slide onto a capstone"),
+            IllegalReason::BlockedByStandingStone { .. } |
+            IllegalReason::CrushNeedsCapstone { .. } => PlyError::Illegal("Cannot slide onto a standing stone"),
+        }
+    }
+}
+
+impl State {
+    /// Returns why `ply` can't be executed in this state, or `None` if it can.  These are
+    /// the same checks `execute_ply` makes.
+    pub fn explain_illegal(&self, ply: &Ply) -> Option<IllegalReason> {
+        let board_size = self.board.len();
+
+        match *ply {
+            Ply::Place { x, y, ref piece } => {
+                if x >= board_size || y >= board_size {
+                    return Some(IllegalReason::OutOfBounds { x: x, y: y });
+                }
+
+                if !self.board[x][y].is_empty() {
+                    return Some(IllegalReason::Occupied { x: x, y: y });
+                }
+
+                if self.is_opening_swap() {
+                    let opponent_color = if self.ply_count % 2 == 0 {
+                        Color::Black
+                    } else {
+                        Color::White
+                    };
+
+                    if *piece != Piece::Flatstone(opponent_color) {
+                        return Some(IllegalReason::OpeningSwap { x: x, y: y });
+                    }
+                }
+
+                let count = match *piece {
+                    Piece::Flatstone(Color::White) |
+                    Piece::StandingStone(Color::White) => self.p1_flatstones,
+                    Piece::Flatstone(Color::Black) |
+                    Piece::StandingStone(Color::Black) => self.p2_flatstones,
+                    Piece::Capstone(Color::White) => self.p1_capstones,
+                    Piece::Capstone(Color::Black) => self.p2_capstones,
+                };
+
+                if count == 0 {
+                    return Some(IllegalReason::NoReserve { piece: piece.clone() });
+                }
+            },
+            Ply::Slide { x, y, direction, ref drops } => {
+                if x >= board_size || y >= board_size {
+                    return Some(IllegalReason::OutOfBounds { x: x, y: y });
+                }
+
+                if self.is_opening_swap() {
+                    return Some(IllegalReason::OpeningSwap { x: x, y: y });
+                }
+
+                let next_color = if self.ply_count % 2 == 0 {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+
+                let stack = &self.board[x][y];
+
+                if let Some(piece) = stack.last() {
+                    if piece.get_color() != next_color {
+                        return Some(IllegalReason::OpponentsStack { x: x, y: y });
+                    }
+                }
+
+                let carry = drops.iter().map(|&drop| drop as usize).sum::<usize>();
+
+                if carry > board_size {
+                    return Some(IllegalReason::CarryLimit { x: x, y: y, carry: carry, limit: board_size });
+                }
+
+                if stack.len() < carry || drops.is_empty() || drops.contains(&0) {
+                    return Some(IllegalReason::NotEnoughPieces { x: x, y: y, carry: carry, height: stack.len() });
+                }
+
+                if !slide_fits(x, y, direction, drops.len(), board_size) {
+                    return Some(IllegalReason::OutOfBounds { x: x, y: y });
+                }
+
+                let (dx, dy) = direction.to_offset();
+                let (mut nx, mut ny) = (x as i8, y as i8);
+
+                for (i, &drop) in drops.iter().enumerate() {
+                    nx += dx;
+                    ny += dy;
+
+                    let (tx, ty) = (nx as usize, ny as usize);
+
+                    match self.board[tx][ty].last() {
+                        Some(&Piece::Capstone(_)) => return Some(IllegalReason::BlockedByCapstone { x: tx, y: ty }),
+                        Some(&Piece::StandingStone(_)) => if i < drops.len() - 1 {
+                            return Some(IllegalReason::BlockedByStandingStone { x: tx, y: ty });
+                        } else {
+                            match stack.last() {
+                                Some(&Piece::Capstone(_)) if drop == 1 => (),
+                                _ => return Some(IllegalReason::CrushNeedsCapstone { x: tx, y: ty }),
+                            }
+                        },
+                        _ => (),
+                    }
+                }
+            },
+        }
+
+        None
+    }
+}
+
+/// Whether `count` spaces in `direction` from `(x, y)` are all on the board.
+fn slide_fits(x: usize, y: usize, direction: Direction, count: usize, board_size: usize) -> bool {
+    let (dx, dy) = direction.to_offset();
+    let tx = x as i8 + dx * count as i8;
+    let ty = y as i8 + dy * count as i8;
+
+    tx >= 0 && tx < board_size as i8 && ty >= 0 && ty < board_size as i8
+}
+
+/// Displays a space in PTN, e.g. `a1`.
+struct Square(usize, usize);
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", (self.0 as u8 + 97) as char, self.1 + 1)
+    }
+}
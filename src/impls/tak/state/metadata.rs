@@ -137,11 +137,13 @@ impl Metadata {
                 self.p1_flatstone_count -= 1;
                 self.p1_flatstones[z].clear(x, y, self.board_size);
                 self.p1_pieces.clear(x, y, self.board_size);
+                trim_layers(&mut self.p1_flatstones);
             },
             Color::Black => {
                 self.p2_flatstone_count -= 1;
                 self.p2_flatstones[z].clear(x, y, self.board_size);
                 self.p2_pieces.clear(x, y, self.board_size);
+                trim_layers(&mut self.p2_flatstones);
             },
         }
     }
@@ -216,6 +218,19 @@ impl Metadata {
         self.p1_road_groups = (self.p1_pieces & !self.standing_stones).get_groups(self.board_size);
         self.p2_road_groups = (self.p2_pieces & !self.standing_stones).get_groups(self.board_size);
     }
+
+    // Brings the road groups up to date with the pieces, only reflooding the groups around
+    // the spaces that have changed since the last update
+    pub fn update_road_groups(&mut self) {
+        let p1_roads = self.p1_pieces & !self.standing_stones;
+        let p2_roads = self.p2_pieces & !self.standing_stones;
+
+        update_groups(&mut self.p1_road_groups, p1_roads, self.board_size);
+        update_groups(&mut self.p2_road_groups, p2_roads, self.board_size);
+
+        debug_assert_eq!(self.p1_road_groups, p1_roads.get_groups(self.board_size));
+        debug_assert_eq!(self.p2_road_groups, p2_roads.get_groups(self.board_size));
+    }
 }
 
 impl Clone for Metadata {
@@ -387,6 +402,31 @@ impl BitmapInterface for Bitmap {
     }
 }
 
+// Groups that lost a space may have split, and groups next to a new space may have merged, so
+// those are flooded again within the new map.  Every other group is unchanged.  The groups are
+// kept in the order that get_groups returns them, by their lowest space.
+fn update_groups(groups: &mut Vec<Bitmap>, map: Bitmap, stride: usize) {
+    let old_map = groups.iter().fold(0, |map, group| map | group);
+    if old_map == map {
+        return;
+    }
+
+    let removed = old_map & !map;
+    let added = map & !old_map;
+    let neighbors = added.grow(BOARD[stride], stride);
+
+    let mut dirty = added;
+    groups.retain(|&group| if group & (removed | neighbors) != 0 {
+        dirty |= group;
+        false
+    } else {
+        true
+    });
+
+    groups.extend((dirty & map).get_groups(stride));
+    groups.sort_by_key(|group| group.trailing_zeros());
+}
+
 fn generate_edge_masks() -> [[Bitmap; 4]; 9] {
     use impls::tak::Direction::*;
 
@@ -414,3 +454,69 @@ fn generate_board_masks() -> [Bitmap; 9] {
 
     board
 }
+
+// Drops empty layers from the top of a player's flatstone maps, so that a stack that grew and
+// shrank back leaves the metadata, and so the state's equality and hash, as they were
+fn trim_layers(layers: &mut Vec<Bitmap>) {
+    while layers.last() == Some(&0) {
+        layers.pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    use impls::tak::Ply;
+    use state::State as StateTrait;
+    use super::*;
+
+    fn hash(state: &State) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_update_groups() {
+        let mut map: Bitmap = 0;
+        map.set(0, 0, 5);
+        map.set(2, 0, 5);
+        let mut groups = map.get_groups(5);
+        assert_eq!(groups.len(), 2);
+
+        // Filling the gap merges the two groups
+        map.set(1, 0, 5);
+        update_groups(&mut groups, map, 5);
+        assert_eq!(groups, vec![map]);
+
+        // And clearing it splits them again
+        map.clear(1, 0, 5);
+        map.set(4, 4, 5);
+        update_groups(&mut groups, map, 5);
+        assert_eq!(groups, map.get_groups(5));
+        assert_eq!(groups.len(), 3);
+    }
+
+    #[test]
+    fn test_shrinking_stack() {
+        let original = State::from_tps("[TPS \"x5/x5/x5/x5/12,2,x3 2 3\"]").unwrap();
+
+        // Black's stone grows a1 to three pieces, and taking it back shrinks it again
+        let mut state = original.clone();
+        let ply = Ply::from_ptn("b1<", Color::Black).unwrap();
+        state.execute_ply(Some(&ply)).unwrap();
+        assert_eq!(state.metadata.p2_flatstones.len(), 3);
+        state.revert_ply(Some(&ply)).unwrap();
+
+        assert_eq!(state.metadata.p2_flatstones, original.metadata.p2_flatstones);
+        assert!(state == original);
+        assert_eq!(hash(&state), hash(&original));
+
+        // Black's stone leaves a1 for good
+        let ply = Ply::from_ptn("a1+", Color::Black).unwrap();
+        state.execute_ply(Some(&ply)).unwrap();
+        assert_eq!(state.metadata.p2_flatstones.len(), 1);
+    }
+}
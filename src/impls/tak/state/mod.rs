@@ -19,12 +19,39 @@
 
 use std::fmt::{self, Write};
 use std::hash::{Hash, Hasher};
-use std::str::FromStr;
 
 use impls::tak::{Color, Piece, Ply};
-use state::State as StateTrait;
+use position_key::PositionKey;
+use state::{PlySequenceError, State as StateTrait};
+
+use self::metadata::{Bitmap, Metadata};
+
+pub use self::builder::{SetupError, StateBuilder};
+pub use self::features::{FEATURE_COUNT, Features, gather_features};
+pub use self::legality::IllegalReason;
+pub use self::tps::TpsError;
+
+/// The difference between the starting reserves of player 1 and player 2, for games played
+/// with a handicap.  Positive values favor player 1.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+pub struct Handicap {
+    pub flatstones: i8,
+    pub capstones: i8,
+}
 
-use self::metadata::Metadata;
+/// Variations on the scoring of flat wins.  The default is the standard rules: no komi, and
+/// only flatstones count.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "with_serde", serde(default))]
+pub struct Rules {
+    /// The number of half flats added to player 2's count, to offset player 1's advantage
+    /// of moving first.  A komi of 2.5 flats is 5.  An odd number rules out draws by count.
+    pub half_komi: u8,
+    /// Whether capstones on top of stacks are counted along with flatstones.
+    pub capstones_count: bool,
+}
 
 /// The state of the game.
 #[derive(Debug, Eq, PartialEq)]
@@ -45,6 +72,8 @@ pub struct State {
     pub ply_count: u16,
 
     ply_crushes: Vec<bool>,
+    handicap: Handicap,
+    rules: Rules,
     metadata: Metadata,
 }
 
@@ -72,18 +101,68 @@ impl State {
             board: vec![vec![Vec::new(); board_size]; board_size],
             ply_count: 0,
             ply_crushes: Vec::new(),
+            handicap: Handicap::default(),
+            rules: Rules::default(),
             metadata: Metadata::new(board_size),
         }
     }
 
+    /// Creates a blank state of the specified board size, with each player starting with
+    /// the given numbers of flatstones and capstones.
+    ///
+    /// The handicap isn't hashed, so a transposition table shouldn't be shared between games
+    /// played with different reserves.
+    ///
+    /// # Panics
+    /// This function panics if it is passed a board size less than 3 or greater than 8, or if
+    /// the players' reserves of either piece differ by more than 127.
+    pub fn with_reserves(board_size: usize, p1_reserves: (u8, u8), p2_reserves: (u8, u8)) -> State {
+        let imbalance = (
+            p1_reserves.0 as i16 - p2_reserves.0 as i16,
+            p1_reserves.1 as i16 - p2_reserves.1 as i16,
+        );
+        if imbalance.0.abs() > i8::max_value() as i16 || imbalance.1.abs() > i8::max_value() as i16 {
+            panic!("Reserve imbalance too large: {:?}", imbalance);
+        }
+
+        let mut state = State::new(board_size);
+        state.p1_flatstones = p1_reserves.0;
+        state.p1_capstones = p1_reserves.1;
+        state.p2_flatstones = p2_reserves.0;
+        state.p2_capstones = p2_reserves.1;
+        state.handicap = Handicap {
+            flatstones: imbalance.0 as i8,
+            capstones: imbalance.1 as i8,
+        };
+        state
+    }
+
+    /// Returns the imbalance in the players' starting reserves.
+    pub fn get_handicap(&self) -> Handicap {
+        self.handicap
+    }
+
+    /// Returns true if the next ply is one of the first two of the game, on which each
+    /// player places one of the other's flatstones rather than one of their own.
+    pub fn is_opening_swap(&self) -> bool {
+        self.ply_count < 2
+    }
+
+    /// Returns the rules used to score flat wins.
+    pub fn get_rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Sets the rules used to score flat wins.
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+    }
+
     /// Creates a state from the given board size and executes the given plies.
-    pub fn from_plies(size: usize, plies: &[Ply]) -> Result<State, String> {
+    pub fn from_plies(size: usize, plies: &[Ply]) -> Result<State, PlySequenceError> {
         let mut state = State::new(size);
-        if let Err(error) = state.execute_plies(plies) {
-            Err(error)
-        } else {
-            Ok(state)
-        }
+        state.execute_plies(plies)?;
+        Ok(state)
     }
 
     /// Creates a state from the given board and a ply count.
@@ -131,198 +210,13 @@ impl State {
             board: board,
             ply_count: ply_count,
             ply_crushes: Vec::new(),
+            handicap: Handicap::default(),
+            rules: Rules::default(),
             metadata: Metadata::new(board_size),
         };
         state.metadata = Metadata::from_state(&state);
         state
     }
-
-    /// Creates a state from a string in TPS format, i.e. `"[TPS \"x5/x5/x5/x5/x5 1 1\"]"`.
-    ///
-    /// Returns `None` if the provided string has an error.
-    pub fn from_tps(tps: &str) -> Option<State> { // XXX Return Result<State, String>
-        if &tps[0..6] != "[TPS \"" || &tps[(tps.len() - 2)..] != "\"]" {
-            return None;
-        }
-
-        let mut chars = tps[6..(tps.len() - 2)].chars();
-
-        let mut x = 0;
-        let mut y = 0;
-        let mut board: Vec<Vec<Vec<Piece>>> = Vec::new();
-        let mut piece_color = None;
-
-        let mut p1_used_flatstones = 0;
-        let mut p1_used_capstones = 0;
-
-        let mut p2_used_flatstones = 0;
-        let mut p2_used_capstones = 0;
-
-        fn ensure_dimensions(board: &mut Vec<Vec<Vec<Piece>>>, x: usize, y: usize) {
-            if x >= board.len() {
-                for _ in board.len()..(x + 1) {
-                    board.push(Vec::new());
-                }
-            }
-
-            for column in board.iter_mut() {
-                if y >= column.len() {
-                    for _ in column.len()..(y + 1) {
-                        column.push(Vec::new());
-                    }
-                }
-            }
-        }
-
-        let mut next = chars.next();
-        while next.is_some() {
-            ensure_dimensions(&mut board, x, y);
-
-            if let Some(color) = piece_color {
-                let piece = match next {
-                    Some('S') => Piece::StandingStone(color),
-                    Some('C') => Piece::Capstone(color),
-                    _ => Piece::Flatstone(color),
-                };
-
-                let (used_flatstones, used_capstones) = match color {
-                    Color::White => (&mut p1_used_flatstones, &mut p1_used_capstones),
-                    Color::Black => (&mut p2_used_flatstones, &mut p2_used_capstones),
-                };
-
-                match piece {
-                    Piece::Capstone(_) => *used_capstones += 1,
-                    _ => *used_flatstones += 1,
-                }
-
-                board[x][y].push(piece);
-
-                piece_color = None;
-                match next {
-                    Some('S') |
-                    Some('C') => next = chars.next(),
-                    _ => (),
-                }
-            }
-
-            match next {
-                Some('x') => match chars.next() {
-                    Some(c) => if c.is_digit(10) {
-                        x += (c as u8 - 49) as usize;
-                    } else if c == ',' {
-                        x += 1;
-                    } else if c == '/' {
-                        x = 0;
-                        y += 1;
-                    } else if c == ' ' {
-                        break;
-                    } else {
-                        return None;
-                    },
-                    _ => return None,
-                },
-                Some(',') => {
-                    x += 1;
-                },
-                Some('/') => {
-                    x = 0;
-                    y += 1;
-                },
-                Some(' ') => break,
-                Some('1') => piece_color = Some(Color::White),
-                Some('2') => piece_color = Some(Color::Black),
-                _ => return None,
-            }
-
-            next = chars.next();
-        }
-
-        let ply_count = {
-            let player = match chars.next() {
-                Some('1') => 0,
-                Some('2') => 1,
-                _ => return None,
-            };
-
-            chars.next();
-
-            let turn_count = match u16::from_str(chars.as_str()) {
-                Ok(c) => if c > 0 {
-                    c - 1
-                } else {
-                    return None
-                },
-                _ => return None,
-            };
-
-            turn_count * 2 + player
-        };
-
-        for column in &mut board {
-            column.reverse();
-        }
-
-        let mut state = State::new(board.len());
-        state.p1_flatstones -= p1_used_flatstones;
-        state.p1_capstones -= p1_used_capstones;
-        state.p2_flatstones -= p2_used_flatstones;
-        state.p2_capstones -= p2_used_capstones;
-        state.board = board;
-        state.ply_count = ply_count;
-        state.metadata = Metadata::from_state(&state);
-
-        Some(state)
-    }
-
-    pub fn to_tps(&self) -> String {
-        let mut tps = String::from("[TPS \"");
-
-        let mut y = self.board.len() - 1;
-        loop {
-            let mut x = 0;
-            while x < self.board.len() {
-                if self.board[x][y].is_empty() {
-                    tps += "x";
-                    let mut empty = 1;
-                    while x + 1 < self.board.len() && self.board[x + 1][y].is_empty() {
-                        x += 1;
-                        empty += 1;
-                    }
-                    if empty > 1 {
-                        tps += &format!("{}", empty);
-                    }
-                } else {
-                    for piece in &self.board[x][y] {
-                        match *piece {
-                            Piece::Flatstone(Color::White) => tps += "1",
-                            Piece::Flatstone(Color::Black) => tps += "2",
-                            Piece::StandingStone(Color::White) => tps += "1S",
-                            Piece::StandingStone(Color::Black) => tps += "2S",
-                            Piece::Capstone(Color::White) => tps += "1C",
-                            Piece::Capstone(Color::Black) => tps += "2C",
-                        }
-                    }
-                }
-
-                x += 1;
-                if x < self.board.len() {
-                    tps += ",";
-                }
-            }
-
-            if y == 0 {
-                break;
-            } else {
-                y -= 1;
-                tps += "/";
-            }
-        }
-
-        tps += &format!(" {} ", self.ply_count % 2 + 1);
-        tps += &format!("{}\"]", self.ply_count / 2 + 1);
-
-        tps
-    }
 }
 
 impl Clone for State {
@@ -335,6 +229,8 @@ impl Clone for State {
             board: self.board.clone(),
             ply_count: self.ply_count,
             ply_crushes: self.ply_crushes.clone(),
+            handicap: self.handicap,
+            rules: self.rules,
             metadata: self.metadata.clone(),
         }
     }
@@ -347,10 +243,14 @@ impl Clone for State {
         self.board.clone_from(&source.board);
         self.ply_count = source.ply_count;
         self.ply_crushes.clone_from(&source.ply_crushes);
+        self.handicap = source.handicap;
+        self.rules = source.rules;
         self.metadata.clone_from(&source.metadata);
     }
 }
 
+/// Hashes the board and the side to move.  The reserves follow from the board, but the handicap
+/// and rules they were set up under don't, and aren't included.
 impl Hash for State {
     fn hash<H>(&self, state: &mut H) where H: Hasher {
         if self.ply_count % 2 == 0 {
@@ -367,6 +267,41 @@ impl Hash for State {
     }
 }
 
+/// Mixes `bitmap` with `salt`, so that the same bitmap in different roles yields unrelated keys.
+fn mix(bitmap: Bitmap, salt: u64) -> u64 {
+    let mut key = bitmap ^ salt.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    key = (key ^ (key >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    key = (key ^ (key >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    key ^ (key >> 31)
+}
+
+/// Keys the same bitmaps as `Hash`, mixing each rather than feeding it through a hasher.
+impl PositionKey for State {
+    fn position_key(&self) -> u64 {
+        let mut key = if self.ply_count % 2 == 0 {
+            0
+        } else {
+            mix(0, 5)
+        };
+
+        // Each player's layers are mixed up to their own count.  Empty layers don't contribute,
+        // so states that differ only in their number of layers agree.
+        for (layer, &p1) in self.metadata.p1_flatstones.iter().enumerate() {
+            if p1 != 0 {
+                key ^= mix(p1, 6 + 2 * layer as u64);
+            }
+        }
+        for (layer, &p2) in self.metadata.p2_flatstones.iter().enumerate() {
+            if p2 != 0 {
+                key ^= mix(p2, 7 + 2 * layer as u64);
+            }
+        }
+
+        key ^ mix(self.metadata.standing_stones, 1) ^ mix(self.metadata.capstones, 2) ^
+            mix(self.metadata.p1_pieces, 3) ^ mix(self.metadata.p2_pieces, 4)
+    }
+}
+
 impl fmt::Display for State {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let board_size = self.board.len();
@@ -457,6 +392,20 @@ pub mod evaluator;
 #[cfg(feature = "with_tak_ann")]
 mod ann;
 
+mod builder;
+
+#[cfg(feature = "with_cli")]
+mod cli;
+
 mod extrapolation;
+mod features;
+mod legality;
 mod metadata;
+mod persist;
+
+#[cfg(feature = "with_serde")]
+mod serialize;
+
 mod state;
+mod symmetry;
+mod tps;
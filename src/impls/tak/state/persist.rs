@@ -0,0 +1,86 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use impls::tak::Piece;
+use impls::tak::state::State;
+use impls::tak::state::metadata::Metadata;
+use persist::{Decoder, Encode, Persistable, PersistError};
+
+impl Persistable for State {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.write_u8(self.board.len() as u8);
+        bytes.write_u32(self.ply_count as u32);
+        bytes.write_u8(self.p1_flatstones);
+        bytes.write_u8(self.p1_capstones);
+        bytes.write_u8(self.p2_flatstones);
+        bytes.write_u8(self.p2_capstones);
+        bytes.write_u8(self.handicap.flatstones as u8);
+        bytes.write_u8(self.handicap.capstones as u8);
+        bytes.write_u8(self.rules.half_komi);
+        bytes.write_u8(self.rules.capstones_count as u8);
+
+        bytes.write_u32(self.ply_crushes.len() as u32);
+        for &crush in &self.ply_crushes {
+            bytes.write_u8(crush as u8);
+        }
+
+        for column in &self.board {
+            for stack in column {
+                bytes.write_u8(stack.len() as u8);
+                for piece in stack {
+                    piece.encode(bytes);
+                }
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<State, PersistError> {
+        let board_size = decoder.read_u8()? as usize;
+        if board_size < 3 || board_size > 8 {
+            return Err(PersistError::Malformed(format!("Illegal board size: {}", board_size)));
+        }
+
+        let mut state = State::new(board_size);
+        state.ply_count = decoder.read_u32()? as u16;
+        state.p1_flatstones = decoder.read_u8()?;
+        state.p1_capstones = decoder.read_u8()?;
+        state.p2_flatstones = decoder.read_u8()?;
+        state.p2_capstones = decoder.read_u8()?;
+        state.handicap.flatstones = decoder.read_u8()? as i8;
+        state.handicap.capstones = decoder.read_u8()? as i8;
+        state.rules.half_komi = decoder.read_u8()?;
+        state.rules.capstones_count = decoder.read_u8()? != 0;
+
+        let crushes = decoder.read_u32()? as usize;
+        state.ply_crushes = decoder.read_bytes(crushes)?.iter().map(|&crush| crush != 0).collect();
+
+        for x in 0..board_size {
+            for y in 0..board_size {
+                let height = decoder.read_u8()?;
+                for _ in 0..height {
+                    let piece = Piece::decode(decoder)?;
+                    state.board[x][y].push(piece);
+                }
+            }
+        }
+
+        state.metadata = Metadata::from_state(&state);
+        Ok(state)
+    }
+}
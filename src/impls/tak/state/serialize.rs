@@ -0,0 +1,120 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+
+use impls::tak::state::{Handicap, Rules, State};
+
+/// The serialized form of a `State`.  The position is stored as TPS, so that it's readable
+/// by anything that understands tak, alongside what TPS can't express.
+#[derive(Deserialize, Serialize)]
+struct SerializedState {
+    tps: String,
+    p1_reserves: (u8, u8),
+    p2_reserves: (u8, u8),
+    handicap: Handicap,
+    #[serde(default)]
+    rules: Rules,
+    ply_crushes: Vec<bool>,
+}
+
+impl Serialize for State {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        SerializedState {
+            tps: self.to_tps(),
+            p1_reserves: (self.p1_flatstones, self.p1_capstones),
+            p2_reserves: (self.p2_flatstones, self.p2_capstones),
+            handicap: self.handicap,
+            rules: self.rules,
+            ply_crushes: self.ply_crushes.clone(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for State {
+    fn deserialize<D>(deserializer: D) -> Result<State, D::Error> where D: Deserializer<'de> {
+        let serialized = SerializedState::deserialize(deserializer)?;
+
+        let mut state = State::from_tps(&serialized.tps).map_err(D::Error::custom)?;
+        state.p1_flatstones = serialized.p1_reserves.0;
+        state.p1_capstones = serialized.p1_reserves.1;
+        state.p2_flatstones = serialized.p2_reserves.0;
+        state.p2_capstones = serialized.p2_reserves.1;
+        state.handicap = serialized.handicap;
+        state.rules = serialized.rules;
+        state.ply_crushes = serialized.ply_crushes;
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json;
+
+    use impls::tak::*;
+    use state::State as StateTrait;
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut state = State::with_reserves(5, (21, 1), (19, 1));
+        state.set_rules(Rules {
+            half_komi: 4,
+            capstones_count: true,
+        });
+        state.execute_plies(&[
+            Ply::from_ptn("a1", Color::Black).unwrap(),
+            Ply::from_ptn("e5", Color::White).unwrap(),
+            Ply::from_ptn("Sb1", Color::White).unwrap(),
+            Ply::from_ptn("Cc1", Color::Black).unwrap(),
+            Ply::from_ptn("d1", Color::White).unwrap(),
+            Ply::from_ptn("c1<", Color::Black).unwrap(),
+        ]).unwrap();
+
+        let json = serde_json::to_string(&state).unwrap();
+        let mut decoded: State = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, state);
+        assert_eq!(decoded.get_handicap(), state.get_handicap());
+        assert_eq!(decoded.get_rules(), state.get_rules());
+
+        // The crush history survives, so the crushing ply can still be reverted
+        decoded.revert_ply(Some(&Ply::from_ptn("c1<", Color::Black).unwrap())).unwrap();
+        assert_eq!(decoded.board[1][0], vec![Piece::StandingStone(Color::White)]);
+    }
+
+    #[test]
+    fn test_invalid_tps() {
+        let json = r#"{"tps":"x5/x5 1 1","p1_reserves":[21,1],"p2_reserves":[21,1],
+            "handicap":{"flatstones":0,"capstones":0},"ply_crushes":[]}"#;
+        assert!(serde_json::from_str::<State>(json).is_err());
+    }
+
+    #[test]
+    fn test_ply_and_resolution() {
+        let plies = [
+            Ply::from_ptn("Ca1", Color::White).unwrap(),
+            Ply::from_ptn("3c3>12", Color::Black).unwrap(),
+        ];
+        let json = serde_json::to_string(&plies).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Ply>>(&json).unwrap(), plies);
+
+        let json = serde_json::to_string(&Resolution::Road(Color::White)).unwrap();
+        assert_eq!(json, r#"{"Road":"White"}"#);
+    }
+}
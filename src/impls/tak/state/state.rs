@@ -22,7 +22,7 @@ use impls::tak::ply::Ply;
 use impls::tak::resolution::Resolution;
 use impls::tak::state::State;
 use impls::tak::state::metadata::{Bitmap, BitmapInterface, BOARD, EDGE};
-use state;
+use state::{self, PlyError};
 
 impl state::State for State {
     type Ply = Ply;
@@ -32,7 +32,7 @@ impl state::State for State {
         self.ply_count as usize
     }
 
-    fn execute_ply(&mut self, ply: Option<&Ply>) -> Result<(), String> {
+    fn execute_ply(&mut self, ply: Option<&Ply>) -> Result<(), PlyError> {
         // Null move
         if ply.is_none() {
             self.ply_count += 1;
@@ -40,14 +40,14 @@ impl state::State for State {
             return Ok(());
         }
 
-        let board_size = self.board.len();
+        let ply = ply.unwrap();
 
-        match *ply.unwrap() {
-            Ply::Place { x, y, ref piece } => {
-                if !self.board[x][y].is_empty() {
-                    return Err(String::from("Cannot place piece in an occupied space."));
-                }
+        if let Some(reason) = self.explain_illegal(ply) {
+            return Err(reason.into());
+        }
 
+        match *ply {
+            Ply::Place { x, y, ref piece } => {
                 let count = match *piece {
                     Piece::Flatstone(color) |
                     Piece::StandingStone(color) => if color == Color::White {
@@ -61,12 +61,7 @@ impl state::State for State {
                         &mut self.p2_capstones
                     },
                 };
-
-                if *count > 0 {
-                    *count -= 1;
-                } else {
-                    return Err(String::from("Insufficient pieces for placement."));
-                }
+                *count -= 1;
 
                 self.board[x][y].push(piece.clone());
 
@@ -79,70 +74,17 @@ impl state::State for State {
 
                 match *piece {
                     Piece::Flatstone(_) |
-                    Piece::Capstone(_) => self.metadata.calculate_road_groups(),
+                    Piece::Capstone(_) => self.metadata.update_road_groups(),
                     _ => (),
                 }
 
                 self.ply_crushes.push(false);
             },
             Ply::Slide { x, y, direction, ref drops } => {
-                // First, verify that the slide is okay
-                let next_color = if self.ply_count % 2 == 0 {
-                    Color::White
-                } else {
-                    Color::Black
-                };
-
-                if let Some(piece) = self.board[x][y].last() {
-                    if piece.get_color() != next_color {
-                        return Err(String::from("Cannot move an opponent's piece."));
-                    }
-                }
-
                 let grab: usize = drops.iter().sum::<u8>() as usize;
-
-                if grab > board_size || self.board[x][y].len() < grab {
-                    return Err(String::from("Illegal carry amount."));
-                }
-
                 let (dx, dy) = direction.to_offset();
 
-                {
-                    let (tx, ty) = (
-                        x as i8 + dx * drops.len() as i8,
-                        y as i8 + dy * drops.len() as i8,
-                    );
-
-                    if tx < 0 || tx >= board_size as i8 ||
-                       ty < 0 || ty >= board_size as i8 {
-                        return Err(String::from("Slide out of bounds."));
-                    }
-                }
-
-                let mut nx = x as i8;
-                let mut ny = y as i8;
-
-                for (i, drop) in drops.iter().enumerate() {
-                    nx += dx;
-                    ny += dy;
-
-                    if !self.board[nx as usize][ny as usize].is_empty() {
-                        match *self.board[nx as usize][ny as usize].last().unwrap() {
-                            Piece::Capstone(_) => return Err(String::from("Cannot slide onto a capstone.")),
-                            Piece::StandingStone(_) => if i == drops.len() - 1 && *drop == 1 {
-                                match *self.board[x][y].last().unwrap() {
-                                    Piece::Capstone(_) => (),
-                                    _ => return Err(String::from("Cannot slide onto a standing stone.")),
-                                }
-                            } else {
-                                return Err(String::from("Cannot slide onto a standing stone."));
-                            },
-                            _ => (),
-                        }
-                    }
-                }
-
-                // If everything checks out, execute the slide
+                // Everything checks out, so execute the slide
                 let mut stack = Vec::new();
 	            for _ in 0..grab {
 	                let piece = self.board[x][y].pop().unwrap();
@@ -211,7 +153,7 @@ impl state::State for State {
                     }
                 }
 
-                self.metadata.calculate_road_groups();
+                self.metadata.update_road_groups();
             },
         }
 
@@ -220,9 +162,9 @@ impl state::State for State {
         Ok(())
     }
 
-    fn revert_ply(&mut self, ply: Option<&Ply>) -> Result<(), String> {
+    fn revert_ply(&mut self, ply: Option<&Ply>) -> Result<(), PlyError> {
         if self.ply_count == 0 {
-            return Err(String::from("No more plies to revert."));
+            return Err(PlyError::NothingToRevert);
         }
 
         // Null move
@@ -237,15 +179,15 @@ impl state::State for State {
         match *ply.unwrap() {
             Ply::Place { x, y, ref piece } => {
                 if self.board[x][y].is_empty() {
-                    return Err(String::from("Cannot remove piece from an empty space."));
+                    return Err(PlyError::Empty);
                 }
 
                 if self.board[x][y].len() != 1 {
-                    return Err(String::from("Cannot remove piece from a stack."));
+                    return Err(PlyError::Illegal("Cannot remove piece from a stack."));
                 }
 
                 if self.board[x][y][0] != *piece {
-                    return Err(String::from("Top piece is the wrong piece to remove."));
+                    return Err(PlyError::Illegal("Top piece is the wrong piece to remove."));
                 }
 
                 let count = match *piece {
@@ -275,7 +217,7 @@ impl state::State for State {
 
                 match *piece {
                     Piece::Flatstone(_) |
-                    Piece::Capstone(_) => self.metadata.calculate_road_groups(),
+                    Piece::Capstone(_) => self.metadata.update_road_groups(),
                     _ => (),
                 }
 
@@ -297,24 +239,24 @@ impl state::State for State {
 
                 if tx < 0 || tx >= board_size as i8 ||
                    ty < 0 || ty >= board_size as i8 {
-                    return Err(String::from("Slide out of bounds."));
+                    return Err(PlyError::OutOfBounds);
                 }
 
                 if self.board[tx as usize][ty as usize].is_empty() {
-                    return Err(String::from("Target space is empty."));
+                    return Err(PlyError::Empty);
                 }
 
                 if self.board[tx as usize][ty as usize].last().unwrap().get_color() != previous_color {
-                    return Err(String::from("Cannot revert move with an opponent's piece"));
+                    return Err(PlyError::WrongColor);
                 }
 
                 if let Some(&crush) = self.ply_crushes.last() {
                     if crush {
                         if self.board[tx as usize][ty as usize].len() < 2 {
-                            return Err(String::from("Not enough pieces to revert standing stone crush."));
+                            return Err(PlyError::Illegal("Not enough pieces to revert standing stone crush."));
                         }
                         if *drops.last().unwrap() != 1 {
-                            return Err(String::from("Move crushed a standing stone, but dropped more than one stone."));
+                            return Err(PlyError::Illegal("Move crushed a standing stone, but dropped more than one stone."));
                         }
                     }
                 }
@@ -322,7 +264,7 @@ impl state::State for State {
                 let grab: usize = drops.iter().sum::<u8>() as usize;
 
                 if grab > board_size {
-                    return Err(String::from("Illegal carry amount."));
+                    return Err(PlyError::Illegal("Illegal carry amount"));
                 }
 
                 let mut px = tx;
@@ -330,7 +272,7 @@ impl state::State for State {
 
                 for drop in drops.iter().rev() {
                     if self.board[px as usize][py as usize].len() < *drop as usize {
-                        return Err(String::from("Insufficient pieces in stack to revert."));
+                        return Err(PlyError::Illegal("Insufficient pieces in stack to revert."));
                     }
 
                     px -= dx;
@@ -394,7 +336,7 @@ impl state::State for State {
                     }
                 }
 
-                self.metadata.calculate_road_groups();
+                self.metadata.update_road_groups();
             },
         }
 
@@ -438,9 +380,17 @@ impl state::State for State {
         } else if (self.p1_flatstones + self.p1_capstones) == 0 ||
                   (self.p2_flatstones + self.p2_capstones) == 0 ||
                   (m.p1_pieces | m.p2_pieces) == BOARD[board_size] {
-            if m.p1_flatstone_count > m.p2_flatstone_count {
+            // Counts are kept in half flats, to allow for half a flat of komi
+            let mut p1_count = m.p1_flatstone_count as u32 * 2;
+            let mut p2_count = m.p2_flatstone_count as u32 * 2 + self.rules.half_komi as u32;
+            if self.rules.capstones_count {
+                p1_count += (m.p1_pieces & m.capstones).get_population() as u32 * 2;
+                p2_count += (m.p2_pieces & m.capstones).get_population() as u32 * 2;
+            }
+
+            if p1_count > p2_count {
                 Some(Resolution::Flat(Color::White))
-            } else if m.p2_flatstone_count > m.p1_flatstone_count {
+            } else if p2_count > p1_count {
                 Some(Resolution::Flat(Color::Black))
             } else {
                 Some(Resolution::Draw)
@@ -456,3 +406,160 @@ impl state::State for State {
         (self.metadata.p1_pieces | self.metadata.p2_pieces).get_population() < (self.board.len() * self.board.len() - 3) as u8
     }
 }
+
+#[cfg(test)]
+mod test {
+    use analysis::{Extrapolatable, RepetitionTracker};
+    use impls::tak::*;
+    use position_key::PositionKey;
+    use resolution::Resolution as ResolutionTrait;
+    use state::{PlyError, State as StateTrait};
+
+    #[test]
+    fn test_ply_errors() {
+        let mut state = State::from_tps("[TPS \"2,x4/x5/x5/x5/x4,1 1 3\"]").unwrap();
+
+        assert_eq!(state.revert_ply(Some(&Ply::from_ptn("b2", Color::White).unwrap())), Err(PlyError::Empty));
+        assert_eq!(state.execute_ply(Some(&Ply::from_ptn("a5", Color::White).unwrap())), Err(PlyError::Occupied));
+        assert_eq!(state.execute_ply(Some(&Ply::from_ptn("a5>", Color::White).unwrap())), Err(PlyError::WrongColor));
+        assert_eq!(state.execute_ply(Some(&Ply::from_ptn("e1-", Color::White).unwrap())), Err(PlyError::OutOfBounds));
+        assert_eq!(state.execute_ply(Some(&Ply::from_ptn("2e1<", Color::White).unwrap())), Err(PlyError::Illegal("Illegal carry amount")));
+
+        let error: String = PlyError::Occupied.into();
+        assert_eq!(error, "The space is already occupied");
+
+        assert_eq!(State::new(5).revert_ply(None), Err(PlyError::NothingToRevert));
+    }
+
+    #[test]
+    fn test_explain_illegal() {
+        let state = State::from_tps("[TPS \"2,x,1S,21,1C/x5/x5/x5/x4,1 1 3\"]").unwrap();
+        let explain = |ptn: &str| state.explain_illegal(&Ply::from_ptn(ptn, Color::White).unwrap());
+
+        assert_eq!(explain("b2"), None);
+        assert_eq!(explain("a5"), Some(IllegalReason::Occupied { x: 0, y: 4 }));
+        assert_eq!(explain("a5>"), Some(IllegalReason::OpponentsStack { x: 0, y: 4 }));
+        assert_eq!(explain("e1-"), Some(IllegalReason::OutOfBounds { x: 4, y: 0 }));
+        assert_eq!(explain("2e1<"), Some(IllegalReason::NotEnoughPieces { x: 4, y: 0, carry: 2, height: 1 }));
+        assert_eq!(explain("6e1<"), Some(IllegalReason::CarryLimit { x: 4, y: 0, carry: 6, limit: 5 }));
+        assert_eq!(explain("d5<"), Some(IllegalReason::CrushNeedsCapstone { x: 2, y: 4 }));
+        assert_eq!(explain("2d5<11"), Some(IllegalReason::BlockedByStandingStone { x: 2, y: 4 }));
+        assert_eq!(explain("d5>"), Some(IllegalReason::BlockedByCapstone { x: 4, y: 4 }));
+        assert_eq!(explain("e5<"), None);
+        assert_eq!(
+            format!("{}", explain("d5>").unwrap()),
+            "Cannot slide onto the capstone at e5",
+        );
+
+        let opening = State::new(5);
+        assert_eq!(
+            opening.explain_illegal(&Ply::from_ptn("a1", Color::White).unwrap()),
+            Some(IllegalReason::OpeningSwap { x: 0, y: 0 }),
+        );
+        assert_eq!(opening.explain_illegal(&Ply::from_ptn("a1", Color::Black).unwrap()), None);
+
+        let mut state = State::from_tps("[TPS \"2,x,1S,21,1C/x5/x5/x5/x4,1 1 3\"]").unwrap();
+        state.p1_capstones = 0;
+        assert_eq!(
+            state.explain_illegal(&Ply::from_ptn("Cb2", Color::White).unwrap()),
+            Some(IllegalReason::NoReserve { piece: Piece::Capstone(Color::White) }),
+        );
+        assert_eq!(state.execute_ply(Some(&Ply::from_ptn("Cb2", Color::White).unwrap())), Err(PlyError::InsufficientPieces));
+    }
+
+    #[test]
+    fn test_position_key() {
+        let play = |ptns: &[&str]| {
+            let mut state = State::new(5);
+            for (i, ptn) in ptns.iter().enumerate() {
+                let color = if i < 2 { Color::Black } else { Color::White };
+                let color = if i % 2 == 0 { color } else { color.flip() };
+                state.execute_ply(Some(&Ply::from_ptn(ptn, color).unwrap())).unwrap();
+            }
+            state
+        };
+
+        // Transpositions share a key, and the side to move is part of it
+        let state = play(&["a1", "e5", "b2", "d4", "c3"]);
+        assert_eq!(state.position_key(), play(&["a1", "e5", "c3", "d4", "b2"]).position_key());
+        assert!(state.position_key() != play(&["a1", "e5", "b2", "d4"]).position_key());
+
+        let mut passed = state.clone();
+        passed.execute_ply(None).unwrap();
+        assert!(passed.position_key() != state.position_key());
+
+        // Layers above the other player's highest still count
+        let tall = State::from_tps("[TPS \"x5/x5/x5/x5/111,2,x3 1 3\"]").unwrap();
+        let short = State::from_tps("[TPS \"x5/x5/x5/x5/1,2,x3 1 3\"]").unwrap();
+        assert!(tall.position_key() != short.position_key());
+
+        let mut repetitions = RepetitionTracker::new(3);
+        repetitions.push_position(&state);
+        passed.execute_ply(None).unwrap();
+        assert_eq!(repetitions.push_position(&passed), 2);
+        assert_eq!(repetitions.get_position_count(&state), 2);
+    }
+
+    #[test]
+    fn test_opening_swap() {
+        let mut state = State::new(5);
+
+        for &(color, ptn) in &[(Color::Black, "a1"), (Color::White, "e5")] {
+            assert!(state.is_opening_swap());
+
+            // Only the opponent's flatstones may be placed
+            let plies = state.extrapolate();
+            assert_eq!(plies.len(), 25 - state.ply_count as usize);
+            assert!(plies.iter().all(|ply| match *ply {
+                Ply::Place { ref piece, .. } => *piece == Piece::Flatstone(color),
+                _ => false,
+            }));
+
+            assert!(state.execute_ply(Some(&Ply::from_ptn(ptn, color.flip()).unwrap())).is_err());
+            assert!(state.execute_ply(Some(&Ply::from_ptn(&format!("C{}", ptn), color).unwrap())).is_err());
+            state.execute_ply(Some(&Ply::from_ptn(ptn, color).unwrap())).unwrap();
+        }
+
+        assert!(!state.is_opening_swap());
+        assert!(state.execute_ply(Some(&Ply::from_ptn("a1>", Color::White).unwrap())).is_err());
+        state.execute_ply(Some(&Ply::from_ptn("e5<", Color::White).unwrap())).unwrap();
+    }
+
+    #[test]
+    fn test_flat_win_rules() {
+        // A full board with no roads: 13 flatstones for player 1, and 11 and a capstone for player 2
+        let mut state = State::from_tps(
+            "[TPS \"1,2,1,2,1/2,1,2C,1,2/1,2,1,2,1/2,1,2,1,2/1,2,1,2,1 1 13\"]"
+        ).unwrap();
+
+        let winners = [
+            (0, false, Some(0)),
+            (0, true, Some(0)),
+            (2, false, Some(0)),
+            (2, true, None),
+            (3, true, Some(1)),
+            (5, false, Some(1)),
+        ];
+        for &(half_komi, capstones_count, winner) in &winners {
+            state.set_rules(Rules {
+                half_komi: half_komi,
+                capstones_count: capstones_count,
+            });
+            let resolution = state.check_resolution().unwrap();
+            assert_eq!(resolution.get_winner(), winner);
+            assert_eq!(resolution.is_draw(), winner.is_none());
+        }
+    }
+
+    #[test]
+    fn test_handicap() {
+        assert_eq!(State::new(5).get_handicap(), Handicap::default());
+
+        let state = State::with_reserves(5, (23, 1), (21, 0));
+        assert_eq!(state.get_handicap(), Handicap { flatstones: 2, capstones: 1 });
+
+        // Reserves past the range of i8 still give the right difference
+        let state = State::with_reserves(8, (200, 2), (100, 0));
+        assert_eq!(state.get_handicap(), Handicap { flatstones: 100, capstones: 2 });
+    }
+}
@@ -0,0 +1,119 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use impls::tak::{Ply, Transform};
+use impls::tak::state::State;
+use impls::tak::state::metadata::Metadata;
+use symmetric::Symmetric;
+
+impl Symmetric for State {
+    type Transform = Transform;
+
+    fn symmetries(&self) -> Vec<Transform> {
+        Transform::all()
+    }
+
+    fn transform(&self, transform: &Transform) -> State {
+        let board_size = self.board.len();
+        let mut board = vec![vec![Vec::new(); board_size]; board_size];
+
+        for x in 0..board_size {
+            for y in 0..board_size {
+                let (tx, ty) = transform.apply(x, y, board_size);
+                board[tx][ty] = self.board[x][y].clone();
+            }
+        }
+
+        let mut state = State {
+            board: board,
+            ply_crushes: self.ply_crushes.clone(),
+            metadata: Metadata::new(board_size),
+            ..*self
+        };
+        state.metadata = Metadata::from_state(&state);
+        state
+    }
+
+    fn transform_ply(&self, ply: &Ply, transform: &Transform) -> Ply {
+        let board_size = self.board.len();
+
+        match *ply {
+            Ply::Place { x, y, ref piece } => {
+                let (tx, ty) = transform.apply(x, y, board_size);
+                Ply::Place {
+                    x: tx,
+                    y: ty,
+                    piece: piece.clone(),
+                }
+            },
+            Ply::Slide { x, y, direction, ref drops } => {
+                let (tx, ty) = transform.apply(x, y, board_size);
+                Ply::Slide {
+                    x: tx,
+                    y: ty,
+                    direction: transform.apply_direction(direction),
+                    drops: drops.clone(),
+                }
+            },
+        }
+    }
+
+    fn invert_transform(&self, transform: &Transform) -> Transform {
+        transform.invert()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use impls::tak::*;
+    use symmetric::Symmetric;
+
+    #[test]
+    fn test_canonicalize() {
+        let state = State::from_tps("[TPS \"21,22221C,1,12212S,x/2121,2S,2,1S,2/x2,2,2,x/1,2111112C,2,x,21/x,1,21,x2 1 32\"]").unwrap();
+        let canonical = state.canonicalize();
+
+        for transform in Transform::all() {
+            let transformed = state.transform(&transform);
+            assert!(transformed.canonicalize() == canonical);
+            assert!(transformed.transform(&transform.invert()) == state);
+        }
+    }
+
+    #[test]
+    fn test_transform_ply() {
+        let plies = ["a1", "e5", "Cc3", "a1>", "c3<", "b1+", "Sd4", "e4"].iter()
+            .enumerate()
+            .map(|(i, ptn)| {
+                let color = if i % 2 == 0 { Color::White } else { Color::Black };
+                Ply::from_ptn(ptn, if i < 2 { color.flip() } else { color }).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let state = State::from_plies(5, &plies).unwrap();
+        let origin = State::new(5);
+
+        for transform in Transform::all() {
+            let transformed_plies = plies.iter()
+                .map(|ply| origin.transform_ply(ply, &transform))
+                .collect::<Vec<_>>();
+            let transformed = State::from_plies(5, &transformed_plies).unwrap();
+            assert!(transformed == state.transform(&transform));
+        }
+    }
+}
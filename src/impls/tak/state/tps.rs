@@ -0,0 +1,457 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::error::Error;
+use std::fmt;
+
+use impls::tak::{Color, Piece};
+use impls::tak::state::State;
+use impls::tak::state::metadata::Metadata;
+
+/// The ways that parsing TPS can fail.  Positions are byte offsets into the string given.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TpsError {
+    /// A character other than what was expected was found at `position`.
+    Unexpected {
+        position: usize,
+        found: char,
+        expected: &'static str,
+    },
+    /// The string ended before the expected part.
+    Incomplete {
+        expected: &'static str,
+    },
+    /// The board isn't one of the sizes from 3 to 8.
+    BoardSize(usize),
+    /// The row beginning at `position` has a different number of spaces than the first row.
+    RowLength {
+        position: usize,
+        length: usize,
+        expected: usize,
+    },
+    /// There are more of a player's pieces on the board than they start with.
+    TooManyPieces(Color),
+    /// The move number at `position` is zero or too large.
+    MoveNumber {
+        position: usize,
+    },
+}
+
+impl fmt::Display for TpsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TpsError::Unexpected { position, found, expected } => write!(f, "Expected {} at position {}, found '{}'", expected, position, found),
+            TpsError::Incomplete { expected } => write!(f, "Expected {}, found the end", expected),
+            TpsError::BoardSize(size) => write!(f, "Unsupported board size: {}", size),
+            TpsError::RowLength { position, length, expected } => write!(f, "The row at position {} has {} spaces instead of {}", position, length, expected),
+            TpsError::TooManyPieces(color) => write!(f, "Too many {:?} pieces on the board", color),
+            TpsError::MoveNumber { position } => write!(f, "Invalid move number at position {}", position),
+        }
+    }
+}
+
+impl Error for TpsError {
+    fn description(&self) -> &str {
+        "Error parsing TPS"
+    }
+}
+
+impl State {
+    /// Creates a state from a string in TPS format, i.e. `"[TPS \"x5/x5/x5/x5/x5 1 1\"]"`.
+    /// The `[TPS "..."]` wrapper may be omitted, and the fields may be separated by any
+    /// amount of whitespace.
+    pub fn from_tps(tps: &str) -> Result<State, TpsError> {
+        let (rows, ply_count) = parse_tps(tps)?;
+
+        let board_size = rows.len();
+        let mut state = State::new(board_size);
+
+        let mut used = [(0, 0), (0, 0)];
+        for (i, row) in rows.into_iter().enumerate() {
+            for (x, stack) in row.into_iter().enumerate() {
+                for piece in &stack {
+                    let (color, used) = match piece.get_color() {
+                        Color::White => (Color::White, &mut used[0]),
+                        Color::Black => (Color::Black, &mut used[1]),
+                    };
+                    match *piece {
+                        Piece::Capstone(_) => used.1 += 1,
+                        _ => used.0 += 1,
+                    }
+
+                    let (flatstones, capstones) = match color {
+                        Color::White => (state.p1_flatstones, state.p1_capstones),
+                        Color::Black => (state.p2_flatstones, state.p2_capstones),
+                    };
+                    if used.0 > flatstones || used.1 > capstones {
+                        return Err(TpsError::TooManyPieces(color));
+                    }
+                }
+                state.board[x][board_size - 1 - i] = stack;
+            }
+        }
+
+        state.p1_flatstones -= used[0].0;
+        state.p1_capstones -= used[0].1;
+        state.p2_flatstones -= used[1].0;
+        state.p2_capstones -= used[1].1;
+        state.ply_count = ply_count;
+        state.metadata = Metadata::from_state(&state);
+
+        Ok(state)
+    }
+
+    /// Returns the state in TPS format, i.e. `"[TPS \"x5/x5/x5/x5/x5 1 1\"]"`.
+    pub fn to_tps(&self) -> String {
+        format_tps(&self.board, self.ply_count)
+    }
+}
+
+/// Formats a board, indexed by column and then row, and a ply count as TPS.
+pub fn format_tps(board: &[Vec<Vec<Piece>>], ply_count: u16) -> String {
+    let mut tps = String::from("[TPS \"");
+
+    let mut y = board.len() - 1;
+    loop {
+        let mut x = 0;
+        while x < board.len() {
+            if board[x][y].is_empty() {
+                tps += "x";
+                let mut empty = 1;
+                while x + 1 < board.len() && board[x + 1][y].is_empty() {
+                    x += 1;
+                    empty += 1;
+                }
+                if empty > 1 {
+                    tps += &format!("{}", empty);
+                }
+            } else {
+                for piece in &board[x][y] {
+                    match *piece {
+                        Piece::Flatstone(Color::White) => tps += "1",
+                        Piece::Flatstone(Color::Black) => tps += "2",
+                        Piece::StandingStone(Color::White) => tps += "1S",
+                        Piece::StandingStone(Color::Black) => tps += "2S",
+                        Piece::Capstone(Color::White) => tps += "1C",
+                        Piece::Capstone(Color::Black) => tps += "2C",
+                    }
+                }
+            }
+
+            x += 1;
+            if x < board.len() {
+                tps += ",";
+            }
+        }
+
+        if y == 0 {
+            break;
+        } else {
+            y -= 1;
+            tps += "/";
+        }
+    }
+
+    tps += &format!(" {} ", ply_count % 2 + 1);
+    tps += &format!("{}\"]", ply_count / 2 + 1);
+
+    tps
+}
+
+/// Parses TPS into its rows of stacks, from the top of the board down, and its ply count,
+/// without checking the pieces against the players' reserves.
+pub fn parse_tps(tps: &str) -> Result<(Vec<Vec<Vec<Piece>>>, u16), TpsError> {
+    let mut parser = Parser {
+        input: tps,
+        position: 0,
+    };
+
+    parser.skip_whitespace();
+    let wrapped = parser.input[parser.position..].starts_with('[');
+    if wrapped {
+        parser.expect_str("[TPS", "\"[TPS\"")?;
+        parser.skip_whitespace();
+        parser.expect('"', "'\"'")?;
+        parser.skip_whitespace();
+    }
+
+    let rows = parser.parse_board()?;
+
+    parser.expect_whitespace("whitespace")?;
+    let player_position = parser.position;
+    let player = match parser.next() {
+        Some('1') => 0,
+        Some('2') => 1,
+        Some(c) => return Err(TpsError::Unexpected { position: player_position, found: c, expected: "a player of 1 or 2" }),
+        None => return Err(TpsError::Incomplete { expected: "a player" }),
+    };
+
+    parser.expect_whitespace("whitespace")?;
+    let move_position = parser.position;
+    let digits = parser.take_while(|c| c.is_digit(10));
+    if digits.is_empty() {
+        return Err(match parser.peek() {
+            Some(c) => TpsError::Unexpected { position: move_position, found: c, expected: "a move number" },
+            None => TpsError::Incomplete { expected: "a move number" },
+        });
+    }
+    let move_number = match digits.parse::<u16>() {
+        Ok(move_number) if move_number > 0 && move_number <= u16::max_value() / 2 => move_number,
+        _ => return Err(TpsError::MoveNumber { position: move_position }),
+    };
+
+    parser.skip_whitespace();
+    if wrapped {
+        parser.expect('"', "'\"'")?;
+        parser.skip_whitespace();
+        parser.expect(']', "']'")?;
+        parser.skip_whitespace();
+    }
+    if let Some(c) = parser.peek() {
+        return Err(TpsError::Unexpected { position: parser.position, found: c, expected: "the end" });
+    }
+
+    Ok((rows, (move_number - 1) * 2 + player))
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if let Some(c) = c {
+            self.position += c.len_utf8();
+        }
+        c
+    }
+
+    fn take_while<F>(&mut self, predicate: F) -> &'a str where
+        F: Fn(char) -> bool {
+        let start = self.position;
+        while self.peek().map_or(false, &predicate) {
+            self.next();
+        }
+        &self.input[start..self.position]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn expect(&mut self, expected: char, description: &'static str) -> Result<(), TpsError> {
+        let position = self.position;
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(TpsError::Unexpected { position: position, found: c, expected: description }),
+            None => Err(TpsError::Incomplete { expected: description }),
+        }
+    }
+
+    fn expect_str(&mut self, expected: &str, description: &'static str) -> Result<(), TpsError> {
+        for c in expected.chars() {
+            self.expect(c, description)?;
+        }
+        Ok(())
+    }
+
+    fn expect_whitespace(&mut self, description: &'static str) -> Result<(), TpsError> {
+        match self.peek() {
+            Some(c) if c.is_whitespace() => {
+                self.skip_whitespace();
+                Ok(())
+            },
+            Some(c) => Err(TpsError::Unexpected { position: self.position, found: c, expected: description }),
+            None => Err(TpsError::Incomplete { expected: description }),
+        }
+    }
+
+    /// Parses the rows of the board, from top to bottom, each holding its stacks from left to
+    /// right, with each stack listed from the bottom up.
+    fn parse_board(&mut self) -> Result<Vec<Vec<Vec<Piece>>>, TpsError> {
+        let mut rows = Vec::new();
+
+        loop {
+            let row_position = self.position;
+            let row = self.parse_row()?;
+
+            if let Some(first) = rows.first().map(|first: &Vec<Vec<Piece>>| first.len()) {
+                if row.len() != first {
+                    return Err(TpsError::RowLength { position: row_position, length: row.len(), expected: first });
+                }
+            }
+            rows.push(row);
+
+            if self.peek() == Some('/') {
+                self.next();
+            } else {
+                break;
+            }
+        }
+
+        let size = rows[0].len();
+        if size < 3 || size > 8 {
+            return Err(TpsError::BoardSize(size));
+        }
+        if rows.len() != size {
+            return Err(TpsError::BoardSize(rows.len()));
+        }
+
+        Ok(rows)
+    }
+
+    fn parse_row(&mut self) -> Result<Vec<Vec<Piece>>, TpsError> {
+        let mut row = Vec::new();
+
+        loop {
+            let position = self.position;
+            match self.next() {
+                Some('x') => {
+                    let count_position = self.position;
+                    match self.peek() {
+                        Some(c) if c >= '1' && c <= '8' => {
+                            self.next();
+                            for _ in 0..(c as u8 - 48) {
+                                row.push(Vec::new());
+                            }
+                        },
+                        Some(c) if c.is_digit(10) => {
+                            return Err(TpsError::Unexpected { position: count_position, found: c, expected: "a count from 1 to 8" });
+                        },
+                        _ => row.push(Vec::new()),
+                    }
+                },
+                Some(c) if c == '1' || c == '2' => {
+                    let mut stack = Vec::new();
+                    let mut color = if c == '1' { Color::White } else { Color::Black };
+                    loop {
+                        match self.peek() {
+                            Some('1') => {
+                                stack.push(Piece::Flatstone(color));
+                                color = Color::White;
+                            },
+                            Some('2') => {
+                                stack.push(Piece::Flatstone(color));
+                                color = Color::Black;
+                            },
+                            Some('S') => {
+                                stack.push(Piece::StandingStone(color));
+                                self.next();
+                                break;
+                            },
+                            Some('C') => {
+                                stack.push(Piece::Capstone(color));
+                                self.next();
+                                break;
+                            },
+                            _ => {
+                                stack.push(Piece::Flatstone(color));
+                                break;
+                            },
+                        }
+                        self.next();
+                    }
+                    row.push(stack);
+                },
+                Some(c) => return Err(TpsError::Unexpected { position: position, found: c, expected: "a space" }),
+                None => return Err(TpsError::Incomplete { expected: "a space" }),
+            }
+
+            if self.peek() == Some(',') {
+                self.next();
+            } else {
+                return Ok(row);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use impls::tak::*;
+
+    #[test]
+    fn test_round_trip() {
+        let tps = [
+            "[TPS \"x5/x5/x5/x5/x5 1 1\"]",
+            "[TPS \"x3/x3/x3 2 1\"]",
+            "[TPS \"x5/x5/x,2S,x3/x,121C,x3/1,x4 1 3\"]",
+            "[TPS \"21,22221C,1,12212S,x/2121,2S,2,1S,2/x2,2,2,x/1,2111112C,2,x,21/x,1,21,x2 1 32\"]",
+            "[TPS \"x8/x8/x8/x8/x8/x8/x8/1C,x6,2C 2 4\"]",
+        ];
+
+        for tps in tps.iter() {
+            assert_eq!(State::from_tps(tps).unwrap().to_tps(), *tps);
+        }
+    }
+
+    #[test]
+    fn test_forms() {
+        let expected = State::from_tps("[TPS \"x5/x5/x,2,1,x2/x,1,2,x2/x5 1 3\"]").unwrap();
+
+        let forms = [
+            "x5/x5/x,2,1,x2/x,1,2,x2/x5 1 3",
+            "  x5/x5/x,2,1,x2/x,1,2,x2/x5\t1   3\n",
+            "[TPS\"x5/x5/x,2,1,x2/x,1,2,x2/x5 1 3\"]",
+            " [TPS  \" x5/x5/x,2,1,x2/x,1,2,x2/x5 1 3 \" ] ",
+            "x,x4/x2,x,x2/x,2,1,x,x/x,1,2,x2/x5 1 3",
+        ];
+
+        for form in forms.iter() {
+            assert_eq!(State::from_tps(form).as_ref(), Ok(&expected), "{}", form);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("", TpsError::Incomplete { expected: "a space" }),
+            ("[TPS", TpsError::Incomplete { expected: "'\"'" }),
+            ("[TSP \"x3/x3/x3 1 1\"]", TpsError::Unexpected { position: 2, found: 'S', expected: "\"[TPS\"" }),
+            ("x3/x3/x3", TpsError::Incomplete { expected: "whitespace" }),
+            ("x3/x3/x3 1", TpsError::Incomplete { expected: "whitespace" }),
+            ("x3/x3/x3 3 1", TpsError::Unexpected { position: 9, found: '3', expected: "a player of 1 or 2" }),
+            ("x3/x3/x3 1 0", TpsError::MoveNumber { position: 11 }),
+            ("x3/x3/x3 1 99999", TpsError::MoveNumber { position: 11 }),
+            ("x3/x3/x3 1 a", TpsError::Unexpected { position: 11, found: 'a', expected: "a move number" }),
+            ("x3/x3/x3 1 1 x", TpsError::Unexpected { position: 13, found: 'x', expected: "the end" }),
+            ("[TPS \"x3/x3/x3 1 1\"", TpsError::Incomplete { expected: "']'" }),
+            ("x3/x3/x3 1 1\"]", TpsError::Unexpected { position: 12, found: '"', expected: "the end" }),
+            ("x3/x3/x0 1 1", TpsError::Unexpected { position: 7, found: '0', expected: "a count from 1 to 8" }),
+            ("x3/x3/x,3 1 1", TpsError::Unexpected { position: 8, found: '3', expected: "a space" }),
+            ("x3/x2/x3 1 1", TpsError::RowLength { position: 3, length: 2, expected: 3 }),
+            ("x3/x3 1 1", TpsError::BoardSize(2)),
+            ("x2/x2 1 1", TpsError::BoardSize(2)),
+            ("x9/x9/x9/x9/x9/x9/x9/x9/x9 1 1", TpsError::Unexpected { position: 1, found: '9', expected: "a count from 1 to 8" }),
+            ("1C,x2/x3/x3 1 2", TpsError::TooManyPieces(Color::White)),
+            ("11111111111,x2/x3/x3 1 2", TpsError::TooManyPieces(Color::White)),
+        ];
+
+        for &(tps, ref error) in cases.iter() {
+            assert_eq!(State::from_tps(tps).as_ref(), Err(error), "{}", tps);
+            assert!(!error.to_string().is_empty());
+        }
+    }
+}
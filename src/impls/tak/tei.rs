@@ -0,0 +1,468 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::any::Any;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use analysis::search::{Analysis, AnalysisReport, Search};
+use impls::tak::{Color, Ply, State};
+use state::State as StateTrait;
+use util::Instant;
+
+/// How often a search waiting on the engine checks for an interrupt.
+const POLL_INTERVAL: u64 = 20;
+
+/// Errors from talking to an external engine.
+#[derive(Debug)]
+pub enum TeiError {
+    /// The engine couldn't be started, or couldn't be written to.
+    Io(io::Error),
+    /// The engine exited, or closed its output.
+    Disconnected,
+    /// The engine said nothing for longer than the timeout while the named response was
+    /// expected.
+    Timeout(&'static str),
+    /// The engine sent a line that doesn't follow the protocol.
+    Protocol(String),
+}
+
+impl fmt::Display for TeiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TeiError::Io(ref error) => write!(f, "Engine I/O error: {}", error),
+            TeiError::Disconnected => write!(f, "The engine disconnected"),
+            TeiError::Timeout(expected) => write!(f, "Timed out waiting for the engine's {}", expected),
+            TeiError::Protocol(ref line) => write!(f, "Unexpected response from the engine: {}", line),
+        }
+    }
+}
+
+impl Error for TeiError {
+    fn description(&self) -> &str {
+        "Error talking to an external engine"
+    }
+}
+
+impl From<io::Error> for TeiError {
+    fn from(error: io::Error) -> TeiError {
+        TeiError::Io(error)
+    }
+}
+
+/// An external tak engine, such as Tiltak or Topaz, run as a child process and driven over
+/// TEI, tak's adaptation of UCI.  It implements `Search<State>`, so it can stand in for a
+/// `PvSearch` wherever plies are chosen, such as in automated matches against this crate's
+/// searches.
+///
+/// Before each search, the engine is sent the state as TPS, after starting a new game if
+/// the board size or komi has changed since the last one.  It's then told to `go` with the
+/// limit set by `set_movetime` or `set_depth`, and is asked to `stop` if the search is
+/// interrupted.  The engine is told to quit when this is dropped.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use zero_sum::analysis::search::Search;
+/// use zero_sum::impls::tak::{State, TeiEngine};
+///
+/// let mut engine = TeiEngine::launch("tiltak", &["--tei"]).unwrap();
+/// engine.set_movetime(500);
+///
+/// let analysis = engine.search(&State::new(5), None);
+/// println!("{}", analysis);
+/// ```
+pub struct TeiEngine {
+    child: Child,
+    input: ChildStdin,
+    output: Receiver<String>,
+    name: Option<String>,
+    author: Option<String>,
+    game: Option<(usize, u8)>,
+    go: String,
+    timeout: Option<Duration>,
+}
+
+impl TeiEngine {
+    /// Starts `program` with `args` and completes the TEI handshake with it.
+    pub fn launch<P, A>(program: P, args: &[A]) -> Result<TeiEngine, TeiError> where
+        P: AsRef<OsStr>,
+        A: AsRef<OsStr> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let input = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Lines are read on their own thread so that a search can wait on them and on its
+        // interrupt at once
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => if sender.send(line).is_err() {
+                        break;
+                    },
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut engine = TeiEngine {
+            child: child,
+            input: input,
+            output: output,
+            name: None,
+            author: None,
+            game: None,
+            go: String::from("movetime 1000"),
+            timeout: None,
+        };
+
+        engine.send("tei")?;
+        loop {
+            let line = engine.receive("teiok")?;
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("teiok"), _) => break,
+                (Some("id"), Some("name")) => engine.name = Some(rest_of(&line, "name")),
+                (Some("id"), Some("author")) => engine.author = Some(rest_of(&line, "author")),
+                _ => (),
+            }
+        }
+
+        Ok(engine)
+    }
+
+    /// Returns the name the engine gave in the handshake, if any.
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_ref().map(|name| name.as_str())
+    }
+
+    /// Returns the author the engine gave in the handshake, if any.
+    pub fn get_author(&self) -> Option<&str> {
+        self.author.as_ref().map(|author| author.as_str())
+    }
+
+    /// Limits each search to `milliseconds` of the engine's time.  This is the default, at
+    /// 1000 milliseconds.
+    pub fn set_movetime(&mut self, milliseconds: u64) {
+        self.go = format!("movetime {}", milliseconds);
+    }
+
+    /// Limits each search to `depth` plies instead of a time.
+    pub fn set_depth(&mut self, depth: u8) {
+        self.go = format!("depth {}", depth);
+    }
+
+    /// Sets how long the engine may stay silent while a response is expected before giving
+    /// up on it.  Defaults to `None`, which waits indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Sets one of the engine's options.
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), TeiError> {
+        self.send(&format!("setoption name {} value {}", name, value))
+    }
+
+    /// Searches `state`, returning the engine's analysis or the reason it couldn't give
+    /// one.  `interrupt` asks the engine to stop early and play the best ply it has.
+    pub fn analyze(&mut self, state: &State, interrupt: Option<Receiver<()>>) -> Result<TeiAnalysis, TeiError> {
+        let game = (state.board.len(), state.get_rules().half_komi);
+        if self.game != Some(game) {
+            self.send(&format!("setoption name HalfKomi value {}", game.1))?;
+            self.send(&format!("teinewgame {}", game.0))?;
+            self.game = Some(game);
+        }
+
+        self.send("isready")?;
+        while self.receive("readyok")? != "readyok" { }
+
+        let tps = state.to_tps();
+        let tps = tps.trim_start_matches("[TPS \"").trim_end_matches("\"]");
+        self.send(&format!("position tps {}", tps))?;
+
+        let start_time = Instant::now();
+        let go = format!("go {}", self.go);
+        self.send(&go)?;
+
+        let mut analysis = TeiAnalysis {
+            best_ply: None,
+            principal_variation: Vec::new(),
+            score: None,
+            depth: 0,
+            nodes: 0,
+            time: 0.0,
+        };
+
+        let mut stopped = false;
+        let mut silence = Duration::from_millis(0);
+        loop {
+            // Checked on every pass, so that a chatty engine can still be stopped
+            if !stopped && interrupt.as_ref().map_or(false, |interrupt| interrupt.try_recv().is_ok()) {
+                self.send("stop")?;
+                stopped = true;
+            }
+
+            let line = match self.output.recv_timeout(Duration::from_millis(POLL_INTERVAL)) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Disconnected) => return Err(TeiError::Disconnected),
+                Err(RecvTimeoutError::Timeout) => {
+                    silence += Duration::from_millis(POLL_INTERVAL);
+                    if self.timeout.map_or(false, |timeout| silence > timeout) {
+                        return Err(TeiError::Timeout("bestmove"));
+                    }
+                    continue;
+                },
+            };
+            silence = Duration::from_millis(0);
+
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("info") => read_info(&mut analysis, state, words),
+                Some("bestmove") => {
+                    let ply = words.next().and_then(|ptn| parse_plies(state, Some(ptn)).pop());
+                    match ply {
+                        Some(ply) => {
+                            if analysis.principal_variation.first() != Some(&ply) {
+                                analysis.principal_variation = vec![ply.clone()];
+                            }
+                            analysis.best_ply = Some(ply);
+                        },
+                        None => return Err(TeiError::Protocol(line)),
+                    }
+                    break;
+                },
+                _ => (),
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        analysis.time = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1_000_000_000.0;
+        Ok(analysis)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), TeiError> {
+        writeln!(self.input, "{}", command)?;
+        self.input.flush()?;
+        Ok(())
+    }
+
+    fn receive(&mut self, expected: &'static str) -> Result<String, TeiError> {
+        match self.timeout {
+            Some(timeout) => self.output.recv_timeout(timeout).map_err(|error| match error {
+                RecvTimeoutError::Timeout => TeiError::Timeout(expected),
+                RecvTimeoutError::Disconnected => TeiError::Disconnected,
+            }),
+            None => self.output.recv().map_err(|_| TeiError::Disconnected),
+        }.map(|line| String::from(line.trim()))
+    }
+}
+
+impl Drop for TeiEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+
+        // Give the engine a moment to exit on its own
+        for _ in 0..50 {
+            match self.child.try_wait() {
+                Ok(Some(_)) | Err(_) => return,
+                Ok(None) => thread::sleep(Duration::from_millis(POLL_INTERVAL)),
+            }
+        }
+
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Search<State> for TeiEngine {
+    /// Searches with `analyze`.  Since a `Search` can't fail, an engine that can't be
+    /// reached gives an analysis without a best ply.
+    fn search(&mut self, state: &State, interrupt: Option<Receiver<()>>) -> Box<Analysis> {
+        match self.analyze(state, interrupt) {
+            Ok(analysis) => Box::new(analysis),
+            Err(_) => Box::new(TeiAnalysis {
+                best_ply: None,
+                principal_variation: Vec::new(),
+                score: None,
+                depth: 0,
+                nodes: 0,
+                time: 0.0,
+            }),
+        }
+    }
+
+    fn get_principal_variation(&self, analysis: &Analysis) -> Vec<Ply> {
+        analysis.as_any().downcast_ref::<TeiAnalysis>().map_or_else(Vec::new, |analysis| analysis.principal_variation.clone())
+    }
+}
+
+/// The results of an external engine's search, gathered from its last `info` line and its
+/// `bestmove`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TeiAnalysis {
+    /// The ply the engine chose.
+    pub best_ply: Option<Ply>,
+    /// The plies of the engine's principal variation that are legal in turn.
+    pub principal_variation: Vec<Ply>,
+    /// The engine's score as it gave it, e.g. `cp 40` or `mate 3`.
+    pub score: Option<String>,
+    pub depth: usize,
+    pub nodes: u64,
+    /// The time taken to answer, in seconds.
+    pub time: f32,
+}
+
+impl fmt::Display for TeiAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.report())
+    }
+}
+
+impl Analysis for TeiAnalysis {
+    fn as_any(&self) -> &Any {
+        self
+    }
+
+    fn report(&self) -> AnalysisReport {
+        AnalysisReport {
+            best_move: self.best_ply.as_ref().map(|ply| ply.to_ptn()),
+            score: self.score.clone().unwrap_or_else(|| String::from("None")),
+            pv: self.principal_variation.iter().map(|ply| ply.to_ptn()).collect(),
+            depth: self.depth,
+            nodes: self.nodes,
+            time: self.time,
+            resolution: None,
+        }
+    }
+}
+
+/// Reads the fields of an `info` line that a `TeiAnalysis` keeps.
+fn read_info<'a, I>(analysis: &mut TeiAnalysis, state: &State, mut words: I) where
+    I: Iterator<Item = &'a str> {
+    while let Some(word) = words.next() {
+        match word {
+            "depth" => if let Some(depth) = words.next().and_then(|depth| depth.parse().ok()) {
+                analysis.depth = depth;
+            },
+            "nodes" => if let Some(nodes) = words.next().and_then(|nodes| nodes.parse().ok()) {
+                analysis.nodes = nodes;
+            },
+            "score" => if let (Some(kind), Some(value)) = (words.next(), words.next()) {
+                analysis.score = Some(format!("{} {}", kind, value));
+            },
+            // The principal variation is always last
+            "pv" => {
+                analysis.principal_variation = parse_plies(state, words);
+                return;
+            },
+            _ => (),
+        }
+    }
+}
+
+/// Parses plies in PTN played in turn from `state`, up to the first that can't be parsed or
+/// played.
+fn parse_plies<'a, I>(state: &State, ptns: I) -> Vec<Ply> where
+    I: IntoIterator<Item = &'a str> {
+    let mut state = state.clone();
+    let mut plies = Vec::new();
+
+    for ptn in ptns {
+        // Each player places the other's flatstone on their first turn
+        let color = match (state.ply_count % 2 == 0, state.is_opening_swap()) {
+            (true, false) | (false, true) => Color::White,
+            _ => Color::Black,
+        };
+
+        match Ply::from_ptn(ptn, color) {
+            Ok(ply) => if state.execute_ply(Some(&ply)).is_ok() {
+                plies.push(ply);
+            } else {
+                break;
+            },
+            Err(_) => break,
+        }
+    }
+
+    plies
+}
+
+/// Returns what follows `keyword` in `line`.
+fn rest_of(line: &str, keyword: &str) -> String {
+    line.splitn(2, keyword).nth(1).map_or_else(String::new, |rest| String::from(rest.trim()))
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::sync::mpsc;
+
+    use analysis::search::Search;
+    use impls::tak::*;
+
+    /// A stand-in engine that always plays a1, or c3 if told to stop first.
+    const ENGINE: &'static str = r#"
+        while read command rest; do
+            case "$command" in
+                tei) echo "id name Stand-in Engine"; echo "id author Nobody"; echo "teiok";;
+                isready) echo "readyok";;
+                go) case "$rest" in
+                    depth*) echo "info depth 3 score cp 12 nodes 345 pv a1 e5 b1 bogus c1"; echo "bestmove a1";;
+                    *) read stop; echo "bestmove c3";;
+                esac;;
+                quit) exit;;
+            esac
+        done
+    "#;
+
+    #[test]
+    fn test_tei_engine() {
+        let mut engine = TeiEngine::launch("sh", &["-c", ENGINE]).unwrap();
+        assert_eq!(engine.get_name(), Some("Stand-in Engine"));
+        assert_eq!(engine.get_author(), Some("Nobody"));
+
+        let state = State::new(5);
+        engine.set_depth(3);
+        let analysis = engine.analyze(&state, None).unwrap();
+        assert_eq!(analysis.best_ply, Some(Ply::from_ptn("a1", Color::Black).unwrap()));
+        assert_eq!(analysis.principal_variation.len(), 3);
+        assert_eq!(analysis.score, Some(String::from("cp 12")));
+        assert_eq!((analysis.depth, analysis.nodes), (3, 345));
+
+        // A time-limited search waits for the interrupt
+        engine.set_movetime(60000);
+        let (sender, receiver) = mpsc::channel();
+        sender.send(()).unwrap();
+        let analysis = engine.search(&state, Some(receiver));
+        assert_eq!(engine.get_principal_variation(&*analysis), vec![Ply::from_ptn("c3", Color::Black).unwrap()]);
+        assert_eq!(analysis.report().best_move, Some(String::from("c3")));
+    }
+}
@@ -0,0 +1,54 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use cli::Interactive;
+use impls::tic_tac_toe::{Board, Ply};
+
+impl Interactive for Board {
+    fn parse_ply(&self, input: &str) -> Result<Ply, String> {
+        let coordinates = input.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|coordinate| !coordinate.is_empty())
+            .map(|coordinate| match coordinate.parse::<usize>() {
+                Ok(coordinate) if coordinate >= 1 && coordinate <= 3 => Ok(coordinate - 1),
+                _ => Err(format!("Invalid coordinate: {}", coordinate)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if coordinates.len() != 2 {
+            return Err(String::from("Expected two coordinates"));
+        }
+
+        Ok(Ply {
+            mark: self.next_mark(),
+            coordinates: (coordinates[0], coordinates[1]),
+        })
+    }
+
+    fn format_ply(&self, ply: &Ply) -> String {
+        format!("{} {}", ply.coordinates.0 + 1, ply.coordinates.1 + 1)
+    }
+
+    fn notation() -> &'static str {
+        "the x and y coordinates from 1 to 3, i.e. \"2 3\""
+    }
+
+    fn player_name(player: u8) -> String {
+        String::from(if player == 0 { "X" } else { "O" })
+    }
+}
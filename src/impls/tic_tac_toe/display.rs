@@ -19,7 +19,7 @@
 
 use std::fmt;
 
-use impls::tic_tac_toe::{Board, Mark, Ply};
+use impls::tic_tac_toe::{Board, Mark, Ply, Resolution};
 
 impl fmt::Display for Mark {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -36,6 +36,15 @@ impl fmt::Display for Ply {
     }
 }
 
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Resolution::Win(mark) => write!(f, "{} wins", mark),
+            Resolution::CatsGame => write!(f, "Cat's game"),
+        }
+    }
+}
+
 impl fmt::Display for Board {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         try!(write!(f, "\n   1  2  3"));
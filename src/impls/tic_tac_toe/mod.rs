@@ -23,6 +23,7 @@ use std::hash::{Hash, Hasher};
 
 /// Either X or O.
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub enum Mark {
     X,
     O,
@@ -30,6 +31,7 @@ pub enum Mark {
 
 /// The placement of a mark in an empty space.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub struct Ply {
     pub mark: Mark,
     pub coordinates: (usize, usize),
@@ -49,6 +51,7 @@ impl Hash for Ply {
 
 /// Either a win or a cat's game.
 #[derive(Debug)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub enum Resolution {
     Win(Mark),
     CatsGame,
@@ -56,6 +59,7 @@ pub enum Resolution {
 
 /// The 3x3 game board.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub struct Board(pub [Option<Mark>; 9], pub u8);
 
 impl Board {
@@ -85,7 +89,37 @@ impl Hash for Board {
     }
 }
 
-pub use self::zero_sum::Evaluator;
+/// One of the eight symmetries of the board.  The board is first mirrored horizontally
+/// if `reflect` is set, then rotated by a quarter turn `rotations` times.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Transform {
+    pub rotations: u8,
+    pub reflect: bool,
+}
+
+impl Transform {
+    /// Transforms the coordinates of a space.
+    pub fn apply(&self, coordinates: (usize, usize)) -> (usize, usize) {
+        let (mut x, mut y) = if self.reflect {
+            (2 - coordinates.0, coordinates.1)
+        } else {
+            coordinates
+        };
+
+        for _ in 0..self.rotations % 4 {
+            let rx = 2 - y;
+            y = x;
+            x = rx;
+        }
+
+        (x, y)
+    }
+}
+
+pub use self::zero_sum::{Evaluator, Features};
+
+#[cfg(feature = "with_cli")]
+mod cli;
 
 mod display;
 mod zero_sum;
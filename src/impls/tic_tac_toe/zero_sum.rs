@@ -20,10 +20,11 @@
 use std::i8;
 
 use analysis::{self, Evaluation as EvaluationTrait};
-use impls::tic_tac_toe::{Board, Mark, Ply, Resolution};
+use impls::tic_tac_toe::{Board, Mark, Ply, Resolution, Transform};
 use ply;
 use resolution;
-use state::{self, State};
+use state::{self, PlyError, State};
+use symmetric;
 
 impl ply::Ply for Ply { }
 
@@ -49,7 +50,7 @@ impl state::State for Board {
         self.1 as usize
     }
 
-    fn execute_ply(&mut self, ply: Option<&Ply>) -> Result<(), String> {
+    fn execute_ply(&mut self, ply: Option<&Ply>) -> Result<(), PlyError> {
         let ply = if let Some(ply) = ply {
             ply
         } else {
@@ -58,13 +59,13 @@ impl state::State for Board {
         };
 
         if ply.coordinates.0 >= 3 || ply.coordinates.1 >= 3 {
-            return Err(String::from("Coordinates out of bounds"));
+            return Err(PlyError::OutOfBounds);
         }
 
         let index = ply.coordinates.0 + 3 * ply.coordinates.1;
 
         if self.0[index].is_some() {
-            return Err(String::from("Space already occupied"));
+            return Err(PlyError::Occupied);
         }
 
         self.0[index] = Some(ply.mark);
@@ -72,7 +73,7 @@ impl state::State for Board {
         Ok(())
     }
 
-    fn revert_ply(&mut self, ply: Option<&Ply>) -> Result<(), String> {
+    fn revert_ply(&mut self, ply: Option<&Ply>) -> Result<(), PlyError> {
         let ply = if let Some(ply) = ply {
             ply
         } else {
@@ -81,17 +82,17 @@ impl state::State for Board {
         };
 
         if ply.coordinates.0 >= 3 || ply.coordinates.1 >= 3 {
-            return Err(String::from("Coordinates out of bounds"));
+            return Err(PlyError::OutOfBounds);
         }
 
         let index = ply.coordinates.0 + 3 * ply.coordinates.1;
 
         if self.0[index].is_none() {
-            return Err(String::from("Space is empty"));
+            return Err(PlyError::Empty);
         }
 
         if self.0[index] != Some(ply.mark) {
-            return Err(String::from("Space has the wrong mark"));
+            return Err(PlyError::WrongColor);
         }
 
         self.0[index] = None;
@@ -140,7 +141,52 @@ impl analysis::Extrapolatable<Ply> for Board {
     }
 }
 
+impl symmetric::Symmetric for Board {
+    type Transform = Transform;
+
+    fn symmetries(&self) -> Vec<Transform> {
+        let mut transforms = Vec::with_capacity(8);
+        for &reflect in &[false, true] {
+            for rotations in 0..4 {
+                transforms.push(Transform {
+                    rotations: rotations,
+                    reflect: reflect,
+                });
+            }
+        }
+        transforms
+    }
+
+    fn transform(&self, transform: &Transform) -> Board {
+        let mut board = Board([None; 9], self.1);
+        for index in 0..9 {
+            let (x, y) = transform.apply((index % 3, index / 3));
+            board.0[x + 3 * y] = self.0[index];
+        }
+        board
+    }
+
+    fn transform_ply(&self, ply: &Ply, transform: &Transform) -> Ply {
+        Ply {
+            mark: ply.mark,
+            coordinates: transform.apply(ply.coordinates),
+        }
+    }
+
+    fn invert_transform(&self, transform: &Transform) -> Transform {
+        if transform.reflect {
+            *transform
+        } else {
+            Transform {
+                rotations: (4 - transform.rotations % 4) % 4,
+                reflect: false,
+            }
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "with_serde", derive(Deserialize, Serialize))]
 pub struct Evaluation(pub i8);
 
 prepare_evaluation_tuple!(Evaluation); // Implements arithmetic operators and display in terms of the inner type
@@ -151,6 +197,7 @@ impl analysis::Evaluation for Evaluation {
     fn win() -> Evaluation { Evaluation(14) }
     fn max() -> Evaluation { Evaluation(i8::MAX) }
     fn is_win(&self) -> bool { self.0 >= 5 }
+    fn encodes_win_distance() -> bool { true }
 }
 
 /// A very simple evaluator.  Gives extra weight to the corners.
@@ -184,3 +231,20 @@ impl analysis::Evaluator for Evaluator {
         }
     }
 }
+
+/// Describes the board with one feature per space for each mark: 1.0 if the space holds that
+/// mark, and 0.0 otherwise.  The nine X features come first.
+pub struct Features;
+
+impl analysis::FeatureExtractor<Board> for Features {
+    fn dimension(&self) -> usize {
+        18
+    }
+
+    fn features(&self, state: &Board, out: &mut [f32]) {
+        for (i, space) in state.0.iter().enumerate() {
+            out[i] = if *space == Some(Mark::X) { 1.0 } else { 0.0 };
+            out[9 + i] = if *space == Some(Mark::O) { 1.0 } else { 0.0 };
+        }
+    }
+}
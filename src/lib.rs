@@ -55,6 +55,34 @@
 //!
 //! for instance, to include the `tak` module.
 //!
+//! The `with_serde` feature derives serde's `Serialize` and `Deserialize` for the game types of
+//! the enabled implementations and for the results of analysis, such as `PvSearchAnalysis`.
+//! A tak `State` is serialized with its position as TPS.
+//!
+//! The `with_cli` feature adds the `cli` module, a terminal game loop for playing the enabled
+//! implementations against the engine.
+//!
+//! The crate builds on stable Rust.  The `with_nightly` feature, which isn't part of `with_all`,
+//! enables the `#[bench]` microbenchmarks of the tak evaluators under `cargo bench`, and needs a
+//! nightly compiler.  For timing searches on stable, see `analysis::bench`.
+//!
+//! The `with_wasm` feature adds the `wasm` module, `wasm-bindgen` bindings for analyzing tak and
+//! tic-tac-toe positions from JavaScript.  The crate builds for `wasm32-unknown-unknown` with
+//! any features but `with_tak_ann`, which needs BLAS, though `analysis::EngineSession` can't
+//! run there, as it times searches on a thread of its own.
+//!
+//! The default `std` feature provides everything that depends on the standard library: the
+//! searches and other analysis tools, persistence, and the implementations.  Without it, the
+//! crate is `no_std`, and needs only `alloc` for the core traits, `Ply`, `Resolution`, `State`,
+//! `analysis::Evaluation`, `analysis::Evaluator`, and `analysis::Extrapolatable`, along with
+//! their companions in `analysis`.  Game models written against them can then be shared with
+//! targets where the full engine can't be built:
+//!
+//! ```toml
+//! [dependencies]
+//! zero_sum = { version = "1.2", default-features = false }
+//! ```
+//!
 //! # Implementation
 //!
 //! The three basic traits are `Ply`, `Resolution`, and `State`.  These form
@@ -70,35 +98,80 @@
 //!
 //! The provided tic-tac-toe implementation is very simple and a usage example can
 //! be found in [examples/tic_tac_toe.rs](https://github.com/cdbfoster/zero_sum/blob/master/examples/tic_tac_toe.rs).
+//! A game of tak against the engine is in [examples/tak.rs](https://github.com/cdbfoster/zero_sum/blob/master/examples/tak.rs).
 
-#![feature(test)]
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "with_nightly", feature(test))]
 
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+#[cfg(feature = "std")]
 extern crate fnv;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "with_nightly"))]
 extern crate test;
 
 #[cfg(feature = "with_tak")]
 #[macro_use]
 extern crate lazy_static;
 
-#[cfg(feature = "with_tak")]
+#[cfg(feature = "std")]
 extern crate rand;
 
 #[cfg(feature = "with_tak_ann")]
 extern crate blas;
 
+#[cfg(feature = "with_serde")]
+extern crate serde;
+
+#[cfg(feature = "with_serde")]
+#[macro_use]
+extern crate serde_derive;
+
+#[cfg(all(test, feature = "with_serde"))]
+extern crate serde_json;
+
+#[cfg(feature = "with_wasm")]
+extern crate js_sys;
+
+#[cfg(feature = "with_wasm")]
+extern crate wasm_bindgen;
+
 #[macro_use]
 pub mod analysis;
 
+#[cfg(feature = "with_cli")]
+pub mod cli;
+
 pub use self::ply::Ply;
-pub use self::resolution::Resolution;
-pub use self::state::State;
+pub use self::position_key::PositionKey;
+pub use self::resolution::{Outcome, Resolution};
+pub use self::state::{PlyError, PlySequenceError, State};
+#[cfg(feature = "std")]
+pub use self::symmetric::Symmetric;
 
-#[cfg(any(feature = "with_tak", feature = "with_tic_tac_toe"))]
+#[cfg(any(feature = "with_connect_four", feature = "with_tak", feature = "with_tic_tac_toe"))]
 pub mod impls;
 
+#[cfg(feature = "std")]
+pub mod persist;
+
 mod ply;
+mod position_key;
 mod resolution;
 mod state;
+#[cfg(feature = "std")]
+mod symmetric;
+#[cfg(feature = "std")]
 mod util;
+
+#[cfg(feature = "with_wasm")]
+pub mod wasm;
+
+// Without the standard library, the core traits find what they need of it in core and alloc
+#[cfg(not(feature = "std"))]
+mod std {
+    pub use alloc::{string, vec};
+    pub use core::{fmt, hash, ops};
+}
@@ -0,0 +1,441 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+//! Framing and integrity checking for files written by this crate.
+//!
+//! A persisted file begins with a four-byte magic number identifying the kind of artifact
+//! and a format version, followed by any number of sections.  Each section is a payload
+//! prefixed with its length and followed by its checksum, so that truncated or corrupted
+//! files are detected rather than misread, and the sections before the damaged one can
+//! still be recovered.
+//!
+//! All integers are little-endian.
+
+use std::error::Error;
+use std::fmt;
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+
+use fnv::FnvHasher;
+
+/// Sections longer than this are treated as corrupt by default, rather than attempting
+/// to allocate for them.
+pub const DEFAULT_MAX_SECTION_LENGTH: u64 = 1 << 30;
+
+/// The ways that loading a persisted file can fail.
+#[derive(Debug)]
+pub enum PersistError {
+    /// The underlying reader or writer failed.
+    Io(io::Error),
+    /// The file does not begin with the expected magic number; it is not the right kind of file.
+    BadMagic,
+    /// The file is intact, but was written in a different version of the format.
+    VersionMismatch {
+        expected: u32,
+        found: u32,
+    },
+    /// The file ends partway through the given section.
+    Truncated {
+        section: usize,
+    },
+    /// The section's declared length is implausibly large.
+    InvalidLength {
+        section: usize,
+        length: u64,
+    },
+    /// The section's contents do not match its checksum.
+    ChecksumMismatch {
+        section: usize,
+    },
+    /// A section passed its checksum, but its contents could not be decoded.
+    Malformed(String),
+}
+
+impl PersistError {
+    /// Returns true if the error indicates a damaged file, as opposed to a file of the wrong
+    /// kind or version, or an I/O failure.
+    pub fn is_corruption(&self) -> bool {
+        match *self {
+            PersistError::Truncated { .. } |
+            PersistError::InvalidLength { .. } |
+            PersistError::ChecksumMismatch { .. } |
+            PersistError::Malformed(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PersistError::Io(ref error) => write!(f, "I/O error: {}", error),
+            PersistError::BadMagic => write!(f, "Unrecognized file type"),
+            PersistError::VersionMismatch { expected, found } => write!(f, "Unsupported version: expected {}, found {}", expected, found),
+            PersistError::Truncated { section } => write!(f, "File truncated in section {}", section),
+            PersistError::InvalidLength { section, length } => write!(f, "Invalid length {} in section {}", length, section),
+            PersistError::ChecksumMismatch { section } => write!(f, "Checksum mismatch in section {}", section),
+            PersistError::Malformed(ref message) => write!(f, "Malformed data: {}", message),
+        }
+    }
+}
+
+impl Error for PersistError {
+    fn description(&self) -> &str {
+        "Error loading persisted data"
+    }
+}
+
+impl From<io::Error> for PersistError {
+    fn from(error: io::Error) -> PersistError {
+        PersistError::Io(error)
+    }
+}
+
+/// Returns the checksum of `bytes`.
+pub fn checksum(bytes: &[u8]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Writes the header and sections of a persisted file.
+pub struct Writer<W> where
+    W: Write {
+    inner: W,
+}
+
+impl<W> Writer<W> where
+    W: Write {
+    /// Writes the header to `inner`.
+    pub fn new(mut inner: W, magic: &[u8; 4], version: u32) -> io::Result<Writer<W>> {
+        inner.write_all(magic)?;
+        inner.write_all(&encode_u32(version))?;
+        Ok(Writer {
+            inner: inner,
+        })
+    }
+
+    /// Writes `payload` as a single section.
+    pub fn write_section(&mut self, payload: &[u8]) -> io::Result<()> {
+        self.inner.write_all(&encode_u64(payload.len() as u64))?;
+        self.inner.write_all(payload)?;
+        self.inner.write_all(&encode_u64(checksum(payload)))
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Verifies the header of a persisted file and reads its sections.
+pub struct Reader<R> where
+    R: Read {
+    inner: R,
+    section: usize,
+    max_section_length: u64,
+    failed: bool,
+}
+
+impl<R> Reader<R> where
+    R: Read {
+    /// Reads and verifies the header from `inner`.
+    pub fn new(mut inner: R, magic: &[u8; 4], version: u32) -> Result<Reader<R>, PersistError> {
+        let mut header = [0; 8];
+        match read_exact_or_eof(&mut inner, &mut header)? {
+            8 => (),
+            _ => return Err(PersistError::BadMagic),
+        }
+
+        if &header[0..4] != magic {
+            return Err(PersistError::BadMagic);
+        }
+
+        let found = decode_u32(&header[4..8]);
+        if found != version {
+            return Err(PersistError::VersionMismatch {
+                expected: version,
+                found: found,
+            });
+        }
+
+        Ok(Reader {
+            inner: inner,
+            section: 0,
+            max_section_length: DEFAULT_MAX_SECTION_LENGTH,
+            failed: false,
+        })
+    }
+
+    /// Sets the length above which a section is considered corrupt.
+    pub fn set_max_section_length(&mut self, length: u64) {
+        self.max_section_length = length;
+    }
+
+    /// Reads and verifies the next section.  Returns `Ok(None)` at the end of the file.
+    /// Once an error has been returned, every subsequent call returns `Ok(None)`.
+    pub fn read_section(&mut self) -> Result<Option<Vec<u8>>, PersistError> {
+        if self.failed {
+            return Ok(None);
+        }
+
+        let result = self.read_section_inner();
+        if result.is_err() {
+            self.failed = true;
+        } else {
+            self.section += 1;
+        }
+        result
+    }
+
+    /// Reads every remaining section, stopping at the first error.  The sections read before
+    /// the error are returned along with it, allowing partial recovery of a damaged file.
+    pub fn read_all(&mut self) -> (Vec<Vec<u8>>, Option<PersistError>) {
+        let mut sections = Vec::new();
+        loop {
+            match self.read_section() {
+                Ok(Some(section)) => sections.push(section),
+                Ok(None) => return (sections, None),
+                Err(error) => return (sections, Some(error)),
+            }
+        }
+    }
+
+    fn read_section_inner(&mut self) -> Result<Option<Vec<u8>>, PersistError> {
+        let section = self.section;
+
+        let mut length = [0; 8];
+        match read_exact_or_eof(&mut self.inner, &mut length)? {
+            0 => return Ok(None),
+            8 => (),
+            _ => return Err(PersistError::Truncated { section: section }),
+        }
+
+        let length = decode_u64(&length);
+        if length > self.max_section_length {
+            return Err(PersistError::InvalidLength {
+                section: section,
+                length: length,
+            });
+        }
+
+        let mut payload = vec![0; length as usize];
+        if read_exact_or_eof(&mut self.inner, &mut payload)? != payload.len() {
+            return Err(PersistError::Truncated { section: section });
+        }
+
+        let mut expected = [0; 8];
+        if read_exact_or_eof(&mut self.inner, &mut expected)? != 8 {
+            return Err(PersistError::Truncated { section: section });
+        }
+
+        if decode_u64(&expected) != checksum(&payload) {
+            return Err(PersistError::ChecksumMismatch { section: section });
+        }
+
+        Ok(Some(payload))
+    }
+}
+
+/// Decodes values from a section's payload.
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Decoder<'a> {
+        Decoder {
+            bytes: bytes,
+            position: 0,
+        }
+    }
+
+    /// Returns true if every byte has been decoded.
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    /// Returns the next `length` bytes.
+    pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], PersistError> {
+        if self.bytes.len() - self.position < length {
+            return Err(PersistError::Malformed(String::from("Unexpected end of section")));
+        }
+        let bytes = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, PersistError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, PersistError> {
+        Ok(decode_u32(self.read_bytes(4)?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, PersistError> {
+        Ok(decode_u64(self.read_bytes(8)?))
+    }
+}
+
+/// Encodes values into a section's payload.
+pub trait Encode {
+    fn write_u8(&mut self, value: u8);
+    fn write_u32(&mut self, value: u32);
+    fn write_u64(&mut self, value: u64);
+}
+
+impl Encode for Vec<u8> {
+    fn write_u8(&mut self, value: u8) {
+        self.push(value);
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.extend_from_slice(&encode_u32(value));
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.extend_from_slice(&encode_u64(value));
+    }
+}
+
+/// A value that can be encoded into a section's payload and decoded from it again.
+pub trait Persistable: Sized {
+    /// Appends the encoding of this value to `bytes`.
+    fn encode(&self, bytes: &mut Vec<u8>);
+
+    /// Decodes a value written by `encode`.
+    fn decode(decoder: &mut Decoder) -> Result<Self, PersistError>;
+}
+
+fn encode_u32(value: u32) -> [u8; 4] {
+    let mut bytes = [0; 4];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (value >> (8 * i)) as u8;
+    }
+    bytes
+}
+
+fn encode_u64(value: u64) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (value >> (8 * i)) as u8;
+    }
+    bytes
+}
+
+fn decode_u32(bytes: &[u8]) -> u32 {
+    bytes.iter().take(4).enumerate().fold(0, |acc, (i, &byte)| acc | (byte as u32) << (8 * i))
+}
+
+fn decode_u64(bytes: &[u8]) -> u64 {
+    bytes.iter().take(8).enumerate().fold(0, |acc, (i, &byte)| acc | (byte as u64) << (8 * i))
+}
+
+// Like read_exact, but returns the number of bytes read if the reader ends early
+fn read_exact_or_eof<R>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> where R: Read {
+    let mut total = 0;
+    while total < buffer.len() {
+        match reader.read(&mut buffer[total..]) {
+            Ok(0) => break,
+            Ok(count) => total += count,
+            Err(ref error) if error.kind() == io::ErrorKind::Interrupted => (),
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAGIC: &'static [u8; 4] = b"TEST";
+
+    fn write_file(sections: &[&[u8]]) -> Vec<u8> {
+        let mut writer = Writer::new(Vec::new(), MAGIC, 1).unwrap();
+        for section in sections {
+            writer.write_section(section).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let file = write_file(&[b"first", b"", b"third"]);
+        let mut reader = Reader::new(&file[..], MAGIC, 1).unwrap();
+        let (sections, error) = reader.read_all();
+        assert!(error.is_none());
+        assert!(sections == vec![b"first".to_vec(), Vec::new(), b"third".to_vec()]);
+    }
+
+    #[test]
+    fn test_header_errors() {
+        let file = write_file(&[b"first"]);
+        match Reader::new(&file[..], b"NOPE", 1) {
+            Err(PersistError::BadMagic) => (),
+            _ => panic!("Expected BadMagic"),
+        }
+        match Reader::new(&file[..], MAGIC, 2) {
+            Err(PersistError::VersionMismatch { expected: 2, found: 1 }) => (),
+            _ => panic!("Expected VersionMismatch"),
+        }
+    }
+
+    #[test]
+    fn test_partial_recovery() {
+        let mut file = write_file(&[b"first", b"second", b"third"]);
+
+        // Corrupt the second section's payload
+        let offset = 8 + (8 + 5 + 8) + 8;
+        file[offset] ^= 0xFF;
+
+        let mut reader = Reader::new(&file[..], MAGIC, 1).unwrap();
+        let (sections, error) = reader.read_all();
+        assert!(sections == vec![b"first".to_vec()]);
+        match error {
+            Some(PersistError::ChecksumMismatch { section: 1 }) => (),
+            _ => panic!("Expected ChecksumMismatch"),
+        }
+
+        // Truncate the third section
+        let file = write_file(&[b"first", b"second", b"third"]);
+        let mut reader = Reader::new(&file[..file.len() - 3], MAGIC, 1).unwrap();
+        let (sections, error) = reader.read_all();
+        assert!(sections.len() == 2);
+        assert!(error.unwrap().is_corruption());
+    }
+
+    #[test]
+    fn test_decoder() {
+        let mut payload = Vec::new();
+        payload.write_u8(7);
+        payload.write_u32(0xDEADBEEF);
+        payload.write_u64(u64::max_value() - 1);
+
+        let mut decoder = Decoder::new(&payload);
+        assert!(decoder.read_u8().unwrap() == 7);
+        assert!(decoder.read_u32().unwrap() == 0xDEADBEEF);
+        assert!(decoder.read_u64().unwrap() == u64::max_value() - 1);
+        assert!(decoder.is_empty());
+        assert!(decoder.read_u8().is_err());
+    }
+}
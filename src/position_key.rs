@@ -0,0 +1,68 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use state::State;
+
+/// A state that can compute a key identifying its position, for tables that look states up
+/// many times per search, like a search's transposition table.
+///
+/// Without this trait, such tables key states by hashing them with their `Hash`
+/// implementations.  A game can implement this separately, i.e. with Zobrist keys or by
+/// mixing the bitboards it already keeps, so that keys are cheap and well distributed without
+/// changing how the state hashes elsewhere.
+///
+/// Keys must be equal for equal states, and should rarely be equal otherwise.  A
+/// transposition table compares the states themselves before trusting a match, and a
+/// `CachedSearch` compares their hashes, so there a collision costs only a lookup.  A
+/// `RepetitionTracker` counts positions by key alone, so there a collision is a false
+/// repetition.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate zero_sum;
+/// # use zero_sum::{Ply, PositionKey, Resolution, State};
+/// # #[derive(Clone, Debug, Hash, PartialEq)]
+/// # struct Move(usize);
+/// # enum End { }
+/// # #[derive(Clone, Eq, Hash, PartialEq)]
+/// struct Board {
+///     x: u64,
+///     o: u64,
+///     ply_count: usize,
+/// }
+///
+/// impl PositionKey for Board {
+///     fn position_key(&self) -> u64 {
+///         let key = self.x.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ self.o.rotate_left(32);
+///         if self.ply_count % 2 == 0 { key } else { !key }
+///     }
+/// }
+/// # impl Ply for Move { }
+/// # impl Resolution for End { fn get_winner(&self) -> Option<u8> { None } fn is_draw(&self) -> bool { false } }
+/// # impl std::fmt::Display for End { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
+/// # impl std::fmt::Display for Move { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
+/// # impl std::fmt::Display for Board { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
+/// # impl State for Board { type Ply = Move; type Resolution = End; fn get_ply_count(&self) -> usize { 0 } fn execute_ply(&mut self, _: Option<&Move>) -> Result<(), zero_sum::PlyError> { Ok(()) } fn revert_ply(&mut self, _: Option<&Move>) -> Result<(), zero_sum::PlyError> { Ok(()) } fn check_resolution(&self) -> Option<End> { None } }
+/// # fn main() { }
+/// ```
+pub trait PositionKey: State {
+    /// Returns the key of the state's position.
+    fn position_key(&self) -> u64;
+}
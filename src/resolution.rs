@@ -17,9 +17,12 @@
 // Copyright 2016-2017 Chris Foster
 //
 
+use std::fmt::Display;
+
 /// A game's resolution.
 ///
-/// This is often an `enum` that represents each ending a game can have.
+/// This is often an `enum` that represents each ending a game can have.  Its `Display`
+/// implementation should describe the ending, i.e. "X wins".
 ///
 /// # Example
 ///
@@ -28,6 +31,8 @@
 /// ```rust
 /// # extern crate zero_sum;
 /// # use zero_sum::Resolution;
+/// use std::fmt;
+///
 /// # #[derive(PartialEq)]
 /// enum Mark { X, O }
 ///
@@ -47,10 +52,40 @@
 ///     }
 ///     fn is_draw(&self) -> bool { if *self == End::CatsGame { true } else { false } }
 /// }
+///
+/// impl fmt::Display for End {
+///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///         match *self {
+///             End::Win(Mark::X) => write!(f, "X wins"),
+///             End::Win(Mark::O) => write!(f, "O wins"),
+///             End::CatsGame => write!(f, "Cat's game"),
+///         }
+///     }
+/// }
 /// # fn main() { }
 /// ```
-pub trait Resolution {
+pub trait Resolution: Display {
     /// Returns the index of the winning player if this `Resolution` represents a win.
     fn get_winner(&self) -> Option<u8>;
     fn is_draw(&self) -> bool;
+
+    /// Returns the outcome of the game for the player with index `player`, or `None` if
+    /// this `Resolution` is neither a win nor a draw.
+    fn score_for(&self, player: u8) -> Option<Outcome> {
+        if let Some(winner) = self.get_winner() {
+            Some(if winner == player { Outcome::Win } else { Outcome::Loss })
+        } else if self.is_draw() {
+            Some(Outcome::Draw)
+        } else {
+            None
+        }
+    }
+}
+
+/// The outcome of a game from one player's perspective.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
 }
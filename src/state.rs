@@ -17,12 +17,95 @@
 // Copyright 2016-2017 Chris Foster
 //
 
-use std::fmt::Display;
+use std::fmt::{self, Display};
 use std::hash::Hash;
+use std::string::{String, ToString};
 
 use ply::Ply;
 use resolution::Resolution;
 
+/// The reasons that a ply can't be executed on, or reverted from, a state.
+///
+/// None of them allocate, so a search can try plies and discard the failures cheaply.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PlyError {
+    /// The space that the ply places a piece in is already occupied.
+    Occupied,
+    /// The space that the ply reverts a piece from is empty.
+    Empty,
+    /// The player doesn't have the pieces that the ply needs.
+    InsufficientPieces,
+    /// The ply reaches off of the board.
+    OutOfBounds,
+    /// The ply uses the other player's pieces.
+    WrongColor,
+    /// The ply being reverted doesn't match the state, so it can't have been the last one executed.
+    Mismatch,
+    /// There are no plies left to revert.
+    NothingToRevert,
+    /// The ply breaks one of the game's other rules, described by the message.
+    Illegal(&'static str),
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlyError::Occupied => write!(f, "The space is already occupied"),
+            PlyError::Empty => write!(f, "The space is empty"),
+            PlyError::InsufficientPieces => write!(f, "Insufficient pieces"),
+            PlyError::OutOfBounds => write!(f, "Out of bounds"),
+            PlyError::WrongColor => write!(f, "Cannot use an opponent's piece"),
+            PlyError::Mismatch => write!(f, "The ply doesn't match the state it's reverted from"),
+            PlyError::NothingToRevert => write!(f, "No more plies to revert"),
+            PlyError::Illegal(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for PlyError {
+    fn description(&self) -> &str {
+        "Illegal ply"
+    }
+}
+
+/// Lets code that reports errors as strings keep using `?` on `execute_ply` and `revert_ply`.
+impl From<PlyError> for String {
+    fn from(error: PlyError) -> String {
+        error.to_string()
+    }
+}
+
+/// The failure of one ply in a sequence passed to `State::execute_plies`.
+///
+/// The plies before `index` were executed and remain on the state.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PlySequenceError {
+    /// The position in the sequence of the ply that failed.
+    pub index: usize,
+    /// The reason it failed.
+    pub error: PlyError,
+}
+
+impl fmt::Display for PlySequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error executing ply {}: {}", self.index, self.error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for PlySequenceError {
+    fn description(&self) -> &str {
+        "Illegal ply in sequence"
+    }
+}
+
+impl From<PlySequenceError> for String {
+    fn from(error: PlySequenceError) -> String {
+        error.to_string()
+    }
+}
+
 /// The state of the game.
 ///
 /// This should represent everything that makes up a single moment of the game, i.e. in chess,
@@ -41,7 +124,7 @@ use resolution::Resolution;
 /// ```rust
 /// # extern crate zero_sum;
 /// # use std::hash::{Hash, Hasher};
-/// # use zero_sum::{Ply, Resolution, State};
+/// # use zero_sum::{Ply, PlyError, Resolution, State};
 /// # #[derive(Clone, Copy, Eq, Hash, PartialEq)]
 /// enum Mark { X, O }
 /// # #[derive(Clone, Debug, Hash, PartialEq)]
@@ -59,12 +142,12 @@ use resolution::Resolution;
 ///         self.1 as usize
 ///     }
 ///
-///     fn execute_ply(&mut self, ply: Option<&Move>) -> Result<(), String> {
+///     fn execute_ply(&mut self, ply: Option<&Move>) -> Result<(), PlyError> {
 ///         // ...
 ///         # Ok(())
 ///     }
 ///
-///     fn revert_ply(&mut self, ply: Option<&Move>) -> Result<(), String> {
+///     fn revert_ply(&mut self, ply: Option<&Move>) -> Result<(), PlyError> {
 ///         // ...
 ///         # Ok(())
 ///     }
@@ -87,6 +170,7 @@ use resolution::Resolution;
 /// }
 /// # impl Ply for Move { }
 /// # impl Resolution for End { fn get_winner(&self) -> Option<u8> { None } fn is_draw(&self) -> bool { false } }
+/// # impl std::fmt::Display for End { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
 /// # impl std::fmt::Display for Move { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
 /// # impl std::fmt::Display for Board { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
 /// # fn main() { }
@@ -99,25 +183,44 @@ pub trait State: Clone + Display + Eq + Hash + PartialEq {
     fn get_ply_count(&self) -> usize;
 
     /// Executes the given ply on this state.  Pass `None` to execute a null move.
-    fn execute_ply(&mut self, ply: Option<&Self::Ply>) -> Result<(), String>;
+    fn execute_ply(&mut self, ply: Option<&Self::Ply>) -> Result<(), PlyError>;
 
     /// Reverts the given ply from the state.  Pass `None` to revert a null move.
-    fn revert_ply(&mut self, ply: Option<&Self::Ply>) -> Result<(), String>;
+    fn revert_ply(&mut self, ply: Option<&Self::Ply>) -> Result<(), PlyError>;
 
     /// Returns `None` if the game has not reached a conclusion.
     fn check_resolution(&self) -> Option<Self::Resolution>;
 
+    /// Returns the resolution of the game when the player to move has no plies that can be
+    /// executed, or `None` if they pass instead, as with a null move.  Searches consult this
+    /// only after finding that no ply from `Extrapolatable::extrapolate` is legal, so it can
+    /// assume as much.  This is optional to implement, returning a default of `None`; a game
+    /// in which a player with no plies loses would return a win for the other player, and one
+    /// ending in stalemate would return a draw.
+    fn no_plies_resolution(&self) -> Option<Self::Resolution> {
+        None
+    }
+
+    /// Returns true if neither player can win from this state any longer, so that the game can
+    /// be adjudicated a draw without being played out.  Searches score such states as draws
+    /// below the root, and match runners end the game there.  A state with a resolution isn't
+    /// a dead draw.  This is optional to implement, returning a default of `false`.
+    fn is_dead_draw(&self) -> bool {
+        false
+    }
+
     /// Returns true if the state is in a good place to allow the null move search optimization.
     /// This is optional to implement, returning a default of `false`.
     fn null_move_allowed(&self) -> bool {
         false
     }
 
-    /// Executes each ply in `plies` on the result of the previous ply.
-    fn execute_plies(&mut self, plies: &[Self::Ply]) -> Result<(), String> {
-        for ply in plies {
+    /// Executes each ply in `plies` on the result of the previous ply.  On failure, returns the
+    /// index of the ply that couldn't be executed along with the reason.
+    fn execute_plies(&mut self, plies: &[Self::Ply]) -> Result<(), PlySequenceError> {
+        for (index, ply) in plies.iter().enumerate() {
             if let Err(error) = self.execute_ply(Some(ply)) {
-                return Err(format!("Error executing plies: {}, {}", ply, error));
+                return Err(PlySequenceError { index: index, error: error });
             }
         }
         Ok(())
@@ -0,0 +1,124 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+use std::hash::Hasher;
+
+use fnv::FnvHasher;
+
+use state::State;
+
+/// A state whose game is unchanged by some set of transformations, i.e. the rotations
+/// and reflections of a square board.
+///
+/// Implementing this trait allows searches to treat symmetric states as transpositions of
+/// one another, which can greatly reduce the size of the search space on small boards.
+///
+/// # Example
+///
+/// For a game played on a line of spaces, mirroring the line is a symmetry:
+///
+/// ```rust
+/// # extern crate zero_sum;
+/// # use zero_sum::{Ply, Resolution, State, Symmetric};
+/// # #[derive(Clone, Debug, Hash, PartialEq)]
+/// struct Move(usize);
+/// # enum End { }
+///
+/// # #[derive(Clone, Eq, Hash, PartialEq)]
+/// struct Line(Vec<Option<bool>>);
+///
+/// impl Symmetric for Line {
+///     type Transform = bool; // Whether or not to mirror
+///
+///     fn symmetries(&self) -> Vec<bool> {
+///         vec![false, true]
+///     }
+///
+///     fn transform(&self, mirror: &bool) -> Line {
+///         let mut line = self.clone();
+///         if *mirror {
+///             line.0.reverse();
+///         }
+///         line
+///     }
+///
+///     fn transform_ply(&self, ply: &Move, mirror: &bool) -> Move {
+///         if *mirror { Move(self.0.len() - 1 - ply.0) } else { ply.clone() }
+///     }
+///
+///     fn invert_transform(&self, mirror: &bool) -> bool {
+///         *mirror
+///     }
+/// }
+/// # impl Ply for Move { }
+/// # impl Resolution for End { fn get_winner(&self) -> Option<u8> { None } fn is_draw(&self) -> bool { false } }
+/// # impl std::fmt::Display for End { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
+/// # impl std::fmt::Display for Move { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
+/// # impl std::fmt::Display for Line { fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) } }
+/// # impl State for Line { type Ply = Move; type Resolution = End; fn get_ply_count(&self) -> usize { 0 } fn execute_ply(&mut self, _: Option<&Move>) -> Result<(), zero_sum::PlyError> { Ok(()) } fn revert_ply(&mut self, _: Option<&Move>) -> Result<(), zero_sum::PlyError> { Ok(()) } fn check_resolution(&self) -> Option<End> { None } }
+/// # fn main() { }
+/// ```
+pub trait Symmetric: State {
+    type Transform: Clone;
+
+    /// Returns every transformation under which the state is equivalent, including the identity.
+    fn symmetries(&self) -> Vec<Self::Transform>;
+
+    /// Returns a copy of the state with `transform` applied.
+    fn transform(&self, transform: &Self::Transform) -> Self;
+
+    /// Returns `ply` transformed by `transform`, so that it applies to
+    /// `self.transform(transform)` as `ply` applies to `self`.
+    fn transform_ply(&self, ply: &Self::Ply, transform: &Self::Transform) -> Self::Ply;
+
+    /// Returns the transformation that undoes `transform`.
+    fn invert_transform(&self, transform: &Self::Transform) -> Self::Transform;
+
+    /// Returns the transformation that takes this state to its canonical form.
+    ///
+    /// The default implementation chooses the symmetry whose result has the lowest hash.
+    /// Any choice is valid, as long as every equivalent state arrives at the same result.
+    fn canonical_transform(&self) -> Self::Transform {
+        let mut symmetries = self.symmetries().into_iter();
+        let mut best = symmetries.next().expect("A state must have at least the identity symmetry");
+        let mut best_hash = hash_state(&self.transform(&best));
+
+        for transform in symmetries {
+            let hash = hash_state(&self.transform(&transform));
+            if hash < best_hash {
+                best = transform;
+                best_hash = hash;
+            }
+        }
+
+        best
+    }
+
+    /// Returns the canonical form of this state.  All states that are equivalent under
+    /// `symmetries` have the same canonical form.
+    fn canonicalize(&self) -> Self {
+        self.transform(&self.canonical_transform())
+    }
+}
+
+fn hash_state<S>(state: &S) -> u64 where S: State {
+    let mut hasher = FnvHasher::default();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
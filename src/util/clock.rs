@@ -0,0 +1,52 @@
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+//! The clock that searches are timed with.  `std::time::Instant` panics on
+//! `wasm32-unknown-unknown`, so there it reads the browser's clock instead.
+
+#[cfg(not(all(target_arch = "wasm32", feature = "with_wasm")))]
+pub use std::time::Instant;
+
+#[cfg(all(target_arch = "wasm32", feature = "with_wasm"))]
+pub use self::wasm::Instant;
+
+#[cfg(all(target_arch = "wasm32", feature = "with_wasm"))]
+mod wasm {
+    use std::time::Duration;
+
+    use js_sys::Date;
+
+    /// A moment in time, in milliseconds since the epoch.
+    #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+    pub struct Instant(f64);
+
+    impl Instant {
+        pub fn now() -> Instant {
+            Instant(Date::now())
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            // The system clock can be set back; never report negative time
+            let milliseconds = (Date::now() - self.0).max(0.0);
+            Duration::new(
+                (milliseconds / 1000.0) as u64,
+                ((milliseconds % 1000.0) * 1_000_000.0) as u32,
+            )
+        }
+    }
+}
@@ -16,7 +16,14 @@
 // Copyright 2016-2017 Chris Foster
 //
 
-use rand::{self, Rng};
+#[cfg(all(target_arch = "wasm32", feature = "with_wasm"))]
+use std::u64;
+
+#[cfg(all(target_arch = "wasm32", feature = "with_wasm"))]
+use js_sys::Math;
+#[cfg(not(all(target_arch = "wasm32", feature = "with_wasm")))]
+use rand;
+use rand::Rng;
 
 #[derive(Clone, Copy)]
 pub struct JKiss32Rng {
@@ -28,6 +35,7 @@ pub struct JKiss32Rng {
 }
 
 impl JKiss32Rng {
+    #[cfg(not(all(target_arch = "wasm32", feature = "with_wasm")))]
     pub fn new() -> JKiss32Rng {
         let mut rng = rand::thread_rng();
         JKiss32Rng {
@@ -38,6 +46,32 @@ impl JKiss32Rng {
             c: false,
         }
     }
+
+    /// There's no OS entropy on `wasm32-unknown-unknown`, so the seed comes from the
+    /// browser's `Math.random`.
+    #[cfg(all(target_arch = "wasm32", feature = "with_wasm"))]
+    pub fn new() -> JKiss32Rng {
+        JKiss32Rng::with_seed((Math::random() * u64::MAX as f64) as u64)
+    }
+
+    /// Creates a generator whose sequence is determined entirely by `seed`.
+    pub fn with_seed(seed: u64) -> JKiss32Rng {
+        let mut seed = seed;
+        let mut next = || {
+            seed = seed.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            (z ^ (z >> 31)) as u32
+        };
+        JKiss32Rng {
+            x: next(),
+            y: next() | 1, // y must not be zero
+            z: next(),
+            w: next(),
+            c: false,
+        }
+    }
 }
 
 impl Rng for JKiss32Rng {
@@ -17,6 +17,8 @@
 // Copyright 2016-2017 Chris Foster
 //
 
+pub use self::clock::Instant;
 pub use self::jkiss32::JKiss32Rng;
 
+mod clock;
 mod jkiss32;
@@ -0,0 +1,147 @@
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+//! Bindings for analyzing positions from JavaScript, for browser-based analysis boards.
+//!
+//! Build the library for `wasm32-unknown-unknown` as a `cdylib`, with this feature and the
+//! implementations wanted, and pass the result through `wasm-bindgen`:
+//!
+//! ```text
+//! cargo rustc --release --lib --crate-type cdylib --target wasm32-unknown-unknown \
+//!     --features "with_wasm with_tak with_tic_tac_toe"
+//! wasm-bindgen --target web target/wasm32-unknown-unknown/release/zero_sum.wasm --out-dir pkg
+//! ```
+//!
+//! Tak positions are given in TPS, and tic-tac-toe positions as nine characters of `X`, `O`,
+//! or `-`, row by row from the top left.  Plies are returned in the notation the `cli`
+//! accepts: PTN for tak, and the x and y coordinates from 1 to 3 for tic-tac-toe.
+//!
+//! A browser can't interrupt a search running on its own thread, so every search is to a
+//! fixed depth, which must be at least 1.  Errors are thrown as strings.
+
+use wasm_bindgen::prelude::*;
+
+use analysis::Evaluator;
+use analysis::search::{Analysis, PvSearch, PvSearchAnalysis, Search};
+use analysis::Extrapolatable;
+use state::State;
+
+#[cfg(feature = "with_tak")]
+use impls::tak;
+#[cfg(feature = "with_tic_tac_toe")]
+use impls::tic_tac_toe;
+
+/// Returns the analysis of the tak position `tps` to `depth`, as it would be displayed.
+#[cfg(feature = "with_tak")]
+#[wasm_bindgen]
+pub fn tak_analyze(tps: &str, depth: u8) -> Result<String, JsValue> {
+    let state = parse_tps(tps)?;
+    Ok(search(&state, tak::evaluator::StaticEvaluator::new(), depth)?.to_string())
+}
+
+/// Returns the best ply, in PTN, for the player to move in the tak position `tps`, searching
+/// to `depth`.
+#[cfg(feature = "with_tak")]
+#[wasm_bindgen]
+pub fn tak_best_move(tps: &str, depth: u8) -> Result<String, JsValue> {
+    let state = parse_tps(tps)?;
+    best_ply(&state, tak::evaluator::StaticEvaluator::new(), depth).map(|ply| ply.to_ptn())
+}
+
+/// Returns the analysis of the tic-tac-toe position `board` to `depth`, as it would be
+/// displayed.
+#[cfg(feature = "with_tic_tac_toe")]
+#[wasm_bindgen]
+pub fn tic_tac_toe_analyze(board: &str, depth: u8) -> Result<String, JsValue> {
+    let state = parse_board(board)?;
+    Ok(search(&state, tic_tac_toe::Evaluator, depth)?.to_string())
+}
+
+/// Returns the best ply, as `"x y"`, for the player to move in the tic-tac-toe position
+/// `board`, searching to `depth`.
+#[cfg(feature = "with_tic_tac_toe")]
+#[wasm_bindgen]
+pub fn tic_tac_toe_best_move(board: &str, depth: u8) -> Result<String, JsValue> {
+    let state = parse_board(board)?;
+    best_ply(&state, tic_tac_toe::Evaluator, depth).map(|ply| {
+        format!("{} {}", ply.coordinates.0 + 1, ply.coordinates.1 + 1)
+    })
+}
+
+fn search<S, E>(state: &S, evaluator: E, depth: u8) -> Result<Box<Analysis>, JsValue> where
+    S: 'static + State + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S> {
+    if depth == 0 {
+        return Err(JsValue::from_str("The depth must be at least 1"));
+    }
+    Ok(PvSearch::with_depth(evaluator, depth).search(state, None))
+}
+
+fn best_ply<S, E>(state: &S, evaluator: E, depth: u8) -> Result<<S as State>::Ply, JsValue> where
+    S: 'static + State + Extrapolatable<<S as State>::Ply>,
+    E: 'static + Evaluator<State = S> {
+    if let Some(resolution) = state.check_resolution() {
+        return Err(JsValue::from_str(&format!("The game is over: {}", resolution)));
+    }
+
+    let analysis = search(state, evaluator, depth)?;
+    let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<S, E>>().unwrap();
+    match analysis.principal_variation.first() {
+        Some(ply) => Ok(ply.clone()),
+        None => Err(JsValue::from_str("The search found no plies")),
+    }
+}
+
+#[cfg(feature = "with_tak")]
+fn parse_tps(tps: &str) -> Result<tak::State, JsValue> {
+    tak::State::from_tps(tps).map_err(|error| JsValue::from_str(&error.to_string()))
+}
+
+#[cfg(feature = "with_tic_tac_toe")]
+fn parse_board(board: &str) -> Result<tic_tac_toe::Board, JsValue> {
+    let mut spaces = [None; 9];
+    let mut marks = 0;
+    let mut count = 0;
+
+    for c in board.chars().filter(|c| !c.is_whitespace()) {
+        if count == 9 {
+            return Err(JsValue::from_str("Expected nine spaces, found more"));
+        }
+        spaces[count] = match c {
+            'X' | 'x' => Some(tic_tac_toe::Mark::X),
+            'O' | 'o' => Some(tic_tac_toe::Mark::O),
+            '-' => None,
+            _ => return Err(JsValue::from_str(&format!("Invalid space: '{}'", c))),
+        };
+        if spaces[count].is_some() {
+            marks += 1;
+        }
+        count += 1;
+    }
+
+    if count != 9 {
+        return Err(JsValue::from_str(&format!("Expected nine spaces, found {}", count)));
+    }
+
+    let x_count = spaces.iter().filter(|&&space| space == Some(tic_tac_toe::Mark::X)).count();
+    if x_count != marks - x_count && x_count != marks - x_count + 1 {
+        return Err(JsValue::from_str("X moves first, and the players alternate"));
+    }
+
+    Ok(tic_tac_toe::Board(spaces, marks as u8))
+}
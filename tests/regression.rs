@@ -0,0 +1,172 @@
+//
+// This file is part of zero_sum.
+//
+// zero_sum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// zero_sum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with zero_sum. If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2016-2017 Chris Foster
+//
+
+//! Runs every case in `tests/regression` against `PvSearch` with the tak `StaticEvaluator`.
+//!
+//! Each case is a text file of lines of the form `key value`; blank lines and lines
+//! beginning with `#` are ignored.
+//!
+//! ```text
+//! # Description of the bug being pinned
+//! tps [TPS "x5/x5/x5/2,2,2,x2/1,1,1,1,x 1 5"]
+//! depth 3
+//! expect win
+//! ```
+//!
+//! The supported expectations are:
+//!
+//! * `win` - the search finds a forced win.
+//! * `not-lose` - the search does not find a forced loss.
+//! * `best <ptn>` - the first ply of the principal variation is `<ptn>`.
+//! * `avoid <ptn>` - the first ply of the principal variation is not `<ptn>`.
+//!
+//! Searches are seeded so that results are reproducible; a case may give its own `seed`.
+
+extern crate zero_sum;
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use zero_sum::analysis::Evaluation;
+use zero_sum::analysis::search::{PvSearch, PvSearchAnalysis, Search};
+use zero_sum::impls::tak::{Color, Ply, State};
+use zero_sum::impls::tak::evaluator::StaticEvaluator;
+use zero_sum::State as StateTrait;
+
+enum Expectation {
+    Win,
+    NotLose,
+    Best(Ply),
+    Avoid(Ply),
+}
+
+struct Case {
+    state: State,
+    depth: u8,
+    seed: u64,
+    expectations: Vec<Expectation>,
+}
+
+impl Case {
+    fn read(path: &Path) -> Result<Case, String> {
+        let file = File::open(path).map_err(|error| format!("Cannot open case: {}", error))?;
+
+        let mut state = None;
+        let mut depth = None;
+        let mut seed = 0;
+        let mut expectations = Vec::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|error| format!("Cannot read case: {}", error))?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.find(' ') {
+                Some(index) => (&line[..index], line[index + 1..].trim()),
+                None => (line, ""),
+            };
+
+            match key {
+                "tps" => state = Some(State::from_tps(value).map_err(|error| format!("Invalid TPS: {}", error))?),
+                "depth" => depth = Some(value.parse::<u8>().map_err(|_| format!("Invalid depth: {}", value))?),
+                "seed" => seed = value.parse::<u64>().map_err(|_| format!("Invalid seed: {}", value))?,
+                "expect" => {
+                    let color = match state {
+                        Some(ref state) => if state.get_ply_count() % 2 == 0 {
+                            Color::White
+                        } else {
+                            Color::Black
+                        },
+                        None => return Err(String::from("Expectations must follow the position")),
+                    };
+
+                    let mut words = value.split_whitespace();
+                    let expectation = match (words.next(), words.next()) {
+                        (Some("win"), None) => Expectation::Win,
+                        (Some("not-lose"), None) => Expectation::NotLose,
+                        (Some("best"), Some(ptn)) => Expectation::Best(Ply::from_ptn(ptn, color)?),
+                        (Some("avoid"), Some(ptn)) => Expectation::Avoid(Ply::from_ptn(ptn, color)?),
+                        _ => return Err(format!("Unknown expectation: {}", value)),
+                    };
+                    expectations.push(expectation);
+                },
+                _ => return Err(format!("Unknown key: {}", key)),
+            }
+        }
+
+        Ok(Case {
+            state: state.ok_or_else(|| String::from("Missing tps"))?,
+            depth: depth.ok_or_else(|| String::from("Missing depth"))?,
+            seed: seed,
+            expectations: expectations,
+        })
+    }
+
+    fn run(&self) -> Result<(), String> {
+        let mut search = PvSearch::with_depth(StaticEvaluator::new(), self.depth);
+        search.set_seed(self.seed);
+        let analysis = search.search(&self.state, None);
+        let analysis = analysis.as_any().downcast_ref::<PvSearchAnalysis<State, StaticEvaluator>>().unwrap();
+
+        let best = analysis.principal_variation.first();
+
+        for expectation in &self.expectations {
+            match *expectation {
+                Expectation::Win => if !analysis.evaluation.is_win() {
+                    return Err(format!("Expected a win, found {}", analysis.evaluation));
+                },
+                Expectation::NotLose => if analysis.evaluation.is_lose() {
+                    return Err(format!("Expected not to lose, found {}", analysis.evaluation));
+                },
+                Expectation::Best(ref ply) => if best != Some(ply) {
+                    return Err(format!("Expected {}, found {}", ply.to_ptn(), best.map_or(String::from("nothing"), |ply| ply.to_ptn())));
+                },
+                Expectation::Avoid(ref ply) => if best == Some(ply) {
+                    return Err(format!("Expected to avoid {}", ply.to_ptn()));
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn regression_corpus() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("regression");
+
+    let mut paths = fs::read_dir(&directory).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |extension| extension == "txt"))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        if let Err(error) = Case::read(path).and_then(|case| case.run()) {
+            failures.push(format!("{}: {}", path.file_name().unwrap().to_string_lossy(), error));
+        }
+    }
+
+    assert!(!paths.is_empty(), "No regression cases found in {}", directory.display());
+    assert!(failures.is_empty(), "{} of {} regression cases failed:\n{}", failures.len(), paths.len(), failures.join("\n"));
+}